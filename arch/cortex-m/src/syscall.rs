@@ -5,6 +5,8 @@ use core::fmt::Write;
 use core::mem;
 use core::ptr::{read_volatile, write_volatile};
 
+use kernel::ErrorCode;
+
 /// This is used in the syscall handler. When set to 1 this means the
 /// svc_handler was called. Marked `pub` because it is used in the cortex-m*
 /// specific handler.
@@ -91,6 +93,32 @@ impl kernel::syscall::UserspaceKernelBoundary for SysCall {
         Ok(())
     }
 
+    unsafe fn initialize_thread(
+        &self,
+        accessible_memory_start: *const u8,
+        app_brk: *const u8,
+        stack_pointer: *const u8,
+        state: &mut Self::StoredState,
+    ) -> Result<(), ErrorCode> {
+        // A thread starts like a process, but on its own stack, which the
+        // AAPCS requires to be 8 byte aligned.
+        state.regs.iter_mut().for_each(|x| *x = 0);
+        state.yield_pc = 0;
+        state.psr = 0x01000000; // Set the Thumb bit and clear everything else.
+        state.psp = stack_pointer as usize & !0x7;
+
+        // Make sure there's enough room on the stack for the initial SVC frame.
+        if state.psp > app_brk as usize
+            || state.psp < accessible_memory_start as usize + SVC_FRAME_SIZE
+        {
+            return Err(ErrorCode::INVAL);
+        }
+
+        // Allocate the kernel frame
+        state.psp -= SVC_FRAME_SIZE;
+        Ok(())
+    }
+
     unsafe fn set_syscall_return_value(
         &self,
         accessible_memory_start: *const u8,
@@ -259,6 +287,24 @@ impl kernel::syscall::UserspaceKernelBoundary for SysCall {
         (switch_reason, Some(new_stack_pointer as *const u8))
     }
 
+    fn store_context(
+        &self,
+        state: &CortexMStoredState,
+        out: &mut [u8],
+    ) -> Result<usize, ErrorCode> {
+        // R4-R11, followed by the yield PC, xPSR and process stack pointer.
+        let special = [state.yield_pc, state.psr, state.psp];
+        let words = state.regs.iter().chain(special.iter());
+        let length = (state.regs.len() + 3) * 4;
+        if out.len() < length {
+            return Err(ErrorCode::SIZE);
+        }
+        for (word, chunk) in words.zip(out.chunks_mut(4)) {
+            chunk.copy_from_slice(&(*word as u32).to_le_bytes());
+        }
+        Ok(length)
+    }
+
     unsafe fn print_context(
         &self,
         accessible_memory_start: *const u8,
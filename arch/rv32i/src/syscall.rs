@@ -5,6 +5,7 @@ use core::fmt::Write;
 use crate::csr::mcause;
 use kernel;
 use kernel::syscall::ContextSwitchReason;
+use kernel::ErrorCode;
 
 /// This holds all of the state that the kernel must keep for the process when
 /// the process is not executing.
@@ -81,6 +82,22 @@ impl kernel::syscall::UserspaceKernelBoundary for SysCall {
         Ok(())
     }
 
+    unsafe fn initialize_thread(
+        &self,
+        _accessible_memory_start: *const u8,
+        _app_brk: *const u8,
+        stack_pointer: *const u8,
+        state: &mut Self::StoredState,
+    ) -> Result<(), ErrorCode> {
+        // A thread starts like a process, but on its own stack, which the
+        // calling convention requires to be 16 byte aligned.
+        state.regs.iter_mut().for_each(|x| *x = 0);
+        state.pc = 0;
+        state.mcause = 0;
+        state.regs[R_SP] = stack_pointer as u32 & !0xf;
+        Ok(())
+    }
+
     unsafe fn set_syscall_return_value(
         &self,
         _accessible_memory_start: *const u8,
@@ -436,6 +453,24 @@ impl kernel::syscall::UserspaceKernelBoundary for SysCall {
         (ret, Some(new_stack_pointer as *const u8))
     }
 
+    fn store_context(
+        &self,
+        state: &Riscv32iStoredState,
+        out: &mut [u8],
+    ) -> Result<usize, ErrorCode> {
+        // x1-x31, followed by the PC, mcause and mtval.
+        let special = [state.pc, state.mcause, state.mtval];
+        let words = state.regs.iter().chain(special.iter());
+        let length = (state.regs.len() + 3) * 4;
+        if out.len() < length {
+            return Err(ErrorCode::SIZE);
+        }
+        for (word, chunk) in words.zip(out.chunks_mut(4)) {
+            chunk.copy_from_slice(&word.to_le_bytes());
+        }
+        Ok(length)
+    }
+
     unsafe fn print_context(
         &self,
         _accessible_memory_start: *const u8,
@@ -0,0 +1,57 @@
+//! Component for sharing an AES engine between AES-CCM users.
+//!
+//! This provides one Component, `AesCcmMuxComponent`, which virtualizes an
+//! AES peripheral so that several users, such as the IEEE 802.15.4 stack, can
+//! each perform AES-CCM operations on it.
+//!
+//! Usage
+//! -----
+//! ```rust
+//! let aes_mux = components::aes_ccm::AesCcmMuxComponent::new(&base_peripherals.ecb)
+//!     .finalize(components::aes_ccm_mux_component_helper!(nrf52840::aes::AesECB));
+//! ```
+
+use capsules::virtual_aes_ccm::MuxAES128CCM;
+use core::mem::MaybeUninit;
+use kernel::common::deferred_call::DeferredCallClient;
+use kernel::component::Component;
+use kernel::hil::symmetric_encryption::{AES128Ctr, AES128, AES128CBC};
+use kernel::static_init_half;
+
+// Setup static space for the objects.
+#[macro_export]
+macro_rules! aes_ccm_mux_component_helper {
+    ($A:ty $(,)?) => {{
+        use capsules::virtual_aes_ccm::MuxAES128CCM;
+        use core::mem::MaybeUninit;
+        static mut BUF: MaybeUninit<MuxAES128CCM<'static, $A>> = MaybeUninit::uninit();
+        &mut BUF
+    };};
+}
+
+pub struct AesCcmMuxComponent<A: 'static + AES128<'static> + AES128Ctr + AES128CBC> {
+    aes: &'static A,
+}
+
+impl<A: 'static + AES128<'static> + AES128Ctr + AES128CBC> AesCcmMuxComponent<A> {
+    pub fn new(aes: &'static A) -> Self {
+        AesCcmMuxComponent { aes }
+    }
+}
+
+impl<A: 'static + AES128<'static> + AES128Ctr + AES128CBC> Component for AesCcmMuxComponent<A> {
+    type StaticInput = &'static mut MaybeUninit<MuxAES128CCM<'static, A>>;
+    type Output = &'static MuxAES128CCM<'static, A>;
+
+    unsafe fn finalize(self, static_buffer: Self::StaticInput) -> Self::Output {
+        let aes_mux = static_init_half!(
+            static_buffer,
+            MuxAES128CCM<'static, A>,
+            MuxAES128CCM::new(self.aes)
+        );
+        aes_mux.register();
+        self.aes.set_client(aes_mux);
+
+        aes_mux
+    }
+}
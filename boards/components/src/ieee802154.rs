@@ -13,7 +13,6 @@
 //!     &nrf52::aes::AESECB,
 //!     PAN_ID,
 //!     SRC_MAC,
-//!     deferred_caller,
 //! )
 //! .finalize(components::ieee802154_component_helper!(
 //!     nrf52::ieee802154_radio::Radio,
@@ -26,7 +25,7 @@ use capsules::ieee802154::device::MacDevice;
 use capsules::ieee802154::mac::{AwakeMac, Mac};
 use core::mem::MaybeUninit;
 use kernel::capabilities;
-use kernel::common::dynamic_deferred_call::DynamicDeferredCall;
+use kernel::common::deferred_call::DeferredCallClient;
 use kernel::component::Component;
 use kernel::hil::radio;
 use kernel::hil::symmetric_encryption::{self, AES128Ctr, AES128, AES128CBC, AES128CCM};
@@ -64,7 +63,6 @@ pub struct Ieee802154Component<
     aes_mux: &'static capsules::virtual_aes_ccm::MuxAES128CCM<'static, A>,
     pan_id: capsules::net::ieee802154::PanID,
     short_addr: u16,
-    deferred_caller: &'static DynamicDeferredCall,
 }
 
 impl<
@@ -79,7 +77,6 @@ impl<
         aes_mux: &'static capsules::virtual_aes_ccm::MuxAES128CCM<'static, A>,
         pan_id: capsules::net::ieee802154::PanID,
         short_addr: u16,
-        deferred_caller: &'static DynamicDeferredCall,
     ) -> Self {
         Self {
             board_kernel,
@@ -88,7 +85,6 @@ impl<
             aes_mux,
             pan_id,
             short_addr,
-            deferred_caller,
         }
     }
 }
@@ -178,7 +174,6 @@ impl<
                 userspace_mac,
                 self.board_kernel.create_grant(self.driver_num, &grant_cap),
                 &mut RADIO_BUF,
-                self.deferred_caller,
             )
         );
 
@@ -188,11 +183,7 @@ impl<
         userspace_mac.set_receive_client(radio_driver);
         userspace_mac.set_pan(self.pan_id);
         userspace_mac.set_address(self.short_addr);
-        radio_driver.initialize_callback_handle(
-            self.deferred_caller
-                .register(radio_driver)
-                .expect("no deferred call slot available for ieee802154 driver"),
-        );
+        radio_driver.register();
 
         (radio_driver, mux_mac)
     }
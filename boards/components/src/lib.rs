@@ -2,6 +2,7 @@
 
 pub mod adc;
 pub mod adc_microphone;
+pub mod aes_ccm;
 pub mod alarm;
 pub mod analog_comparator;
 pub mod app_flash_driver;
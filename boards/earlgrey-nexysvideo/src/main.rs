@@ -15,7 +15,6 @@ use capsules::virtual_hmac::VirtualMuxHmac;
 use capsules::virtual_sha::VirtualMuxSha;
 use earlgrey::chip::EarlGreyDefaultPeripherals;
 use kernel::capabilities;
-use kernel::common::dynamic_deferred_call::{DynamicDeferredCall, DynamicDeferredCallClientState};
 use kernel::common::registers::interfaces::ReadWriteable;
 use kernel::component::Component;
 use kernel::hil;
@@ -45,7 +44,8 @@ const NUM_UPCALLS_IPC: usize = NUM_PROCS + 1;
 //
 // Actual memory for holding the active process structures. Need an empty list
 // at least.
-static mut PROCESSES: [Option<&'static dyn kernel::procs::Process>; 4] = [None; NUM_PROCS];
+static mut PROCESSES: [kernel::procs::ProcessSlot; 4] =
+    [kernel::procs::ProcessSlot::EMPTY; NUM_PROCS];
 
 // Test access to the peripherals
 #[cfg(test)]
@@ -157,18 +157,11 @@ unsafe fn setup() -> (
 
     let board_kernel = static_init!(kernel::Kernel, kernel::Kernel::new(&PROCESSES));
 
-    let dynamic_deferred_call_clients =
-        static_init!([DynamicDeferredCallClientState; 3], Default::default());
-    let dynamic_deferred_caller = static_init!(
-        DynamicDeferredCall,
-        DynamicDeferredCall::new(dynamic_deferred_call_clients)
-    );
-    DynamicDeferredCall::set_global_instance(dynamic_deferred_caller);
-
     let peripherals = static_init!(
         EarlGreyDefaultPeripherals,
-        EarlGreyDefaultPeripherals::new(dynamic_deferred_caller)
+        EarlGreyDefaultPeripherals::new()
     );
+    peripherals.init();
 
     // Configure kernel debug gpios as early as possible
     kernel::debug::assign_gpios(
@@ -181,7 +174,6 @@ unsafe fn setup() -> (
     let uart_mux = components::console::UartMuxComponent::new(
         &peripherals.uart0,
         earlgrey::uart::UART0_BAUDRATE,
-        dynamic_deferred_caller,
     )
     .finalize(());
 
@@ -349,12 +341,6 @@ unsafe fn setup() -> (
 
     peripherals.i2c0.set_master_client(i2c_master);
 
-    peripherals.aes.initialise(
-        dynamic_deferred_caller
-            .register(&peripherals.aes)
-            .expect("dynamic deferred caller out of slots"),
-    );
-
     // USB support is currently broken in the OpenTitan hardware
     // See https://github.com/lowRISC/opentitan/issues/2598 for more details
     // let usb = usb::UsbComponent::new(board_kernel).finalize(());
@@ -397,12 +383,6 @@ unsafe fn setup() -> (
     let _mux_otbn = crate::otbn::AccelMuxComponent::new(&peripherals.otbn)
         .finalize(otbn_mux_component_helper!(1024));
 
-    peripherals.otbn.initialise(
-        dynamic_deferred_caller
-            .register(&peripherals.otbn)
-            .expect("dynamic deferred caller out of slots"),
-    );
-
     /// These symbols are defined in the linker script.
     extern "C" {
         /// Beginning of the ROM region containing app images.
@@ -504,7 +484,6 @@ unsafe fn setup() -> (
             &mut _sappmem as *mut u8,
             &_eappmem as *const u8 as usize - &_sappmem as *const u8 as usize,
         ),
-        &mut PROCESSES,
         &FAULT_RESPONSE,
         &process_mgmt_cap,
     )
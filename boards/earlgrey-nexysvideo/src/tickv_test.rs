@@ -31,5 +31,5 @@ pub unsafe fn run_tickv_tests(
     tickv.set_client(test);
 
     // Kick start the tests by adding a key
-    tickv.append_key(key, value).unwrap();
+    tickv.append_key(key, value, 3).unwrap();
 }
@@ -10,15 +10,14 @@
 #![deny(missing_docs)]
 
 use capsules::virtual_alarm::VirtualMuxAlarm;
-use capsules::virtual_i2c::{I2CDevice, MuxI2C};
+use capsules::virtual_i2c::I2CDevice;
 use capsules::virtual_spi::VirtualSpiMasterDevice;
 use kernel::capabilities;
-use kernel::common::dynamic_deferred_call::{DynamicDeferredCall, DynamicDeferredCallClientState};
 use kernel::component::Component;
 use kernel::hil;
-use kernel::hil::i2c::I2CMaster;
 use kernel::hil::led::LedLow;
 use kernel::hil::Controller;
+use kernel::sleep::{DeadlineSleepPolicy, SleepPolicy, SleepState};
 use kernel::Platform;
 #[allow(unused_imports)]
 use kernel::{create_capability, debug, debug_gpio, static_init};
@@ -39,7 +38,8 @@ const NUM_PROCS: usize = 20;
 const NUM_UPCALLS_IPC: usize = NUM_PROCS + 1;
 
 // Actual memory for holding the active process structures.
-static mut PROCESSES: [Option<&'static dyn kernel::procs::Process>; NUM_PROCS] = [None; NUM_PROCS];
+static mut PROCESSES: [kernel::procs::ProcessSlot; NUM_PROCS] =
+    [kernel::procs::ProcessSlot::EMPTY; NUM_PROCS];
 
 static mut CHIP: Option<&'static sam4l::chip::Sam4l<Sam4lDefaultPeripherals>> = None;
 
@@ -48,6 +48,10 @@ static mut CHIP: Option<&'static sam4l::chip::Sam4l<Sam4lDefaultPeripherals>> =
 #[link_section = ".stack_buffer"]
 pub static mut STACK_MEMORY: [u8; 0x1000] = [0; 0x1000];
 
+/// Output of the last kernel panic, kept across resets.
+#[link_section = ".noinit"]
+static mut PANIC_RECORD: kernel::debug::PanicRecord = kernel::debug::PanicRecord::new();
+
 /// A structure representing this platform that holds references to all
 /// capsules for this platform.
 struct Hail {
@@ -73,6 +77,8 @@ struct Hail {
     ipc: kernel::ipc::IPC<NUM_PROCS, NUM_UPCALLS_IPC>,
     crc: &'static capsules::crc::CrcDriver<'static, sam4l::crccu::Crccu<'static>>,
     dac: &'static capsules::dac::Dac<'static>,
+    panic_report: &'static capsules::panic_report::PanicReport,
+    sleep_policy: &'static DeadlineSleepPolicy<'static, sam4l::ast::Ast<'static>>,
 }
 
 /// Mapping of integer syscalls to objects that implement syscalls.
@@ -101,11 +107,16 @@ impl Platform for Hail {
             capsules::crc::DRIVER_NUM => f(Some(self.crc)),
 
             capsules::dac::DRIVER_NUM => f(Some(self.dac)),
+            capsules::panic_report::DRIVER_NUM => f(Some(self.panic_report)),
 
             kernel::ipc::DRIVER_NUM => f(Some(&self.ipc)),
             _ => f(None),
         }
     }
+
+    fn select_sleep_state(&self, states: &[SleepState]) -> Option<usize> {
+        self.sleep_policy.select_sleep_state(states)
+    }
 }
 
 /// Helper function called during bring-up that configures multiplexed I/O.
@@ -214,24 +225,12 @@ pub unsafe fn main() {
         Some(&peripherals.pa[14]),
     );
 
-    let dynamic_deferred_call_clients =
-        static_init!([DynamicDeferredCallClientState; 2], Default::default());
-    let dynamic_deferred_caller = static_init!(
-        DynamicDeferredCall,
-        DynamicDeferredCall::new(dynamic_deferred_call_clients)
-    );
-    DynamicDeferredCall::set_global_instance(dynamic_deferred_caller);
-
     // Initialize USART0 for Uart
     peripherals.usart0.set_mode(sam4l::usart::UsartMode::Uart);
 
     // Create a shared UART channel for the console and for kernel debug.
-    let uart_mux = components::console::UartMuxComponent::new(
-        &peripherals.usart0,
-        115200,
-        dynamic_deferred_caller,
-    )
-    .finalize(());
+    let uart_mux =
+        components::console::UartMuxComponent::new(&peripherals.usart0, 115200).finalize(());
     uart_mux.initialize();
 
     hil::uart::Transmit::set_transmit_client(&peripherals.usart0, uart_mux);
@@ -249,6 +248,21 @@ pub unsafe fn main() {
             .finalize(());
     components::debug_writer::DebugWriterComponent::new(uart_mux).finalize(());
 
+    // Report the panic from before the last reset, and save future ones.
+    let panic_report = static_init!(
+        capsules::panic_report::PanicReport,
+        capsules::panic_report::PanicReport::new(
+            &PANIC_RECORD,
+            &mut capsules::panic_report::REPORT_BUFFER,
+            board_kernel.create_grant(
+                capsules::panic_report::DRIVER_NUM,
+                &memory_allocation_capability
+            ),
+        )
+    );
+    kernel::debug::set_panic_record(&mut PANIC_RECORD);
+    process_console.set_panic_report(panic_report);
+
     // Initialize USART3 for UART for the nRF serialization link.
     peripherals.usart3.set_mode(sam4l::usart::UsartMode::Uart);
     // Create the Nrf51822Serialization driver for passing BLE commands
@@ -265,11 +279,15 @@ pub unsafe fn main() {
         .finalize(components::alarm_mux_component_helper!(sam4l::ast::Ast));
     peripherals.ast.configure(mux_alarm);
 
-    let sensors_i2c = static_init!(
-        MuxI2C<'static>,
-        MuxI2C::new(&peripherals.i2c1, None, dynamic_deferred_caller)
+    // Only sleep deeply if the next alarm is far enough away. The chip checks
+    // itself that no peripheral needs the high speed clocks.
+    let sleep_policy = static_init!(
+        DeadlineSleepPolicy<'static, sam4l::ast::Ast<'static>>,
+        DeadlineSleepPolicy::new(&peripherals.ast, &[])
     );
-    peripherals.i2c1.set_master_client(sensors_i2c);
+
+    let sensors_i2c = components::i2c::I2CMuxComponent::new(&peripherals.i2c1, None)
+        .finalize(components::i2c_mux_component_helper!());
 
     // SI7021 Temperature / Humidity Sensor, address: 0x40
     let si7021 = components::si7021::SI7021Component::new(sensors_i2c, mux_alarm, 0x40)
@@ -481,6 +499,8 @@ pub unsafe fn main() {
         ),
         crc,
         dac,
+        panic_report,
+        sleep_policy,
     };
 
     // Setup the UART bus for nRF51 serialization..
@@ -516,7 +536,6 @@ pub unsafe fn main() {
             &mut _sappmem as *mut u8,
             &_eappmem as *const u8 as usize - &_sappmem as *const u8 as usize,
         ),
-        &mut PROCESSES,
         fault_policy,
         &process_management_capability,
     )
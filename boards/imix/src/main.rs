@@ -13,19 +13,14 @@ mod imix_components;
 use capsules::alarm::AlarmDriver;
 use capsules::net::ieee802154::MacAddress;
 use capsules::net::ipv6::ip_utils::IPAddr;
-use capsules::virtual_aes_ccm::MuxAES128CCM;
 use capsules::virtual_alarm::VirtualMuxAlarm;
-use capsules::virtual_i2c::MuxI2C;
 use capsules::virtual_spi::VirtualSpiMasterDevice;
 //use capsules::virtual_timer::MuxTimer;
 use kernel::capabilities;
-use kernel::common::dynamic_deferred_call::{DynamicDeferredCall, DynamicDeferredCallClientState};
 use kernel::component::Component;
-use kernel::hil::i2c::I2CMaster;
 use kernel::hil::radio;
 #[allow(unused_imports)]
 use kernel::hil::radio::{RadioConfig, RadioData};
-use kernel::hil::symmetric_encryption::AES128;
 //use kernel::hil::time::Alarm;
 use kernel::hil::led::LedHigh;
 use kernel::hil::Controller;
@@ -94,7 +89,8 @@ const PAN_ID: u16 = 0xABCD;
 // how should the kernel respond when a process faults
 const FAULT_RESPONSE: kernel::procs::PanicFaultPolicy = kernel::procs::PanicFaultPolicy {};
 
-static mut PROCESSES: [Option<&'static dyn kernel::procs::Process>; NUM_PROCS] = [None; NUM_PROCS];
+static mut PROCESSES: [kernel::procs::ProcessSlot; NUM_PROCS] =
+    [kernel::procs::ProcessSlot::EMPTY; NUM_PROCS];
 
 static mut CHIP: Option<&'static sam4l::chip::Sam4l<Sam4lDefaultPeripherals>> = None;
 
@@ -301,19 +297,10 @@ pub unsafe fn main() {
 
     let board_kernel = static_init!(kernel::Kernel, kernel::Kernel::new(&PROCESSES));
 
-    let dynamic_deferred_call_clients =
-        static_init!([DynamicDeferredCallClientState; 4], Default::default());
-    let dynamic_deferred_caller = static_init!(
-        DynamicDeferredCall,
-        DynamicDeferredCall::new(dynamic_deferred_call_clients)
-    );
-    DynamicDeferredCall::set_global_instance(dynamic_deferred_caller);
-
     // # CONSOLE
     // Create a shared UART channel for the consoles and for kernel debug.
     peripherals.usart3.set_mode(sam4l::usart::UsartMode::Uart);
-    let uart_mux =
-        UartMuxComponent::new(&peripherals.usart3, 115200, dynamic_deferred_caller).finalize(());
+    let uart_mux = UartMuxComponent::new(&peripherals.usart3, 115200).finalize(());
 
     let pconsole = ProcessConsoleComponent::new(board_kernel, uart_mux).finalize(());
     let console =
@@ -338,11 +325,8 @@ pub unsafe fn main() {
         .finalize(components::alarm_component_helper!(sam4l::ast::Ast));
 
     // # I2C and I2C Sensors
-    let mux_i2c = static_init!(
-        MuxI2C<'static>,
-        MuxI2C::new(&peripherals.i2c2, None, dynamic_deferred_caller)
-    );
-    peripherals.i2c2.set_master_client(mux_i2c);
+    let mux_i2c = components::i2c::I2CMuxComponent::new(&peripherals.i2c2, None)
+        .finalize(components::i2c_mux_component_helper!());
 
     let ambient_light = AmbientLightComponent::new(
         board_kernel,
@@ -476,16 +460,8 @@ pub unsafe fn main() {
     let serial_num_bottom_16 = (serial_num.get_lower_64() & 0x0000_0000_0000_ffff) as u16;
     let src_mac_from_serial_num: MacAddress = MacAddress::Short(serial_num_bottom_16);
 
-    let aes_mux = static_init!(
-        MuxAES128CCM<'static, sam4l::aes::Aes>,
-        MuxAES128CCM::new(&peripherals.aes, dynamic_deferred_caller)
-    );
-    peripherals.aes.set_client(aes_mux);
-    aes_mux.initialize_callback_handle(
-        dynamic_deferred_caller
-            .register(aes_mux)
-            .expect("no deferred call slot available for ccm mux"),
-    );
+    let aes_mux = components::aes_ccm::AesCcmMuxComponent::new(&peripherals.aes)
+        .finalize(components::aes_ccm_mux_component_helper!(sam4l::aes::Aes));
 
     // Can this initialize be pushed earlier, or into component? -pal
     let _ = rf233.initialize(&mut RF233_BUF, &mut RF233_REG_WRITE, &mut RF233_REG_READ);
@@ -496,7 +472,6 @@ pub unsafe fn main() {
         aes_mux,
         PAN_ID,
         serial_num_bottom_16,
-        dynamic_deferred_caller,
     )
     .finalize(components::ieee802154_component_helper!(
         capsules::rf233::RF233<'static, VirtualSpiMasterDevice<'static, sam4l::spi::SpiHw>>,
@@ -610,17 +585,15 @@ pub unsafe fn main() {
     //
     //test::virtual_uart_rx_test::run_virtual_uart_receive(uart_mux);
     //test::rng_test::run_entropy32(&peripherals.trng);
-    //test::virtual_aes_ccm_test::run(&peripherals.aes, dynamic_deferred_caller);
+    //test::virtual_aes_ccm_test::run(&peripherals.aes);
     //test::aes_test::run_aes128_ctr(&peripherals.aes);
     //test::aes_test::run_aes128_cbc(&peripherals.aes);
     //test::log_test::run(
     //    mux_alarm,
-    //    dynamic_deferred_caller,
     //    &peripherals.flash_controller,
     //);
     //test::linear_log_test::run(
     //    mux_alarm,
-    //    dynamic_deferred_caller,
     //    &peripherals.flash_controller,
     //);
     //test::icmp_lowpan_test::run(mux_mac, mux_alarm);
@@ -673,7 +646,6 @@ pub unsafe fn main() {
             &mut _sappmem as *mut u8,
             &_eappmem as *const u8 as usize - &_sappmem as *const u8 as usize,
         ),
-        &mut PROCESSES,
         &FAULT_RESPONSE,
         &process_mgmt_cap,
     )
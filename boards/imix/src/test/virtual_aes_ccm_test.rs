@@ -36,7 +36,7 @@
 //! aes_ccm_test passed: (current_test=2, encrypting=false, tag_is_valid=true)
 use capsules::test::aes_ccm::Test;
 use capsules::virtual_aes_ccm;
-use kernel::common::dynamic_deferred_call::DynamicDeferredCall;
+use kernel::common::deferred_call::DeferredCallClient;
 use kernel::hil::symmetric_encryption::{AES128, AES128CCM, AES128_BLOCK_SIZE};
 use kernel::static_init;
 use sam4l::aes::Aes;
@@ -44,20 +44,10 @@ use sam4l::aes::Aes;
 type AESCCMMUX = virtual_aes_ccm::MuxAES128CCM<'static, Aes<'static>>;
 type AESCCMCLIENT = virtual_aes_ccm::VirtualAES128CCM<'static, Aes<'static>>;
 
-pub unsafe fn run(
-    aes: &'static sam4l::aes::Aes,
-    dynamic_deferred_caller: &'static DynamicDeferredCall,
-) {
+pub unsafe fn run(aes: &'static sam4l::aes::Aes) {
     // mux
-    let ccm_mux = static_init!(
-        AESCCMMUX,
-        virtual_aes_ccm::MuxAES128CCM::new(aes, dynamic_deferred_caller)
-    );
-    ccm_mux.initialize_callback_handle(
-        dynamic_deferred_caller
-            .register(ccm_mux)
-            .expect("no deferred call slot available for ccm mux"),
-    );
+    let ccm_mux = static_init!(AESCCMMUX, virtual_aes_ccm::MuxAES128CCM::new(aes));
+    ccm_mux.register();
     aes.set_client(ccm_mux);
     // ---------------- ONE CLIENT ---------------------
     // client 1
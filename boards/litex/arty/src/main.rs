@@ -8,7 +8,6 @@
 
 use capsules::virtual_alarm::{MuxAlarm, VirtualMuxAlarm};
 use kernel::capabilities;
-use kernel::common::dynamic_deferred_call::{DynamicDeferredCall, DynamicDeferredCallClientState};
 use kernel::common::registers::interfaces::ReadWriteable;
 use kernel::common::StaticRef;
 use kernel::component::Component;
@@ -46,7 +45,7 @@ struct LiteXArtyInterruptablePeripherals {
     >,
     ethmac0: &'static litex_vexriscv::liteeth::LiteEth<'static, socc::SoCRegisterFmt>,
 }
-impl InterruptService<()> for LiteXArtyInterruptablePeripherals {
+impl InterruptService for LiteXArtyInterruptablePeripherals {
     unsafe fn service_interrupt(&self, interrupt: u32) -> bool {
         match interrupt as usize {
             socc::UART_INTERRUPT => {
@@ -64,10 +63,6 @@ impl InterruptService<()> for LiteXArtyInterruptablePeripherals {
             _ => false,
         }
     }
-
-    unsafe fn service_deferred_call(&self, _task: ()) -> bool {
-        false
-    }
 }
 
 const NUM_PROCS: usize = 4;
@@ -75,7 +70,8 @@ const NUM_UPCALLS_IPC: usize = NUM_PROCS + 1;
 
 // Actual memory for holding the active process structures. Need an
 // empty list at least.
-static mut PROCESSES: [Option<&'static dyn kernel::procs::Process>; NUM_PROCS] = [None; NUM_PROCS];
+static mut PROCESSES: [kernel::procs::ProcessSlot; NUM_PROCS] =
+    [kernel::procs::ProcessSlot::EMPTY; NUM_PROCS];
 
 // Reference to the chip, led controller and UART hardware for panic
 // dumps
@@ -172,14 +168,6 @@ pub unsafe fn main() {
 
     let board_kernel = static_init!(kernel::Kernel, kernel::Kernel::new(&PROCESSES));
 
-    let dynamic_deferred_call_clients =
-        static_init!([DynamicDeferredCallClientState; 2], Default::default());
-    let dynamic_deferred_caller = static_init!(
-        DynamicDeferredCall,
-        DynamicDeferredCall::new(dynamic_deferred_call_clients)
-    );
-    DynamicDeferredCall::set_global_instance(dynamic_deferred_caller);
-
     // ---------- LED CONTROLLER HARDWARE ----------
 
     // Initialize the LEDs, stopping any patterns from the bootloader
@@ -315,24 +303,15 @@ pub unsafe fn main() {
                 ),
                 socc::ClockFrequency::frequency()
             )),
-            dynamic_deferred_caller,
         )
     );
-    uart0.initialize(
-        dynamic_deferred_caller
-            .register(uart0)
-            .expect("dynamic deferred caller out of slots"),
-    );
+    uart0.initialize();
 
     PANIC_REFERENCES.uart = Some(uart0);
 
     // Create a shared UART channel for the console and for kernel debug.
-    let uart_mux = components::console::UartMuxComponent::new(
-        uart0,
-        socc::UART_BAUDRATE,
-        dynamic_deferred_caller,
-    )
-    .finalize(());
+    let uart_mux =
+        components::console::UartMuxComponent::new(uart0, socc::UART_BAUDRATE).finalize(());
 
     // ---------- ETHERNET ----------
 
@@ -465,7 +444,6 @@ pub unsafe fn main() {
             &mut _sappmem as *mut u8,
             &_eappmem as *const u8 as usize - &_sappmem as *const u8 as usize,
         ),
-        &mut PROCESSES,
         &FAULT_RESPONSE,
         &process_mgmt_cap,
     )
@@ -7,7 +7,6 @@
 
 use capsules::virtual_alarm::{MuxAlarm, VirtualMuxAlarm};
 use kernel::capabilities;
-use kernel::common::dynamic_deferred_call::{DynamicDeferredCall, DynamicDeferredCallClientState};
 use kernel::common::registers::interfaces::ReadWriteable;
 use kernel::common::StaticRef;
 use kernel::component::Component;
@@ -46,7 +45,7 @@ struct LiteXSimInterruptablePeripherals {
     ethmac0: &'static litex_vexriscv::liteeth::LiteEth<'static, socc::SoCRegisterFmt>,
 }
 
-impl InterruptService<()> for LiteXSimInterruptablePeripherals {
+impl InterruptService for LiteXSimInterruptablePeripherals {
     unsafe fn service_interrupt(&self, interrupt: u32) -> bool {
         match interrupt as usize {
             socc::UART_INTERRUPT => {
@@ -64,10 +63,6 @@ impl InterruptService<()> for LiteXSimInterruptablePeripherals {
             _ => false,
         }
     }
-
-    unsafe fn service_deferred_call(&self, _task: ()) -> bool {
-        false
-    }
 }
 
 const NUM_PROCS: usize = 4;
@@ -75,7 +70,8 @@ const NUM_UPCALLS_IPC: usize = NUM_PROCS + 1;
 
 // Actual memory for holding the active process structures. Need an
 // empty list at least.
-static mut PROCESSES: [Option<&'static dyn kernel::procs::Process>; NUM_PROCS] = [None; NUM_PROCS];
+static mut PROCESSES: [kernel::procs::ProcessSlot; NUM_PROCS] =
+    [kernel::procs::ProcessSlot::EMPTY; NUM_PROCS];
 
 // Reference to the chip and UART hardware for panic dumps
 struct LiteXSimPanicReferences {
@@ -162,14 +158,6 @@ pub unsafe fn main() {
 
     let board_kernel = static_init!(kernel::Kernel, kernel::Kernel::new(&PROCESSES));
 
-    let dynamic_deferred_call_clients =
-        static_init!([DynamicDeferredCallClientState; 2], Default::default());
-    let dynamic_deferred_caller = static_init!(
-        DynamicDeferredCall,
-        DynamicDeferredCall::new(dynamic_deferred_call_clients)
-    );
-    DynamicDeferredCall::set_global_instance(dynamic_deferred_caller);
-
     // --------- TIMER & UPTIME CORE; ALARM INITIALIZATION ----------
 
     // Initialize the hardware timer
@@ -279,14 +267,9 @@ pub unsafe fn main() {
                     as *const litex_vexriscv::uart::LiteXUartRegisters<socc::SoCRegisterFmt>,
             ),
             None, // LiteX simulator has no UART phy
-            dynamic_deferred_caller,
         )
     );
-    uart0.initialize(
-        dynamic_deferred_caller
-            .register(uart0)
-            .expect("dynamic deferred caller out of slots"),
-    );
+    uart0.initialize();
 
     PANIC_REFERENCES.uart = Some(uart0);
 
@@ -294,9 +277,7 @@ pub unsafe fn main() {
     //
     // The baudrate is ingnored, as no UART phy is present in the
     // verilated simulation.
-    let uart_mux =
-        components::console::UartMuxComponent::new(uart0, 115200, dynamic_deferred_caller)
-            .finalize(());
+    let uart_mux = components::console::UartMuxComponent::new(uart0, 115200).finalize(());
 
     // ---------- ETHERNET ----------
 
@@ -414,7 +395,6 @@ pub unsafe fn main() {
             &mut _sappmem as *mut u8,
             &_eappmem as *const u8 as usize - &_sappmem as *const u8 as usize,
         ),
-        &mut PROCESSES,
         &FAULT_RESPONSE,
         &process_mgmt_cap,
     )
@@ -8,16 +8,12 @@
 #![cfg_attr(not(doc), no_main)]
 #![deny(missing_docs)]
 
-use capsules::virtual_aes_ccm::MuxAES128CCM;
 use kernel::capabilities;
-use kernel::common::dynamic_deferred_call::{DynamicDeferredCall, DynamicDeferredCallClientState};
 use kernel::component::Component;
 use kernel::hil::gpio::Configure;
 use kernel::hil::gpio::Interrupt;
 use kernel::hil::gpio::Output;
-use kernel::hil::i2c::I2CMaster;
 use kernel::hil::led::LedLow;
-use kernel::hil::symmetric_encryption::AES128;
 use kernel::hil::time::Counter;
 use kernel::hil::usb::Client;
 use kernel::mpu::MPU;
@@ -87,7 +83,8 @@ const NUM_PROCS: usize = 8;
 const NUM_UPCALLS_IPC: usize = NUM_PROCS + 1;
 
 // State for loading and holding applications.
-static mut PROCESSES: [Option<&'static dyn kernel::procs::Process>; NUM_PROCS] = [None; NUM_PROCS];
+static mut PROCESSES: [kernel::procs::ProcessSlot; NUM_PROCS] =
+    [kernel::procs::ProcessSlot::EMPTY; NUM_PROCS];
 
 static mut CHIP: Option<&'static nrf52840::chip::NRF52<Nrf52840DefaultPeripherals>> = None;
 static mut CDC_REF_FOR_PANIC: Option<
@@ -258,14 +255,6 @@ pub unsafe fn main() {
     // Deferred Call (Dynamic) Setup
     //--------------------------------------------------------------------------
 
-    let dynamic_deferred_call_clients =
-        static_init!([DynamicDeferredCallClientState; 4], Default::default());
-    let dynamic_deferred_caller = static_init!(
-        DynamicDeferredCall,
-        DynamicDeferredCall::new(dynamic_deferred_call_clients)
-    );
-    DynamicDeferredCall::set_global_instance(dynamic_deferred_caller);
-
     //--------------------------------------------------------------------------
     // ALARM & TIMER
     //--------------------------------------------------------------------------
@@ -310,7 +299,6 @@ pub unsafe fn main() {
         0x005a,
         strings,
         mux_alarm,
-        dynamic_deferred_caller,
         Some(&baud_rate_reset_bootloader_enter),
     )
     .finalize(components::usb_cdc_acm_component_helper!(
@@ -320,8 +308,7 @@ pub unsafe fn main() {
     CDC_REF_FOR_PANIC = Some(cdc); //for use by panic handler
 
     // Create a shared UART channel for the console and for kernel debug.
-    let uart_mux = components::console::UartMuxComponent::new(cdc, 115200, dynamic_deferred_caller)
-        .finalize(());
+    let uart_mux = components::console::UartMuxComponent::new(cdc, 115200).finalize(());
 
     let pconsole =
         components::process_console::ProcessConsoleComponent::new(board_kernel, uart_mux)
@@ -352,15 +339,12 @@ pub unsafe fn main() {
     // SENSORS
     //--------------------------------------------------------------------------
 
-    let sensors_i2c_bus = static_init!(
-        capsules::virtual_i2c::MuxI2C<'static>,
-        capsules::virtual_i2c::MuxI2C::new(&base_peripherals.twi0, None, dynamic_deferred_caller)
-    );
+    let sensors_i2c_bus = components::i2c::I2CMuxComponent::new(&base_peripherals.twi0, None)
+        .finalize(components::i2c_mux_component_helper!());
     base_peripherals.twi0.configure(
         nrf52840::pinmux::Pinmux::new(I2C_SCL_PIN as u32),
         nrf52840::pinmux::Pinmux::new(I2C_SDA_PIN as u32),
     );
-    base_peripherals.twi0.set_master_client(sensors_i2c_bus);
 
     &nrf52840_peripherals.gpio_port[I2C_PULLUP_PIN].make_output();
     &nrf52840_peripherals.gpio_port[I2C_PULLUP_PIN].set();
@@ -420,15 +404,8 @@ pub unsafe fn main() {
     )
     .finalize(());
 
-    let aes_mux = static_init!(
-        MuxAES128CCM<'static, nrf52840::aes::AesECB>,
-        MuxAES128CCM::new(&base_peripherals.ecb, dynamic_deferred_caller)
-    );
-    base_peripherals.ecb.set_client(aes_mux);
-    aes_mux.initialize_callback_handle(
-        dynamic_deferred_caller
-            .register(aes_mux)
-            .expect("no deferred call slot available for ccm mux"),
+    let aes_mux = components::aes_ccm::AesCcmMuxComponent::new(&base_peripherals.ecb).finalize(
+        components::aes_ccm_mux_component_helper!(nrf52840::aes::AesECB),
     );
     use capsules::net::ieee802154::MacAddress;
     use capsules::virtual_alarm::VirtualMuxAlarm;
@@ -443,7 +420,6 @@ pub unsafe fn main() {
         aes_mux,
         PAN_ID,
         serial_num_bottom_16,
-        dynamic_deferred_caller,
     )
     .finalize(components::ieee802154_component_helper!(
         nrf52840::ieee802154_radio::Radio,
@@ -536,12 +512,10 @@ pub unsafe fn main() {
     //--------------------------------------------------------------------------
     // test::linear_log_test::run(
     //     mux_alarm,
-    //     dynamic_deferred_caller,
     //     &nrf52840_peripherals.nrf52.nvmc,
     // );
     // test::log_test::run(
     //     mux_alarm,
-    //     dynamic_deferred_caller,
     //     &nrf52840_peripherals.nrf52.nvmc,
     // );
 
@@ -575,7 +549,6 @@ pub unsafe fn main() {
             &mut _sappmem as *mut u8,
             &_eappmem as *const u8 as usize - &_sappmem as *const u8 as usize,
         ),
-        &mut PROCESSES,
         &FAULT_RESPONSE,
         &process_management_capability,
     )
@@ -12,7 +12,6 @@ use capsules::lsm303xx;
 use capsules::virtual_alarm::VirtualMuxAlarm;
 use components::gpio::GpioComponent;
 use kernel::capabilities;
-use kernel::common::dynamic_deferred_call::{DynamicDeferredCall, DynamicDeferredCallClientState};
 use kernel::component::Component;
 use kernel::hil::gpio::Configure;
 use kernel::hil::gpio::Output;
@@ -36,8 +35,8 @@ const NUM_PROCS: usize = 4;
 const NUM_UPCALLS_IPC: usize = NUM_PROCS + 1;
 
 // Actual memory for holding the active process structures.
-static mut PROCESSES: [Option<&'static dyn kernel::procs::Process>; NUM_PROCS] =
-    [None, None, None, None];
+static mut PROCESSES: [kernel::procs::ProcessSlot; NUM_PROCS] =
+    [kernel::procs::ProcessSlot::EMPTY; NUM_PROCS];
 
 // Static reference to chip for panic dumps.
 static mut CHIP: Option<&'static stm32f303xc::chip::Stm32f3xx<Stm32f3xxDefaultPeripherals>> = None;
@@ -181,6 +180,8 @@ unsafe fn set_pin_primary_functions(
         pin.set();
     });
 
+    // The clock is needed to initialize SPI1. Once the SPI mux is idle it
+    // gates the clock through a `SharedClock`.
     spi1.enable_clock();
 
     // I2C1 has the LSM303DLHC sensor connected
@@ -339,14 +340,6 @@ pub unsafe fn main() {
     peripherals.setup_circular_deps();
 
     let board_kernel = static_init!(kernel::Kernel, kernel::Kernel::new(&PROCESSES));
-    let dynamic_deferred_call_clients =
-        static_init!([DynamicDeferredCallClientState; 2], Default::default());
-    let dynamic_deferred_caller = static_init!(
-        DynamicDeferredCall,
-        DynamicDeferredCall::new(dynamic_deferred_call_clients)
-    );
-    DynamicDeferredCall::set_global_instance(dynamic_deferred_caller);
-
     let chip = static_init!(
         stm32f303xc::chip::Stm32f3xx<Stm32f3xxDefaultPeripherals>,
         stm32f303xc::chip::Stm32f3xx::new(peripherals, rcc)
@@ -357,12 +350,8 @@ pub unsafe fn main() {
 
     // Create a shared UART channel for kernel debug.
     peripherals.usart1.enable_clock();
-    let uart_mux = components::console::UartMuxComponent::new(
-        &peripherals.usart1,
-        115200,
-        dynamic_deferred_caller,
-    )
-    .finalize(());
+    let uart_mux =
+        components::console::UartMuxComponent::new(&peripherals.usart1, 115200).finalize(());
 
     // `finalize()` configures the underlying USART, so we need to
     // tell `send_byte()` not to configure the USART again.
@@ -605,6 +594,11 @@ pub unsafe fn main() {
     // L3GD20 sensor
     let spi_mux = components::spi::SpiMuxComponent::new(&peripherals.spi1)
         .finalize(components::spi_mux_component_helper!(stm32f303xc::spi::Spi));
+    let spi_clock = static_init!(
+        kernel::power::SharedClock<'static>,
+        kernel::power::SharedClock::new(peripherals.spi1.clock(), None)
+    );
+    spi_mux.set_power_resource(spi_clock);
 
     let l3gd20 =
         components::l3gd20::L3gd20SpiComponent::new(board_kernel, capsules::l3gd20::DRIVER_NUM)
@@ -632,9 +626,8 @@ pub unsafe fn main() {
 
     // LSM303DLHC
 
-    let mux_i2c =
-        components::i2c::I2CMuxComponent::new(&peripherals.i2c1, None, dynamic_deferred_caller)
-            .finalize(components::i2c_mux_component_helper!());
+    let mux_i2c = components::i2c::I2CMuxComponent::new(&peripherals.i2c1, None)
+        .finalize(components::i2c_mux_component_helper!());
 
     let lsm303dlhc = components::lsm303dlhc::Lsm303dlhcI2CComponent::new(
         board_kernel,
@@ -783,7 +776,6 @@ pub unsafe fn main() {
             &mut _sappmem as *mut u8,
             &_eappmem as *const u8 as usize - &_sappmem as *const u8 as usize,
         ),
-        &mut PROCESSES,
         &FAULT_RESPONSE,
         &process_management_capability,
     )
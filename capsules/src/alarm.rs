@@ -244,6 +244,20 @@ impl<'a, A: Alarm<'a>> Driver for AlarmDriver<'a, A> {
             )
     }
 
+    /// Once a process has unsubscribed and has no alarm armed the grant holds
+    /// nothing for it anymore, so the memory is returned to the process.
+    fn unsubscribed(&self, appid: ProcessId, _subscribe_num: usize) {
+        let unused = self
+            .app_alarms
+            .enter(appid, |td, upcalls| {
+                matches!(td.expiration, Expiration::Disabled) && !upcalls.has_subscriptions()
+            })
+            .unwrap_or(false);
+        if unused {
+            let _ = self.app_alarms.free(appid);
+        }
+    }
+
     fn allocate_grant(&self, appid: ProcessId) -> Result<(), kernel::procs::Error> {
         self.app_alarms.enter(appid, |_, _| {})
     }
@@ -0,0 +1,574 @@
+//! Software Ed25519 credentials verifier for checking apps at boot.
+//!
+//! Verifies `Ed25519` credentials in TBF footers: signatures of the app's
+//! integrity region, as specified in RFC 8032, made with the private key that
+//! belongs to the public key the verifier is created with. Unlike a hash, a
+//! valid signature can only be made by the holder of the private key, so a
+//! board that requires credentials only runs apps the key holder signed.
+//!
+//! The arithmetic follows TweetNaCl. It does not depend on secret data for
+//! branches or memory accesses, so `sign()` can be used for signing apps on
+//! the host, for example in tests. Like the SHA-256 verifier, verification is
+//! synchronous since processes are loaded before the kernel loop starts.
+//!
+//! Usage
+//! -----
+//!
+//! ```rust
+//! let ed25519_verifier = static_init!(
+//!     capsules::app_checker_ed25519::AppCheckerEd25519,
+//!     capsules::app_checker_ed25519::AppCheckerEd25519::new(APP_SIGNING_KEY)
+//! );
+//! let verifiers = static_init!(
+//!     [&'static dyn kernel::procs::CredentialsVerifier; 1],
+//!     [ed25519_verifier]
+//! );
+//! let checker = static_init!(
+//!     kernel::procs::AppCheckerVerify<'static>,
+//!     kernel::procs::AppCheckerVerify::new(verifiers, true)
+//! );
+//! ```
+
+use kernel::procs::{CredentialsVerifier, TbfFooterV2CredentialsType};
+
+/// An element of the field of integers modulo 2^255 - 19, as sixteen 16 bit
+/// limbs, least significant first. Limbs may temporarily exceed 16 bits.
+type Field = [i64; 16];
+
+/// A point on the curve in extended coordinates `(X, Y, Z, T)`.
+type Point = [Field; 4];
+
+const ZERO: Field = [0; 16];
+const ONE: Field = [1, 0, 0, 0, 0, 0, 0, 0, 0, 0, 0, 0, 0, 0, 0, 0];
+
+/// The curve constant `d = -121665 / 121666`.
+const D: Field = [
+    0x78a3, 0x1359, 0x4dca, 0x75eb, 0xd8ab, 0x4141, 0x0a4d, 0x0070, 0xe898, 0x7779, 0x4079, 0x8cc7,
+    0xfe73, 0x2b6f, 0x6cee, 0x5203,
+];
+
+/// `2 * d`.
+const D2: Field = [
+    0xf159, 0x26b2, 0x9b94, 0xebd6, 0xb156, 0x8283, 0x149a, 0x00e0, 0xd130, 0xeef3, 0x80f2, 0x198e,
+    0xfce7, 0x56df, 0xd9dc, 0x2406,
+];
+
+/// Coordinates of the base point.
+const BASE_X: Field = [
+    0xd51a, 0x8f25, 0x2d60, 0xc956, 0xa7b2, 0x9525, 0xc760, 0x692c, 0xdc5c, 0xfdd6, 0xe231, 0xc0a4,
+    0x53fe, 0xcd6e, 0x36d3, 0x2169,
+];
+const BASE_Y: Field = [
+    0x6658, 0x6666, 0x6666, 0x6666, 0x6666, 0x6666, 0x6666, 0x6666, 0x6666, 0x6666, 0x6666, 0x6666,
+    0x6666, 0x6666, 0x6666, 0x6666,
+];
+
+/// A square root of -1.
+const SQRT_M1: Field = [
+    0xa0b0, 0x4a0e, 0x1b27, 0xc4ee, 0xe478, 0xad2f, 0x1806, 0x2f43, 0xd7a7, 0x3dfb, 0x0099, 0x2b4d,
+    0xdf0b, 0x4fc1, 0x2480, 0x2b83,
+];
+
+/// The order of the base point, little endian.
+const L: [i64; 32] = [
+    0xed, 0xd3, 0xf5, 0x5c, 0x1a, 0x63, 0x12, 0x58, 0xd6, 0x9c, 0xf7, 0xa2, 0xde, 0xf9, 0xde, 0x14,
+    0, 0, 0, 0, 0, 0, 0, 0, 0, 0, 0, 0, 0, 0, 0, 0x10,
+];
+
+/// Round constants from FIPS 180-4.
+#[rustfmt::skip]
+const K: [u64; 80] = [
+    0x428a2f98d728ae22, 0x7137449123ef65cd, 0xb5c0fbcfec4d3b2f, 0xe9b5dba58189dbbc,
+    0x3956c25bf348b538, 0x59f111f1b605d019, 0x923f82a4af194f9b, 0xab1c5ed5da6d8118,
+    0xd807aa98a3030242, 0x12835b0145706fbe, 0x243185be4ee4b28c, 0x550c7dc3d5ffb4e2,
+    0x72be5d74f27b896f, 0x80deb1fe3b1696b1, 0x9bdc06a725c71235, 0xc19bf174cf692694,
+    0xe49b69c19ef14ad2, 0xefbe4786384f25e3, 0x0fc19dc68b8cd5b5, 0x240ca1cc77ac9c65,
+    0x2de92c6f592b0275, 0x4a7484aa6ea6e483, 0x5cb0a9dcbd41fbd4, 0x76f988da831153b5,
+    0x983e5152ee66dfab, 0xa831c66d2db43210, 0xb00327c898fb213f, 0xbf597fc7beef0ee4,
+    0xc6e00bf33da88fc2, 0xd5a79147930aa725, 0x06ca6351e003826f, 0x142929670a0e6e70,
+    0x27b70a8546d22ffc, 0x2e1b21385c26c926, 0x4d2c6dfc5ac42aed, 0x53380d139d95b3df,
+    0x650a73548baf63de, 0x766a0abb3c77b2a8, 0x81c2c92e47edaee6, 0x92722c851482353b,
+    0xa2bfe8a14cf10364, 0xa81a664bbc423001, 0xc24b8b70d0f89791, 0xc76c51a30654be30,
+    0xd192e819d6ef5218, 0xd69906245565a910, 0xf40e35855771202a, 0x106aa07032bbd1b8,
+    0x19a4c116b8d2d0c8, 0x1e376c085141ab53, 0x2748774cdf8eeb99, 0x34b0bcb5e19b48a8,
+    0x391c0cb3c5c95a63, 0x4ed8aa4ae3418acb, 0x5b9cca4f7763e373, 0x682e6ff3d6b2b8a3,
+    0x748f82ee5defb2fc, 0x78a5636f43172f60, 0x84c87814a1f0ab72, 0x8cc702081a6439ec,
+    0x90befffa23631e28, 0xa4506cebde82bde9, 0xbef9a3f7b2c67915, 0xc67178f2e372532b,
+    0xca273eceea26619c, 0xd186b8c721c0c207, 0xeada7dd6cde0eb1e, 0xf57d4f7fee6ed178,
+    0x06f067aa72176fba, 0x0a637dc5a2c898a6, 0x113f9804bef90dae, 0x1b710b35131c471b,
+    0x28db77f523047d84, 0x32caab7b40c72493, 0x3c9ebe0a15c9bebc, 0x431d67c49c100d4c,
+    0x4cc5d4becb3e42b6, 0x597f299cfc657e2a, 0x5fcb6fab3ad6faec, 0x6c44198c4a475817,
+];
+
+/// Initial hash value from FIPS 180-4.
+#[rustfmt::skip]
+const H0: [u64; 8] = [
+    0x6a09e667f3bcc908, 0xbb67ae8584caa73b, 0x3c6ef372fe94f82b, 0xa54ff53a5f1d36f1,
+    0x510e527fade682d1, 0x9b05688c2b3e6c1f, 0x1f83d9abfb41bd6b, 0x5be0cd19137e2179,
+];
+
+pub struct AppCheckerEd25519 {
+    public_key: [u8; 32],
+}
+
+impl AppCheckerEd25519 {
+    /// Create a verifier that accepts apps signed with the private key
+    /// belonging to `public_key`.
+    pub const fn new(public_key: [u8; 32]) -> AppCheckerEd25519 {
+        AppCheckerEd25519 { public_key }
+    }
+}
+
+impl CredentialsVerifier for AppCheckerEd25519 {
+    fn format(&self) -> TbfFooterV2CredentialsType {
+        TbfFooterV2CredentialsType::Ed25519
+    }
+
+    fn verify(&self, integrity_region: &[u8], credential: &[u8]) -> bool {
+        verify(&self.public_key, integrity_region, credential)
+    }
+}
+
+/// Returns whether `signature` is a valid Ed25519 signature of `message` for
+/// `public_key`.
+pub fn verify(public_key: &[u8; 32], message: &[u8], signature: &[u8]) -> bool {
+    if signature.len() != 64 || !scalar_is_canonical(&signature[32..]) {
+        return false;
+    }
+    let mut q = match unpack_negated(public_key) {
+        Some(q) => q,
+        None => return false,
+    };
+
+    let mut h = Sha512::new()
+        .update(&signature[..32])
+        .update(public_key)
+        .update(message)
+        .finish();
+    reduce(&mut h);
+
+    // R = S * B - h * A
+    let mut p = scalar_mult(&mut q, &h[..32]);
+    let mut s = [0; 32];
+    s.copy_from_slice(&signature[32..]);
+    let sb = scalar_base(&s);
+    add(&mut p, &sb);
+    pack(&p)[..] == signature[..32]
+}
+
+/// Derive the public key for the 32 byte private key `secret_key`.
+pub fn public_key(secret_key: &[u8; 32]) -> [u8; 32] {
+    pack(&scalar_base(&expand(secret_key)[..32]))
+}
+
+/// Sign `message` with the 32 byte private key `secret_key`.
+pub fn sign(secret_key: &[u8; 32], message: &[u8]) -> [u8; 64] {
+    let expanded = expand(secret_key);
+    let public_key = pack(&scalar_base(&expanded[..32]));
+
+    let mut r = Sha512::new()
+        .update(&expanded[32..])
+        .update(message)
+        .finish();
+    reduce(&mut r);
+    let mut signature = [0; 64];
+    signature[..32].copy_from_slice(&pack(&scalar_base(&r[..32])));
+
+    let mut h = Sha512::new()
+        .update(&signature[..32])
+        .update(&public_key)
+        .update(message)
+        .finish();
+    reduce(&mut h);
+
+    // S = r + h * a mod L
+    let mut x = [0; 64];
+    for (i, byte) in r[..32].iter().enumerate() {
+        x[i] = *byte as i64;
+    }
+    for (i, h) in h[..32].iter().enumerate() {
+        for (j, a) in expanded[..32].iter().enumerate() {
+            x[i + j] += *h as i64 * *a as i64;
+        }
+    }
+    let mut s = [0; 32];
+    mod_l(&mut s, &mut x);
+    signature[32..].copy_from_slice(&s);
+    signature
+}
+
+/// Hash the private key into the clamped secret scalar, followed by the
+/// prefix used to derive the nonce.
+fn expand(secret_key: &[u8; 32]) -> [u8; 64] {
+    let mut expanded = Sha512::new().update(secret_key).finish();
+    expanded[0] &= 248;
+    expanded[31] &= 127;
+    expanded[31] |= 64;
+    expanded
+}
+
+/// Whether the little endian scalar `s` is less than `L`, as RFC 8032
+/// requires of signatures.
+fn scalar_is_canonical(s: &[u8]) -> bool {
+    for (s, l) in s.iter().zip(L.iter()).rev() {
+        if (*s as i64) < *l {
+            return true;
+        } else if (*s as i64) > *l {
+            return false;
+        }
+    }
+    false
+}
+
+/// Reduce the 64 byte little endian number in `r` modulo `L`, leaving the
+/// result in the first 32 bytes.
+fn reduce(r: &mut [u8; 64]) {
+    let mut x = [0; 64];
+    for (x, r) in x.iter_mut().zip(r.iter()) {
+        *x = *r as i64;
+    }
+    let mut reduced = [0; 32];
+    mod_l(&mut reduced, &mut x);
+    *r = [0; 64];
+    r[..32].copy_from_slice(&reduced);
+}
+
+fn mod_l(r: &mut [u8; 32], x: &mut [i64; 64]) {
+    for i in (32..64).rev() {
+        let mut carry = 0;
+        for j in (i - 32)..(i - 12) {
+            x[j] += carry - 16 * x[i] * L[j - (i - 32)];
+            carry = (x[j] + 128) >> 8;
+            x[j] -= carry << 8;
+        }
+        x[i - 12] += carry;
+        x[i] = 0;
+    }
+    let mut carry = 0;
+    for j in 0..32 {
+        x[j] += carry - (x[31] >> 4) * L[j];
+        carry = x[j] >> 8;
+        x[j] &= 255;
+    }
+    for j in 0..32 {
+        x[j] -= carry * L[j];
+    }
+    for i in 0..32 {
+        x[i + 1] += x[i] >> 8;
+        r[i] = (x[i] & 255) as u8;
+    }
+}
+
+/// Propagate the carries of `o`, leaving every limb below 2^16.
+fn carry(o: &mut Field) {
+    for i in 0..16 {
+        o[i] += 1 << 16;
+        let c = o[i] >> 16;
+        if i < 15 {
+            o[i + 1] += c - 1;
+        } else {
+            // 2^256 = 38 mod 2^255 - 19
+            o[0] += 38 * (c - 1);
+        }
+        o[i] -= c << 16;
+    }
+}
+
+/// Swap `p` and `q` if `b` is 1, without branching on `b`.
+fn select(p: &mut Field, q: &mut Field, b: i64) {
+    let mask = !(b - 1);
+    for (p, q) in p.iter_mut().zip(q.iter_mut()) {
+        let t = mask & (*p ^ *q);
+        *p ^= t;
+        *q ^= t;
+    }
+}
+
+/// Encode `n`, fully reduced, as 32 little endian bytes.
+fn pack_field(n: &Field) -> [u8; 32] {
+    let mut t = *n;
+    carry(&mut t);
+    carry(&mut t);
+    carry(&mut t);
+    // Subtract the modulus twice, keeping the result if it did not borrow.
+    for _ in 0..2 {
+        let mut m = ZERO;
+        m[0] = t[0] - 0xffed;
+        for i in 1..15 {
+            m[i] = t[i] - 0xffff - ((m[i - 1] >> 16) & 1);
+            m[i - 1] &= 0xffff;
+        }
+        m[15] = t[15] - 0x7fff - ((m[14] >> 16) & 1);
+        let borrow = (m[15] >> 16) & 1;
+        m[14] &= 0xffff;
+        select(&mut t, &mut m, 1 - borrow);
+    }
+    let mut o = [0; 32];
+    for (bytes, limb) in o.chunks_exact_mut(2).zip(t.iter()) {
+        bytes[0] = *limb as u8;
+        bytes[1] = (*limb >> 8) as u8;
+    }
+    o
+}
+
+fn unpack_field(n: &[u8; 32]) -> Field {
+    let mut o = ZERO;
+    for (limb, bytes) in o.iter_mut().zip(n.chunks_exact(2)) {
+        *limb = bytes[0] as i64 + ((bytes[1] as i64) << 8);
+    }
+    o[15] &= 0x7fff;
+    o
+}
+
+fn not_equal(a: &Field, b: &Field) -> bool {
+    pack_field(a) != pack_field(b)
+}
+
+/// The lowest bit of the reduced `a`, its "sign".
+fn parity(a: &Field) -> u8 {
+    pack_field(a)[0] & 1
+}
+
+fn field_add(a: &Field, b: &Field) -> Field {
+    let mut o = ZERO;
+    for ((o, a), b) in o.iter_mut().zip(a.iter()).zip(b.iter()) {
+        *o = a + b;
+    }
+    o
+}
+
+fn field_sub(a: &Field, b: &Field) -> Field {
+    let mut o = ZERO;
+    for ((o, a), b) in o.iter_mut().zip(a.iter()).zip(b.iter()) {
+        *o = a - b;
+    }
+    o
+}
+
+fn field_mul(a: &Field, b: &Field) -> Field {
+    let mut t = [0; 31];
+    for (i, a) in a.iter().enumerate() {
+        for (j, b) in b.iter().enumerate() {
+            t[i + j] += a * b;
+        }
+    }
+    for i in 0..15 {
+        t[i] += 38 * t[i + 16];
+    }
+    let mut o = ZERO;
+    o.copy_from_slice(&t[..16]);
+    carry(&mut o);
+    carry(&mut o);
+    o
+}
+
+fn field_square(a: &Field) -> Field {
+    field_mul(a, a)
+}
+
+/// `a^(p - 2)`, the inverse of `a`.
+fn field_invert(a: &Field) -> Field {
+    let mut c = *a;
+    for i in (0..254).rev() {
+        c = field_square(&c);
+        if i != 2 && i != 4 {
+            c = field_mul(&c, a);
+        }
+    }
+    c
+}
+
+/// `a^((p - 5) / 8)`, used to compute square roots.
+fn field_pow2523(a: &Field) -> Field {
+    let mut c = *a;
+    for i in (0..251).rev() {
+        c = field_square(&c);
+        if i != 1 {
+            c = field_mul(&c, a);
+        }
+    }
+    c
+}
+
+/// Add `q` to `p`.
+fn add(p: &mut Point, q: &Point) {
+    let a = field_mul(&field_sub(&p[1], &p[0]), &field_sub(&q[1], &q[0]));
+    let b = field_mul(&field_add(&p[0], &p[1]), &field_add(&q[0], &q[1]));
+    let c = field_mul(&field_mul(&p[3], &q[3]), &D2);
+    let d = field_mul(&p[2], &q[2]);
+    let d = field_add(&d, &d);
+    let e = field_sub(&b, &a);
+    let f = field_sub(&d, &c);
+    let g = field_add(&d, &c);
+    let h = field_add(&b, &a);
+
+    p[0] = field_mul(&e, &f);
+    p[1] = field_mul(&h, &g);
+    p[2] = field_mul(&g, &f);
+    p[3] = field_mul(&e, &h);
+}
+
+fn swap(p: &mut Point, q: &mut Point, b: i64) {
+    for (p, q) in p.iter_mut().zip(q.iter_mut()) {
+        select(p, q, b);
+    }
+}
+
+fn pack(p: &Point) -> [u8; 32] {
+    let z_inverse = field_invert(&p[2]);
+    let x = field_mul(&p[0], &z_inverse);
+    let y = field_mul(&p[1], &z_inverse);
+    let mut r = pack_field(&y);
+    r[31] ^= parity(&x) << 7;
+    r
+}
+
+/// Multiply `q` by the little endian scalar `s` with a constant time ladder.
+/// `q` is clobbered.
+fn scalar_mult(q: &mut Point, s: &[u8]) -> Point {
+    let mut p = [ZERO, ONE, ONE, ZERO];
+    for i in (0..256).rev() {
+        let b = ((s[i / 8] >> (i & 7)) & 1) as i64;
+        swap(&mut p, q, b);
+        add(q, &p);
+        let double = p;
+        add(&mut p, &double);
+        swap(&mut p, q, b);
+    }
+    p
+}
+
+fn scalar_base(s: &[u8]) -> Point {
+    let mut q = [BASE_X, BASE_Y, ONE, field_mul(&BASE_X, &BASE_Y)];
+    scalar_mult(&mut q, s)
+}
+
+/// Decode the point `p` and negate it. Returns `None` if `p` is not on the
+/// curve.
+fn unpack_negated(p: &[u8; 32]) -> Option<Point> {
+    let y = unpack_field(p);
+    let num = field_square(&y);
+    let den = field_mul(&num, &D);
+    let num = field_sub(&num, &ONE);
+    let den = field_add(&ONE, &den);
+
+    // x = sqrt(num / den), computed as num * den^3 * (num * den^7)^((p-5)/8).
+    let den2 = field_square(&den);
+    let den4 = field_square(&den2);
+    let den6 = field_mul(&den4, &den2);
+    let mut t = field_mul(&field_mul(&den6, &num), &den);
+    t = field_pow2523(&t);
+    t = field_mul(&field_mul(&field_mul(&t, &num), &den), &den);
+    let mut x = field_mul(&t, &den);
+
+    if not_equal(&field_mul(&field_square(&x), &den), &num) {
+        x = field_mul(&x, &SQRT_M1);
+    }
+    if not_equal(&field_mul(&field_square(&x), &den), &num) {
+        return None;
+    }
+    if parity(&x) == p[31] >> 7 {
+        x = field_sub(&ZERO, &x);
+    }
+    let xy = field_mul(&x, &y);
+    Some([x, y, ONE, xy])
+}
+
+/// Incremental SHA-512, as Ed25519 hashes several pieces of data together.
+struct Sha512 {
+    state: [u64; 8],
+    block: [u8; 128],
+    buffered: usize,
+    length: u64,
+}
+
+impl Sha512 {
+    fn new() -> Sha512 {
+        Sha512 {
+            state: H0,
+            block: [0; 128],
+            buffered: 0,
+            length: 0,
+        }
+    }
+
+    fn update(mut self, mut data: &[u8]) -> Sha512 {
+        self.length = self.length.wrapping_add(data.len() as u64);
+        while !data.is_empty() {
+            let n = core::cmp::min(128 - self.buffered, data.len());
+            self.block[self.buffered..self.buffered + n].copy_from_slice(&data[..n]);
+            self.buffered += n;
+            data = &data[n..];
+            if self.buffered == 128 {
+                compress(&mut self.state, &self.block);
+                self.buffered = 0;
+            }
+        }
+        self
+    }
+
+    fn finish(mut self) -> [u8; 64] {
+        // Pad with a one bit, zeros and the message length in bits. This
+        // needs another block if the length does not fit.
+        let bit_len = (self.length as u128).wrapping_mul(8);
+        let buffered = self.buffered;
+        self.block[buffered] = 0x80;
+        self.block[buffered + 1..].iter_mut().for_each(|b| *b = 0);
+        if buffered >= 112 {
+            compress(&mut self.state, &self.block);
+            self.block = [0; 128];
+        }
+        self.block[112..].copy_from_slice(&bit_len.to_be_bytes());
+        compress(&mut self.state, &self.block);
+
+        let mut hash = [0; 64];
+        for (bytes, word) in hash.chunks_exact_mut(8).zip(self.state.iter()) {
+            bytes.copy_from_slice(&word.to_be_bytes());
+        }
+        hash
+    }
+}
+
+fn compress(state: &mut [u64; 8], block: &[u8; 128]) {
+    let mut w = [0u64; 80];
+    for (w, word) in w.iter_mut().zip(block.chunks_exact(8)) {
+        let mut bytes = [0; 8];
+        bytes.copy_from_slice(word);
+        *w = u64::from_be_bytes(bytes);
+    }
+    for i in 16..80 {
+        let s0 = w[i - 15].rotate_right(1) ^ w[i - 15].rotate_right(8) ^ (w[i - 15] >> 7);
+        let s1 = w[i - 2].rotate_right(19) ^ w[i - 2].rotate_right(61) ^ (w[i - 2] >> 6);
+        w[i] = w[i - 16]
+            .wrapping_add(s0)
+            .wrapping_add(w[i - 7])
+            .wrapping_add(s1);
+    }
+
+    let [mut a, mut b, mut c, mut d, mut e, mut f, mut g, mut h] = *state;
+    for i in 0..80 {
+        let s1 = e.rotate_right(14) ^ e.rotate_right(18) ^ e.rotate_right(41);
+        let ch = (e & f) ^ (!e & g);
+        let temp1 = h
+            .wrapping_add(s1)
+            .wrapping_add(ch)
+            .wrapping_add(K[i])
+            .wrapping_add(w[i]);
+        let s0 = a.rotate_right(28) ^ a.rotate_right(34) ^ a.rotate_right(39);
+        let maj = (a & b) ^ (a & c) ^ (b & c);
+        let temp2 = s0.wrapping_add(maj);
+
+        h = g;
+        g = f;
+        f = e;
+        e = d.wrapping_add(temp1);
+        d = c;
+        c = b;
+        b = a;
+        a = temp1.wrapping_add(temp2);
+    }
+
+    for (s, v) in state.iter_mut().zip([a, b, c, d, e, f, g, h].iter()) {
+        *s = s.wrapping_add(*v);
+    }
+}
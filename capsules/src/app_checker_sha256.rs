@@ -0,0 +1,155 @@
+//! Software SHA-256 credentials verifier for checking apps at boot.
+//!
+//! Verifies `SHA256` credentials in TBF footers by hashing the app's
+//! integrity region and comparing the result with the credential. This only
+//! detects corrupted or modified apps: anyone can compute the hash of a
+//! modified app, so boards that need to authenticate apps should use a
+//! signature verifier instead, such as `app_checker_ed25519`.
+//!
+//! The hash is computed synchronously, as processes are loaded before the
+//! kernel loop starts and cannot wait for asynchronous digest hardware.
+//!
+//! Usage
+//! -----
+//!
+//! ```rust
+//! let sha256_verifier = static_init!(
+//!     capsules::app_checker_sha256::AppCheckerSha256,
+//!     capsules::app_checker_sha256::AppCheckerSha256::new()
+//! );
+//! let verifiers = static_init!(
+//!     [&'static dyn kernel::procs::CredentialsVerifier; 1],
+//!     [sha256_verifier]
+//! );
+//! let checker = static_init!(
+//!     kernel::procs::AppCheckerVerify<'static>,
+//!     kernel::procs::AppCheckerVerify::new(verifiers, true)
+//! );
+//! let id_policy = static_init!(
+//!     kernel::procs::ShortIdFromTbf,
+//!     kernel::procs::ShortIdFromTbf::new(true)
+//! );
+//!
+//! kernel::procs::load_and_check_processes(
+//!     board_kernel,
+//!     chip,
+//!     app_flash,
+//!     app_memory,
+//!     &FAULT_RESPONSE,
+//!     checker,
+//!     id_policy,
+//!     &process_management_capability,
+//! )
+//! .unwrap_or_else(|err| {
+//!     debug!("Error loading processes!");
+//!     debug!("{:?}", err);
+//! });
+//! ```
+
+use kernel::procs::{CredentialsVerifier, TbfFooterV2CredentialsType};
+
+/// Round constants from FIPS 180-4.
+const K: [u32; 64] = [
+    0x428a2f98, 0x71374491, 0xb5c0fbcf, 0xe9b5dba5, 0x3956c25b, 0x59f111f1, 0x923f82a4, 0xab1c5ed5,
+    0xd807aa98, 0x12835b01, 0x243185be, 0x550c7dc3, 0x72be5d74, 0x80deb1fe, 0x9bdc06a7, 0xc19bf174,
+    0xe49b69c1, 0xefbe4786, 0x0fc19dc6, 0x240ca1cc, 0x2de92c6f, 0x4a7484aa, 0x5cb0a9dc, 0x76f988da,
+    0x983e5152, 0xa831c66d, 0xb00327c8, 0xbf597fc7, 0xc6e00bf3, 0xd5a79147, 0x06ca6351, 0x14292967,
+    0x27b70a85, 0x2e1b2138, 0x4d2c6dfc, 0x53380d13, 0x650a7354, 0x766a0abb, 0x81c2c92e, 0x92722c85,
+    0xa2bfe8a1, 0xa81a664b, 0xc24b8b70, 0xc76c51a3, 0xd192e819, 0xd6990624, 0xf40e3585, 0x106aa070,
+    0x19a4c116, 0x1e376c08, 0x2748774c, 0x34b0bcb5, 0x391c0cb3, 0x4ed8aa4a, 0x5b9cca4f, 0x682e6ff3,
+    0x748f82ee, 0x78a5636f, 0x84c87814, 0x8cc70208, 0x90befffa, 0xa4506ceb, 0xbef9a3f7, 0xc67178f2,
+];
+
+/// Initial hash value from FIPS 180-4.
+const H0: [u32; 8] = [
+    0x6a09e667, 0xbb67ae85, 0x3c6ef372, 0xa54ff53a, 0x510e527f, 0x9b05688c, 0x1f83d9ab, 0x5be0cd19,
+];
+
+pub struct AppCheckerSha256 {}
+
+impl AppCheckerSha256 {
+    pub const fn new() -> AppCheckerSha256 {
+        AppCheckerSha256 {}
+    }
+}
+
+impl CredentialsVerifier for AppCheckerSha256 {
+    fn format(&self) -> TbfFooterV2CredentialsType {
+        TbfFooterV2CredentialsType::SHA256
+    }
+
+    fn verify(&self, integrity_region: &[u8], credential: &[u8]) -> bool {
+        sha256(integrity_region) == credential
+    }
+}
+
+/// Compute the SHA-256 hash of `data`.
+pub fn sha256(data: &[u8]) -> [u8; 32] {
+    let mut state = H0;
+
+    let mut chunks = data.chunks_exact(64);
+    for block in &mut chunks {
+        compress(&mut state, block);
+    }
+
+    // Pad the final partial block with a one bit, zeros and the message
+    // length in bits. This needs a second block if the length does not fit.
+    let remainder = chunks.remainder();
+    let mut last = [0; 128];
+    last[..remainder.len()].copy_from_slice(remainder);
+    last[remainder.len()] = 0x80;
+    let padded_len = if remainder.len() < 56 { 64 } else { 128 };
+    let bit_len = (data.len() as u64).wrapping_mul(8);
+    last[padded_len - 8..padded_len].copy_from_slice(&bit_len.to_be_bytes());
+    for block in last[..padded_len].chunks_exact(64) {
+        compress(&mut state, block);
+    }
+
+    let mut hash = [0; 32];
+    for (bytes, word) in hash.chunks_exact_mut(4).zip(state.iter()) {
+        bytes.copy_from_slice(&word.to_be_bytes());
+    }
+    hash
+}
+
+fn compress(state: &mut [u32; 8], block: &[u8]) {
+    let mut w = [0u32; 64];
+    for (i, word) in block.chunks_exact(4).enumerate() {
+        w[i] = u32::from_be_bytes([word[0], word[1], word[2], word[3]]);
+    }
+    for i in 16..64 {
+        let s0 = w[i - 15].rotate_right(7) ^ w[i - 15].rotate_right(18) ^ (w[i - 15] >> 3);
+        let s1 = w[i - 2].rotate_right(17) ^ w[i - 2].rotate_right(19) ^ (w[i - 2] >> 10);
+        w[i] = w[i - 16]
+            .wrapping_add(s0)
+            .wrapping_add(w[i - 7])
+            .wrapping_add(s1);
+    }
+
+    let [mut a, mut b, mut c, mut d, mut e, mut f, mut g, mut h] = *state;
+    for i in 0..64 {
+        let s1 = e.rotate_right(6) ^ e.rotate_right(11) ^ e.rotate_right(25);
+        let ch = (e & f) ^ (!e & g);
+        let temp1 = h
+            .wrapping_add(s1)
+            .wrapping_add(ch)
+            .wrapping_add(K[i])
+            .wrapping_add(w[i]);
+        let s0 = a.rotate_right(2) ^ a.rotate_right(13) ^ a.rotate_right(22);
+        let maj = (a & b) ^ (a & c) ^ (b & c);
+        let temp2 = s0.wrapping_add(maj);
+
+        h = g;
+        g = f;
+        f = e;
+        e = d.wrapping_add(temp1);
+        d = c;
+        c = b;
+        b = a;
+        a = temp1.wrapping_add(temp2);
+    }
+
+    for (word, value) in state.iter_mut().zip([a, b, c, d, e, f, g, h].iter()) {
+        *word = word.wrapping_add(*value);
+    }
+}
@@ -0,0 +1,737 @@
+//! Install, start and remove apps while the kernel is running.
+//!
+//! The app loader writes a TBF image to free space at the end of the app flash
+//! region and then asks the kernel to create a process for it in a free
+//! process slot. This allows a single app to be installed or updated without
+//! reflashing the board.
+//!
+//! New apps are appended to the linked list of apps in flash, so they are
+//! loaded by `load_processes()` when the board next boots. The TBF must be
+//! padded so that its start and size satisfy the MPU's alignment rules, just
+//! like apps installed with tockloader.
+//!
+//! Loading an app is a three step process:
+//!
+//! 1. `setup()` reserves flash for an image of a given length.
+//! 2. `write()` writes the image, in chunks of any size and in any order.
+//! 3. `load()` parses the image and starts the new process.
+//!
+//! If the image is not loaded, because `abort()` is called or because `load()`
+//! refuses it, the flash reserved for it is erased so that it is not loaded
+//! when the board next boots either. The loader is busy until the erase
+//! completes.
+//!
+//! `unload()` terminates a process, frees its process slot and releases its
+//! flash. If the app is at the end of the linked list of apps, its flash (and
+//! that of any padding before it) is erased and becomes free space for new
+//! apps. Otherwise its header is replaced with a padding header, so that it is
+//! skipped at boot and erased once the apps after it are removed.
+//!
+//! The RAM of an unloaded process is only reused if the process was installed
+//! by the app loader. The RAM of apps loaded at boot is not reclaimed until
+//! the board reboots, so replacing such an app needs RAM to spare in the pool
+//! the board gives the loader.
+//!
+//! These operations are available to other capsules (for example one
+//! receiving apps over the console or USB) through the methods on
+//! `AppLoader`, and to userspace through the syscall interface. As installing
+//! and removing apps can change the behavior of the whole system, only the
+//! app with the short ID the board passes as `privileged_app` can use the
+//! syscall interface; the driver does not exist for other apps. Boards should
+//! use a short ID that is only given to apps with verified credentials.
+//!
+//! Usage
+//! -----
+//!
+//! ```rust
+//! # use kernel::static_init;
+//!
+//! let dynamic_process_loader = static_init!(
+//!     kernel::procs::DynamicProcessLoader<nrf52840::chip::NRF52<Nrf52840DefaultPeripherals>>,
+//!     kernel::procs::DynamicProcessLoader::new(
+//!         board_kernel,
+//!         chip,
+//!         &mut DYNAMIC_APP_MEMORY,
+//!         &FAULT_RESPONSE,
+//!         None,
+//!         &kernel::procs::ShortIdFromTbf::new(true),
+//!         &process_management_capability,
+//!     )
+//! );
+//! let app_loader = static_init!(
+//!     capsules::app_loader::AppLoader<'static, nrf52840::nvmc::Nvmc, Capability>,
+//!     capsules::app_loader::AppLoader::new(
+//!         board_kernel,
+//!         &base_peripherals.nvmc,
+//!         dynamic_process_loader,
+//!         app_flash,
+//!         static_init!(nrf52840::nvmc::NrfPage, nrf52840::nvmc::NrfPage::default()),
+//!         board_kernel.create_grant(capsules::app_loader::DRIVER_NUM, &grant_cap),
+//!         kernel::ShortID::Fixed(core::num::NonZeroU32::new(0x10ad).unwrap()),
+//!         process_management_capability,
+//!     )
+//! );
+//! hil::flash::HasClient::set_client(&base_peripherals.nvmc, app_loader);
+//! ```
+
+use core::cell::Cell;
+use core::cmp;
+use core::convert::TryInto;
+use core::mem;
+use kernel::capabilities::ProcessManagementCapability;
+use kernel::common::cells::{OptionalCell, TakeCell};
+use kernel::hil;
+use kernel::procs::DynamicProcessLoading;
+use kernel::ErrorCode;
+use kernel::{
+    CommandReturn, Driver, Grant, Kernel, ProcessId, ReadOnlyProcessBuffer, ReadableProcessBuffer,
+    ShortID,
+};
+
+/// Syscall driver number.
+use crate::driver;
+pub const DRIVER_NUM: usize = driver::NUM::AppLoader as usize;
+
+/// Size of a TBF header without any TLVs, which is all a padding entry needs.
+const PADDING_HEADER_SIZE: usize = 16;
+
+/// Receives the results of operations started through the `AppLoader` API.
+pub trait AppLoaderClient {
+    /// A chunk of the new app was written to flash.
+    fn write_done(&self, buffer: &'static mut [u8], result: Result<(), ErrorCode>);
+
+    /// The flash of an unloaded app was released.
+    fn unload_done(&self, result: Result<(), ErrorCode>);
+
+    /// The flash reserved for an app image that was aborted or failed to load
+    /// was erased.
+    fn abort_done(&self, result: Result<(), ErrorCode>);
+}
+
+/// What is written to flash.
+#[derive(Clone, Copy, PartialEq)]
+enum Source {
+    /// A chunk of the new app from the client's or process's buffer.
+    Buffer,
+    /// Erased flash.
+    Erased,
+    /// Erased flash, replacing an app image that was not loaded.
+    Discarded,
+    /// A padding header replacing the header of an unloaded app.
+    Padding([u8; PADDING_HEADER_SIZE]),
+}
+
+/// A write to `app_flash`, which may span several pages.
+#[derive(Clone, Copy)]
+struct Write {
+    /// Offset of the write in `app_flash`.
+    position: usize,
+    length: usize,
+    source: Source,
+    /// Bytes already written.
+    done: usize,
+    /// Bytes in the page being written.
+    in_flight: usize,
+}
+
+#[derive(Default)]
+pub struct App {
+    buffer: ReadOnlyProcessBuffer,
+}
+
+pub struct AppLoader<'a, F: hil::flash::Flash + 'static, C: ProcessManagementCapability> {
+    kernel: &'static Kernel,
+    flash: &'a F,
+    loader: &'a dyn DynamicProcessLoading,
+    /// Flash region holding the linked list of apps. New apps are written
+    /// after the last app in this region.
+    app_flash: &'static [u8],
+    pagebuffer: TakeCell<'static, F::Page>,
+    /// Buffer of a kernel client's write.
+    buffer: TakeCell<'static, [u8]>,
+    write: Cell<Option<Write>>,
+    apps: Grant<App, 1>,
+    client: OptionalCell<&'a dyn AppLoaderClient>,
+    /// The process using the loader through the syscall interface, if any.
+    current_app: OptionalCell<ProcessId>,
+    /// Offset in `app_flash` and length of the image being written.
+    new_app: Cell<Option<(usize, usize)>>,
+    /// The app allowed to use the syscall interface.
+    privileged_app: ShortID,
+    capability: C,
+}
+
+impl<'a, F: hil::flash::Flash, C: ProcessManagementCapability> AppLoader<'a, F, C> {
+    /// `app_flash` must start and end on page boundaries. Only the app with
+    /// the `Fixed` short ID `privileged_app` can use the syscall interface;
+    /// with `ShortID::LocallyUnique` no app can.
+    pub fn new(
+        kernel: &'static Kernel,
+        flash: &'a F,
+        loader: &'a dyn DynamicProcessLoading,
+        app_flash: &'static [u8],
+        pagebuffer: &'static mut F::Page,
+        grant: Grant<App, 1>,
+        privileged_app: ShortID,
+        capability: C,
+    ) -> AppLoader<'a, F, C> {
+        AppLoader {
+            kernel,
+            flash,
+            loader,
+            app_flash,
+            pagebuffer: TakeCell::new(pagebuffer),
+            buffer: TakeCell::empty(),
+            write: Cell::new(None),
+            apps: grant,
+            client: OptionalCell::empty(),
+            current_app: OptionalCell::empty(),
+            new_app: Cell::new(None),
+            privileged_app,
+            capability,
+        }
+    }
+
+    pub fn set_client(&self, client: &'a dyn AppLoaderClient) {
+        self.client.set(client);
+    }
+
+    /// Size of a flash page.
+    pub fn page_size(&self) -> usize {
+        self.pagebuffer
+            .map_or(mem::size_of::<F::Page>(), |page| page.as_mut().len())
+    }
+
+    /// Call `f` with the offset and length of each entry in the linked list of
+    /// apps, and whether the entry is an app (rather than padding). Returns
+    /// the offset of the end of the list.
+    fn each_entry<G: FnMut(usize, usize, bool)>(&self, mut f: G) -> usize {
+        let mut offset = 0;
+        while let Some(header) = self.app_flash.get(offset..offset + 8) {
+            let header = match header.try_into() {
+                Ok(header) => header,
+                Err(_) => break,
+            };
+            let (is_app, entry_length) = match tock_tbf::parse::parse_tbf_header_lengths(header) {
+                Ok((version, header_length, entry_length)) => {
+                    let is_app = self
+                        .app_flash
+                        .get(offset..offset + header_length as usize)
+                        .and_then(|header| tock_tbf::parse::parse_tbf_header(header, version).ok())
+                        .map_or(false, |header| header.is_app());
+                    (is_app, entry_length)
+                }
+                Err(tock_tbf::types::InitialTbfParseError::InvalidHeader(entry_length)) => {
+                    (false, entry_length)
+                }
+                Err(tock_tbf::types::InitialTbfParseError::UnableToParse) => break,
+            };
+            if entry_length == 0 {
+                break;
+            }
+            f(offset, entry_length as usize, is_app);
+            offset += entry_length as usize;
+        }
+        offset
+    }
+
+    /// Reserve flash for a new app image of `length` bytes.
+    pub fn setup(&self, length: usize) -> Result<(), ErrorCode> {
+        if self.new_app.get().is_some() || self.write.get().is_some() {
+            return Err(ErrorCode::BUSY);
+        }
+        let page_size = self.page_size();
+        if self.app_flash.as_ptr() as usize % page_size != 0 || length < 8 {
+            return Err(ErrorCode::INVAL);
+        }
+        let start = self.each_entry(|_, _, _| {});
+        if start + length > self.app_flash.len() {
+            return Err(ErrorCode::SIZE);
+        }
+        self.new_app.set(Some((start, length)));
+        Ok(())
+    }
+
+    /// Write the first `length` bytes of `buffer` at `offset` in the new app
+    /// image. The client is notified when the write completes.
+    pub fn write(
+        &self,
+        offset: usize,
+        buffer: &'static mut [u8],
+        length: usize,
+    ) -> Result<(), (ErrorCode, &'static mut [u8])> {
+        if length > buffer.len() {
+            return Err((ErrorCode::SIZE, buffer));
+        }
+        if let Err(err) = self.start_app_write(offset, length) {
+            return Err((err, buffer));
+        }
+        self.buffer.replace(buffer);
+        self.write_next_page().map_err(|err| {
+            self.write.set(None);
+            (err, self.buffer.take().unwrap_or(&mut []))
+        })
+    }
+
+    fn start_app_write(&self, offset: usize, length: usize) -> Result<(), ErrorCode> {
+        let (start, app_length) = self.new_app.get().ok_or(ErrorCode::RESERVE)?;
+        if self.write.get().is_some() {
+            return Err(ErrorCode::BUSY);
+        }
+        if length == 0 {
+            return Err(ErrorCode::INVAL);
+        }
+        if offset + length > app_length {
+            return Err(ErrorCode::SIZE);
+        }
+        self.write.set(Some(Write {
+            position: start + offset,
+            length,
+            source: Source::Buffer,
+            done: 0,
+            in_flight: 0,
+        }));
+        Ok(())
+    }
+
+    /// Write the part of the current write that falls in the next page.
+    fn write_next_page(&self) -> Result<(), ErrorCode> {
+        let mut write = self.write.get().ok_or(ErrorCode::FAIL)?;
+
+        let page_size = self.page_size();
+        let position = write.position + write.done;
+        let page_offset = position % page_size;
+        let page_start = position - page_offset;
+        let length = cmp::min(write.length - write.done, page_size - page_offset);
+        let current = self
+            .app_flash
+            .get(page_start..page_start + page_size)
+            .ok_or(ErrorCode::INVAL)?;
+
+        let pagebuffer = self.pagebuffer.take().ok_or(ErrorCode::BUSY)?;
+        // Keep the rest of the page, which may hold the end of another app.
+        let page = pagebuffer.as_mut();
+        page.copy_from_slice(current);
+        let dest = &mut page[page_offset..page_offset + length];
+        let source = write.done..write.done + length;
+        let copied = match write.source {
+            Source::Buffer => match self.current_app.extract() {
+                Some(appid) => self
+                    .apps
+                    .enter(appid, |app, _| {
+                        app.buffer
+                            .enter(|buffer| {
+                                if buffer.len() < source.end {
+                                    Err(ErrorCode::SIZE)
+                                } else {
+                                    buffer[source.clone()].copy_to_slice(dest);
+                                    Ok(())
+                                }
+                            })
+                            .unwrap_or(Err(ErrorCode::RESERVE))
+                    })
+                    .unwrap_or_else(|err| Err(err.into())),
+                None => self.buffer.map_or(Err(ErrorCode::RESERVE), |buffer| {
+                    dest.copy_from_slice(&buffer[source.clone()]);
+                    Ok(())
+                }),
+            },
+            Source::Erased | Source::Discarded => {
+                dest.iter_mut().for_each(|byte| *byte = 0xFF);
+                Ok(())
+            }
+            Source::Padding(header) => {
+                dest.copy_from_slice(&header[source]);
+                Ok(())
+            }
+        };
+        if let Err(err) = copied {
+            self.pagebuffer.replace(pagebuffer);
+            return Err(err);
+        }
+
+        write.in_flight = length;
+        self.write.set(Some(write));
+        let page_number = (self.app_flash.as_ptr() as usize + page_start) / page_size;
+        self.flash
+            .write_page(page_number, pagebuffer)
+            .map_err(|(err, pagebuffer)| {
+                self.pagebuffer.replace(pagebuffer);
+                err
+            })
+    }
+
+    /// Notify whoever started the current write that it is finished.
+    fn write_done(&self, result: Result<(), ErrorCode>) {
+        let source = match self.write.take() {
+            Some(write) => write.source,
+            None => return,
+        };
+        match self.current_app.extract() {
+            Some(appid) => {
+                if source != Source::Buffer {
+                    // Erasing or unloading ends the app's use of the loader.
+                    self.current_app.clear();
+                }
+                let _ = self.apps.enter(appid, |_app, upcalls| {
+                    upcalls
+                        .schedule_upcall(0, kernel::into_statuscode(result), 0, 0)
+                        .ok();
+                });
+            }
+            None => match source {
+                Source::Buffer => {
+                    self.buffer.take().map(|buffer| {
+                        self.client
+                            .map(move |client| client.write_done(buffer, result));
+                    });
+                }
+                Source::Erased | Source::Padding(_) => {
+                    self.client.map(|client| client.unload_done(result));
+                }
+                Source::Discarded => {
+                    self.client.map(|client| client.abort_done(result));
+                }
+            },
+        }
+    }
+
+    /// Create a process for the new app image and start it.
+    ///
+    /// If the image cannot be loaded it is erased, as if `abort()` was called,
+    /// and the client is notified when this completes.
+    pub fn load(&self) -> Result<ProcessId, ErrorCode> {
+        let (start, length) = self.new_app.get().ok_or(ErrorCode::RESERVE)?;
+        if self.write.get().is_some() {
+            return Err(ErrorCode::BUSY);
+        }
+        match self
+            .loader
+            .load_process(&self.app_flash[start..start + length])
+        {
+            Ok(processid) => {
+                self.new_app.set(None);
+                Ok(processid)
+            }
+            Err(_) => {
+                // If the erase cannot be started, the image stays reserved
+                // so that it can still be aborted.
+                let _ = self.discard_new_app();
+                Err(ErrorCode::FAIL)
+            }
+        }
+    }
+
+    /// Give up on loading the new app image, and erase the flash reserved
+    /// for it. The client is notified when the erase completes.
+    pub fn abort(&self) -> Result<(), ErrorCode> {
+        if self.write.get().is_some() {
+            return Err(ErrorCode::BUSY);
+        }
+        self.discard_new_app()
+    }
+
+    /// Start erasing the flash reserved for the new app image, so that
+    /// whatever was written there is not loaded at boot.
+    fn discard_new_app(&self) -> Result<(), ErrorCode> {
+        let (start, length) = self.new_app.get().ok_or(ErrorCode::RESERVE)?;
+        self.write.set(Some(Write {
+            position: start,
+            length,
+            source: Source::Discarded,
+            done: 0,
+            in_flight: 0,
+        }));
+        match self.write_next_page() {
+            Ok(()) => {
+                self.new_app.set(None);
+                Ok(())
+            }
+            Err(err) => {
+                self.write.set(None);
+                Err(err)
+            }
+        }
+    }
+
+    /// Terminate the process, free its process slot and release its flash.
+    /// The process must have been loaded from `app_flash`. The client is
+    /// notified when its flash has been released.
+    pub fn unload(&self, processid: ProcessId) -> Result<(), ErrorCode> {
+        if self.new_app.get().is_some() || self.write.get().is_some() {
+            return Err(ErrorCode::BUSY);
+        }
+
+        let flash = Cell::new(None);
+        self.kernel
+            .process_each_capability(&self.capability, |process| {
+                if process.processid() == processid {
+                    flash.set(Some((
+                        process.flash_start() as usize,
+                        process.flash_end() as usize,
+                    )));
+                }
+            });
+        let (flash_start, flash_end) = flash.get().ok_or(ErrorCode::INVAL)?;
+        let region_start = self.app_flash.as_ptr() as usize;
+        if flash_start < region_start || flash_end > region_start + self.app_flash.len() {
+            return Err(ErrorCode::INVAL);
+        }
+        let offset = flash_start - region_start;
+        let length = flash_end - flash_start;
+        if length < PADDING_HEADER_SIZE {
+            return Err(ErrorCode::INVAL);
+        }
+
+        // Find where the apps before this one end, to see whether this app is
+        // at the end of the list once it is removed.
+        let mut apps_end = 0;
+        let list_end = self.each_entry(|entry, entry_length, is_app| {
+            if is_app && entry != offset {
+                apps_end = entry + entry_length;
+            }
+        });
+
+        self.loader.unload_process(processid, &self.capability)?;
+
+        let write = if apps_end <= offset {
+            Write {
+                position: apps_end,
+                length: list_end - apps_end,
+                source: Source::Erased,
+                done: 0,
+                in_flight: 0,
+            }
+        } else {
+            Write {
+                position: offset,
+                length: PADDING_HEADER_SIZE,
+                source: Source::Padding(padding_header(length as u32)),
+                done: 0,
+                in_flight: 0,
+            }
+        };
+        self.write.set(Some(write));
+        self.write_next_page().map_err(|err| {
+            self.write.set(None);
+            err
+        })
+    }
+
+    /// Whether the board allows `appid` to use the syscall interface.
+    fn is_privileged(&self, appid: ProcessId) -> bool {
+        match self.privileged_app {
+            ShortID::Fixed(_) => appid.short_app_id() == self.privileged_app,
+            ShortID::LocallyUnique => false,
+        }
+    }
+
+    /// Whether `appid` may use the loader. Only one process can use the
+    /// loader at a time, and not while a kernel client is using it.
+    fn owned_by(&self, appid: ProcessId) -> bool {
+        if self.new_app.get().is_none() && self.write.get().is_none() {
+            true
+        } else {
+            self.current_app.map_or(false, |current| *current == appid)
+        }
+    }
+}
+
+/// Build the header of a padding entry of `total_size` bytes.
+fn padding_header(total_size: u32) -> [u8; PADDING_HEADER_SIZE] {
+    let version_and_size = 2u32 | ((PADDING_HEADER_SIZE as u32) << 16);
+    let flags = 0u32;
+    let checksum = version_and_size ^ total_size ^ flags;
+
+    let mut header = [0; PADDING_HEADER_SIZE];
+    header[0..4].copy_from_slice(&version_and_size.to_le_bytes());
+    header[4..8].copy_from_slice(&total_size.to_le_bytes());
+    header[8..12].copy_from_slice(&flags.to_le_bytes());
+    header[12..16].copy_from_slice(&checksum.to_le_bytes());
+    header
+}
+
+impl<F: hil::flash::Flash, C: ProcessManagementCapability> hil::flash::Client<F>
+    for AppLoader<'_, F, C>
+{
+    fn read_complete(&self, pagebuffer: &'static mut F::Page, _error: hil::flash::Error) {
+        self.pagebuffer.replace(pagebuffer);
+    }
+
+    fn write_complete(&self, pagebuffer: &'static mut F::Page, error: hil::flash::Error) {
+        self.pagebuffer.replace(pagebuffer);
+        if error != hil::flash::Error::CommandComplete {
+            self.write_done(Err(ErrorCode::FAIL));
+            return;
+        }
+
+        if let Some(mut write) = self.write.get() {
+            write.done += write.in_flight;
+            self.write.set(Some(write));
+            if write.done < write.length {
+                if let Err(err) = self.write_next_page() {
+                    self.write_done(Err(err));
+                }
+            } else {
+                self.write_done(Ok(()));
+            }
+        }
+    }
+
+    fn erase_complete(&self, _error: hil::flash::Error) {}
+}
+
+impl<F: hil::flash::Flash, C: ProcessManagementCapability> Driver for AppLoader<'_, F, C> {
+    /// Setup buffer to write from.
+    ///
+    /// ### `allow_num`
+    ///
+    /// - `0`: Set the buffer holding the next chunk of the app to write.
+    fn allow_readonly(
+        &self,
+        appid: ProcessId,
+        allow_num: usize,
+        mut slice: ReadOnlyProcessBuffer,
+    ) -> Result<ReadOnlyProcessBuffer, (ReadOnlyProcessBuffer, ErrorCode)> {
+        if !self.is_privileged(appid) {
+            return Err((slice, ErrorCode::NOSUPPORT));
+        }
+        let res = match allow_num {
+            0 => self
+                .apps
+                .enter(appid, |app, _| {
+                    mem::swap(&mut app.buffer, &mut slice);
+                    Ok(())
+                })
+                .unwrap_or_else(|err| Err(err.into())),
+            _ => Err(ErrorCode::NOSUPPORT),
+        };
+
+        match res {
+            Ok(()) => Ok(slice),
+            Err(e) => Err((slice, e)),
+        }
+    }
+
+    // Setup callbacks.
+    //
+    // ### `subscribe_num`
+    //
+    // - `0`: Write, unload or erase done callback, passed the status of the
+    //   operation. An erase follows an abort or a failed load.
+
+    /// App loader control.
+    ///
+    /// Only the board's privileged app can use these commands; for any other
+    /// app they return `NOSUPPORT`.
+    ///
+    /// ### `command_num`
+    ///
+    /// - `0`: Driver check.
+    /// - `1`: Get the flash page size.
+    /// - `2`: Reserve flash for a new app of `arg1` bytes.
+    /// - `3`: Write the first `arg2` bytes of the allowed buffer at offset
+    ///   `arg1` in the new app.
+    /// - `4`: Load the new app. Returns the identifier of the new process. If
+    ///   the app cannot be loaded, its image is erased and the callback is
+    ///   called once the loader can be used again.
+    /// - `5`: Abort loading the new app and erase its image. The callback is
+    ///   called when the erase completes.
+    /// - `6`: Unload the process with identifier `arg1` and release its flash.
+    fn command(
+        &self,
+        command_num: usize,
+        arg1: usize,
+        arg2: usize,
+        appid: ProcessId,
+    ) -> CommandReturn {
+        if !self.is_privileged(appid) {
+            return CommandReturn::failure(ErrorCode::NOSUPPORT);
+        }
+        if command_num > 1 && !self.owned_by(appid) {
+            return CommandReturn::failure(ErrorCode::BUSY);
+        }
+
+        match command_num {
+            0 /* This driver exists. */ => CommandReturn::success(),
+
+            1 /* Page size */ => CommandReturn::success_u32(self.page_size() as u32),
+
+            2 /* Setup */ => match self.setup(arg1) {
+                Ok(()) => {
+                    self.current_app.set(appid);
+                    CommandReturn::success()
+                }
+                Err(e) => CommandReturn::failure(e),
+            },
+
+            3 /* Write */ => {
+                let res = self
+                    .apps
+                    .enter(appid, |app, _| {
+                        if app.buffer.len() < arg2 {
+                            Err(ErrorCode::SIZE)
+                        } else {
+                            Ok(())
+                        }
+                    })
+                    .unwrap_or_else(|err| Err(err.into()))
+                    .and_then(|()| self.start_app_write(arg1, arg2))
+                    .and_then(|()| {
+                        self.write_next_page().map_err(|err| {
+                            self.write.set(None);
+                            err
+                        })
+                    });
+                match res {
+                    Ok(()) => CommandReturn::success(),
+                    Err(e) => CommandReturn::failure(e),
+                }
+            }
+
+            4 /* Load */ => match self.load() {
+                Ok(processid) => {
+                    self.current_app.clear();
+                    CommandReturn::success_u32(processid.id() as u32)
+                }
+                Err(e) => {
+                    // The app keeps the loader until its image is erased.
+                    if self.new_app.get().is_none() && self.write.get().is_none() {
+                        self.current_app.clear();
+                    }
+                    CommandReturn::failure(e)
+                }
+            },
+
+            5 /* Abort */ => match self.abort() {
+                Ok(()) => CommandReturn::success(),
+                Err(e) => CommandReturn::failure(e),
+            },
+
+            6 /* Unload */ => {
+                let processid = OptionalCell::empty();
+                self.kernel
+                    .process_each_capability(&self.capability, |process| {
+                        if process.processid().id() == arg1 {
+                            processid.set(process.processid());
+                        }
+                    });
+                match processid.extract().map_or(Err(ErrorCode::INVAL), |processid| {
+                    self.unload(processid)
+                }) {
+                    Ok(()) => {
+                        self.current_app.set(appid);
+                        CommandReturn::success()
+                    }
+                    Err(e) => CommandReturn::failure(e),
+                }
+            }
+
+            _ /* Unknown command num */ => CommandReturn::failure(ErrorCode::NOSUPPORT),
+        }
+    }
+
+    fn allocate_grant(&self, processid: ProcessId) -> Result<(), kernel::procs::Error> {
+        self.apps.enter(processid, |_, _| {})
+    }
+}
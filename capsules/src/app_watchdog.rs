@@ -0,0 +1,329 @@
+//! Software watchdog for userspace processes.
+//!
+//! Processes register a heartbeat interval with this capsule, and must then
+//! send a heartbeat at least once per interval. A process that misses its
+//! heartbeat is assumed to be hung and is restarted with `try_restart()`.
+//! Registrations are kept by the short ID of the app, so they outlive the
+//! restart: a restarted process has one interval to send a heartbeat or
+//! register again before it is restarted once more. Only processes with a
+//! fixed short ID can use the watchdog, so that no app can stop the monitoring
+//! of another one by claiming its name.
+//!
+//! The board also lists the short IDs of the apps it considers critical. The
+//! capsule is `healthy()` only while every critical process is loaded, has not
+//! faulted or exited, and has not missed two heartbeats in a row. Boards return
+//! this from `Platform::watchdog_healthy()`, so that the kernel stops tickling
+//! the hardware watchdog, and the chip resets, when a critical process cannot
+//! be recovered by restarting it.
+//!
+//! Usage
+//! -----
+//!
+//! ```rust
+//! # use kernel::static_init;
+//! # use capsules::app_watchdog::AppWatchdog;
+//! # use capsules::virtual_alarm::VirtualMuxAlarm;
+//!
+//! let critical = static_init!(
+//!     [ShortID; 1],
+//!     [ShortID::Fixed(NonZeroU32::new(0x100).unwrap())]
+//! );
+//! let watchdog_alarm = static_init!(VirtualMuxAlarm<'static, Ast>, VirtualMuxAlarm::new(mux_alarm));
+//! let app_watchdog = static_init!(
+//!     AppWatchdog<'static, VirtualMuxAlarm<'static, Ast>, Capability, 4>,
+//!     AppWatchdog::new(board_kernel, watchdog_alarm, critical, Capability)
+//! );
+//! watchdog_alarm.set_alarm_client(app_watchdog);
+//! ```
+//!
+//! Userspace Interface
+//! -------------------
+//!
+//! ### Command
+//!
+//! - `0`: Driver check.
+//! - `1`: Start monitoring the process, with a heartbeat interval in
+//!   milliseconds given in `r2`. Starting again changes the interval.
+//! - `2`: Heartbeat.
+//! - `3`: Stop monitoring the process.
+//!
+//! Commands other than `0` return `NOSUPPORT` for processes without a fixed
+//! short ID.
+
+use core::cell::Cell;
+use kernel::capabilities::ProcessManagementCapability;
+use kernel::common::cells::OptionalCell;
+use kernel::hil::time::{self, Alarm, Ticks};
+use kernel::procs::{Process, State};
+use kernel::{CommandReturn, Driver, ErrorCode, Kernel, ProcessId, ShortID};
+
+/// Syscall driver number.
+use crate::driver;
+pub const DRIVER_NUM: usize = driver::NUM::AppWatchdog as usize;
+
+/// Completion code of processes restarted for missing their heartbeat.
+pub const MISSED_HEARTBEAT: u32 = 0xDEAD;
+
+/// Consecutive missed heartbeats after which a critical process is no longer
+/// healthy.
+const CRITICAL_MISSES: usize = 2;
+
+#[derive(Clone, Copy)]
+struct Heartbeat<T: Ticks> {
+    short_id: ShortID,
+    /// Interval in ticks of the alarm.
+    interval: T,
+    /// Time of the last heartbeat, or of the last restart.
+    reference: T,
+    /// Heartbeats missed since the last one received.
+    misses: usize,
+}
+
+impl<T: Ticks> Heartbeat<T> {
+    // Only used as an array initializer, each copy is a distinct slot.
+    #[allow(clippy::declare_interior_mutable_const)]
+    const NONE: Cell<Option<Heartbeat<T>>> = Cell::new(None);
+
+    /// Whether the heartbeat is due at `now`.
+    fn missed(&self, now: T) -> bool {
+        now.wrapping_sub(self.reference) >= self.interval
+    }
+
+    /// Ticks from `now` until the heartbeat is due.
+    fn remaining(&self, now: T) -> T {
+        if self.missed(now) {
+            T::from(0)
+        } else {
+            self.interval.wrapping_sub(now.wrapping_sub(self.reference))
+        }
+    }
+}
+
+pub struct AppWatchdog<'a, A: Alarm<'a>, C: ProcessManagementCapability, const NUM_APPS: usize> {
+    kernel: &'static Kernel,
+    alarm: &'a A,
+    critical: &'static [ShortID],
+    /// Process identifiers of the first `NUM_APPS` critical apps, so that
+    /// `healthy()` only searches the processes again after a restart.
+    critical_processes: [OptionalCell<ProcessId>; NUM_APPS],
+    heartbeats: [Cell<Option<Heartbeat<A::Ticks>>>; NUM_APPS],
+    capability: C,
+}
+
+impl<'a, A: Alarm<'a>, C: ProcessManagementCapability, const NUM_APPS: usize>
+    AppWatchdog<'a, A, C, NUM_APPS>
+{
+    /// Monitor up to `NUM_APPS` processes. The apps with the short IDs in
+    /// `critical` must stay healthy for the hardware watchdog to be tickled.
+    pub fn new(
+        kernel: &'static Kernel,
+        alarm: &'a A,
+        critical: &'static [ShortID],
+        capability: C,
+    ) -> AppWatchdog<'a, A, C, NUM_APPS> {
+        const NO_PROCESS: OptionalCell<ProcessId> = OptionalCell::empty();
+        AppWatchdog {
+            kernel,
+            alarm,
+            critical,
+            critical_processes: [NO_PROCESS; NUM_APPS],
+            heartbeats: [Heartbeat::<A::Ticks>::NONE; NUM_APPS],
+            capability,
+        }
+    }
+
+    /// Whether all critical processes are alive and sending their heartbeats.
+    pub fn healthy(&self) -> bool {
+        self.critical.iter().enumerate().all(|(i, short_id)| {
+            self.critical_alive(*short_id, self.critical_processes.get(i))
+                && self
+                    .find(*short_id)
+                    .and_then(|slot| slot.get())
+                    .map_or(true, |heartbeat| heartbeat.misses < CRITICAL_MISSES)
+        })
+    }
+
+    /// Whether the process of the critical app `short_id` is loaded and has
+    /// not faulted or exited. `cache` holds the identifier of the process
+    /// found last time, if any.
+    fn critical_alive(&self, short_id: ShortID, cache: Option<&OptionalCell<ProcessId>>) -> bool {
+        let alive = |process: &dyn Process| match process.get_state() {
+            State::Faulted | State::Terminated => false,
+            _ => true,
+        };
+        if let Some(processid) = cache.and_then(|cache| cache.extract()) {
+            let cached = self.kernel.process_map_or_capability(
+                &self.capability,
+                None,
+                processid,
+                |process| Some(alive(process)),
+            );
+            if let Some(alive) = cached {
+                return alive;
+            }
+        }
+
+        // The process restarted or was not looked up yet.
+        let found = Cell::new(None);
+        self.kernel
+            .process_each_capability(&self.capability, |process| {
+                if process.short_app_id() == short_id {
+                    found.set(Some((process.processid(), alive(process))));
+                }
+            });
+        match found.get() {
+            Some((processid, alive)) => {
+                if let Some(cache) = cache {
+                    cache.set(processid);
+                }
+                alive
+            }
+            None => false,
+        }
+    }
+
+    fn find(&self, short_id: ShortID) -> Option<&Cell<Option<Heartbeat<A::Ticks>>>> {
+        self.heartbeats.iter().find(|slot| {
+            slot.get()
+                .map_or(false, |heartbeat| heartbeat.short_id == short_id)
+        })
+    }
+
+    fn start(&self, short_id: ShortID, interval_ms: usize) -> Result<(), ErrorCode> {
+        if interval_ms == 0 || interval_ms > u32::MAX as usize {
+            return Err(ErrorCode::INVAL);
+        }
+        let slot = self
+            .find(short_id)
+            .or_else(|| self.heartbeats.iter().find(|slot| slot.get().is_none()))
+            .ok_or(ErrorCode::NOMEM)?;
+        // A process registering again after a restart has not recovered
+        // until it sends a heartbeat, so its misses are kept.
+        slot.set(Some(Heartbeat {
+            short_id,
+            interval: A::ticks_from_ms(interval_ms as u32),
+            reference: self.alarm.now(),
+            misses: slot.get().map_or(0, |heartbeat| heartbeat.misses),
+        }));
+        self.reset_alarm();
+        Ok(())
+    }
+
+    fn heartbeat(&self, short_id: ShortID) -> Result<(), ErrorCode> {
+        let slot = self.find(short_id).ok_or(ErrorCode::OFF)?;
+        slot.set(slot.get().map(|heartbeat| Heartbeat {
+            reference: self.alarm.now(),
+            misses: 0,
+            ..heartbeat
+        }));
+        self.reset_alarm();
+        Ok(())
+    }
+
+    fn stop(&self, short_id: ShortID) -> Result<(), ErrorCode> {
+        self.find(short_id).ok_or(ErrorCode::OFF)?.set(None);
+        self.reset_alarm();
+        Ok(())
+    }
+
+    /// Set the alarm for the earliest heartbeat deadline.
+    fn reset_alarm(&self) {
+        let now = self.alarm.now();
+        let next = self
+            .heartbeats
+            .iter()
+            .filter_map(|slot| slot.get())
+            .map(|heartbeat| heartbeat.remaining(now))
+            .min();
+        match next {
+            Some(remaining) => self.alarm.set_alarm(now, remaining),
+            None => {
+                let _ = self.alarm.disarm();
+            }
+        }
+    }
+}
+
+impl<'a, A: Alarm<'a>, C: ProcessManagementCapability, const NUM_APPS: usize> time::AlarmClient
+    for AppWatchdog<'a, A, C, NUM_APPS>
+{
+    fn alarm(&self) {
+        let now = self.alarm.now();
+        for slot in self.heartbeats.iter() {
+            if let Some(heartbeat) = slot.get() {
+                if !heartbeat.missed(now) {
+                    continue;
+                }
+                // Only restart processes that could still be sending
+                // heartbeats. A process that faulted or exited is left to the
+                // fault policy, and one stopped from the process console
+                // stays stopped.
+                let restarted = Cell::new(false);
+                self.kernel
+                    .process_each_capability(&self.capability, |process| {
+                        if process.short_app_id() == heartbeat.short_id {
+                            match process.get_state() {
+                                State::Running | State::Yielded | State::Unstarted => {
+                                    process.try_restart(MISSED_HEARTBEAT);
+                                    restarted.set(true);
+                                }
+                                _ => {}
+                            }
+                        }
+                    });
+                if restarted.get() {
+                    slot.set(Some(Heartbeat {
+                        reference: now,
+                        misses: heartbeat.misses + 1,
+                        ..heartbeat
+                    }));
+                } else {
+                    slot.set(None);
+                }
+            }
+        }
+        self.reset_alarm();
+    }
+}
+
+impl<'a, A: Alarm<'a>, C: ProcessManagementCapability, const NUM_APPS: usize> Driver
+    for AppWatchdog<'a, A, C, NUM_APPS>
+{
+    /// Register and send heartbeats.
+    ///
+    /// ### `command_num`
+    ///
+    /// - `0`: Driver check.
+    /// - `1`: Start monitoring the process, with a heartbeat interval of `r2`
+    ///   milliseconds.
+    /// - `2`: Heartbeat.
+    /// - `3`: Stop monitoring the process.
+    fn command(
+        &self,
+        command_num: usize,
+        data: usize,
+        _: usize,
+        appid: ProcessId,
+    ) -> CommandReturn {
+        if command_num == 0 {
+            return CommandReturn::success();
+        }
+        let res = match appid.short_app_id() {
+            ShortID::Fixed(_) => match command_num {
+                1 => self.start(appid.short_app_id(), data),
+                2 => self.heartbeat(appid.short_app_id()),
+                3 => self.stop(appid.short_app_id()),
+                _ => Err(ErrorCode::NOSUPPORT),
+            },
+            ShortID::LocallyUnique => Err(ErrorCode::NOSUPPORT),
+        };
+        match res {
+            Ok(()) => CommandReturn::success(),
+            Err(e) => CommandReturn::failure(e),
+        }
+    }
+
+    fn allocate_grant(&self, _processid: ProcessId) -> Result<(), kernel::procs::Error> {
+        Ok(())
+    }
+}
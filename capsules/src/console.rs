@@ -150,6 +150,25 @@ impl<'a> Console<'a> {
         }
     }
 
+    /// Give the grant of `app_id` back to the process once it has no buffers
+    /// allowed, no upcalls subscribed and no transfer in progress.
+    fn free_if_unused(&self, app_id: ProcessId) {
+        let busy = self.tx_in_progress.contains(&app_id) || self.rx_in_progress.contains(&app_id);
+        let unused = self
+            .apps
+            .enter(app_id, |app, upcalls| {
+                app.write_buffer.len() == 0
+                    && app.read_buffer.len() == 0
+                    && app.write_remaining == 0
+                    && !app.pending_write
+                    && !upcalls.has_subscriptions()
+            })
+            .unwrap_or(false);
+        if unused && !busy {
+            let _ = self.apps.free(app_id);
+        }
+    }
+
     /// Internal helper function for starting a receive operation
     fn receive_new(&self, app_id: ProcessId, app: &mut App, len: usize) -> Result<(), ErrorCode> {
         if self.rx_buffer.is_none() {
@@ -196,6 +215,7 @@ impl Driver for Console<'_> {
                 .map_err(ErrorCode::from),
             _ => Err(ErrorCode::NOSUPPORT),
         };
+        self.free_if_unused(appid);
 
         if let Err(e) = res {
             Err((slice, e))
@@ -224,6 +244,7 @@ impl Driver for Console<'_> {
                 .map_err(ErrorCode::from),
             _ => Err(ErrorCode::NOSUPPORT),
         };
+        self.free_if_unused(appid);
 
         if let Err(e) = res {
             Err((slice, e))
@@ -286,6 +307,10 @@ impl Driver for Console<'_> {
         }
     }
 
+    fn unsubscribed(&self, processid: ProcessId, _subscribe_num: usize) {
+        self.free_if_unused(processid);
+    }
+
     fn allocate_grant(&self, processid: ProcessId) -> Result<(), kernel::procs::Error> {
         self.apps.enter(processid, |_, _| {})
     }
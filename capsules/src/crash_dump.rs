@@ -0,0 +1,480 @@
+//! Saves the state of faulted processes to flash.
+//!
+//! When a process faults the kernel normally prints its state with
+//! `print_full_process()`, and that information is lost if nothing is
+//! listening on the debug UART. `CrashDump` is a `ProcessFaultPolicy` that
+//! instead serialises the state of the faulting process into a reserved
+//! region of flash, where it survives reboots. It then defers to another
+//! fault policy to decide what the kernel does with the process.
+//!
+//! Saved crash dumps can be listed with the `crashes` command of the
+//! `ProcessConsole`, or read out of the flash region by a host tool.
+//!
+//! Flash Format
+//! ------------
+//!
+//! Each flash page of the region holds one crash dump. The region is used as
+//! a ring: the dump with the highest sequence number is the newest one, and
+//! when all pages are full the oldest dump is overwritten. Pages that do not
+//! start with the magic value are empty. All fields are little endian.
+//!
+//! ```text
+//! 0          4          8       9       10         12         16
+//! +----------+----------+-------+-------+----------+----------+
+//! | magic    | sequence | vers. | state | restarts | short ID |
+//! +----------+----------+-------+-------+----------+----------+
+//! 16         20      21        22        24         28         32
+//! +----------+-------+---------+---------+----------+----------+
+//! | syscalls | class | name len| regs len| arg 0    | arg 1    |
+//! +----------+-------+---------+---------+----------+----------+
+//! 32           34         36
+//! +------------+----------+------+-----------+-------+
+//! | stack len  | reserved | name | registers | stack |
+//! +------------+----------+------+-----------+-------+
+//! ```
+//!
+//! - `magic` is `0x48535243` ("CRSH").
+//! - `state` is the state of the process when it faulted: 0 Running,
+//!   1 Yielded, 2 StoppedRunning, 3 StoppedYielded, 4 Faulted, 5 Terminated,
+//!   6 Unstarted.
+//! - `short ID` is 0 if the process has no fixed short ID.
+//! - `class` is the `SyscallClass` of the last system call of the process,
+//!   or 0xff if it has not made one. `arg 0` and `arg 1` are the driver and
+//!   subdriver number for subscribe, command and allow, the operand and
+//!   argument for memop, and the identifier and argument for yield and exit.
+//! - `registers` are the stored registers of the process as written by
+//!   `UserspaceKernelBoundary::store_context()`.
+//! - `stack` is the process stack, starting at the lowest address the stack
+//!   pointer has reached, truncated to fit in the page.
+//!
+//! Usage
+//! -----
+//!
+//! ```rust
+//! # use kernel::static_init;
+//! # use capsules::crash_dump::CrashDump;
+//!
+//! let crash_dump = static_init!(
+//!     CrashDump<'static, sam4l::flashcalw::FLASHCALW>,
+//!     CrashDump::new(
+//!         &sam4l::flashcalw::FLASH_CONTROLLER,
+//!         &FAULT_RESPONSE,
+//!         0x3e000 / 512,
+//!         4,
+//!         &mut CRASH_DUMP_PAGE,
+//!     )
+//! );
+//! hil::flash::HasClient::set_client(&sam4l::flashcalw::FLASH_CONTROLLER, crash_dump);
+//! crash_dump.initialize();
+//! crash_dump.set_client(process_console);
+//! process_console.set_crash_dumps(crash_dump);
+//! ```
+//!
+//! and pass `crash_dump` as the fault policy when loading processes.
+
+use core::cell::Cell;
+use core::convert::TryFrom;
+use core::str;
+
+use kernel::common::cells::{OptionalCell, TakeCell};
+use kernel::hil::flash::{self, Flash};
+use kernel::procs::{FaultAction, Process, ProcessFaultPolicy, State};
+use kernel::syscall::{Syscall, SyscallClass};
+use kernel::{ErrorCode, ShortID};
+
+/// Value at the start of every page holding a crash dump.
+pub const MAGIC: u32 = 0x48535243;
+/// Version of the crash dump format.
+pub const VERSION: u8 = 1;
+/// Length of the fixed part of a crash dump, before the variable length
+/// process name, registers and stack.
+pub const HEADER_LENGTH: usize = 36;
+
+const NO_SYSCALL: u8 = 0xff;
+
+const STATES: [State; 7] = [
+    State::Running,
+    State::Yielded,
+    State::StoppedRunning,
+    State::StoppedYielded,
+    State::Faulted,
+    State::Terminated,
+    State::Unstarted,
+];
+
+/// A crash dump read back from flash.
+pub struct CrashRecord<'b> {
+    /// Increases by one for each crash dump that is saved.
+    pub sequence: u32,
+    pub process_name: &'b str,
+    /// The fixed short ID of the process, or 0 if it had none.
+    pub short_id: u32,
+    /// The state of the process when it faulted, if known.
+    pub state: Option<State>,
+    pub restart_count: u16,
+    pub syscall_count: u32,
+    /// The class and first two arguments of the last system call.
+    pub last_syscall: Option<(SyscallClass, u32, u32)>,
+    pub registers: &'b [u8],
+    pub stack: &'b [u8],
+}
+
+/// Receives crash dumps read back from flash.
+pub trait CrashDumpClient {
+    /// Called for each saved crash dump, oldest first.
+    fn crash_dump(&self, record: &CrashRecord);
+
+    /// Called once all crash dumps have been read.
+    fn crash_dumps_done(&self, result: Result<(), ErrorCode>);
+}
+
+/// Interface for reading saved crash dumps.
+pub trait CrashDumpReader {
+    /// Read all saved crash dumps, passing each of them to the client.
+    /// Returns `BUSY` if a crash dump is being written or read.
+    fn read_crash_dumps(&self) -> Result<(), ErrorCode>;
+}
+
+#[derive(Clone, Copy, PartialEq)]
+enum Operation {
+    /// Not initialized yet, crash dumps are dropped.
+    Uninitialized,
+    Idle,
+    /// Looking for the newest crash dump, currently reading the given slot.
+    Scanning(usize),
+    Writing,
+    /// Reading crash dumps for the client, currently the given slot relative
+    /// to the oldest one.
+    Reading(usize),
+}
+
+pub struct CrashDump<'a, F: Flash + 'static> {
+    flash: &'a F,
+    policy: &'a dyn ProcessFaultPolicy,
+    first_page: usize,
+    num_pages: usize,
+    page: TakeCell<'static, F::Page>,
+    operation: Cell<Operation>,
+    /// Slot the next crash dump is written to, which is also the slot of the
+    /// oldest crash dump.
+    next_slot: Cell<usize>,
+    next_sequence: Cell<u32>,
+    dropped: Cell<usize>,
+    client: OptionalCell<&'a dyn CrashDumpClient>,
+}
+
+impl<'a, F: Flash> CrashDump<'a, F> {
+    /// Create a crash dump store in the `num_pages` flash pages starting at
+    /// page `first_page`. `policy` decides what happens to the faulted
+    /// process.
+    pub fn new(
+        flash: &'a F,
+        policy: &'a dyn ProcessFaultPolicy,
+        first_page: usize,
+        num_pages: usize,
+        page: &'static mut F::Page,
+    ) -> CrashDump<'a, F> {
+        CrashDump {
+            flash: flash,
+            policy: policy,
+            first_page: first_page,
+            num_pages: num_pages,
+            page: TakeCell::new(page),
+            operation: Cell::new(Operation::Uninitialized),
+            next_slot: Cell::new(0),
+            next_sequence: Cell::new(0),
+            dropped: Cell::new(0),
+            client: OptionalCell::empty(),
+        }
+    }
+
+    pub fn set_client(&self, client: &'a dyn CrashDumpClient) {
+        self.client.set(client);
+    }
+
+    /// Find the newest saved crash dump so new ones do not overwrite it.
+    /// Crash dumps are only saved once this has finished.
+    pub fn initialize(&self) -> Result<(), ErrorCode> {
+        if self.operation.get() != Operation::Uninitialized {
+            return Err(ErrorCode::ALREADY);
+        }
+        if self.num_pages == 0 {
+            return Err(ErrorCode::INVAL);
+        }
+        self.read_slot(Operation::Scanning(0), 0)
+    }
+
+    /// Number of crash dumps that could not be saved because the flash was
+    /// busy or the store was not initialized.
+    pub fn dropped_count(&self) -> usize {
+        self.dropped.get()
+    }
+
+    fn read_slot(&self, operation: Operation, slot: usize) -> Result<(), ErrorCode> {
+        let page = self.page.take().ok_or(ErrorCode::BUSY)?;
+        match self.flash.read_page(self.first_page + slot, page) {
+            Ok(()) => {
+                self.operation.set(operation);
+                Ok(())
+            }
+            Err((error, page)) => {
+                self.page.replace(page);
+                Err(error)
+            }
+        }
+    }
+
+    fn save(&self, process: &dyn Process) -> Result<(), ErrorCode> {
+        if self.operation.get() != Operation::Idle {
+            return Err(ErrorCode::BUSY);
+        }
+        let page = self.page.take().ok_or(ErrorCode::BUSY)?;
+        encode(page.as_mut(), self.next_sequence.get(), process);
+
+        let slot = self.next_slot.get();
+        match self.flash.write_page(self.first_page + slot, page) {
+            Ok(()) => {
+                self.operation.set(Operation::Writing);
+                self.next_slot.set((slot + 1) % self.num_pages);
+                self.next_sequence
+                    .set(self.next_sequence.get().wrapping_add(1));
+                Ok(())
+            }
+            Err((error, page)) => {
+                self.page.replace(page);
+                Err(error)
+            }
+        }
+    }
+
+    fn next_read(&self, operation: Operation, slot: usize) {
+        if slot >= self.num_pages {
+            self.operation.set(Operation::Idle);
+            self.client.map(|client| client.crash_dumps_done(Ok(())));
+            return;
+        }
+        let _ = self
+            .read_slot(operation, (self.next_slot.get() + slot) % self.num_pages)
+            .map_err(|error| {
+                self.operation.set(Operation::Idle);
+                self.client
+                    .map(|client| client.crash_dumps_done(Err(error)));
+            });
+    }
+}
+
+impl<'a, F: Flash> ProcessFaultPolicy for CrashDump<'a, F> {
+    fn action(&self, process: &dyn Process) -> FaultAction {
+        if self.save(process).is_err() {
+            self.dropped.set(self.dropped.get() + 1);
+        }
+        self.policy.action(process)
+    }
+}
+
+impl<'a, F: Flash> CrashDumpReader for CrashDump<'a, F> {
+    fn read_crash_dumps(&self) -> Result<(), ErrorCode> {
+        match self.operation.get() {
+            Operation::Idle => self.read_slot(Operation::Reading(0), self.next_slot.get()),
+            Operation::Uninitialized => Err(ErrorCode::OFF),
+            _ => Err(ErrorCode::BUSY),
+        }
+    }
+}
+
+impl<'a, F: Flash> flash::Client<F> for CrashDump<'a, F> {
+    fn read_complete(&self, page: &'static mut F::Page, error: flash::Error) {
+        let record = match error {
+            flash::Error::CommandComplete => decode(page.as_mut()),
+            flash::Error::FlashError => None,
+        };
+        match self.operation.get() {
+            Operation::Scanning(slot) => {
+                if let Some(record) = record {
+                    if record.sequence >= self.next_sequence.get() {
+                        self.next_sequence.set(record.sequence.wrapping_add(1));
+                        self.next_slot.set((slot + 1) % self.num_pages);
+                    }
+                }
+                self.page.replace(page);
+                if slot + 1 < self.num_pages {
+                    if self
+                        .read_slot(Operation::Scanning(slot + 1), slot + 1)
+                        .is_err()
+                    {
+                        self.operation.set(Operation::Idle);
+                    }
+                } else {
+                    self.operation.set(Operation::Idle);
+                }
+            }
+            Operation::Reading(slot) => {
+                if let Some(record) = record {
+                    self.client.map(|client| client.crash_dump(&record));
+                }
+                self.page.replace(page);
+                self.next_read(Operation::Reading(slot + 1), slot + 1);
+            }
+            _ => {
+                self.page.replace(page);
+            }
+        }
+    }
+
+    fn write_complete(&self, page: &'static mut F::Page, _error: flash::Error) {
+        self.page.replace(page);
+        self.operation.set(Operation::Idle);
+    }
+
+    fn erase_complete(&self, _error: flash::Error) {}
+}
+
+fn put_u16(buf: &mut [u8], offset: usize, value: u16) {
+    buf[offset..offset + 2].copy_from_slice(&value.to_le_bytes());
+}
+
+fn put_u32(buf: &mut [u8], offset: usize, value: u32) {
+    buf[offset..offset + 4].copy_from_slice(&value.to_le_bytes());
+}
+
+fn get_u16(buf: &[u8], offset: usize) -> u16 {
+    u16::from_le_bytes([buf[offset], buf[offset + 1]])
+}
+
+fn get_u32(buf: &[u8], offset: usize) -> u32 {
+    u32::from_le_bytes([
+        buf[offset],
+        buf[offset + 1],
+        buf[offset + 2],
+        buf[offset + 3],
+    ])
+}
+
+fn encode_syscall(syscall: Syscall) -> (u8, usize, usize) {
+    match syscall {
+        Syscall::Yield { which, address } => (SyscallClass::Yield as u8, which, address as usize),
+        Syscall::YieldWaitFor {
+            driver_number,
+            subdriver_number,
+        } => (SyscallClass::Yield as u8, driver_number, subdriver_number),
+        Syscall::Subscribe {
+            driver_number,
+            subdriver_number,
+            ..
+        } => (
+            SyscallClass::Subscribe as u8,
+            driver_number,
+            subdriver_number,
+        ),
+        Syscall::Command {
+            driver_number,
+            subdriver_number,
+            ..
+        } => (SyscallClass::Command as u8, driver_number, subdriver_number),
+        Syscall::ReadWriteAllow {
+            driver_number,
+            subdriver_number,
+            ..
+        } => (
+            SyscallClass::ReadWriteAllow as u8,
+            driver_number,
+            subdriver_number,
+        ),
+        Syscall::ReadOnlyAllow {
+            driver_number,
+            subdriver_number,
+            ..
+        } => (
+            SyscallClass::ReadOnlyAllow as u8,
+            driver_number,
+            subdriver_number,
+        ),
+        Syscall::Memop { operand, arg0 } => (SyscallClass::Memop as u8, operand, arg0),
+        Syscall::Exit {
+            which,
+            completion_code,
+        } => (SyscallClass::Exit as u8, which, completion_code),
+        Syscall::Thread {
+            operation, arg0, ..
+        } => (SyscallClass::Thread as u8, operation, arg0),
+    }
+}
+
+/// Serialise the state of `process` into `buf`, which must be at least
+/// `HEADER_LENGTH` bytes long.
+fn encode(buf: &mut [u8], sequence: u32, process: &dyn Process) {
+    for byte in buf.iter_mut() {
+        *byte = 0xff;
+    }
+
+    let state = STATES
+        .iter()
+        .position(|state| *state == process.get_state())
+        .unwrap_or(0xff);
+    let short_id = match process.short_app_id() {
+        ShortID::LocallyUnique => 0,
+        ShortID::Fixed(id) => id.get(),
+    };
+    let (class, arg0, arg1) = process
+        .debug_last_syscall()
+        .map_or((NO_SYSCALL, 0, 0), encode_syscall);
+
+    put_u32(buf, 0, MAGIC);
+    put_u32(buf, 4, sequence);
+    buf[8] = VERSION;
+    buf[9] = state as u8;
+    put_u16(buf, 10, process.get_restart_count() as u16);
+    put_u32(buf, 12, short_id);
+    put_u32(buf, 16, process.debug_syscall_count() as u32);
+    buf[20] = class;
+    put_u32(buf, 24, arg0 as u32);
+    put_u32(buf, 28, arg1 as u32);
+    put_u16(buf, 34, 0);
+
+    let mut offset = HEADER_LENGTH;
+    let name = process.get_process_name().as_bytes();
+    let name_length = name.len().min(u8::MAX as usize).min(buf.len() - offset);
+    buf[offset..offset + name_length].copy_from_slice(&name[..name_length]);
+    buf[21] = name_length as u8;
+    offset += name_length;
+
+    let registers_length = process.get_stored_state(&mut buf[offset..]).unwrap_or(0);
+    put_u16(buf, 22, registers_length as u16);
+    offset += registers_length;
+
+    let stack_length = process.debug_copy_stack(&mut buf[offset..]);
+    put_u16(buf, 32, stack_length as u16);
+}
+
+/// Parse the crash dump in `buf`, if there is one.
+fn decode(buf: &[u8]) -> Option<CrashRecord> {
+    if buf.len() < HEADER_LENGTH || get_u32(buf, 0) != MAGIC || buf[8] != VERSION {
+        return None;
+    }
+
+    let name_end = HEADER_LENGTH + buf[21] as usize;
+    let registers_end = name_end + get_u16(buf, 22) as usize;
+    let stack_end = registers_end + get_u16(buf, 32) as usize;
+    if stack_end > buf.len() {
+        return None;
+    }
+
+    let last_syscall = match buf[20] {
+        NO_SYSCALL => None,
+        class => SyscallClass::try_from(class)
+            .ok()
+            .map(|class| (class, get_u32(buf, 24), get_u32(buf, 28))),
+    };
+
+    Some(CrashRecord {
+        sequence: get_u32(buf, 4),
+        process_name: str::from_utf8(&buf[HEADER_LENGTH..name_end]).unwrap_or(""),
+        short_id: get_u32(buf, 12),
+        state: STATES.get(buf[9] as usize).copied(),
+        restart_count: get_u16(buf, 10),
+        syscall_count: get_u32(buf, 16),
+        last_syscall: last_syscall,
+        registers: &buf[name_end..registers_end],
+        stack: &buf[registers_end..stack_end],
+    })
+}
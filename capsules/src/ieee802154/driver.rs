@@ -11,9 +11,7 @@ use core::cell::Cell;
 use core::cmp::min;
 use core::mem;
 use kernel::common::cells::{MapCell, OptionalCell, TakeCell};
-use kernel::common::dynamic_deferred_call::{
-    DeferredCallHandle, DynamicDeferredCall, DynamicDeferredCallClient,
-};
+use kernel::common::deferred_call::{DeferredCall, DeferredCallClient};
 use kernel::{
     CommandReturn, Driver, ErrorCode, Grant, ProcessId, ReadOnlyProcessBuffer,
     ReadWriteProcessBuffer, ReadableProcessBuffer, WriteableProcessBuffer,
@@ -184,10 +182,7 @@ pub struct RadioDriver<'a> {
     kernel_tx: TakeCell<'static, [u8]>,
 
     /// Used to ensure callbacks are delivered during upcalls
-    deferred_caller: &'a DynamicDeferredCall,
-
-    /// Also used for deferred calls
-    handle: OptionalCell<DeferredCallHandle>,
+    deferred_call: DeferredCall,
 
     /// Used to deliver callbacks to the correct app during deferred calls
     saved_appid: OptionalCell<ProcessId>,
@@ -201,7 +196,6 @@ impl<'a> RadioDriver<'a> {
         mac: &'a dyn device::MacDevice<'a>,
         grant: Grant<App, 2>,
         kernel_tx: &'static mut [u8],
-        deferred_caller: &'a DynamicDeferredCall,
     ) -> RadioDriver<'a> {
         RadioDriver {
             mac,
@@ -212,17 +206,12 @@ impl<'a> RadioDriver<'a> {
             apps: grant,
             current_app: OptionalCell::empty(),
             kernel_tx: TakeCell::new(kernel_tx),
-            deferred_caller,
             saved_appid: OptionalCell::empty(),
             saved_result: OptionalCell::empty(),
-            handle: OptionalCell::empty(),
+            deferred_call: DeferredCall::new(),
         }
     }
 
-    pub fn initialize_callback_handle(&self, handle: DeferredCallHandle) {
-        self.handle.replace(handle);
-    }
-
     // Neighbor management functions
 
     /// Add a new neighbor to the end of the list if there is still space
@@ -375,7 +364,7 @@ impl<'a> RadioDriver<'a> {
         if result != Ok(()) {
             self.saved_appid.set(appid);
             self.saved_result.set(result);
-            self.handle.map(|handle| self.deferred_caller.set(*handle));
+            self.deferred_call.set();
         }
     }
 
@@ -462,8 +451,8 @@ impl<'a> RadioDriver<'a> {
     }
 }
 
-impl DynamicDeferredCallClient for RadioDriver<'_> {
-    fn call(&self, _handle: DeferredCallHandle) {
+impl DeferredCallClient for RadioDriver<'_> {
+    fn handle_deferred_call(&self) {
         let _ = self
             .apps
             .enter(self.saved_appid.expect("missing appid"), |_app, upcalls| {
@@ -477,6 +466,10 @@ impl DynamicDeferredCallClient for RadioDriver<'_> {
                     .ok();
             });
     }
+
+    fn register(&'static self) {
+        self.deferred_call.register(self);
+    }
 }
 
 impl framer::DeviceProcedure for RadioDriver<'_> {
@@ -0,0 +1,455 @@
+//! Userspace access to a key-value store.
+//!
+//! Apps store small records under keys of any length. Each app has its own
+//! namespace of keys: the key passed to the KV store is the namespace's
+//! storage ID followed by the app's key, so two apps using the same key do
+//! not see each other's values. By default an app uses the namespace of its
+//! write ID (see `kernel::StoragePermissions`). An app can select the
+//! namespace of another storage ID to read its values, if its permissions
+//! allow reading that ID, or to change them, if they allow modifying it.
+//! Apps without storage permissions cannot use this driver.
+//!
+//! ```text
+//! +-----------------------+
+//! |                       |
+//! |      userspace        |
+//! |                       |
+//! +-----------------------+
+//!
+//!    kernel::Driver
+//!
+//! +-----------------------+
+//! |                       |
+//! |  KV driver (this)     |
+//! |                       |
+//! +-----------------------+
+//!
+//!    hil::kv_store
+//!
+//! +-----------------------+
+//! |                       |
+//! |  capsules::kv_store   |
+//! |                       |
+//! +-----------------------+
+//! ```
+//!
+//! Usage
+//! -----
+//!
+//! ```rust
+//! let kv_driver = static_init!(
+//!     capsules::kv_driver::KVStoreDriver<'static>,
+//!     capsules::kv_driver::KVStoreDriver::new(
+//!         kv_store,
+//!         board_kernel.create_grant(capsules::kv_driver::DRIVER_NUM, &grant_cap),
+//!         &mut capsules::kv_driver::KEY_BUFFER,
+//!         &mut capsules::kv_driver::VALUE_BUFFER,
+//!     )
+//! );
+//! kv_store.set_client(kv_driver);
+//! ```
+
+use core::cmp;
+use core::mem;
+use kernel::common::cells::{OptionalCell, TakeCell};
+use kernel::common::leasable_buffer::LeasableBuffer;
+use kernel::hil::kv_store::{KVStore, StoreClient};
+use kernel::ErrorCode;
+use kernel::{
+    CommandReturn, Driver, Grant, ProcessId, ReadOnlyProcessBuffer, ReadWriteProcessBuffer,
+    ReadableProcessBuffer, WriteableProcessBuffer,
+};
+
+/// Syscall driver number.
+use crate::driver;
+pub const DRIVER_NUM: usize = driver::NUM::KVStore as usize;
+
+/// Length of the namespace in front of each key.
+const NAMESPACE_LENGTH: usize = 4;
+
+/// Default buffer for keys, which limits app keys to 60 bytes.
+pub static mut KEY_BUFFER: [u8; 64] = [0; 64];
+/// Default buffer for values, including the KV store header.
+pub static mut VALUE_BUFFER: [u8; 256] = [0; 256];
+
+#[derive(Clone, Copy, PartialEq)]
+enum Operation {
+    Get,
+    Set,
+    Add,
+    Update,
+    Delete,
+}
+
+#[derive(Default)]
+pub struct App {
+    pending: Option<Operation>,
+    key: ReadOnlyProcessBuffer,
+    value: ReadOnlyProcessBuffer,
+    output: ReadWriteProcessBuffer,
+    // Storage ID of the namespace to use, if not the app's write ID.
+    namespace: Option<u32>,
+}
+
+pub struct KVStoreDriver<'a> {
+    kv_store: &'a dyn KVStore<'a>,
+    apps: Grant<App, 1>,
+    key_buffer: TakeCell<'static, [u8]>,
+    value_buffer: TakeCell<'static, [u8]>,
+    // The app whose operation is in progress.
+    current_app: OptionalCell<ProcessId>,
+}
+
+impl<'a> KVStoreDriver<'a> {
+    pub fn new(
+        kv_store: &'a dyn KVStore<'a>,
+        grant: Grant<App, 1>,
+        key_buffer: &'static mut [u8],
+        value_buffer: &'static mut [u8],
+    ) -> KVStoreDriver<'a> {
+        KVStoreDriver {
+            kv_store,
+            apps: grant,
+            key_buffer: TakeCell::new(key_buffer),
+            value_buffer: TakeCell::new(value_buffer),
+            current_app: OptionalCell::empty(),
+        }
+    }
+
+    /// Start `operation` for the app now, or queue it if another operation
+    /// is in progress.
+    fn enqueue(&self, appid: ProcessId, operation: Operation) -> Result<(), ErrorCode> {
+        self.apps
+            .enter(appid, |app, _| {
+                if app.pending.is_some() {
+                    return Err(ErrorCode::BUSY);
+                }
+                if self.current_app.is_some() {
+                    app.pending = Some(operation);
+                    Ok(())
+                } else {
+                    self.start(appid, app, operation)
+                }
+            })
+            .unwrap_or_else(|err| Err(err.into()))
+    }
+
+    /// Copy the app's key (and value) into the kernel buffers and pass them
+    /// to the KV store.
+    fn start(
+        &self,
+        appid: ProcessId,
+        app: &mut App,
+        operation: Operation,
+    ) -> Result<(), ErrorCode> {
+        let permissions = appid.get_storage_permissions().ok_or(ErrorCode::FAIL)?;
+        let namespace = match app
+            .namespace
+            .or_else(|| permissions.write_id().map(|id| id.get()))
+        {
+            Some(namespace) => namespace,
+            None => return Err(ErrorCode::FAIL),
+        };
+        let allowed = match operation {
+            Operation::Get => permissions.can_read(namespace),
+            _ => permissions.can_modify(namespace),
+        };
+        if !allowed {
+            return Err(ErrorCode::FAIL);
+        }
+
+        let key_buffer = self.key_buffer.take().ok_or(ErrorCode::BUSY)?;
+        let key_length = app
+            .key
+            .enter(|key| {
+                let length = key.len();
+                if NAMESPACE_LENGTH + length > key_buffer.len() {
+                    return Err(ErrorCode::SIZE);
+                }
+                key_buffer[0..NAMESPACE_LENGTH].copy_from_slice(&namespace.to_le_bytes());
+                key.copy_to_slice(&mut key_buffer[NAMESPACE_LENGTH..NAMESPACE_LENGTH + length]);
+                Ok(length)
+            })
+            .unwrap_or(Err(ErrorCode::RESERVE));
+        let key_length = match key_length {
+            Ok(0) => Err(ErrorCode::INVAL),
+            result => result,
+        };
+        let mut key = LeasableBuffer::new(key_buffer);
+        match key_length {
+            Ok(length) => key.slice(0..NAMESPACE_LENGTH + length),
+            Err(e) => {
+                self.key_buffer.replace(key.take());
+                return Err(e);
+            }
+        }
+
+        if operation == Operation::Delete {
+            return match self.kv_store.delete(key, permissions) {
+                Ok(()) => {
+                    self.current_app.set(appid);
+                    Ok(())
+                }
+                Err((key, e)) => {
+                    self.key_buffer.replace(key.take());
+                    Err(e)
+                }
+            };
+        }
+
+        let value_buffer = match self.value_buffer.take() {
+            Some(value_buffer) => value_buffer,
+            None => {
+                self.key_buffer.replace(key.take());
+                return Err(ErrorCode::BUSY);
+            }
+        };
+        let header = self.kv_store.header_size();
+        let mut value = LeasableBuffer::new(value_buffer);
+        let res = match operation {
+            Operation::Get => self.kv_store.get(key, value, permissions),
+            _ => {
+                let value_length = app
+                    .value
+                    .enter(|data| {
+                        let length = data.len();
+                        if header + length > value.len() {
+                            return Err(ErrorCode::SIZE);
+                        }
+                        data.copy_to_slice(&mut value[header..header + length]);
+                        Ok(length)
+                    })
+                    .unwrap_or(Err(ErrorCode::RESERVE));
+                match value_length {
+                    Ok(length) => {
+                        value.slice(header..header + length);
+                        match operation {
+                            Operation::Set => self.kv_store.set(key, value, permissions),
+                            Operation::Add => self.kv_store.add(key, value, permissions),
+                            _ => self.kv_store.update(key, value, permissions),
+                        }
+                    }
+                    Err(e) => Err((key, value, e)),
+                }
+            }
+        };
+        match res {
+            Ok(()) => {
+                self.current_app.set(appid);
+                Ok(())
+            }
+            Err((key, value, e)) => {
+                self.key_buffer.replace(key.take());
+                self.value_buffer.replace(value.take());
+                Err(e)
+            }
+        }
+    }
+
+    /// Return the buffers, notify the app whose operation completed and start
+    /// the next queued operation.
+    fn complete(
+        &self,
+        result: Result<(), ErrorCode>,
+        key: LeasableBuffer<'static, u8>,
+        value: Option<LeasableBuffer<'static, u8>>,
+        output: bool,
+    ) {
+        self.key_buffer.replace(key.take());
+        let mut length = 0;
+        if let Some(value) = value {
+            if output && result.is_ok() {
+                length = value.len();
+            }
+            let value = value.take();
+            self.current_app.map(|appid| {
+                let _ = self.apps.enter(*appid, |app, _| {
+                    let _ = app.output.mut_enter(|output| {
+                        let header = self.kv_store.header_size();
+                        let copy = cmp::min(output.len(), length);
+                        output[0..copy].copy_from_slice(&value[header..header + copy]);
+                    });
+                });
+            });
+            self.value_buffer.replace(value);
+        }
+
+        self.current_app.take().map(|appid| {
+            let _ = self.apps.enter(appid, |_, upcalls| {
+                upcalls
+                    .schedule_upcall(0, kernel::into_statuscode(result), length, 0)
+                    .ok();
+            });
+        });
+
+        self.check_queue();
+    }
+
+    fn check_queue(&self) {
+        for cntr in self.apps.iter() {
+            let appid = cntr.processid();
+            let started = cntr.enter(|app, upcalls| match app.pending.take() {
+                Some(operation) => match self.start(appid, app, operation) {
+                    Ok(()) => true,
+                    Err(e) => {
+                        upcalls
+                            .schedule_upcall(0, kernel::into_statuscode(Err(e)), 0, 0)
+                            .ok();
+                        false
+                    }
+                },
+                None => false,
+            });
+            if started {
+                break;
+            }
+        }
+    }
+}
+
+impl StoreClient for KVStoreDriver<'_> {
+    fn get_complete(
+        &self,
+        result: Result<(), ErrorCode>,
+        key: LeasableBuffer<'static, u8>,
+        value: LeasableBuffer<'static, u8>,
+    ) {
+        self.complete(result, key, Some(value), true);
+    }
+
+    fn set_complete(
+        &self,
+        result: Result<(), ErrorCode>,
+        key: LeasableBuffer<'static, u8>,
+        value: LeasableBuffer<'static, u8>,
+    ) {
+        self.complete(result, key, Some(value), false);
+    }
+
+    fn delete_complete(&self, result: Result<(), ErrorCode>, key: LeasableBuffer<'static, u8>) {
+        self.complete(result, key, None, false);
+    }
+}
+
+impl Driver for KVStoreDriver<'_> {
+    /// Setup shared kernel-writable buffers.
+    ///
+    /// ### `allow_num`
+    ///
+    /// - `0`: Setup a buffer to read values into.
+    fn allow_readwrite(
+        &self,
+        appid: ProcessId,
+        allow_num: usize,
+        mut slice: ReadWriteProcessBuffer,
+    ) -> Result<ReadWriteProcessBuffer, (ReadWriteProcessBuffer, ErrorCode)> {
+        let res = match allow_num {
+            0 => self
+                .apps
+                .enter(appid, |app, _| {
+                    mem::swap(&mut slice, &mut app.output);
+                    Ok(())
+                })
+                .unwrap_or_else(|err| Err(err.into())),
+            _ => Err(ErrorCode::NOSUPPORT),
+        };
+
+        match res {
+            Ok(()) => Ok(slice),
+            Err(e) => Err((slice, e)),
+        }
+    }
+
+    /// Setup shared kernel-readable buffers.
+    ///
+    /// ### `allow_num`
+    ///
+    /// - `0`: Setup the key.
+    /// - `1`: Setup the value to store.
+    fn allow_readonly(
+        &self,
+        appid: ProcessId,
+        allow_num: usize,
+        mut slice: ReadOnlyProcessBuffer,
+    ) -> Result<ReadOnlyProcessBuffer, (ReadOnlyProcessBuffer, ErrorCode)> {
+        let res = self
+            .apps
+            .enter(appid, |app, _| match allow_num {
+                0 => {
+                    mem::swap(&mut slice, &mut app.key);
+                    Ok(())
+                }
+                1 => {
+                    mem::swap(&mut slice, &mut app.value);
+                    Ok(())
+                }
+                _ => Err(ErrorCode::NOSUPPORT),
+            })
+            .unwrap_or_else(|err| Err(err.into()));
+
+        match res {
+            Ok(()) => Ok(slice),
+            Err(e) => Err((slice, e)),
+        }
+    }
+
+    // Setup callbacks.
+    //
+    // ### `subscribe_num`
+    //
+    // - `0`: Setup a callback for when an operation completes. The first
+    //   argument is the status code, and the second the length of the value
+    //   that was read.
+
+    /// Command interface.
+    ///
+    /// ### `command_num`
+    ///
+    /// - `0`: Return Ok(()) if this driver is included on the platform.
+    /// - `1`: Read the value stored under the key.
+    /// - `2`: Store the value under the key, replacing any existing value.
+    /// - `3`: Store the value under the key, if the key does not exist yet.
+    /// - `4`: Replace the value stored under the key.
+    /// - `5`: Delete the value stored under the key.
+    /// - `6`: Select the namespace of keys by the storage ID that owns it, or
+    ///   `0` for the app's own namespace.
+    ///
+    /// Operations report `NOSUPPORT` if the key does not exist (or for `3`,
+    /// already exists), and `FAIL` if the app's storage permissions do not
+    /// allow the operation.
+    fn command(
+        &self,
+        command_num: usize,
+        data: usize,
+        _: usize,
+        appid: ProcessId,
+    ) -> CommandReturn {
+        let operation = match command_num {
+            0 => return CommandReturn::success(),
+            1 => Operation::Get,
+            2 => Operation::Set,
+            3 => Operation::Add,
+            4 => Operation::Update,
+            5 => Operation::Delete,
+            6 => {
+                let res = self.apps.enter(appid, |app, _| {
+                    app.namespace = if data == 0 { None } else { Some(data as u32) };
+                });
+                return match res {
+                    Ok(()) => CommandReturn::success(),
+                    Err(e) => CommandReturn::failure(e.into()),
+                };
+            }
+            _ => return CommandReturn::failure(ErrorCode::NOSUPPORT),
+        };
+
+        match self.enqueue(appid, operation) {
+            Ok(()) => CommandReturn::success(),
+            Err(e) => CommandReturn::failure(e),
+        }
+    }
+
+    fn allocate_grant(&self, processid: ProcessId) -> Result<(), kernel::procs::Error> {
+        self.apps.enter(processid, |_, _| {})
+    }
+}
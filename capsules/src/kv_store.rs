@@ -0,0 +1,448 @@
+//! Key-value store that enforces storage permissions.
+//!
+//! This capsule implements `hil::kv_store` on top of any `hil::kv_system`
+//! implementation, such as TicKV. Keys are hashed by the KV system, and each
+//! value is stored with a header recording the write ID of the process that
+//! stored it, so that later accesses can be checked against the caller's
+//! `StoragePermissions`.
+//!
+//! ```text
+//! +-----------------------+
+//! |                       |
+//! |  Capsule using K-V    |
+//! |                       |
+//! +-----------------------+
+//!
+//!    hil::kv_store
+//!
+//! +-----------------------+
+//! |                       |
+//! |  K-V in Tock (this)   |
+//! |                       |
+//! +-----------------------+
+//!
+//!    hil::kv_system
+//!
+//! +-----------------------+
+//! |                       |
+//! |  K-V library          |
+//! |                       |
+//! +-----------------------+
+//! ```
+//!
+//! Setting, adding, updating and deleting a value first reads the existing
+//! value into an internal buffer to check who owns it, so that buffer must be
+//! large enough for the largest value stored.
+//!
+//! Usage
+//! -----
+//!
+//! ```rust
+//! let kv_store = static_init!(
+//!     capsules::kv_store::KVStore<'static, TicKVStore<'static, FlashUser<'static, FlashCtrl>>, TicKVKeyType>,
+//!     capsules::kv_store::KVStore::new(
+//!         tickv,
+//!         static_init!(capsules::tickv::TicKVKeyType, [0; 8]),
+//!         &mut capsules::kv_store::CHECK_BUFFER,
+//!     )
+//! );
+//! tickv.set_client(kv_store);
+//! ```
+
+use core::cell::Cell;
+use kernel::common::cells::{MapCell, OptionalCell, TakeCell};
+use kernel::common::leasable_buffer::LeasableBuffer;
+use kernel::hil::kv_store::{self, StoreClient};
+use kernel::hil::kv_system::{self, KVSystem, KeyType};
+use kernel::{ErrorCode, StoragePermissions};
+
+/// Version of the header stored in front of each value.
+const HEADER_VERSION: u8 = 0;
+
+/// Length of the header: the version, the length of the value and the write
+/// ID of the process that stored it.
+pub const HEADER_LENGTH: usize = 9;
+
+/// Default buffer for checking the owner of existing values.
+pub static mut CHECK_BUFFER: [u8; 256] = [0; 256];
+
+#[derive(Clone, Copy, PartialEq)]
+enum Operation {
+    None,
+    Get,
+    Set,
+    Add,
+    Update,
+    Delete,
+}
+
+pub struct KVStore<'a, K: KVSystem<'a, K = T>, T: 'static + KeyType> {
+    kv_system: &'a K,
+    client: OptionalCell<&'a dyn StoreClient>,
+    operation: Cell<Operation>,
+    permissions: OptionalCell<StoragePermissions>,
+
+    // The caller's key while an operation is in progress.
+    key: MapCell<LeasableBuffer<'static, u8>>,
+    hashed_key: TakeCell<'static, T>,
+    // The caller's value, with the header filled in, while a value is being
+    // stored.
+    value: TakeCell<'static, [u8]>,
+    value_length: Cell<usize>,
+    check_buffer: TakeCell<'static, [u8]>,
+}
+
+impl<'a, K: KVSystem<'a, K = T>, T: 'static + KeyType> KVStore<'a, K, T> {
+    pub fn new(
+        kv_system: &'a K,
+        hashed_key: &'static mut T,
+        check_buffer: &'static mut [u8],
+    ) -> KVStore<'a, K, T> {
+        KVStore {
+            kv_system,
+            client: OptionalCell::empty(),
+            operation: Cell::new(Operation::None),
+            permissions: OptionalCell::empty(),
+            key: MapCell::empty(),
+            hashed_key: TakeCell::new(hashed_key),
+            value: TakeCell::empty(),
+            value_length: Cell::new(0),
+            check_buffer: TakeCell::new(check_buffer),
+        }
+    }
+
+    /// Hash `key` and remember the operation, or return why it cannot start.
+    fn start(
+        &self,
+        operation: Operation,
+        key: &LeasableBuffer<'static, u8>,
+        permissions: StoragePermissions,
+    ) -> Result<&'static mut T, ErrorCode> {
+        if self.operation.get() != Operation::None {
+            return Err(ErrorCode::BUSY);
+        }
+        let hashed_key = self.hashed_key.take().ok_or(ErrorCode::BUSY)?;
+        let unhashed_key = &key[..];
+        if let Err(e) = self.kv_system.generate_key(unhashed_key, hashed_key) {
+            self.hashed_key.replace(hashed_key);
+            return Err(e);
+        }
+        self.operation.set(operation);
+        self.permissions.set(permissions);
+        Ok(hashed_key)
+    }
+
+    /// Start storing `value` under `key`, after checking who owns any value
+    /// already stored.
+    fn store(
+        &self,
+        operation: Operation,
+        key: LeasableBuffer<'static, u8>,
+        mut value: LeasableBuffer<'static, u8>,
+        permissions: StoragePermissions,
+    ) -> Result<
+        (),
+        (
+            LeasableBuffer<'static, u8>,
+            LeasableBuffer<'static, u8>,
+            ErrorCode,
+        ),
+    > {
+        let write_id = match permissions.write_id() {
+            Some(write_id) => write_id,
+            None => return Err((key, value, ErrorCode::FAIL)),
+        };
+        let length = value.len();
+        value.reset();
+        if HEADER_LENGTH + length > value.len() {
+            value.slice(HEADER_LENGTH..HEADER_LENGTH + length);
+            return Err((key, value, ErrorCode::SIZE));
+        }
+        let hashed_key = match self.start(operation, &key, permissions) {
+            Ok(hashed_key) => hashed_key,
+            Err(e) => {
+                value.slice(HEADER_LENGTH..HEADER_LENGTH + length);
+                return Err((key, value, e));
+            }
+        };
+
+        let buffer = value.take();
+        buffer[0] = HEADER_VERSION;
+        buffer[1..5].copy_from_slice(&(length as u32).to_le_bytes());
+        buffer[5..9].copy_from_slice(&write_id.get().to_le_bytes());
+        self.value.replace(buffer);
+        self.value_length.set(length);
+        self.key.replace(key);
+
+        self.check(hashed_key);
+        Ok(())
+    }
+
+    /// Read the value currently stored under the key to find its owner.
+    fn check(&self, hashed_key: &'static mut T) {
+        let check_buffer = self.check_buffer.take().unwrap();
+        if let Err((hashed_key, check_buffer, e)) =
+            self.kv_system.get_value(hashed_key, check_buffer)
+        {
+            self.check_buffer.replace(check_buffer);
+            self.finish(hashed_key, e);
+        }
+    }
+
+    fn checked(
+        &self,
+        hashed_key: &'static mut T,
+        result: Result<(), ErrorCode>,
+        check_buffer: &'static mut [u8],
+    ) {
+        let owner = header_owner(check_buffer);
+        self.check_buffer.replace(check_buffer);
+        let operation = self.operation.get();
+
+        match result {
+            Err(ErrorCode::NOSUPPORT) => match operation {
+                Operation::Set | Operation::Add => self.append(hashed_key),
+                _ => self.finish(hashed_key, Err(ErrorCode::NOSUPPORT)),
+            },
+            Err(e) => self.finish(hashed_key, Err(e)),
+            Ok(()) => {
+                let allowed = owner.map_or(false, |owner| {
+                    self.permissions
+                        .map_or(false, |permissions| permissions.can_modify(owner))
+                });
+                if !allowed {
+                    self.finish(hashed_key, Err(ErrorCode::FAIL));
+                } else if operation == Operation::Add {
+                    self.finish(hashed_key, Err(ErrorCode::NOSUPPORT));
+                } else if let Err((hashed_key, e)) = self.kv_system.invalidate_key(hashed_key) {
+                    self.finish(hashed_key, e);
+                }
+            }
+        }
+    }
+
+    fn append(&self, hashed_key: &'static mut T) {
+        let value = self.value.take().unwrap();
+        let length = HEADER_LENGTH + self.value_length.get();
+        if let Err((hashed_key, value, e)) = self.kv_system.append_key(hashed_key, value, length) {
+            self.value.replace(value);
+            self.finish(hashed_key, e);
+        }
+    }
+
+    /// Return the buffers of the current operation to the client.
+    fn finish(&self, hashed_key: &'static mut T, result: Result<(), ErrorCode>) {
+        self.hashed_key.replace(hashed_key);
+        let operation = self.operation.replace(Operation::None);
+        self.permissions.clear();
+        let key = match self.key.take() {
+            Some(key) => key,
+            None => return,
+        };
+
+        match operation {
+            Operation::Delete => {
+                self.client
+                    .map(|client| client.delete_complete(result, key));
+            }
+            Operation::Set | Operation::Add | Operation::Update => {
+                if let Some(value) = self.value.take() {
+                    let mut value = LeasableBuffer::new(value);
+                    value.slice(HEADER_LENGTH..HEADER_LENGTH + self.value_length.get());
+                    self.client
+                        .map(move |client| client.set_complete(result, key, value));
+                }
+            }
+            Operation::Get | Operation::None => {}
+        }
+    }
+}
+
+/// The write ID in the header at the start of `buffer`, if it holds a value
+/// stored by this capsule.
+fn header_owner(buffer: &[u8]) -> Option<u32> {
+    if buffer.len() < HEADER_LENGTH || buffer[0] != HEADER_VERSION {
+        return None;
+    }
+    Some(u32::from_le_bytes([
+        buffer[5], buffer[6], buffer[7], buffer[8],
+    ]))
+}
+
+/// The length of the value after the header at the start of `buffer`.
+fn header_length(buffer: &[u8]) -> Option<usize> {
+    if buffer.len() < HEADER_LENGTH {
+        return None;
+    }
+    Some(u32::from_le_bytes([buffer[1], buffer[2], buffer[3], buffer[4]]) as usize)
+}
+
+impl<'a, K: KVSystem<'a, K = T>, T: 'static + KeyType> kv_system::Client<T> for KVStore<'a, K, T> {
+    fn append_key_complete(
+        &self,
+        result: Result<(), ErrorCode>,
+        key: &'static mut T,
+        value: &'static mut [u8],
+    ) {
+        self.value.replace(value);
+        self.finish(key, result);
+    }
+
+    fn get_value_complete(
+        &self,
+        result: Result<(), ErrorCode>,
+        key: &'static mut T,
+        ret_buf: &'static mut [u8],
+    ) {
+        if self.operation.get() != Operation::Get {
+            self.checked(key, result, ret_buf);
+            return;
+        }
+
+        // Only let the caller see values it may read.
+        let result = result.and_then(|()| match header_owner(ret_buf) {
+            Some(owner)
+                if self
+                    .permissions
+                    .map_or(false, |permissions| permissions.can_read(owner)) =>
+            {
+                Ok(())
+            }
+            _ => Err(ErrorCode::FAIL),
+        });
+        // The length comes from flash, so don't trust it to fit the buffer.
+        let result = result.and_then(|()| match header_length(ret_buf) {
+            Some(length) if length <= ret_buf.len() - HEADER_LENGTH => Ok(length),
+            _ => Err(ErrorCode::FAIL),
+        });
+        let mut value = LeasableBuffer::new(ret_buf);
+        match result {
+            Ok(length) => value.slice(HEADER_LENGTH..HEADER_LENGTH + length),
+            Err(_) => value.slice(HEADER_LENGTH..HEADER_LENGTH),
+        }
+        let result = result.map(|_| ());
+
+        self.hashed_key.replace(key);
+        self.operation.set(Operation::None);
+        self.permissions.clear();
+        if let Some(key) = self.key.take() {
+            self.client
+                .map(move |client| client.get_complete(result, key, value));
+        }
+    }
+
+    fn invalidate_key_complete(&self, result: Result<(), ErrorCode>, key: &'static mut T) {
+        match (result, self.operation.get()) {
+            (Ok(()), Operation::Set) | (Ok(()), Operation::Update) => self.append(key),
+            (result, _) => self.finish(key, result),
+        }
+    }
+
+    fn garbage_collect_complete(&self, _result: Result<(), ErrorCode>) {}
+}
+
+impl<'a, K: KVSystem<'a, K = T>, T: 'static + KeyType> kv_store::KVStore<'a> for KVStore<'a, K, T> {
+    fn set_client(&self, client: &'a dyn StoreClient) {
+        self.client.set(client);
+    }
+
+    fn header_size(&self) -> usize {
+        HEADER_LENGTH
+    }
+
+    fn get(
+        &self,
+        key: LeasableBuffer<'static, u8>,
+        mut value: LeasableBuffer<'static, u8>,
+        permissions: StoragePermissions,
+    ) -> Result<
+        (),
+        (
+            LeasableBuffer<'static, u8>,
+            LeasableBuffer<'static, u8>,
+            ErrorCode,
+        ),
+    > {
+        let hashed_key = match self.start(Operation::Get, &key, permissions) {
+            Ok(hashed_key) => hashed_key,
+            Err(e) => return Err((key, value, e)),
+        };
+        value.reset();
+        match self.kv_system.get_value(hashed_key, value.take()) {
+            Ok(()) => {
+                self.key.replace(key);
+                Ok(())
+            }
+            Err((hashed_key, ret_buf, e)) => {
+                self.hashed_key.replace(hashed_key);
+                self.operation.set(Operation::None);
+                self.permissions.clear();
+                let mut value = LeasableBuffer::new(ret_buf);
+                value.slice(HEADER_LENGTH..);
+                Err((key, value, e.err().unwrap_or(ErrorCode::FAIL)))
+            }
+        }
+    }
+
+    fn set(
+        &self,
+        key: LeasableBuffer<'static, u8>,
+        value: LeasableBuffer<'static, u8>,
+        permissions: StoragePermissions,
+    ) -> Result<
+        (),
+        (
+            LeasableBuffer<'static, u8>,
+            LeasableBuffer<'static, u8>,
+            ErrorCode,
+        ),
+    > {
+        self.store(Operation::Set, key, value, permissions)
+    }
+
+    fn add(
+        &self,
+        key: LeasableBuffer<'static, u8>,
+        value: LeasableBuffer<'static, u8>,
+        permissions: StoragePermissions,
+    ) -> Result<
+        (),
+        (
+            LeasableBuffer<'static, u8>,
+            LeasableBuffer<'static, u8>,
+            ErrorCode,
+        ),
+    > {
+        self.store(Operation::Add, key, value, permissions)
+    }
+
+    fn update(
+        &self,
+        key: LeasableBuffer<'static, u8>,
+        value: LeasableBuffer<'static, u8>,
+        permissions: StoragePermissions,
+    ) -> Result<
+        (),
+        (
+            LeasableBuffer<'static, u8>,
+            LeasableBuffer<'static, u8>,
+            ErrorCode,
+        ),
+    > {
+        self.store(Operation::Update, key, value, permissions)
+    }
+
+    fn delete(
+        &self,
+        key: LeasableBuffer<'static, u8>,
+        permissions: StoragePermissions,
+    ) -> Result<(), (LeasableBuffer<'static, u8>, ErrorCode)> {
+        let hashed_key = match self.start(Operation::Delete, &key, permissions) {
+            Ok(hashed_key) => hashed_key,
+            Err(e) => return Err((key, e)),
+        };
+        self.key.replace(key);
+        self.check(hashed_key);
+        Ok(())
+    }
+}
@@ -14,12 +14,17 @@ pub mod ambient_light;
 pub mod analog_comparator;
 pub mod analog_sensor;
 pub mod apds9960;
+pub mod app_checker_ed25519;
+pub mod app_checker_sha256;
 pub mod app_flash_driver;
+pub mod app_loader;
+pub mod app_watchdog;
 pub mod ble_advertising_driver;
 pub mod bus;
 pub mod button;
 pub mod buzzer_driver;
 pub mod console;
+pub mod crash_dump;
 pub mod crc;
 pub mod ctap;
 pub mod dac;
@@ -38,6 +43,8 @@ pub mod i2c_master;
 pub mod i2c_master_slave_driver;
 pub mod ieee802154;
 pub mod isl29035;
+pub mod kv_driver;
+pub mod kv_store;
 pub mod l3gd20;
 pub mod led;
 pub mod led_matrix;
@@ -57,6 +64,7 @@ pub mod nonvolatile_storage_driver;
 pub mod nonvolatile_to_pages;
 pub mod nrf51822_serialization;
 pub mod panic_button;
+pub mod panic_report;
 pub mod pca9544a;
 pub mod process_console;
 pub mod proximity;
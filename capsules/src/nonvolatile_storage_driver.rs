@@ -1,9 +1,15 @@
 //! This provides kernel and userspace access to nonvolatile memory.
 //!
-//! This is an initial implementation that does not provide safety for
-//! individual userland applications. Each application has full access to
-//! the entire memory space that has been provided to userland. Future revisions
-//! should update this to limit applications to only their allocated regions.
+//! By default, each application has full access to the entire memory space
+//! that has been provided to userland. Boards can instead isolate apps from
+//! each other with `set_app_regions()`, which splits the userspace memory into
+//! fixed-size regions. Each region belongs to one storage ID (see
+//! `kernel::StoragePermissions`), recorded in a small header at the start of
+//! the region so that ownership persists across reboots. An app reads and
+//! writes the region of its write ID, which is claimed the first time the app
+//! writes. An app can select the region of another storage ID to read it, if
+//! its permissions allow reading that ID, or to write it, if they allow
+//! modifying it. Apps without storage permissions cannot access storage.
 //!
 //! However, the kernel accessible memory does not have to be the same range
 //! as the userspace accessible address space. The kernel memory can overlap
@@ -52,6 +58,9 @@
 //!         3000,                        // The length of the kernel region.
 //!         &mut capsules::nonvolatile_storage_driver::BUFFER));
 //! hil::nonvolatile_storage::NonvolatileStorage::set_client(fm25cl, nonvolatile_storage);
+//!
+//! // Optionally, split the userspace region into four 500 byte app regions.
+//! nonvolatile_storage.set_app_regions(500, static_init!([u32; 4], [0; 4]));
 //! ```
 
 use core::cell::Cell;
@@ -71,6 +80,27 @@ pub const DRIVER_NUM: usize = driver::NUM::NvmStorage as usize;
 
 pub static mut BUFFER: [u8; 512] = [0; 512];
 
+/// Marks the header of an app region that has been claimed.
+const REGION_MAGIC: u32 = 0x5256_4e54;
+/// Length of the header at the start of each app region: the magic value and
+/// the storage ID that owns the region.
+const REGION_HEADER_LENGTH: usize = 8;
+
+/// How userspace memory is divided between apps.
+#[derive(Clone, Copy, PartialEq)]
+enum Regions {
+    /// All apps share the userspace memory.
+    Shared,
+    /// The memory is split into app regions whose owners are not known yet.
+    Unscanned,
+    /// Reading the header of this region to find its owner.
+    Scanning(usize),
+    /// The owners of all regions are known.
+    Ready,
+    /// Writing the header of this region to claim it for the current app.
+    Claiming(usize),
+}
+
 #[derive(Clone, Copy, PartialEq)]
 pub enum NonvolatileCommand {
     UserspaceRead,
@@ -92,6 +122,8 @@ pub struct App {
     length: usize,
     buffer_read: ReadWriteProcessBuffer,
     buffer_write: ReadOnlyProcessBuffer,
+    // Storage ID of the app region to access, if not the app's own.
+    storage_id: Option<u32>,
 }
 
 impl Default for App {
@@ -103,6 +135,7 @@ impl Default for App {
             length: 0,
             buffer_read: ReadWriteProcessBuffer::default(),
             buffer_write: ReadOnlyProcessBuffer::default(),
+            storage_id: None,
         }
     }
 }
@@ -127,6 +160,13 @@ pub struct NonvolatileStorage<'a> {
     // How many bytes allocated to kernel.
     kernel_length: usize,
 
+    // How the userspace memory is divided between apps.
+    regions: Cell<Regions>,
+    // Size of each app region, including its header.
+    region_size: Cell<usize>,
+    // Storage ID owning each app region, or 0 if the region is free.
+    region_owners: TakeCell<'static, [u32]>,
+
     // Optional client for the kernel. Only needed if the kernel intends to use
     // this nonvolatile storage.
     kernel_client:
@@ -162,6 +202,9 @@ impl<'a> NonvolatileStorage<'a> {
             userspace_length: userspace_length,
             kernel_start_address: kernel_start_address,
             kernel_length: kernel_length,
+            regions: Cell::new(Regions::Shared),
+            region_size: Cell::new(0),
+            region_owners: TakeCell::empty(),
             kernel_client: OptionalCell::empty(),
             kernel_pending_command: Cell::new(false),
             kernel_command: Cell::new(NonvolatileCommand::KernelRead),
@@ -171,6 +214,31 @@ impl<'a> NonvolatileStorage<'a> {
         }
     }
 
+    /// Split the userspace memory into app regions of `region_size` bytes, so
+    /// that each app can only access the regions its storage permissions
+    /// allow. `owners` holds the owner of each region, and its length limits
+    /// the number of regions. This must be called before apps use the driver.
+    pub fn set_app_regions(&self, region_size: usize, owners: &'static mut [u32]) {
+        if region_size <= REGION_HEADER_LENGTH {
+            return;
+        }
+        let count = cmp::min(owners.len(), self.userspace_length / region_size);
+        if count == 0 {
+            return;
+        }
+        self.region_size.set(region_size);
+        self.region_owners.replace(&mut owners[..count]);
+        self.regions.set(Regions::Unscanned);
+    }
+
+    // How many bytes each app can access.
+    fn userspace_available(&self) -> usize {
+        match self.regions.get() {
+            Regions::Shared => self.userspace_length,
+            _ => self.region_size.get() - REGION_HEADER_LENGTH,
+        }
+    }
+
     // Check so see if we are doing something. If not, go ahead and do this
     // command. If so, this is queued and will be run when the pending
     // command completes.
@@ -186,10 +254,8 @@ impl<'a> NonvolatileStorage<'a> {
             NonvolatileCommand::UserspaceRead | NonvolatileCommand::UserspaceWrite => {
                 // Userspace sees memory that starts at address 0 even if it
                 // is offset in the physical memory.
-                if offset >= self.userspace_length
-                    || length > self.userspace_length
-                    || offset + length > self.userspace_length
-                {
+                let available = self.userspace_available();
+                if offset >= available || length > available || offset + length > available {
                     return Err(ErrorCode::INVAL);
                 }
             }
@@ -225,6 +291,10 @@ impl<'a> NonvolatileStorage<'a> {
                                 return Err(ErrorCode::RESERVE);
                             }
 
+                            // Refuse commands the app's storage permissions
+                            // do not allow before queueing them.
+                            self.check_permissions(appid, app, command)?;
+
                             // Shorten the length if the application gave us nowhere to
                             // put it.
                             let active_len = cmp::min(length, allow_buf_len);
@@ -237,26 +307,11 @@ impl<'a> NonvolatileStorage<'a> {
                                 self.current_user
                                     .set(NonvolatileUser::App { app_id: appid });
 
-                                // Need to copy bytes if this is a write!
-                                if command == NonvolatileCommand::UserspaceWrite {
-                                    let _ = app.buffer_write.enter(|app_buffer| {
-                                        self.buffer.map(|kernel_buffer| {
-                                            // Check that the internal buffer and the buffer that was
-                                            // allowed are long enough.
-                                            let write_len =
-                                                cmp::min(active_len, kernel_buffer.len());
-
-                                            let d = &app_buffer[0..write_len];
-                                            for (i, c) in
-                                                kernel_buffer[0..write_len].iter_mut().enumerate()
-                                            {
-                                                *c = d[i].get();
-                                            }
-                                        });
-                                    });
-                                }
-
-                                self.userspace_call_driver(command, offset, active_len)
+                                self.userspace_start(appid, app, command, offset, active_len)
+                                    .map_err(|e| {
+                                        self.current_user.clear();
+                                        e
+                                    })
                             } else {
                                 // Some app is using the storage, we must wait.
                                 if app.pending_command == true {
@@ -265,10 +320,7 @@ impl<'a> NonvolatileStorage<'a> {
                                     Err(ErrorCode::NOMEM)
                                 } else {
                                     // We can store this, so lets do it.
-                                    app.pending_command = true;
-                                    app.command = command;
-                                    app.offset = offset;
-                                    app.length = active_len;
+                                    Self::queue(app, command, offset, active_len);
                                     Ok(())
                                 }
                             }
@@ -313,6 +365,100 @@ impl<'a> NonvolatileStorage<'a> {
         }
     }
 
+    fn queue(app: &mut App, command: NonvolatileCommand, offset: usize, length: usize) {
+        app.pending_command = true;
+        app.command = command;
+        app.offset = offset;
+        app.length = length;
+    }
+
+    // Check that the app may run `command` on the app region it selected, and
+    // return the storage ID of that region. All apps may access all of
+    // userspace memory if it is not split into app regions.
+    fn check_permissions(
+        &self,
+        appid: ProcessId,
+        app: &App,
+        command: NonvolatileCommand,
+    ) -> Result<u32, ErrorCode> {
+        if self.regions.get() == Regions::Shared {
+            return Ok(0);
+        }
+        let permissions = appid.get_storage_permissions().ok_or(ErrorCode::FAIL)?;
+        let storage_id = match app.storage_id {
+            Some(storage_id) => storage_id,
+            None => permissions.write_id().ok_or(ErrorCode::NOSUPPORT)?.get(),
+        };
+        let allowed = match command {
+            NonvolatileCommand::UserspaceRead => permissions.can_read(storage_id),
+            _ => permissions.can_modify(storage_id),
+        };
+        if allowed {
+            Ok(storage_id)
+        } else {
+            Err(ErrorCode::NOSUPPORT)
+        }
+    }
+
+    // Start a command for the current app. If the app region it accesses is
+    // not known yet, first find or claim it and leave the command queued.
+    fn userspace_start(
+        &self,
+        appid: ProcessId,
+        app: &mut App,
+        command: NonvolatileCommand,
+        offset: usize,
+        length: usize,
+    ) -> Result<(), ErrorCode> {
+        let region_start = match self.regions.get() {
+            Regions::Shared => 0,
+            Regions::Unscanned => {
+                self.scan_region(0)?;
+                Self::queue(app, command, offset, length);
+                return Ok(());
+            }
+            _ => {
+                let storage_id = self.check_permissions(appid, app, command)?;
+                match self.find_region(storage_id) {
+                    Some(region) => region * self.region_size.get() + REGION_HEADER_LENGTH,
+                    None => {
+                        // Apps claim a region for their own data the first
+                        // time they write.
+                        let own = appid
+                            .get_storage_permissions()
+                            .and_then(|permissions| permissions.write_id())
+                            .map_or(false, |write_id| write_id.get() == storage_id);
+                        if command != NonvolatileCommand::UserspaceWrite || !own {
+                            return Err(ErrorCode::INVAL);
+                        }
+                        let region = self.find_region(0).ok_or(ErrorCode::NOMEM)?;
+                        self.claim_region(region, storage_id)?;
+                        Self::queue(app, command, offset, length);
+                        return Ok(());
+                    }
+                }
+            }
+        };
+
+        // Need to copy bytes if this is a write!
+        if command == NonvolatileCommand::UserspaceWrite {
+            let _ = app.buffer_write.enter(|app_buffer| {
+                self.buffer.map(|kernel_buffer| {
+                    // Check that the internal buffer and the buffer that was
+                    // allowed are long enough.
+                    let write_len = cmp::min(length, kernel_buffer.len());
+
+                    let d = &app_buffer[0..write_len];
+                    for (i, c) in kernel_buffer[0..write_len].iter_mut().enumerate() {
+                        *c = d[i].get();
+                    }
+                });
+            });
+        }
+
+        self.userspace_call_driver(command, region_start + offset, length)
+    }
+
     fn userspace_call_driver(
         &self,
         command: NonvolatileCommand,
@@ -343,6 +489,73 @@ impl<'a> NonvolatileStorage<'a> {
             })
     }
 
+    // Find the app region owned by `storage_id`. Storage ID 0 finds a free
+    // region.
+    fn find_region(&self, storage_id: u32) -> Option<usize> {
+        self.region_owners.map_or(None, |owners| {
+            owners.iter().position(|owner| *owner == storage_id)
+        })
+    }
+
+    // Read the header of `region` to find its owner.
+    fn scan_region(&self, region: usize) -> Result<(), ErrorCode> {
+        self.regions.set(Regions::Scanning(region));
+        let res = self.userspace_call_driver(
+            NonvolatileCommand::UserspaceRead,
+            region * self.region_size.get(),
+            REGION_HEADER_LENGTH,
+        );
+        if res.is_err() {
+            self.regions.set(Regions::Unscanned);
+        }
+        res
+    }
+
+    fn region_scanned(&self, region: usize, buffer: &'static mut [u8]) {
+        let word =
+            |i: usize| u32::from_le_bytes([buffer[i], buffer[i + 1], buffer[i + 2], buffer[i + 3]]);
+        let owner = if word(0) == REGION_MAGIC { word(4) } else { 0 };
+        let count = self.region_owners.map_or(0, |owners| {
+            owners[region] = owner;
+            owners.len()
+        });
+        self.buffer.replace(buffer);
+
+        if region + 1 < count {
+            if self.scan_region(region + 1).is_ok() {
+                return;
+            }
+        } else {
+            self.regions.set(Regions::Ready);
+        }
+        // Run the commands that waited for the scan.
+        self.current_user.clear();
+        self.check_queue();
+    }
+
+    // Write the header of `region` to claim it for `storage_id`.
+    fn claim_region(&self, region: usize, storage_id: u32) -> Result<(), ErrorCode> {
+        self.buffer.map(|buffer| {
+            buffer[0..4].copy_from_slice(&REGION_MAGIC.to_le_bytes());
+            buffer[4..8].copy_from_slice(&storage_id.to_le_bytes());
+        });
+        self.regions.set(Regions::Claiming(region));
+        let res = self.userspace_call_driver(
+            NonvolatileCommand::UserspaceWrite,
+            region * self.region_size.get(),
+            REGION_HEADER_LENGTH,
+        );
+        match res {
+            Ok(()) => {
+                // Record the owner now, so that the region is not claimed
+                // twice. The write finishes before any other command runs.
+                self.region_owners.map(|owners| owners[region] = storage_id);
+            }
+            Err(_) => self.regions.set(Regions::Ready),
+        }
+        res
+    }
+
     fn check_queue(&self) {
         // Check if there are any pending events.
         if self.kernel_pending_command.get() {
@@ -368,17 +581,24 @@ impl<'a> NonvolatileStorage<'a> {
             // If the kernel is not requesting anything, check all of the apps.
             for cntr in self.apps.iter() {
                 let appid = cntr.processid();
-                let started_command = cntr.enter(|app, _| {
+                let started_command = cntr.enter(|app, upcalls| {
                     if app.pending_command {
                         app.pending_command = false;
                         self.current_user
                             .set(NonvolatileUser::App { app_id: appid });
-                        if let Ok(()) =
-                            self.userspace_call_driver(app.command, app.offset, app.length)
-                        {
-                            true
-                        } else {
-                            false
+                        let (command, offset, length) = (app.command, app.offset, app.length);
+                        match self.userspace_start(appid, app, command, offset, length) {
+                            Ok(()) => true,
+                            Err(_) => {
+                                // Report that nothing was read or written.
+                                self.current_user.clear();
+                                let upcall = match command {
+                                    NonvolatileCommand::UserspaceRead => 0,
+                                    _ => 1,
+                                };
+                                upcalls.schedule_upcall(upcall, 0, 0, 0).ok();
+                                false
+                            }
                         }
                     } else {
                         false
@@ -395,6 +615,11 @@ impl<'a> NonvolatileStorage<'a> {
 /// This is the callback client for the underlying physical storage driver.
 impl hil::nonvolatile_storage::NonvolatileStorageClient<'static> for NonvolatileStorage<'_> {
     fn read_done(&self, buffer: &'static mut [u8], length: usize) {
+        if let Regions::Scanning(region) = self.regions.get() {
+            self.region_scanned(region, buffer);
+            return;
+        }
+
         // Switch on which user of this capsule generated this callback.
         self.current_user.take().map(|user| {
             match user {
@@ -429,6 +654,15 @@ impl hil::nonvolatile_storage::NonvolatileStorageClient<'static> for Nonvolatile
     }
 
     fn write_done(&self, buffer: &'static mut [u8], length: usize) {
+        if let Regions::Claiming(_) = self.regions.get() {
+            // The app's write waits in its queue.
+            self.regions.set(Regions::Ready);
+            self.buffer.replace(buffer);
+            self.current_user.clear();
+            self.check_queue();
+            return;
+        }
+
         // Switch on which user of this capsule generated this callback.
         self.current_user.take().map(|user| {
             match user {
@@ -556,6 +790,9 @@ impl Driver for NonvolatileStorage<'_> {
     /// - `1`: Return the number of bytes available to userspace.
     /// - `2`: Start a read from the nonvolatile storage.
     /// - `3`: Start a write to the nonvolatile_storage.
+    /// - `4`: Select the app region that reads and writes access by the
+    ///   storage ID that owns it, or `0` for the app's own region. Only
+    ///   supported if userspace memory is split into app regions.
     fn command(
         &self,
         command_num: usize,
@@ -570,7 +807,7 @@ impl Driver for NonvolatileStorage<'_> {
 
             1 /* How many bytes are accessible from userspace */ => {
                 // TODO: Would break on 64-bit platforms
-                CommandReturn::success_u32(self.userspace_length as u32)
+                CommandReturn::success_u32(self.userspace_available() as u32)
             },
 
             2 /* Issue a read command */ => {
@@ -603,6 +840,20 @@ impl Driver for NonvolatileStorage<'_> {
                 }
             }
 
+            4 /* Select the app region to access */ => {
+                if self.regions.get() == Regions::Shared {
+                    return CommandReturn::failure(ErrorCode::NOSUPPORT);
+                }
+                let res = self.apps.enter(appid, |app, _| {
+                    app.storage_id = if offset == 0 { None } else { Some(offset as u32) };
+                });
+
+                match res {
+                    Ok(()) => CommandReturn::success(),
+                    Err(e) => CommandReturn::failure(e.into()),
+                }
+            }
+
             _ => CommandReturn::failure(ErrorCode::NOSUPPORT),
         }
     }
@@ -0,0 +1,169 @@
+//! Reports why the kernel panicked before the last reset.
+//!
+//! The kernel saves the output of a panic in a `kernel::debug::PanicRecord`
+//! kept in memory that is not initialized at boot. On the next boot this
+//! capsule copies the saved panic out of the record before the kernel starts
+//! using it again, and makes it available to the `ProcessConsole` and to
+//! userspace.
+//!
+//! Usage
+//! -----
+//!
+//! Saving panics is opt-in. A board enables it by placing a record in the
+//! `.noinit` section, which `boards/kernel_layout.ld` provides, and passing it
+//! to `kernel::debug::set_panic_record()`:
+//!
+//! ```rust
+//! # use kernel::static_init;
+//! # use capsules::panic_report::PanicReport;
+//!
+//! #[link_section = ".noinit"]
+//! static mut PANIC_RECORD: kernel::debug::PanicRecord = kernel::debug::PanicRecord::new();
+//!
+//! let panic_report = static_init!(
+//!     PanicReport,
+//!     PanicReport::new(
+//!         &PANIC_RECORD,
+//!         &mut capsules::panic_report::REPORT_BUFFER,
+//!         board_kernel.create_grant(capsules::panic_report::DRIVER_NUM, &grant_cap),
+//!     )
+//! );
+//! kernel::debug::set_panic_record(&mut PANIC_RECORD);
+//! process_console.set_panic_report(panic_report);
+//! ```
+//!
+//! Userspace Interface
+//! -------------------
+//!
+//! ### Command
+//!
+//! - `0`: Driver check.
+//! - `1`: Return the length of the saved panic output, which is 0 if the
+//!   kernel did not panic before the last reset.
+//! - `2`: Copy the saved panic output into the buffer allowed with read-write
+//!   allow 0, and return the number of bytes copied.
+
+use core::cmp;
+use core::mem;
+use kernel::debug::{PanicRecord, PANIC_RECORD_LENGTH};
+use kernel::{
+    CommandReturn, Driver, ErrorCode, Grant, ProcessId, ReadWriteProcessBuffer,
+    WriteableProcessBuffer,
+};
+
+/// Syscall driver number.
+use crate::driver;
+pub const DRIVER_NUM: usize = driver::NUM::PanicReport as usize;
+
+/// Default buffer for the saved panic output.
+pub static mut REPORT_BUFFER: [u8; PANIC_RECORD_LENGTH] = [0; PANIC_RECORD_LENGTH];
+
+#[derive(Default)]
+pub struct App {
+    buffer: ReadWriteProcessBuffer,
+}
+
+pub struct PanicReport {
+    apps: Grant<App, 1>,
+    report: &'static [u8],
+}
+
+impl PanicReport {
+    /// Copy the panic saved in `record`, if any, into `buffer`. This must be
+    /// done before `record` is passed to `kernel::debug::set_panic_record()`.
+    pub fn new(
+        record: &PanicRecord,
+        buffer: &'static mut [u8],
+        grant: Grant<App, 1>,
+    ) -> PanicReport {
+        let text = record.text().unwrap_or(&[]);
+        let length = cmp::min(text.len(), buffer.len());
+        buffer[..length].copy_from_slice(&text[..length]);
+        PanicReport {
+            apps: grant,
+            report: &buffer[..length],
+        }
+    }
+
+    /// The output of the panic before the last reset, if there was one.
+    pub fn previous_panic(&self) -> Option<&[u8]> {
+        if self.report.is_empty() {
+            None
+        } else {
+            Some(self.report)
+        }
+    }
+
+    /// The panic message and location: the first line of the saved output.
+    pub fn reason(&self) -> Option<&[u8]> {
+        self.previous_panic().and_then(|report| {
+            report
+                .split(|byte| *byte == b'\r' || *byte == b'\n')
+                .find(|line| !line.is_empty())
+        })
+    }
+}
+
+impl Driver for PanicReport {
+    /// Setup a buffer to copy the saved panic output into.
+    ///
+    /// ### `allow_num`
+    ///
+    /// - `0`: Buffer for the panic output.
+    fn allow_readwrite(
+        &self,
+        appid: ProcessId,
+        allow_num: usize,
+        mut slice: ReadWriteProcessBuffer,
+    ) -> Result<ReadWriteProcessBuffer, (ReadWriteProcessBuffer, ErrorCode)> {
+        let res = match allow_num {
+            0 => self
+                .apps
+                .enter(appid, |app, _| {
+                    mem::swap(&mut slice, &mut app.buffer);
+                    Ok(())
+                })
+                .unwrap_or_else(|err| Err(err.into())),
+            _ => Err(ErrorCode::NOSUPPORT),
+        };
+
+        match res {
+            Ok(()) => Ok(slice),
+            Err(e) => Err((slice, e)),
+        }
+    }
+
+    /// Read the saved panic output.
+    ///
+    /// ### `command_num`
+    ///
+    /// - `0`: Driver check.
+    /// - `1`: Return the length of the saved panic output.
+    /// - `2`: Copy the saved panic output into the allowed buffer.
+    fn command(&self, command_num: usize, _: usize, _: usize, appid: ProcessId) -> CommandReturn {
+        match command_num {
+            0 => CommandReturn::success(),
+            1 => CommandReturn::success_u32(self.report.len() as u32),
+            2 => {
+                let res = self.apps.enter(appid, |app, _| {
+                    app.buffer
+                        .mut_enter(|buffer| {
+                            let length = cmp::min(buffer.len(), self.report.len());
+                            buffer[..length].copy_from_slice(&self.report[..length]);
+                            length
+                        })
+                        .unwrap_or(0)
+                });
+                match res {
+                    Ok(length) => CommandReturn::success_u32(length as u32),
+                    Err(e) => CommandReturn::failure(e.into()),
+                }
+            }
+            _ => CommandReturn::failure(ErrorCode::NOSUPPORT),
+        }
+    }
+
+    fn allocate_grant(&self, processid: ProcessId) -> Result<(), kernel::procs::Error> {
+        self.apps.enter(processid, |_, _| {})
+    }
+}
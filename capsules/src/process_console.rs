@@ -9,10 +9,17 @@
 //!  - 'help' prints the available commands and arguments
 //!  - 'status' prints the current system status
 //!  - 'list' lists the current processes with their IDs and running state
+//!  - 'top' shows how much CPU time each process has used
 //!  - 'stop n' stops the process with name n
 //!  - 'start n' starts the stopped process with name n
 //!  - 'fault n' forces the process with name n into a fault state
 //!  - 'panic' causes the kernel to run the panic handler
+//!  - 'lastpanic' prints why the kernel panicked before the last reset, if
+//!    the board provides a `capsules::panic_report::PanicReport`
+//!  - 'crashes' lists the crash dumps of faulted processes saved in flash, if
+//!    the board provides a `capsules::crash_dump::CrashDump`
+//!  - 'trace' controls and prints the kernel trace, if the board provides a
+//!    `kernel::trace::Tracer`
 //!
 //! ### `list` Command Fields:
 //!
@@ -30,6 +37,37 @@
 //! - `Grants`: The number of grants that have been initialized for the process
 //!   out of the total number of grants defined by the kernel.
 //!
+//! ### `top` Command Fields:
+//!
+//! - `PID`: The identifier for the process.
+//! - `Name`: The process name.
+//! - `CPU%`: The share of the CPU time used by all processes that this process
+//!   used.
+//! - `CPU ms`: How long the process has been scheduled for, including the
+//!   time the kernel spent on its behalf. Only time the process ran under a
+//!   timeslice is measured, so this is 0 with cooperative schedulers.
+//! - `Kernel ms`: How much of that time was spent in the kernel, handling the
+//!   process's syscalls and switching to it.
+//! - `Wakeups`: How many times the process was resumed with an upcall after
+//!   yielding.
+//! - `State`: The state the process is in.
+//!
+//! ### `trace` Command Arguments:
+//!
+//! - none: Print whether tracing is on and how many events are buffered.
+//! - `on`, `off`: Start or stop recording events.
+//! - `app n`: Only record events of the process with name n, `app all` to
+//!   record every process again.
+//! - `driver n`: Only record system calls and upcalls of driver number n
+//!   (decimal, or hexadecimal with a `0x` prefix), `driver all` to record
+//!   every event again.
+//! - `dump`: Print and remove the buffered events, oldest first, one per line:
+//!   the timestamp in microseconds, the process ID, the kind of event, its
+//!   detail, the driver number and the two arguments.
+//! - `raw`: Like `dump`, but print each event as the hexadecimal encoding of
+//!   `TraceEvent::to_bytes()`, for tools on the host to decode.
+//! - `clear`: Discard the buffered events.
+//!
 //! Setup
 //! -----
 //!
@@ -105,6 +143,16 @@
 //! Timeslice expirations: 0
 //! ```
 //!
+//! To see which processes use the CPU, use the top command:
+//!
+//! ```text
+//! top
+//! Total CPU time: 1520 ms
+//!  PID    Name                CPU%    CPU ms  Kernel ms  Wakeups    State
+//!   00    blink                 7%       108         31      113  Yielded
+//!   01    c_hello              92%      1412         12        8  Running
+//! ```
+//!
 //! and you can control processes with the `start` and `stop` commands:
 //!
 //! ```text
@@ -114,16 +162,22 @@
 
 use core::cell::Cell;
 use core::cmp;
+use core::convert::TryFrom;
 use core::fmt;
 use core::fmt::write;
 use core::str;
 use kernel::capabilities::ProcessManagementCapability;
-use kernel::common::cells::TakeCell;
+use kernel::common::cells::{OptionalCell, TakeCell};
 use kernel::ProcessId;
 
+use crate::crash_dump::{CrashDumpClient, CrashDumpReader, CrashRecord};
+use crate::panic_report::PanicReport;
+
 use kernel::debug;
 use kernel::hil::uart;
 use kernel::introspection::KernelInfo;
+use kernel::syscall::SyscallClass;
+use kernel::trace::{self, TraceEvent, TraceEventKind, Tracer};
 use kernel::ErrorCode;
 use kernel::Kernel;
 
@@ -162,6 +216,13 @@ enum WriterState {
     ProcessProtected,
 }
 
+/// How to print the events of the kernel trace while dumping it.
+#[derive(PartialEq, Eq, Copy, Clone)]
+enum TraceOutput {
+    Text,
+    Raw,
+}
+
 impl Default for WriterState {
     fn default() -> Self {
         WriterState::Empty
@@ -212,6 +273,18 @@ pub struct ProcessConsole<'a, C: ProcessManagementCapability> {
     /// Memory addresses of where the kernel is placed in memory on chip.
     kernel_addresses: KernelAddresses,
 
+    /// Saved crash dumps of faulted processes, if the board keeps them.
+    crash_dumps: OptionalCell<&'a dyn CrashDumpReader>,
+
+    /// Why the kernel panicked before the last reset, if the board saves it.
+    panic_report: OptionalCell<&'a PanicReport>,
+
+    /// The kernel trace, if the board records one.
+    tracer: OptionalCell<&'a Tracer>,
+
+    /// Set while printing the trace, one event per transmission.
+    trace_output: Cell<Option<TraceOutput>>,
+
     /// This capsule needs to use potentially dangerous APIs related to
     /// processes, and requires a capability to access those APIs.
     capability: C,
@@ -276,10 +349,32 @@ impl<'a, C: ProcessManagementCapability> ProcessConsole<'a, C> {
             execute: Cell::new(false),
             kernel: kernel,
             kernel_addresses: kernel_addresses,
+            crash_dumps: OptionalCell::empty(),
+            panic_report: OptionalCell::empty(),
+            tracer: OptionalCell::empty(),
+            trace_output: Cell::new(None),
             capability: capability,
         }
     }
 
+    /// Enable the `crashes` command, listing the crash dumps in
+    /// `crash_dumps`. The console must also be set as their client.
+    pub fn set_crash_dumps(&self, crash_dumps: &'a dyn CrashDumpReader) {
+        self.crash_dumps.set(crash_dumps);
+    }
+
+    /// Enable the `lastpanic` command, printing the panic saved in
+    /// `panic_report`.
+    pub fn set_panic_report(&self, panic_report: &'a PanicReport) {
+        self.panic_report.set(panic_report);
+    }
+
+    /// Enable the `trace` command, controlling and printing `tracer`. The
+    /// kernel must also be recording into it.
+    pub fn set_tracer(&self, tracer: &'a Tracer) {
+        self.tracer.set(tracer);
+    }
+
     pub fn start(&self) -> Result<(), ErrorCode> {
         if self.running.get() == false {
             self.rx_buffer.take().map(|buffer| {
@@ -302,7 +397,7 @@ impl<'a, C: ProcessManagementCapability> ProcessConsole<'a, C> {
 
             let _ = self.write_bytes(b"Welcome to the process console.\n");
             let _ = self.write_bytes(
-                b"Valid commands are: help status list stop start fault process kernel\n",
+                b"Valid commands are: help status list top stop start fault process kernel lastpanic crashes trace\n",
             );
         }
         Ok(())
@@ -798,8 +893,9 @@ impl<'a, C: ProcessManagementCapability> ProcessConsole<'a, C> {
                         if clean_str.starts_with("help") {
                             let _ = self.write_bytes(b"Welcome to the process console.\n");
                             let _ = self.write_bytes(b"Valid commands are: ");
-                            let _ = self
-                                .write_bytes(b"help status list stop start fault process kernel\n");
+                            let _ = self.write_bytes(
+                                b"help status list top stop start fault process kernel lastpanic crashes trace\n",
+                            );
                         } else if clean_str.starts_with("start") {
                             let argument = clean_str.split_whitespace().nth(1);
                             argument.map(|name| {
@@ -889,6 +985,46 @@ impl<'a, C: ProcessManagementCapability> ProcessConsole<'a, C> {
                                         ),
                                     );
 
+                                    let _ = self
+                                        .write_bytes(&(console_writer.buf)[..console_writer.size]);
+                                });
+                        } else if clean_str.starts_with("top") {
+                            let info: KernelInfo = KernelInfo::new(self.kernel);
+                            let total_us = info.cpu_time_us(&self.capability);
+                            let mut console_writer = ConsoleWriter::new();
+                            let _ = write(
+                                &mut console_writer,
+                                format_args!("Total CPU time: {} ms\n", total_us / 1000),
+                            );
+                            let _ = self.write_bytes(&(console_writer.buf)[..console_writer.size]);
+                            let _ = self.write_bytes(b" PID    Name                CPU%    CPU ms  ");
+                            let _ = self.write_bytes(b"Kernel ms  Wakeups    State\n");
+                            self.kernel
+                                .process_each_capability(&self.capability, |proc| {
+                                    let process_id = proc.processid();
+                                    let cpu_us = info.app_cpu_time_us(process_id, &self.capability);
+                                    let kernel_us =
+                                        info.app_kernel_time_us(process_id, &self.capability);
+                                    let percent = if total_us == 0 {
+                                        0
+                                    } else {
+                                        cpu_us * 100 / total_us
+                                    };
+                                    let mut console_writer = ConsoleWriter::new();
+                                    let _ = write(
+                                        &mut console_writer,
+                                        format_args!(
+                                            "  {:?}\t{:<20}{:3}%{:10}{:11}{:9}  {:?}\n",
+                                            process_id,
+                                            proc.get_process_name(),
+                                            percent,
+                                            cpu_us / 1000,
+                                            kernel_us / 1000,
+                                            info.number_app_wakeups(process_id, &self.capability),
+                                            proc.get_state(),
+                                        ),
+                                    );
+
                                     let _ = self
                                         .write_bytes(&(console_writer.buf)[..console_writer.size]);
                                 });
@@ -936,6 +1072,49 @@ impl<'a, C: ProcessManagementCapability> ProcessConsole<'a, C> {
                                         }
                                     });
                             });
+                        } else if clean_str.starts_with("lastpanic") {
+                            match self.panic_report.map(|report| report.reason()) {
+                                None => {
+                                    let _ = self.write_bytes(b"No panic report storage\n");
+                                }
+                                Some(None) => {
+                                    let _ = self.write_bytes(b"No panic before the last reset\n");
+                                }
+                                Some(Some(reason)) => {
+                                    let mut console_writer = ConsoleWriter::new();
+                                    let _ = write(
+                                        &mut console_writer,
+                                        format_args!(
+                                            "Kernel panicked before the last reset: {}\n",
+                                            str::from_utf8(reason).unwrap_or("(invalid)")
+                                        ),
+                                    );
+                                    let _ = self
+                                        .write_bytes(&(console_writer.buf)[..console_writer.size]);
+                                }
+                            }
+                        } else if clean_str.starts_with("crashes") {
+                            let result = self
+                                .crash_dumps
+                                .map_or(Err(ErrorCode::NOSUPPORT), |crash_dumps| {
+                                    crash_dumps.read_crash_dumps()
+                                });
+                            match result {
+                                Ok(()) => {}
+                                Err(ErrorCode::NOSUPPORT) => {
+                                    let _ = self.write_bytes(b"No crash dump storage\n");
+                                }
+                                Err(_) => {
+                                    let _ = self.write_bytes(b"Crash dumps busy\n");
+                                }
+                            }
+                        } else if clean_str.starts_with("trace") {
+                            match self.tracer.map(|tracer| *tracer) {
+                                Some(tracer) => self.trace_command(tracer, clean_str),
+                                None => {
+                                    let _ = self.write_bytes(b"No trace buffer\n");
+                                }
+                            }
                         } else if clean_str.starts_with("kernel") {
                             let mut console_writer = ConsoleWriter::new();
                             let _ = write(
@@ -953,8 +1132,9 @@ impl<'a, C: ProcessManagementCapability> ProcessConsole<'a, C> {
                             self.write_state(WriterState::KernelStart, None);
                         } else {
                             let _ = self.write_bytes(b"Valid commands are: ");
-                            let _ = self
-                                .write_bytes(b"help status list stop start fault process kernel\n");
+                            let _ = self.write_bytes(
+                                b"help status list top stop start fault process kernel lastpanic crashes trace\n",
+                            );
                         }
                     }
                     Err(_e) => {
@@ -974,6 +1154,95 @@ impl<'a, C: ProcessManagementCapability> ProcessConsole<'a, C> {
         self.command_index.set(0);
     }
 
+    fn trace_command(&self, tracer: &Tracer, command: &str) {
+        let mut arguments = command.split_whitespace().skip(1);
+        match (arguments.next(), arguments.next()) {
+            (None, _) => {
+                let mut console_writer = ConsoleWriter::new();
+                let _ = write(
+                    &mut console_writer,
+                    format_args!(
+                        "Trace {}, {} events, {} dropped\n",
+                        if tracer.is_enabled() { "on" } else { "off" },
+                        tracer.len(),
+                        tracer.dropped()
+                    ),
+                );
+                let _ = self.write_bytes(&(console_writer.buf)[..console_writer.size]);
+            }
+            (Some("on"), _) => tracer.enable(),
+            (Some("off"), _) => tracer.disable(),
+            (Some("clear"), _) => tracer.clear(),
+            (Some("app"), Some("all")) => tracer.set_process_filter(None),
+            (Some("app"), Some(name)) => {
+                let found = Cell::new(false);
+                self.kernel
+                    .process_each_capability(&self.capability, |proc| {
+                        if proc.get_process_name() == name {
+                            tracer.set_process_filter(Some(proc.processid()));
+                            found.set(true);
+                        }
+                    });
+                if !found.get() {
+                    let _ = self.write_bytes(b"No such process\n");
+                }
+            }
+            (Some("driver"), Some("all")) => tracer.set_driver_filter(None),
+            (Some("driver"), Some(number)) => {
+                let parsed = match number.strip_prefix("0x") {
+                    Some(hex) => u32::from_str_radix(hex, 16),
+                    None => number.parse::<u32>(),
+                };
+                match parsed {
+                    Ok(driver_num) => tracer.set_driver_filter(Some(driver_num)),
+                    Err(_) => {
+                        let _ = self.write_bytes(b"Invalid driver number\n");
+                    }
+                }
+            }
+            (Some("dump"), _) => {
+                self.trace_output.set(Some(TraceOutput::Text));
+                self.write_trace_event();
+            }
+            (Some("raw"), _) => {
+                self.trace_output.set(Some(TraceOutput::Raw));
+                self.write_trace_event();
+            }
+            _ => {
+                let _ = self.write_bytes(
+                    b"Usage: trace [on|off|clear|dump|raw|app <name|all>|driver <num|all>]\n",
+                );
+            }
+        }
+    }
+
+    /// Print the oldest event of the trace while dumping it. The next event is
+    /// printed once this one has been transmitted.
+    fn write_trace_event(&self) {
+        let output = match self.trace_output.get() {
+            Some(output) => output,
+            None => return,
+        };
+        let event = self.tracer.and_then(|tracer| tracer.pop());
+        let mut console_writer = ConsoleWriter::new();
+        match (event, output) {
+            (Some(event), TraceOutput::Text) => {
+                write_trace_event_text(&mut console_writer, &event);
+            }
+            (Some(event), TraceOutput::Raw) => {
+                for byte in event.to_bytes().iter() {
+                    let _ = write(&mut console_writer, format_args!("{:02x}", byte));
+                }
+                let _ = write(&mut console_writer, format_args!("\n"));
+            }
+            (None, _) => {
+                self.trace_output.set(None);
+                return;
+            }
+        }
+        let _ = self.write_bytes(&(console_writer.buf)[..console_writer.size]);
+    }
+
     fn write_state(&self, state: WriterState, process: Option<ProcessId>) {
         if self.writer_state.get() == WriterState::Empty {
             self.writer_state.replace(state);
@@ -1026,6 +1295,84 @@ impl<'a, C: ProcessManagementCapability> ProcessConsole<'a, C> {
     }
 }
 
+fn write_trace_event_text(console_writer: &mut ConsoleWriter, event: &TraceEvent) {
+    let _ = write(console_writer, format_args!("{:10} ", event.time_us));
+    let _ = match event.process {
+        trace::NONE => write(console_writer, format_args!("   - ")),
+        process => write(console_writer, format_args!("{:4} ", process)),
+    };
+    let _ = match (event.kind, SyscallClass::try_from(event.detail)) {
+        (TraceEventKind::Syscall, Ok(class)) => {
+            write(console_writer, format_args!("Syscall({:?})", class))
+        }
+        (kind, _) => write(console_writer, format_args!("{:?}({})", kind, event.detail)),
+    };
+    let _ = match event.driver {
+        trace::NONE => write(console_writer, format_args!(" -")),
+        driver => write(console_writer, format_args!(" {:#x}", driver)),
+    };
+    let _ = write(
+        console_writer,
+        format_args!(" {:#x} {:#x}\n", event.arg0, event.arg1),
+    );
+}
+
+impl<'a, C: ProcessManagementCapability> CrashDumpClient for ProcessConsole<'a, C> {
+    fn crash_dump(&self, record: &CrashRecord) {
+        let mut console_writer = ConsoleWriter::new();
+        let _ = write(
+            &mut console_writer,
+            format_args!(
+                "Crash {}: {} (short ID {:#x}) in state {:?}, restarts {}\n",
+                record.sequence,
+                record.process_name,
+                record.short_id,
+                record.state,
+                record.restart_count
+            ),
+        );
+        let _ = write(
+            &mut console_writer,
+            format_args!(" Syscalls: {}, last: ", record.syscall_count),
+        );
+        let _ = match record.last_syscall {
+            Some((class, arg0, arg1)) => write(
+                &mut console_writer,
+                format_args!("{:?}({:#x}, {:#x})\n", class, arg0, arg1),
+            ),
+            None => write(&mut console_writer, format_args!("none\n")),
+        };
+        let _ = self.write_bytes(&(console_writer.buf)[..console_writer.size]);
+
+        for line in record.registers.chunks(32) {
+            console_writer.clear();
+            let _ = write(&mut console_writer, format_args!(" "));
+            for word in line.chunks(4) {
+                let mut value = 0;
+                for (i, byte) in word.iter().enumerate() {
+                    value |= (*byte as u32) << (8 * i);
+                }
+                let _ = write(&mut console_writer, format_args!(" {:08x}", value));
+            }
+            let _ = write(&mut console_writer, format_args!("\n"));
+            let _ = self.write_bytes(&(console_writer.buf)[..console_writer.size]);
+        }
+
+        console_writer.clear();
+        let _ = write(
+            &mut console_writer,
+            format_args!(" Stack: {} bytes saved\n", record.stack.len()),
+        );
+        let _ = self.write_bytes(&(console_writer.buf)[..console_writer.size]);
+    }
+
+    fn crash_dumps_done(&self, result: Result<(), ErrorCode>) {
+        if result.is_err() {
+            let _ = self.write_bytes(b"Failed to read crash dumps\n");
+        }
+    }
+}
+
 impl<'a, C: ProcessManagementCapability> uart::TransmitClient for ProcessConsole<'a, C> {
     fn transmitted_buffer(
         &self,
@@ -1059,6 +1406,11 @@ impl<'a, C: ProcessManagementCapability> uart::TransmitClient for ProcessConsole
             self.write_state(WriterState::Empty, None);
         }
 
+        // Continue printing the trace if we are dumping it.
+        if !self.tx_in_progress.get() {
+            self.write_trace_event();
+        }
+
         // Check if we just received and echoed a newline character, and
         // therefore need to process the received message.
         if self.execute.get() {
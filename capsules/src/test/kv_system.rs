@@ -84,20 +84,11 @@ impl<'a, S: KVSystem<'static>, T: KeyType> KVSystemTest<'a, S, T> {
 impl<'a, S: KVSystem<'static, K = T>, T: KeyType + core::fmt::Debug> kv_system::Client<T>
     for KVSystemTest<'a, S, T>
 {
-    fn generate_key_complete(
-        &self,
-        _result: Result<(), ErrorCode>,
-        _unhashed_key: &'static [u8],
-        _key_buf: &'static T,
-    ) {
-        unimplemented!()
-    }
-
     fn append_key_complete(
         &self,
         result: Result<(), ErrorCode>,
         key: &'static mut T,
-        value: &'static [u8],
+        value: &'static mut [u8],
     ) {
         match result {
             Ok(()) => {
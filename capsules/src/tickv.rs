@@ -4,7 +4,7 @@
 //! using the TicKV library (libraries/tickv).
 //!
 //! This capsule interfaces with flash and exposes the Tock `hil::kv_system`
-//! interface to others.
+//! interface to others, such as `capsules::kv_store`.
 //!
 //! +-----------------------+
 //! |                       |
@@ -31,6 +31,8 @@
 //!    hil::flash
 
 use core::cell::Cell;
+#[allow(deprecated)]
+use core::hash::{Hasher, SipHasher};
 use kernel::common::cells::{OptionalCell, TakeCell};
 use kernel::hil::flash::{self, Flash};
 use kernel::hil::kv_system::{self, KVSystem};
@@ -97,7 +99,7 @@ impl<'a, F: Flash> tickv::flash_controller::FlashController<512> for TickFSFlast
 
         if self
             .flash
-            .write_page((0x20040000 + address) / 512, data_buf)
+            .write_page(self.region_offset + address / 512, data_buf)
             .is_err()
         {
             return Err(tickv::error_codes::ErrorCode::WriteFail);
@@ -115,12 +117,40 @@ impl<'a, F: Flash> tickv::flash_controller::FlashController<512> for TickFSFlast
 
 pub type TicKVKeyType = [u8; 8];
 
+/// Convert a TicKV error into the `ErrorCode` described by `hil::kv_system`.
+fn into_error_code(error: tickv::error_codes::ErrorCode) -> ErrorCode {
+    match error {
+        tickv::error_codes::ErrorCode::KeyNotFound
+        | tickv::error_codes::ErrorCode::KeyAlreadyExists => ErrorCode::NOSUPPORT,
+        tickv::error_codes::ErrorCode::RegionFull | tickv::error_codes::ErrorCode::FlashFull => {
+            ErrorCode::NOMEM
+        }
+        tickv::error_codes::ErrorCode::BufferTooSmall(_) => ErrorCode::SIZE,
+        _ => ErrorCode::FAIL,
+    }
+}
+
+/// Whether TicKV is waiting for the flash to continue the operation, including
+/// when it has queued a write that has not completed yet.
+fn is_pending(
+    ret: &Result<tickv::success_codes::SuccessCode, tickv::error_codes::ErrorCode>,
+) -> bool {
+    match ret {
+        Ok(tickv::success_codes::SuccessCode::Queued)
+        | Err(tickv::error_codes::ErrorCode::ReadNotReady(_))
+        | Err(tickv::error_codes::ErrorCode::WriteNotReady(_))
+        | Err(tickv::error_codes::ErrorCode::EraseNotReady(_)) => true,
+        _ => false,
+    }
+}
+
 pub struct TicKVStore<'a, F: Flash + 'static> {
     tickv: AsyncTicKV<'a, TickFSFlastCtrl<'a, F>, 512>,
     operation: Cell<Operation>,
     next_operation: Cell<Operation>,
 
-    value_buffer: Cell<Option<&'static [u8]>>,
+    value_buffer: TakeCell<'static, [u8]>,
+    value_length: Cell<usize>,
     key_buffer: TakeCell<'static, [u8; 8]>,
     ret_buffer: TakeCell<'static, [u8]>,
 
@@ -145,7 +175,8 @@ impl<'a, F: Flash> TicKVStore<'a, F> {
             tickv,
             operation: Cell::new(Operation::None),
             next_operation: Cell::new(Operation::None),
-            value_buffer: Cell::new(None),
+            value_buffer: TakeCell::empty(),
+            value_length: Cell::new(0),
             key_buffer: TakeCell::empty(),
             ret_buffer: TakeCell::empty(),
             client: OptionalCell::empty(),
@@ -165,6 +196,7 @@ impl<'a, F: Flash> TicKVStore<'a, F> {
                 match self.append_key(
                     self.key_buffer.take().unwrap(),
                     self.value_buffer.take().unwrap(),
+                    self.value_length.get(),
                 ) {
                     Err((key, value, error)) => {
                         self.client.map(move |cb| {
@@ -244,32 +276,41 @@ impl<'a, F: Flash> flash::Client<F> for TicKVStore<'a, F> {
                         );
                     });
                 }
-                Err(tickv::error_codes::ErrorCode::EraseNotReady(_)) | Ok(_) => {}
-                _ => {
+                Err(e) if !is_pending(&ret) => {
                     self.operation.set(Operation::None);
                     self.client.map(|cb| {
                         cb.get_value_complete(
-                            Err(ErrorCode::FAIL),
+                            Err(into_error_code(e)),
                             self.key_buffer.take().unwrap(),
                             self.ret_buffer.take().unwrap(),
                         );
                     });
                 }
+                _ => {}
             },
-            Operation::AppendKey => match ret {
-                Ok(tickv::success_codes::SuccessCode::Complete)
-                | Ok(tickv::success_codes::SuccessCode::Written) => {
+            Operation::AppendKey => {
+                if !is_pending(&ret) {
                     self.operation.set(Operation::None);
+                    self.client.map(|cb| {
+                        cb.append_key_complete(
+                            ret.map(|_| ()).map_err(into_error_code),
+                            self.key_buffer.take().unwrap(),
+                            self.tickv.get_stored_value_buffer().unwrap(),
+                        );
+                    });
                 }
-                _ => {}
-            },
-            Operation::InvalidateKey => match ret {
-                Ok(tickv::success_codes::SuccessCode::Complete)
-                | Ok(tickv::success_codes::SuccessCode::Written) => {
+            }
+            Operation::InvalidateKey => {
+                if !is_pending(&ret) {
                     self.operation.set(Operation::None);
+                    self.client.map(|cb| {
+                        cb.invalidate_key_complete(
+                            ret.map(|_| ()).map_err(into_error_code),
+                            self.key_buffer.take().unwrap(),
+                        );
+                    });
                 }
-                _ => {}
-            },
+            }
             Operation::GarbageCollect => match ret {
                 Ok(tickv::success_codes::SuccessCode::Complete)
                 | Ok(tickv::success_codes::SuccessCode::Written) => {
@@ -352,42 +393,52 @@ impl<'a, F: Flash> KVSystem<'a> for TicKVStore<'a, F> {
         self.client.set(client);
     }
 
-    fn generate_key(
+    #[allow(deprecated)]
+    fn generate_key(&self, unhashed_key: &[u8], key_buf: &mut Self::K) -> Result<(), ErrorCode> {
+        let mut hasher = SipHasher::new();
+        hasher.write(unhashed_key);
+        *key_buf = hasher.finish().to_le_bytes();
+        Ok(())
+    }
+
+    fn append_key(
         &self,
-        _unhashed_key: &'static mut [u8],
-        _key_buf: &'static mut Self::K,
+        key: &'static mut Self::K,
+        value: &'static mut [u8],
+        length: usize,
     ) -> Result<
         (),
         (
-            &'static mut [u8],
             &'static mut Self::K,
+            &'static mut [u8],
             Result<(), ErrorCode>,
         ),
     > {
-        unimplemented!()
-    }
-
-    fn append_key(
-        &self,
-        key: &'static mut Self::K,
-        value: &'static [u8],
-    ) -> Result<(), (&'static mut Self::K, &'static [u8], Result<(), ErrorCode>)> {
+        if length > value.len() {
+            return Err((key, value, Err(ErrorCode::INVAL)));
+        }
         match self.operation.get() {
             Operation::None => {
                 self.operation.set(Operation::AppendKey);
 
-                match self.tickv.append_key(u64::from_le_bytes(*key), value) {
+                match self
+                    .tickv
+                    .append_key(u64::from_le_bytes(*key), value, length)
+                {
                     Ok(_ret) => {
                         self.key_buffer.replace(key);
                         Ok(())
                     }
-                    Err(e) => match e {
+                    Err((value, e)) => match e {
                         tickv::error_codes::ErrorCode::ReadNotReady(_)
                         | tickv::error_codes::ErrorCode::WriteNotReady(_) => {
                             self.key_buffer.replace(key);
                             Ok(())
                         }
-                        _ => Err((key, value, Err(ErrorCode::FAIL))),
+                        _ => {
+                            self.operation.set(Operation::None);
+                            Err((key, value.unwrap(), Err(into_error_code(e))))
+                        }
                     },
                 }
             }
@@ -396,7 +447,8 @@ impl<'a, F: Flash> KVSystem<'a> for TicKVStore<'a, F> {
                 // We can save this request and start it after init
                 self.next_operation.set(Operation::AppendKey);
                 self.key_buffer.replace(key);
-                self.value_buffer.replace(Some(value));
+                self.value_buffer.replace(value);
+                self.value_length.set(length);
                 Ok(())
             }
             _ => {
@@ -433,7 +485,10 @@ impl<'a, F: Flash> KVSystem<'a> for TicKVStore<'a, F> {
                             self.key_buffer.replace(key);
                             Ok(())
                         }
-                        _ => Err((key, buf.unwrap(), Err(ErrorCode::FAIL))),
+                        _ => {
+                            self.operation.set(Operation::None);
+                            Err((key, buf.unwrap(), Err(into_error_code(e))))
+                        }
                     },
                 }
             }
@@ -471,7 +526,10 @@ impl<'a, F: Flash> KVSystem<'a> for TicKVStore<'a, F> {
                             self.key_buffer.replace(key);
                             Ok(())
                         }
-                        _ => Err((key, Err(ErrorCode::FAIL))),
+                        _ => {
+                            self.operation.set(Operation::None);
+                            Err((key, Err(into_error_code(e))))
+                        }
                     },
                 }
             }
@@ -42,7 +42,7 @@
 //! ```rust
 //! # use capsules::test::aes_ccm::Test;
 //! # use capsules::virtual_aes_ccm;
-//! # use kernel::common::dynamic_deferred_call::DynamicDeferredCall;
+//! # use kernel::common::deferred_call::DeferredCallClient;
 //! # use kernel::hil::symmetric_encryption::{AES128, AES128CCM, AES128_BLOCK_SIZE};
 //! # use kernel::static_init;
 //! # use sam4l::aes::{Aes, AES};
@@ -51,11 +51,7 @@
 //! // mux
 //! let ccm_mux = static_init!(AESCCMMUX, virtual_aes_ccm::MuxAES128CCM::new(&AES));
 //! AES.set_client(ccm_mux);
-//! ccm_mux.initialize_callback_handle(
-//!     dynamic_deferred_caller
-//!         .register(ccm_mux)
-//!         .expect("no deferred call slot available for ccm mux"),
-//! );
+//! ccm_mux.register();
 //! const CRYPT_SIZE: usize = 7 * AES128_BLOCK_SIZE;
 //! let crypt_buf1 = static_init!([u8; CRYPT_SIZE], [0x00; CRYPT_SIZE]);
 //! let ccm_client1 = static_init!(
@@ -84,9 +80,7 @@ use crate::net::stream::SResult;
 use crate::net::stream::{encode_bytes, encode_u16};
 use core::cell::Cell;
 use kernel::common::cells::{OptionalCell, TakeCell};
-use kernel::common::dynamic_deferred_call::{
-    DeferredCallHandle, DynamicDeferredCall, DynamicDeferredCallClient,
-};
+use kernel::common::deferred_call::{DeferredCall, DeferredCallClient};
 use kernel::common::{List, ListLink, ListNode};
 use kernel::debug;
 use kernel::hil::symmetric_encryption;
@@ -139,19 +133,17 @@ pub struct MuxAES128CCM<'a, A: AES128<'a> + AES128Ctr + AES128CBC> {
     aes: &'a A,
     clients: List<'a, VirtualAES128CCM<'a, A>>,
     inflight: OptionalCell<&'a VirtualAES128CCM<'a, A>>,
-    deferred_caller: &'a DynamicDeferredCall,
-    handle: OptionalCell<DeferredCallHandle>,
+    deferred_call: DeferredCall,
 }
 
 impl<'a, A: AES128<'a> + AES128Ctr + AES128CBC> MuxAES128CCM<'a, A> {
-    pub fn new(aes: &'a A, deferred_caller: &'a DynamicDeferredCall) -> MuxAES128CCM<'a, A> {
+    pub fn new(aes: &'a A) -> MuxAES128CCM<'a, A> {
         aes.enable(); // enable the hardware, in case it's forgotten elsewhere
         MuxAES128CCM {
             aes: aes,
             clients: List::new(),
             inflight: OptionalCell::empty(),
-            deferred_caller: deferred_caller,
-            handle: OptionalCell::empty(),
+            deferred_call: DeferredCall::new(),
         }
     }
 
@@ -165,24 +157,12 @@ impl<'a, A: AES128<'a> + AES128Ctr + AES128CBC> MuxAES128CCM<'a, A> {
         self.aes.disable();
     }
 
-    /// inorder to receive callbacks correctly, please call  
-    /// ```rust
-    /// mux.initialize_callback_handle(
-    ///     dynamic_deferred_caller.register(mux)
-    ///     .expect("no deferred call slot available for ccm mux")
-    /// );
-    /// ```
-    /// after the creation of the mux
-    pub fn initialize_callback_handle(&self, handle: DeferredCallHandle) {
-        self.handle.replace(handle);
-    }
-
     /// Asynchronously executes the next operation, if any. Used by calls
     /// to trigger do_next_op such that it will execute after the call
     /// returns.
     /// See virtual_uart::MuxUart<'a>::do_next_op_async
     fn do_next_op_async(&self) {
-        self.handle.map(|handle| self.deferred_caller.set(*handle));
+        self.deferred_call.set();
     }
 
     fn do_next_op(&self) {
@@ -219,10 +199,14 @@ impl<'a, A: AES128<'a> + AES128Ctr + AES128CBC> MuxAES128CCM<'a, A> {
     }
 }
 
-impl<'a, A: AES128<'a> + AES128Ctr + AES128CBC> DynamicDeferredCallClient for MuxAES128CCM<'a, A> {
-    fn call(&self, _handle: DeferredCallHandle) {
+impl<'a, A: AES128<'a> + AES128Ctr + AES128CBC> DeferredCallClient for MuxAES128CCM<'a, A> {
+    fn handle_deferred_call(&self) {
         self.do_next_op();
     }
+
+    fn register(&'static self) {
+        self.deferred_call.register(self);
+    }
 }
 
 impl<'a, A: AES128<'a> + AES128Ctr + AES128CBC> symmetric_encryption::Client<'a>
@@ -5,11 +5,10 @@
 
 use core::cell::Cell;
 use kernel::common::cells::{OptionalCell, TakeCell};
-use kernel::common::dynamic_deferred_call::{
-    DeferredCallHandle, DynamicDeferredCall, DynamicDeferredCallClient,
-};
+use kernel::common::deferred_call::{DeferredCall, DeferredCallClient};
 use kernel::common::{List, ListLink, ListNode};
 use kernel::hil::i2c::{self, Error, I2CClient, I2CHwMasterClient};
+use kernel::power::{PowerRequest, PowerResource};
 
 pub struct MuxI2C<'a> {
     i2c: &'a dyn i2c::I2CMaster,
@@ -19,8 +18,8 @@ pub struct MuxI2C<'a> {
     enabled: Cell<usize>,
     i2c_inflight: OptionalCell<&'a I2CDevice<'a>>,
     smbus_inflight: OptionalCell<&'a SMBusDevice<'a>>,
-    deferred_caller: &'a DynamicDeferredCall,
-    handle: OptionalCell<DeferredCallHandle>,
+    deferred_call: DeferredCall,
+    power: PowerRequest<'a>,
 }
 
 impl I2CHwMasterClient for MuxI2C<'_> {
@@ -39,11 +38,7 @@ impl I2CHwMasterClient for MuxI2C<'_> {
 }
 
 impl<'a> MuxI2C<'a> {
-    pub const fn new(
-        i2c: &'a dyn i2c::I2CMaster,
-        smbus: Option<&'a dyn i2c::SMBusMaster>,
-        deferred_caller: &'a DynamicDeferredCall,
-    ) -> MuxI2C<'a> {
+    pub fn new(i2c: &'a dyn i2c::I2CMaster, smbus: Option<&'a dyn i2c::SMBusMaster>) -> MuxI2C<'a> {
         MuxI2C {
             i2c: i2c,
             smbus,
@@ -52,13 +47,15 @@ impl<'a> MuxI2C<'a> {
             enabled: Cell::new(0),
             i2c_inflight: OptionalCell::empty(),
             smbus_inflight: OptionalCell::empty(),
-            deferred_caller: deferred_caller,
-            handle: OptionalCell::empty(),
+            deferred_call: DeferredCall::new(),
+            power: PowerRequest::new(),
         }
     }
 
-    pub fn initialize_callback_handle(&self, handle: DeferredCallHandle) {
-        self.handle.replace(handle);
+    /// Set the clock or power domain of the bus, which the mux holds only
+    /// while it has operations in flight.
+    pub fn set_power_resource(&self, resource: &'a dyn PowerResource) {
+        self.power.set_resource(resource);
     }
 
     fn enable(&self) {
@@ -87,6 +84,7 @@ impl<'a> MuxI2C<'a> {
                 .iter()
                 .find(|node| node.operation.get() != Op::Idle);
             mnode.map(|node| {
+                self.power.acquire();
                 node.buffer.take().map(|buf| {
                     match node.operation.get() {
                         Op::Write(len) => match self.i2c.write(node.addr, buf, len) {
@@ -132,6 +130,7 @@ impl<'a> MuxI2C<'a> {
                     .iter()
                     .find(|node| node.operation.get() != Op::Idle);
                 mnode.map(|node| {
+                    self.power.acquire();
                     node.buffer.take().map(|buf| match node.operation.get() {
                         Op::Write(len) => {
                             match self.smbus.unwrap().smbus_write(node.addr, buf, len) {
@@ -176,6 +175,10 @@ impl<'a> MuxI2C<'a> {
                     self.smbus_inflight.set(node);
                 });
             }
+
+            if self.i2c_inflight.is_none() && self.smbus_inflight.is_none() {
+                self.power.relinquish();
+            }
         }
     }
 
@@ -188,14 +191,18 @@ impl<'a> MuxI2C<'a> {
     ///
     /// https://github.com/tock/tock/issues/1496
     fn do_next_op_async(&self) {
-        self.handle.map(|handle| self.deferred_caller.set(*handle));
+        self.deferred_call.set();
     }
 }
 
-impl<'a> DynamicDeferredCallClient for MuxI2C<'a> {
-    fn call(&self, _handle: DeferredCallHandle) {
+impl<'a> DeferredCallClient for MuxI2C<'a> {
+    fn handle_deferred_call(&self) {
         self.do_next_op();
     }
+
+    fn register(&'static self) {
+        self.deferred_call.register(self);
+    }
 }
 
 #[derive(Copy, Clone, PartialEq)]
@@ -4,6 +4,7 @@ use core::cell::Cell;
 use kernel::common::cells::{OptionalCell, TakeCell};
 use kernel::common::{List, ListLink, ListNode};
 use kernel::hil;
+use kernel::power::{PowerRequest, PowerResource};
 use kernel::ErrorCode;
 
 /// The Mux struct manages multiple Spi clients. Each client may have
@@ -12,6 +13,7 @@ pub struct MuxSpiMaster<'a, Spi: hil::spi::SpiMaster> {
     spi: &'a Spi,
     devices: List<'a, VirtualSpiMasterDevice<'a, Spi>>,
     inflight: OptionalCell<&'a VirtualSpiMasterDevice<'a, Spi>>,
+    power: PowerRequest<'a>,
 }
 
 impl<Spi: hil::spi::SpiMaster> hil::spi::SpiMasterClient for MuxSpiMaster<'_, Spi> {
@@ -34,9 +36,16 @@ impl<'a, Spi: hil::spi::SpiMaster> MuxSpiMaster<'a, Spi> {
             spi: spi,
             devices: List::new(),
             inflight: OptionalCell::empty(),
+            power: PowerRequest::new(),
         }
     }
 
+    /// Set the clock or power domain of the bus, which the mux holds only
+    /// while it has operations to run.
+    pub fn set_power_resource(&self, resource: &'a dyn PowerResource) {
+        self.power.set_resource(resource);
+    }
+
     fn do_next_op(&self) {
         if self.inflight.is_none() {
             let mnode = self
@@ -44,6 +53,7 @@ impl<'a, Spi: hil::spi::SpiMaster> MuxSpiMaster<'a, Spi> {
                 .iter()
                 .find(|node| node.operation.get() != Op::Idle);
             mnode.map(|node| {
+                self.power.acquire();
                 self.spi.specify_chip_select(node.chip_select.get());
                 let op = node.operation.get();
                 // Need to set idle here in case callback changes state
@@ -77,6 +87,9 @@ impl<'a, Spi: hil::spi::SpiMaster> MuxSpiMaster<'a, Spi> {
                     Op::Idle => {} // Can't get here...
                 }
             });
+            if self.inflight.is_none() {
+                self.power.relinquish();
+            }
         }
     }
 }
@@ -46,11 +46,10 @@ use core::cmp;
 use kernel::ErrorCode;
 
 use kernel::common::cells::{OptionalCell, TakeCell};
-use kernel::common::dynamic_deferred_call::{
-    DeferredCallHandle, DynamicDeferredCall, DynamicDeferredCallClient,
-};
+use kernel::common::deferred_call::{DeferredCall, DeferredCallClient};
 use kernel::common::{List, ListLink, ListNode};
 use kernel::hil::uart;
+use kernel::power::{PowerRequest, PowerResource};
 
 const RX_BUF_LEN: usize = 64;
 pub static mut RX_BUF: [u8; RX_BUF_LEN] = [0; RX_BUF_LEN];
@@ -62,8 +61,8 @@ pub struct MuxUart<'a> {
     inflight: OptionalCell<&'a UartDevice<'a>>,
     buffer: TakeCell<'static, [u8]>,
     completing_read: Cell<bool>,
-    deferred_caller: &'a DynamicDeferredCall,
-    handle: OptionalCell<DeferredCallHandle>,
+    deferred_call: DeferredCall,
+    power: PowerRequest<'a>,
 }
 
 impl<'a> uart::TransmitClient for MuxUart<'a> {
@@ -78,6 +77,7 @@ impl<'a> uart::TransmitClient for MuxUart<'a> {
             device.transmitted_buffer(tx_buffer, tx_len, rcode);
         });
         self.do_next_op();
+        self.update_power();
     }
 }
 
@@ -186,16 +186,12 @@ impl<'a> uart::ReceiveClient for MuxUart<'a> {
         if read_pending {
             self.start_receive(next_read_len);
         }
+        self.update_power();
     }
 }
 
 impl<'a> MuxUart<'a> {
-    pub fn new(
-        uart: &'a dyn uart::Uart<'a>,
-        buffer: &'static mut [u8],
-        speed: u32,
-        deferred_caller: &'a DynamicDeferredCall,
-    ) -> MuxUart<'a> {
+    pub fn new(uart: &'a dyn uart::Uart<'a>, buffer: &'static mut [u8], speed: u32) -> MuxUart<'a> {
         MuxUart {
             uart: uart,
             speed: speed,
@@ -203,8 +199,22 @@ impl<'a> MuxUart<'a> {
             inflight: OptionalCell::empty(),
             buffer: TakeCell::new(buffer),
             completing_read: Cell::new(false),
-            deferred_caller: deferred_caller,
-            handle: OptionalCell::empty(),
+            deferred_call: DeferredCall::new(),
+            power: PowerRequest::new(),
+        }
+    }
+
+    /// Set the clock or power domain of the UART, which the mux holds only
+    /// while it is transmitting or receiving.
+    pub fn set_power_resource(&self, resource: &'a dyn PowerResource) {
+        self.power.set_resource(resource);
+    }
+
+    /// Release the clock or power domain if nothing is being transmitted or
+    /// received.
+    fn update_power(&self) {
+        if self.inflight.is_none() && self.buffer.is_some() {
+            self.power.relinquish();
         }
     }
 
@@ -218,14 +228,11 @@ impl<'a> MuxUart<'a> {
         });
     }
 
-    pub fn initialize_callback_handle(&self, handle: DeferredCallHandle) {
-        self.handle.replace(handle);
-    }
-
     fn do_next_op(&self) {
         if self.inflight.is_none() {
             let mnode = self.devices.iter().find(|node| node.operation.is_some());
             mnode.map(|node| {
+                self.power.acquire();
                 node.tx_buffer.take().map(|buf| {
                     node.operation.map(move |op| match op {
                         Operation::Transmit { len } => {
@@ -284,6 +291,7 @@ impl<'a> MuxUart<'a> {
             },
             |rxbuf| {
                 // Case (3). No ongoing receive calls, we can start one now.
+                self.power.acquire();
                 let len = cmp::min(rx_len, rxbuf.len());
                 let _ = self.uart.receive_buffer(rxbuf, len);
                 false
@@ -300,14 +308,18 @@ impl<'a> MuxUart<'a> {
     ///
     /// https://github.com/tock/tock/issues/1496
     fn do_next_op_async(&self) {
-        self.handle.map(|handle| self.deferred_caller.set(*handle));
+        self.deferred_call.set();
     }
 }
 
-impl<'a> DynamicDeferredCallClient for MuxUart<'a> {
-    fn call(&self, _handle: DeferredCallHandle) {
+impl<'a> DeferredCallClient for MuxUart<'a> {
+    fn handle_deferred_call(&self) {
         self.do_next_op();
     }
+
+    fn register(&'static self) {
+        self.deferred_call.register(self);
+    }
 }
 
 #[derive(Copy, Clone, PartialEq)]
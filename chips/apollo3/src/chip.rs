@@ -5,14 +5,14 @@ use cortexm4;
 use kernel::Chip;
 use kernel::InterruptService;
 
-pub struct Apollo3<I: InterruptService<()> + 'static> {
+pub struct Apollo3<I: InterruptService + 'static> {
     mpu: cortexm4::mpu::MPU,
     userspace_kernel_boundary: cortexm4::syscall::SysCall,
     scheduler_timer: cortexm4::systick::SysTick,
     interrupt_service: &'static I,
 }
 
-impl<I: InterruptService<()> + 'static> Apollo3<I> {
+impl<I: InterruptService + 'static> Apollo3<I> {
     pub unsafe fn new(interrupt_service: &'static I) -> Self {
         Self {
             mpu: cortexm4::mpu::MPU::new(),
@@ -59,7 +59,7 @@ impl Apollo3DefaultPeripherals {
     }
 }
 
-impl kernel::InterruptService<()> for Apollo3DefaultPeripherals {
+impl kernel::InterruptService for Apollo3DefaultPeripherals {
     unsafe fn service_interrupt(&self, interrupt: u32) -> bool {
         use crate::nvic;
         match interrupt {
@@ -78,12 +78,9 @@ impl kernel::InterruptService<()> for Apollo3DefaultPeripherals {
         }
         true
     }
-    unsafe fn service_deferred_call(&self, _: ()) -> bool {
-        false
-    }
 }
 
-impl<I: InterruptService<()> + 'static> Chip for Apollo3<I> {
+impl<I: InterruptService + 'static> Chip for Apollo3<I> {
     type MPU = cortexm4::mpu::MPU;
     type UserspaceKernelBoundary = cortexm4::syscall::SysCall;
     type SchedulerTimer = cortexm4::systick::SysTick;
@@ -15,7 +15,7 @@ use crate::plic::Plic;
 use crate::plic::PLIC;
 use kernel::InterruptService;
 
-pub struct E310x<'a, A: 'static + Alarm<'static>, I: InterruptService<()> + 'a> {
+pub struct E310x<'a, A: 'static + Alarm<'static>, I: InterruptService + 'a> {
     userspace_kernel_boundary: rv32i::syscall::SysCall,
     pmp: PMP<4>,
     plic: &'a Plic,
@@ -50,7 +50,7 @@ impl<'a> E310xDefaultPeripherals<'a> {
     }
 }
 
-impl<'a> InterruptService<()> for E310xDefaultPeripherals<'a> {
+impl<'a> InterruptService for E310xDefaultPeripherals<'a> {
     unsafe fn service_interrupt(&self, interrupt: u32) -> bool {
         match interrupt {
             interrupts::UART0 => self.uart0.handle_interrupt(),
@@ -63,13 +63,9 @@ impl<'a> InterruptService<()> for E310xDefaultPeripherals<'a> {
         }
         true
     }
-
-    unsafe fn service_deferred_call(&self, _: ()) -> bool {
-        false
-    }
 }
 
-impl<'a, A: 'static + Alarm<'static>, I: InterruptService<()> + 'a> E310x<'a, A, I> {
+impl<'a, A: 'static + Alarm<'static>, I: InterruptService + 'a> E310x<'a, A, I> {
     pub unsafe fn new(
         alarm: &'static A,
         plic_interrupt_service: &'a I,
@@ -103,9 +99,7 @@ impl<'a, A: 'static + Alarm<'static>, I: InterruptService<()> + 'a> E310x<'a, A,
     }
 }
 
-impl<'a, A: 'static + Alarm<'static>, I: InterruptService<()> + 'a> kernel::Chip
-    for E310x<'a, A, I>
-{
+impl<'a, A: 'static + Alarm<'static>, I: InterruptService + 'a> kernel::Chip for E310x<'a, A, I> {
     type MPU = PMP<4>;
     type UserspaceKernelBoundary = rv32i::syscall::SysCall;
     type SchedulerTimer = kernel::VirtualSchedulerTimer<A>;
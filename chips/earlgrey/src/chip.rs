@@ -2,7 +2,7 @@
 
 use core::fmt::Write;
 use kernel;
-use kernel::common::dynamic_deferred_call::DynamicDeferredCall;
+use kernel::common::deferred_call::DeferredCallClient;
 use kernel::common::registers::interfaces::{ReadWriteable, Readable, Writeable};
 use kernel::hil::time::Alarm;
 use kernel::{Chip, InterruptService};
@@ -15,7 +15,7 @@ use crate::interrupts;
 use crate::plic::Plic;
 use crate::plic::PLIC;
 
-pub struct EarlGrey<'a, A: 'static + Alarm<'static>, I: InterruptService<()> + 'a> {
+pub struct EarlGrey<'a, A: 'static + Alarm<'static>, I: InterruptService + 'a> {
     userspace_kernel_boundary: SysCall,
     pub pmp: PMP<8>,
     plic: &'a Plic,
@@ -37,13 +37,13 @@ pub struct EarlGreyDefaultPeripherals<'a> {
 }
 
 impl<'a> EarlGreyDefaultPeripherals<'a> {
-    pub fn new(deferred_caller: &'static DynamicDeferredCall) -> Self {
+    pub fn new() -> Self {
         Self {
-            aes: crate::aes::Aes::new(deferred_caller),
+            aes: crate::aes::Aes::new(),
             hmac: lowrisc::hmac::Hmac::new(crate::hmac::HMAC0_BASE),
             usb: lowrisc::usbdev::Usb::new(crate::usbdev::USB0_BASE),
             uart0: lowrisc::uart::Uart::new(crate::uart::UART0_BASE, CONFIG.peripheral_freq),
-            otbn: lowrisc::otbn::Otbn::new(crate::otbn::OTBN_BASE, deferred_caller),
+            otbn: lowrisc::otbn::Otbn::new(crate::otbn::OTBN_BASE),
             gpio_port: crate::gpio::Port::new(),
             i2c0: lowrisc::i2c::I2c::new(
                 crate::i2c::I2C0_BASE,
@@ -55,9 +55,14 @@ impl<'a> EarlGreyDefaultPeripherals<'a> {
             ),
         }
     }
+
+    pub fn init(&'static self) {
+        self.aes.register();
+        self.otbn.register();
+    }
 }
 
-impl<'a> InterruptService<()> for EarlGreyDefaultPeripherals<'a> {
+impl<'a> InterruptService for EarlGreyDefaultPeripherals<'a> {
     unsafe fn service_interrupt(&self, interrupt: u32) -> bool {
         match interrupt {
             interrupts::UART0_TX_WATERMARK..=interrupts::UART0_RX_PARITYERR => {
@@ -84,13 +89,9 @@ impl<'a> InterruptService<()> for EarlGreyDefaultPeripherals<'a> {
         }
         true
     }
-
-    unsafe fn service_deferred_call(&self, _: ()) -> bool {
-        false
-    }
 }
 
-impl<'a, A: 'static + Alarm<'static>, I: InterruptService<()> + 'a> EarlGrey<'a, A, I> {
+impl<'a, A: 'static + Alarm<'static>, I: InterruptService + 'a> EarlGrey<'a, A, I> {
     pub unsafe fn new(
         virtual_alarm: &'static A,
         plic_interrupt_service: &'a I,
@@ -197,7 +198,7 @@ impl<'a, A: 'static + Alarm<'static>, I: InterruptService<()> + 'a> EarlGrey<'a,
     }
 }
 
-impl<'a, A: 'static + Alarm<'static>, I: InterruptService<()> + 'a> kernel::Chip
+impl<'a, A: 'static + Alarm<'static>, I: InterruptService + 'a> kernel::Chip
     for EarlGrey<'a, A, I>
 {
     type MPU = PMP<8>;
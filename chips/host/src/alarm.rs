@@ -0,0 +1,140 @@
+//! 1 MHz alarm backed by a host timer thread.
+//!
+//! The counter is the number of microseconds since the alarm was created,
+//! truncated to 32 bits. When an alarm is set, a helper thread sleeps until
+//! the deadline and then raises the alarm's interrupt line. The client is
+//! called from `handle_interrupt()` on the kernel thread.
+
+use core::cell::Cell;
+use std::sync::{Arc, Condvar, Mutex};
+use std::thread;
+use std::time::{Duration, Instant};
+
+use kernel::common::cells::OptionalCell;
+use kernel::hil::time::{self, Alarm, Ticks, Ticks32, Time};
+use kernel::ErrorCode;
+
+use crate::chip::InterruptLine;
+
+#[derive(Default)]
+struct TimerThread {
+    deadline: Mutex<Option<Instant>>,
+    changed: Condvar,
+}
+
+impl TimerThread {
+    fn run(&self, line: InterruptLine) {
+        let mut deadline = self.deadline.lock().unwrap();
+        loop {
+            match *deadline {
+                None => deadline = self.changed.wait(deadline).unwrap(),
+                Some(expiry) => {
+                    let now = Instant::now();
+                    if now >= expiry {
+                        *deadline = None;
+                        line.raise();
+                    } else {
+                        deadline = self.changed.wait_timeout(deadline, expiry - now).unwrap().0;
+                    }
+                }
+            }
+        }
+    }
+
+    fn set(&self, deadline: Option<Instant>) {
+        *self.deadline.lock().unwrap() = deadline;
+        self.changed.notify_all();
+    }
+}
+
+pub struct HostAlarm<'a> {
+    epoch: Instant,
+    timer: Arc<TimerThread>,
+    reference: Cell<Ticks32>,
+    dt: Cell<Ticks32>,
+    armed: Cell<bool>,
+    client: OptionalCell<&'a dyn time::AlarmClient>,
+}
+
+impl<'a> HostAlarm<'a> {
+    /// Create an alarm that signals expiration on `line`. The board's
+    /// `InterruptService` must call `handle_interrupt()` for that line.
+    pub fn new(line: InterruptLine) -> HostAlarm<'a> {
+        let timer = Arc::new(TimerThread::default());
+        let thread_timer = timer.clone();
+        thread::spawn(move || thread_timer.run(line));
+        HostAlarm {
+            epoch: Instant::now(),
+            timer,
+            reference: Cell::new(0.into()),
+            dt: Cell::new(0.into()),
+            armed: Cell::new(false),
+            client: OptionalCell::empty(),
+        }
+    }
+
+    pub fn handle_interrupt(&self) {
+        if !self.armed.get() {
+            return;
+        }
+        let reference = self.reference.get();
+        let expiry = reference.wrapping_add(self.dt.get());
+        if self.now().within_range(reference, expiry) {
+            // Spurious: the alarm was moved after the interrupt was raised.
+            return;
+        }
+        self.armed.set(false);
+        self.client.map(|client| client.alarm());
+    }
+}
+
+impl Time for HostAlarm<'_> {
+    type Frequency = time::Freq1MHz;
+    type Ticks = Ticks32;
+
+    fn now(&self) -> Ticks32 {
+        (self.epoch.elapsed().as_micros() as u32).into()
+    }
+}
+
+impl<'a> Alarm<'a> for HostAlarm<'a> {
+    fn set_alarm_client(&'a self, client: &'a dyn time::AlarmClient) {
+        self.client.set(client);
+    }
+
+    fn set_alarm(&self, reference: Ticks32, dt: Ticks32) {
+        let dt = core::cmp::max(dt, self.minimum_dt());
+        self.reference.set(reference);
+        self.dt.set(dt);
+        self.armed.set(true);
+
+        let now = self.now();
+        let expiry = reference.wrapping_add(dt);
+        let remaining_us = if now.within_range(reference, expiry) {
+            expiry.wrapping_sub(now).into_u32()
+        } else {
+            0
+        };
+        self.timer.set(Some(
+            Instant::now() + Duration::from_micros(remaining_us.into()),
+        ));
+    }
+
+    fn get_alarm(&self) -> Ticks32 {
+        self.reference.get().wrapping_add(self.dt.get())
+    }
+
+    fn disarm(&self) -> Result<(), ErrorCode> {
+        self.armed.set(false);
+        self.timer.set(None);
+        Ok(())
+    }
+
+    fn is_armed(&self) -> bool {
+        self.armed.get()
+    }
+
+    fn minimum_dt(&self) -> Ticks32 {
+        1.into()
+    }
+}
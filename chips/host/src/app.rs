@@ -0,0 +1,421 @@
+//! Userspace side of the host system call interface.
+//!
+//! An `App` is handed to each host program when its process starts. It
+//! provides the Tock system call ABI (TRD 104) as plain method calls, plus a
+//! few helpers to access process memory.
+//!
+//! Upcalls are Rust closures. Since the kernel only deals in function
+//! addresses, each subscribed closure is given a unique address inside the
+//! application binary, and the runtime maps function calls at that address
+//! back to the closure.
+//!
+//! Threads are created the same way: `thread_create()` registers the thread
+//! closure under an address in the application binary, which the kernel
+//! passes back when it starts the thread. Upcalls only ever run on the main
+//! thread, so only the main thread may subscribe.
+
+use core::cell::{Cell, RefCell};
+use std::panic;
+use std::rc::Rc;
+use std::sync::mpsc::{Receiver, Sender};
+use std::sync::{Arc, Mutex};
+
+use kernel::procs::FunctionCall;
+use kernel::syscall::{Syscall, SyscallReturn, YieldCall};
+
+use crate::syscall::{ProcessEvent, ProcessEventMessage, ProcessTornDown, Resume, ResumeMessage};
+
+/// Distance between the addresses assigned to upcall closures.
+const UPCALL_STRIDE: usize = core::mem::size_of::<usize>();
+
+/// Slot of the address given to thread entry points. Upcall closures use the
+/// slots before it.
+const THREAD_ENTRY_SLOT: usize = 32;
+
+type Upcall = Rc<dyn Fn(&App, usize, usize, usize)>;
+
+/// The closure a thread runs, with the argument passed to `thread_create()`.
+pub type ThreadFunction = Arc<dyn Fn(&App, usize) + Send + Sync>;
+
+/// A thread entry point, and the process memory layout the thread runs with.
+#[derive(Clone)]
+pub(crate) struct ThreadEntry {
+    pub(crate) pc: usize,
+    pub(crate) flash_start: usize,
+    pub(crate) memory_start: usize,
+    pub(crate) memory_len: usize,
+    pub(crate) function: ThreadFunction,
+}
+
+/// Thread entry points of all processes, keyed by address. The kernel looks
+/// the entry up while handling the create call, so each process needs only
+/// one.
+pub(crate) type ThreadEntries = Arc<Mutex<Vec<ThreadEntry>>>;
+
+pub struct App {
+    to_kernel: Sender<ProcessEventMessage>,
+    from_kernel: Receiver<ResumeMessage>,
+    flash_start: usize,
+    memory_start: usize,
+    memory_len: usize,
+    app_break: Cell<usize>,
+    /// Subscribed upcalls, indexed by slot. The slot determines the upcall's
+    /// address.
+    upcalls: RefCell<Vec<((usize, usize), Upcall)>>,
+    thread_entries: ThreadEntries,
+}
+
+impl App {
+    /// Create the runtime from the initial function call the kernel
+    /// scheduled for the process.
+    pub(crate) fn new(
+        to_kernel: Sender<ProcessEventMessage>,
+        from_kernel: Receiver<ResumeMessage>,
+        init: FunctionCall,
+        thread_entries: ThreadEntries,
+    ) -> App {
+        App {
+            to_kernel,
+            from_kernel,
+            flash_start: init.argument0,
+            memory_start: init.argument1,
+            memory_len: init.argument2,
+            app_break: Cell::new(init.argument3),
+            upcalls: RefCell::new(Vec::new()),
+            thread_entries,
+        }
+    }
+
+    /// Create the runtime for a thread the process created.
+    pub(crate) fn new_thread(
+        to_kernel: Sender<ProcessEventMessage>,
+        from_kernel: Receiver<ResumeMessage>,
+        entry: &ThreadEntry,
+        app_break: usize,
+        thread_entries: ThreadEntries,
+    ) -> App {
+        App {
+            to_kernel,
+            from_kernel,
+            flash_start: entry.flash_start,
+            memory_start: entry.memory_start,
+            memory_len: entry.memory_len,
+            app_break: Cell::new(app_break),
+            upcalls: RefCell::new(Vec::new()),
+            thread_entries,
+        }
+    }
+
+    /// Start of the application binary in flash.
+    pub fn flash_start(&self) -> usize {
+        self.flash_start
+    }
+
+    /// Start of process memory.
+    pub fn memory_start(&self) -> usize {
+        self.memory_start
+    }
+
+    /// Length of the memory region allocated to the process, including the
+    /// kernel-owned part.
+    pub fn memory_len(&self) -> usize {
+        self.memory_len
+    }
+
+    /// Current end of process-accessible memory.
+    pub fn app_break(&self) -> usize {
+        self.app_break.get()
+    }
+
+    /// Hand control to the kernel and wait to be resumed.
+    fn trap(&self, syscall: Syscall) -> Resume {
+        if self
+            .to_kernel
+            .send(ProcessEventMessage(ProcessEvent::Syscall(syscall)))
+            .is_err()
+        {
+            panic::resume_unwind(Box::new(ProcessTornDown));
+        }
+        match self.from_kernel.recv() {
+            Ok(ResumeMessage(resume)) => resume,
+            Err(_) => panic::resume_unwind(Box::new(ProcessTornDown)),
+        }
+    }
+
+    /// Issue a system call that is expected to return a value.
+    fn syscall(&self, syscall: Syscall) -> SyscallReturn {
+        match self.trap(syscall) {
+            Resume::Return(rval) => rval,
+            Resume::Call(_) | Resume::Continue => {
+                panic!("kernel did not return a value for {:?}", syscall)
+            }
+        }
+    }
+
+    /// Run the upcall the kernel pushed for this process.
+    fn dispatch(&self, call: FunctionCall) {
+        let slot = (call.pc - self.flash_start) / UPCALL_STRIDE - 1;
+        let upcall = self.upcalls.borrow().get(slot).map(|(_, f)| f.clone());
+        match upcall {
+            Some(upcall) => upcall(self, call.argument0, call.argument1, call.argument2),
+            None => panic!("jumped to invalid upcall address {:#x}", call.pc),
+        }
+    }
+
+    fn yield_syscall(&self, which: YieldCall) -> bool {
+        let syscall = Syscall::Yield {
+            which: which as usize,
+            address: self.memory_start as *mut u8,
+        };
+        match self.trap(syscall) {
+            Resume::Call(call) => {
+                self.dispatch(call);
+                true
+            }
+            Resume::Continue | Resume::Return(_) => false,
+        }
+    }
+
+    /// Block until an upcall has run.
+    pub fn yield_wait(&self) {
+        self.yield_syscall(YieldCall::Wait);
+    }
+
+    /// Run a pending upcall if there is one. Returns whether an upcall ran.
+    pub fn yield_no_wait(&self) -> bool {
+        self.yield_syscall(YieldCall::NoWait)
+    }
+
+    /// Block until the kernel schedules upcall `subscribe` of `driver`, and
+    /// return its arguments without running the upcall function.
+    pub fn yield_wait_for(&self, driver: usize, subscribe: usize) -> SyscallReturn {
+        self.syscall(Syscall::YieldWaitFor {
+            driver_number: driver,
+            subdriver_number: subscribe,
+        })
+    }
+
+    /// Repeatedly yield until `cond` returns true.
+    pub fn yield_for<F: Fn() -> bool>(&self, cond: F) {
+        while !cond() {
+            self.yield_wait();
+        }
+    }
+
+    pub fn command(
+        &self,
+        driver: usize,
+        command: usize,
+        arg0: usize,
+        arg1: usize,
+    ) -> SyscallReturn {
+        self.syscall(Syscall::Command {
+            driver_number: driver,
+            subdriver_number: command,
+            arg0,
+            arg1,
+        })
+    }
+
+    /// Subscribe `upcall` to subscribe number `subscribe` of `driver`. The
+    /// upcall is passed the three upcall arguments.
+    pub fn subscribe<F>(&self, driver: usize, subscribe: usize, upcall: F) -> SyscallReturn
+    where
+        F: Fn(&App, usize, usize, usize) + 'static,
+    {
+        let key = (driver, subscribe);
+        let slot = {
+            let mut upcalls = self.upcalls.borrow_mut();
+            match upcalls.iter().position(|(k, _)| *k == key) {
+                Some(slot) => {
+                    upcalls[slot].1 = Rc::new(upcall);
+                    slot
+                }
+                None => {
+                    upcalls.push((key, Rc::new(upcall)));
+                    upcalls.len() - 1
+                }
+            }
+        };
+        self.syscall(Syscall::Subscribe {
+            driver_number: driver,
+            subdriver_number: subscribe,
+            upcall_ptr: (self.flash_start + UPCALL_STRIDE * (slot + 1)) as *mut (),
+            appdata: 0,
+        })
+    }
+
+    /// Remove the upcall for subscribe number `subscribe` of `driver`.
+    pub fn unsubscribe(&self, driver: usize, subscribe: usize) -> SyscallReturn {
+        self.syscall(Syscall::Subscribe {
+            driver_number: driver,
+            subdriver_number: subscribe,
+            upcall_ptr: core::ptr::null_mut(),
+            appdata: 0,
+        })
+    }
+
+    pub fn allow_readwrite(
+        &self,
+        driver: usize,
+        allow: usize,
+        address: usize,
+        len: usize,
+    ) -> SyscallReturn {
+        self.syscall(Syscall::ReadWriteAllow {
+            driver_number: driver,
+            subdriver_number: allow,
+            allow_address: address as *mut u8,
+            allow_size: len,
+        })
+    }
+
+    pub fn allow_readonly(
+        &self,
+        driver: usize,
+        allow: usize,
+        address: usize,
+        len: usize,
+    ) -> SyscallReturn {
+        self.syscall(Syscall::ReadOnlyAllow {
+            driver_number: driver,
+            subdriver_number: allow,
+            allow_address: address as *const u8,
+            allow_size: len,
+        })
+    }
+
+    pub fn memop(&self, operand: usize, arg0: usize) -> SyscallReturn {
+        self.syscall(Syscall::Memop { operand, arg0 })
+    }
+
+    /// Move the process break to `address`.
+    pub fn brk(&self, address: usize) -> SyscallReturn {
+        let rval = self.memop(0, address);
+        if let SyscallReturn::Success = rval {
+            self.app_break.set(address);
+        }
+        rval
+    }
+
+    /// Allocate `len` word-aligned bytes of process memory by moving the
+    /// break. Returns the address of the allocation.
+    pub fn alloc(&self, len: usize) -> Option<usize> {
+        let start = self.app_break.get();
+        let start = (start + UPCALL_STRIDE - 1) & !(UPCALL_STRIDE - 1);
+        match self.brk(start + len) {
+            SyscallReturn::Success => Some(start),
+            _ => None,
+        }
+    }
+
+    fn check_accessible(&self, address: usize, len: usize) {
+        assert!(
+            address >= self.memory_start && address + len <= self.app_break.get(),
+            "access to {:#x}..{:#x} outside process memory",
+            address,
+            address + len
+        );
+    }
+
+    /// Read `len` bytes of process-accessible memory starting at `address`.
+    pub fn read_bytes(&self, address: usize, len: usize) -> Vec<u8> {
+        self.check_accessible(address, len);
+        // Safety: the range is inside process-accessible memory, and the
+        // kernel never runs while the process does.
+        unsafe { core::slice::from_raw_parts(address as *const u8, len).to_vec() }
+    }
+
+    /// Write `data` to process-accessible memory starting at `address`.
+    pub fn write_bytes(&self, address: usize, data: &[u8]) {
+        self.check_accessible(address, data.len());
+        // Safety: see `read_bytes()`.
+        unsafe {
+            core::slice::from_raw_parts_mut(address as *mut u8, data.len()).copy_from_slice(data)
+        }
+    }
+
+    fn thread_syscall(
+        &self,
+        operation: usize,
+        arg0: usize,
+        arg1: usize,
+        arg2: usize,
+    ) -> SyscallReturn {
+        self.syscall(Syscall::Thread {
+            operation,
+            arg0,
+            arg1,
+            arg2,
+        })
+    }
+
+    /// Create a thread that runs `function` with `argument`, on the stack
+    /// that grows down from `stack_top`. Returns the thread identifier.
+    pub fn thread_create<F>(&self, stack_top: usize, argument: usize, function: F) -> SyscallReturn
+    where
+        F: Fn(&App, usize) + Send + Sync + 'static,
+    {
+        let pc = self.flash_start + UPCALL_STRIDE * THREAD_ENTRY_SLOT;
+        let entry = ThreadEntry {
+            pc,
+            flash_start: self.flash_start,
+            memory_start: self.memory_start,
+            memory_len: self.memory_len,
+            function: Arc::new(function),
+        };
+        {
+            let mut entries = self.thread_entries.lock().unwrap();
+            entries.retain(|entry| entry.pc != pc);
+            entries.push(entry);
+        }
+        self.thread_syscall(0, pc, stack_top, argument)
+    }
+
+    /// Block until `thread` exits. Returns its completion code.
+    pub fn thread_join(&self, thread: usize) -> SyscallReturn {
+        self.thread_syscall(1, thread, 0, 0)
+    }
+
+    /// Let the other threads of the process run.
+    pub fn thread_yield(&self) -> SyscallReturn {
+        self.thread_syscall(2, 0, 0, 0)
+    }
+
+    /// Exit the current thread. Not allowed on the main thread.
+    pub fn thread_exit(&self, completion_code: u32) -> ! {
+        self.trap(Syscall::Thread {
+            operation: 3,
+            arg0: completion_code as usize,
+            arg1: 0,
+            arg2: 0,
+        });
+        panic!("thread_exit({}) returned", completion_code);
+    }
+
+    /// Identifier of the current thread. The main thread is 0.
+    pub fn thread_id(&self) -> usize {
+        match self.thread_syscall(4, 0, 0, 0) {
+            SyscallReturn::SuccessU32(thread) => thread as usize,
+            rval => panic!("thread id failed: {:?}", rval),
+        }
+    }
+
+    fn exit(&self, which: usize, completion_code: usize) -> ! {
+        self.trap(Syscall::Exit {
+            which,
+            completion_code,
+        });
+        panic!("exit({}) returned", which);
+    }
+
+    /// Exit and ask the kernel not to restart the process.
+    pub fn exit_terminate(&self, completion_code: usize) -> ! {
+        self.exit(0, completion_code)
+    }
+
+    /// Exit and ask the kernel to restart the process.
+    pub fn exit_restart(&self, completion_code: usize) -> ! {
+        self.exit(1, completion_code)
+    }
+}
@@ -0,0 +1,195 @@
+//! Chip trait setup.
+//!
+//! Interrupts are modeled as a bitmask shared between the kernel thread and
+//! any peripheral threads. Peripherals hold an `InterruptLine` and raise it
+//! from whatever thread they run on; the kernel services pending lines from
+//! the kernel thread, exactly as it would bottom-half handlers on hardware.
+
+use core::cell::Cell;
+use core::fmt::Write;
+use std::sync::{Arc, Condvar, Mutex};
+
+use kernel::sleep::SleepState;
+use kernel::Chip;
+use kernel::InterruptService;
+
+use crate::mpu::HostMpu;
+use crate::scheduler_timer::HostSchedulerTimer;
+use crate::syscall::SysCall;
+
+/// Number of interrupt lines supported by the host interrupt controller.
+pub const NUM_INTERRUPTS: u32 = 64;
+
+/// Sleep states of the host chip. They all just block the kernel thread, but
+/// have latencies typical of a microcontroller so sleep policies can be
+/// exercised.
+pub const SLEEP_STATES: [SleepState; 3] = [
+    SleepState::new("wait", 0, 0),
+    SleepState::new("stop", 50, 100),
+    SleepState::new("standby", 1000, 4000),
+];
+
+/// Watchdog that counts how often the kernel tickles it, and never fires.
+#[derive(Default)]
+pub struct HostWatchdog {
+    tickles: Cell<usize>,
+}
+
+impl HostWatchdog {
+    /// How many times the kernel tickled or resumed the watchdog.
+    pub fn tickles(&self) -> usize {
+        self.tickles.get()
+    }
+}
+
+impl kernel::watchdog::WatchDog for HostWatchdog {
+    fn tickle(&self) {
+        self.tickles.set(self.tickles.get() + 1);
+    }
+}
+
+#[derive(Default)]
+struct InterruptState {
+    pending: Mutex<u64>,
+    wakeup: Condvar,
+}
+
+/// Handle to a single interrupt line that can be raised from any thread.
+#[derive(Clone)]
+pub struct InterruptLine {
+    state: Arc<InterruptState>,
+    number: u32,
+}
+
+impl InterruptLine {
+    /// The interrupt number passed to `InterruptService::service_interrupt()`.
+    pub fn number(&self) -> u32 {
+        self.number
+    }
+
+    /// Mark this interrupt as pending and wake the chip if it is sleeping.
+    pub fn raise(&self) {
+        let mut pending = self.state.pending.lock().unwrap();
+        *pending |= 1 << self.number;
+        self.state.wakeup.notify_all();
+    }
+}
+
+pub struct HostChip<I: InterruptService + 'static> {
+    mpu: HostMpu,
+    userspace_kernel_boundary: SysCall,
+    scheduler_timer: HostSchedulerTimer,
+    interrupts: Arc<InterruptState>,
+    interrupt_service: &'static I,
+    sleep_counts: [Cell<usize>; SLEEP_STATES.len()],
+    watchdog: HostWatchdog,
+}
+
+impl<I: InterruptService + 'static> HostChip<I> {
+    pub fn new(interrupt_service: &'static I) -> Self {
+        Self {
+            mpu: HostMpu::new(),
+            userspace_kernel_boundary: SysCall::new(),
+            scheduler_timer: HostSchedulerTimer::new(),
+            interrupts: Arc::new(InterruptState::default()),
+            interrupt_service,
+            sleep_counts: Default::default(),
+            watchdog: HostWatchdog::default(),
+        }
+    }
+
+    /// How many times the kernel put the chip in sleep state `state`.
+    pub fn sleep_count(&self, state: usize) -> usize {
+        self.sleep_counts[state].get()
+    }
+
+    /// Get a handle to interrupt line `number` to give to a peripheral.
+    pub fn interrupt_line(&self, number: u32) -> InterruptLine {
+        assert!(number < NUM_INTERRUPTS, "invalid interrupt {}", number);
+        InterruptLine {
+            state: self.interrupts.clone(),
+            number,
+        }
+    }
+
+    /// Atomically take the lowest numbered pending interrupt, if any.
+    fn next_pending(&self) -> Option<u32> {
+        let mut pending = self.interrupts.pending.lock().unwrap();
+        if *pending == 0 {
+            None
+        } else {
+            let interrupt = pending.trailing_zeros();
+            *pending &= !(1 << interrupt);
+            Some(interrupt)
+        }
+    }
+}
+
+impl<I: InterruptService + 'static> Chip for HostChip<I> {
+    type MPU = HostMpu;
+    type UserspaceKernelBoundary = SysCall;
+    type SchedulerTimer = HostSchedulerTimer;
+    type WatchDog = HostWatchdog;
+
+    fn service_pending_interrupts(&self) {
+        while let Some(interrupt) = self.next_pending() {
+            if !unsafe { self.interrupt_service.service_interrupt(interrupt) } {
+                panic!("unhandled interrupt, {}", interrupt);
+            }
+        }
+    }
+
+    fn has_pending_interrupts(&self) -> bool {
+        *self.interrupts.pending.lock().unwrap() != 0
+    }
+
+    fn mpu(&self) -> &HostMpu {
+        &self.mpu
+    }
+
+    fn scheduler_timer(&self) -> &HostSchedulerTimer {
+        &self.scheduler_timer
+    }
+
+    fn watchdog(&self) -> &Self::WatchDog {
+        &self.watchdog
+    }
+
+    fn userspace_kernel_boundary(&self) -> &SysCall {
+        &self.userspace_kernel_boundary
+    }
+
+    /// Block the kernel thread until an interrupt is raised.
+    fn sleep(&self) {
+        let mut pending = self.interrupts.pending.lock().unwrap();
+        while *pending == 0 {
+            pending = self.interrupts.wakeup.wait(pending).unwrap();
+        }
+    }
+
+    fn sleep_states(&self) -> &[SleepState] {
+        &SLEEP_STATES
+    }
+
+    fn sleep_in_state(&self, state: usize) {
+        self.sleep_counts[state].set(self.sleep_counts[state].get() + 1);
+        self.sleep();
+    }
+
+    /// Interrupts are only ever serviced on the kernel thread, so there is
+    /// nothing that can preempt `f`.
+    unsafe fn atomic<F, R>(&self, f: F) -> R
+    where
+        F: FnOnce() -> R,
+    {
+        f()
+    }
+
+    unsafe fn print_state(&self, write: &mut dyn Write) {
+        let _ = write.write_fmt(format_args!(
+            "\r\n---| Host Chip State |---\r\n\
+             Pending interrupts: {:#018x}\r\n",
+            *self.interrupts.pending.lock().unwrap()
+        ));
+    }
+}
@@ -0,0 +1,165 @@
+//! Memory-mapped flash backed by host memory.
+//!
+//! Like flash on a microcontroller, the contents can be read directly through
+//! the slice returned by `region()`, while writes and erases go through
+//! `hil::flash::Flash`. Page numbers are absolute: page `n` starts at address
+//! `n * PAGE_SIZE`. Operations complete immediately, but the client is only
+//! called from `handle_interrupt()` on the kernel thread.
+
+use core::cell::Cell;
+
+use kernel::common::cells::{OptionalCell, TakeCell};
+use kernel::hil;
+use kernel::ErrorCode;
+
+use crate::chip::InterruptLine;
+
+pub const PAGE_SIZE: usize = 512;
+
+pub struct HostPage(pub [u8; PAGE_SIZE]);
+
+impl Default for HostPage {
+    fn default() -> Self {
+        HostPage([0; PAGE_SIZE])
+    }
+}
+
+impl AsMut<[u8]> for HostPage {
+    fn as_mut(&mut self) -> &mut [u8] {
+        &mut self.0
+    }
+}
+
+#[derive(Clone, Copy, PartialEq)]
+enum Operation {
+    Read,
+    Write,
+    Erase,
+}
+
+pub struct HostFlash<'a> {
+    region: &'static [u8],
+    line: InterruptLine,
+    client: OptionalCell<&'a dyn hil::flash::Client<HostFlash<'a>>>,
+    buffer: TakeCell<'static, HostPage>,
+    completed: Cell<Option<Operation>>,
+}
+
+impl<'a> HostFlash<'a> {
+    /// Create `size` bytes of page-aligned flash, starting with `contents`
+    /// and erased (`0xFF`) after that. Completions are signalled on `line`.
+    pub fn new(contents: &[u8], size: usize, line: InterruptLine) -> HostFlash<'a> {
+        assert!(contents.len() <= size, "flash contents do not fit");
+        let pages = (size + PAGE_SIZE - 1) / PAGE_SIZE;
+        // Allocate one spare page so the region can start on a page boundary.
+        let memory = Box::leak(vec![0xFFu8; (pages + 1) * PAGE_SIZE].into_boxed_slice());
+        let offset = memory.as_ptr().align_offset(PAGE_SIZE);
+        let region = &mut memory[offset..offset + pages * PAGE_SIZE];
+        region[..contents.len()].copy_from_slice(contents);
+        HostFlash {
+            region,
+            line,
+            client: OptionalCell::empty(),
+            buffer: TakeCell::empty(),
+            completed: Cell::new(None),
+        }
+    }
+
+    /// The memory-mapped contents of the flash.
+    pub fn region(&self) -> &'static [u8] {
+        self.region
+    }
+
+    fn page(&self, page_number: usize) -> Result<*mut u8, ErrorCode> {
+        let start = page_number.checked_mul(PAGE_SIZE).ok_or(ErrorCode::INVAL)?;
+        let base = self.region.as_ptr() as usize;
+        if start < base || start + PAGE_SIZE > base + self.region.len() {
+            return Err(ErrorCode::INVAL);
+        }
+        Ok(start as *mut u8)
+    }
+
+    fn start(&self, operation: Operation) -> Result<(), ErrorCode> {
+        if self.completed.get().is_some() {
+            return Err(ErrorCode::BUSY);
+        }
+        self.completed.set(Some(operation));
+        self.line.raise();
+        Ok(())
+    }
+
+    pub fn handle_interrupt(&self) {
+        let operation = match self.completed.take() {
+            Some(operation) => operation,
+            None => return,
+        };
+        let error = hil::flash::Error::CommandComplete;
+        self.client.map(|client| match operation {
+            Operation::Read => self
+                .buffer
+                .take()
+                .map(|buffer| client.read_complete(buffer, error)),
+            Operation::Write => self
+                .buffer
+                .take()
+                .map(|buffer| client.write_complete(buffer, error)),
+            Operation::Erase => Some(client.erase_complete(error)),
+        });
+    }
+}
+
+impl<'a, C: hil::flash::Client<Self>> hil::flash::HasClient<'a, C> for HostFlash<'a> {
+    fn set_client(&'a self, client: &'a C) {
+        self.client.set(client);
+    }
+}
+
+impl hil::flash::Flash for HostFlash<'_> {
+    type Page = HostPage;
+
+    fn read_page(
+        &self,
+        page_number: usize,
+        buf: &'static mut HostPage,
+    ) -> Result<(), (ErrorCode, &'static mut HostPage)> {
+        let page = match self.page(page_number) {
+            Ok(page) => page,
+            Err(err) => return Err((err, buf)),
+        };
+        if let Err(err) = self.start(Operation::Read) {
+            return Err((err, buf));
+        }
+        // Safety: `page` is a whole page inside the region.
+        unsafe { core::ptr::copy_nonoverlapping(page, buf.0.as_mut_ptr(), PAGE_SIZE) };
+        self.buffer.replace(buf);
+        Ok(())
+    }
+
+    fn write_page(
+        &self,
+        page_number: usize,
+        buf: &'static mut HostPage,
+    ) -> Result<(), (ErrorCode, &'static mut HostPage)> {
+        let page = match self.page(page_number) {
+            Ok(page) => page,
+            Err(err) => return Err((err, buf)),
+        };
+        if let Err(err) = self.start(Operation::Write) {
+            return Err((err, buf));
+        }
+        // Safety: `page` is a whole page inside the region. Writing through
+        // the region is how flash behaves: its contents change underneath
+        // readers holding a shared slice.
+        unsafe { core::ptr::copy_nonoverlapping(buf.0.as_ptr(), page, PAGE_SIZE) };
+        self.buffer.replace(buf);
+        Ok(())
+    }
+
+    fn erase_page(&self, page_number: usize) -> Result<(), ErrorCode> {
+        let page = self.page(page_number)?;
+        self.start(Operation::Erase)?;
+        // Safety: see `write_page()`.
+        unsafe { core::ptr::write_bytes(page, 0xFF, PAGE_SIZE) };
+        Ok(())
+    }
+}
@@ -0,0 +1,26 @@
+//! Virtual chip that runs the Tock kernel on the host operating system.
+//!
+//! This crate lets the kernel loop, capsules, and simulated processes run
+//! inside `cargo test`. Each process is a Rust closure executing on its own
+//! OS thread, and the kernel and process threads pass control back and forth
+//! so that only one of them runs at a time. See the README for the
+//! limitations of this approach.
+
+#![crate_name = "host"]
+#![crate_type = "rlib"]
+
+pub mod alarm;
+pub mod app;
+pub mod chip;
+pub mod flash;
+pub mod mpu;
+pub mod scheduler_timer;
+pub mod syscall;
+pub mod tbf;
+
+#[cfg(test)]
+mod tests;
+
+pub use crate::app::App;
+pub use crate::chip::{HostChip, InterruptLine};
+pub use crate::tbf::TbfBuilder;
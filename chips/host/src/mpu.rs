@@ -0,0 +1,227 @@
+//! Bookkeeping-only memory protection unit.
+//!
+//! Host processes run as native code in the same address space as the
+//! kernel, so nothing can actually be protected. `HostMpu` still performs the
+//! region allocation the kernel asks for and checks its invariants, which lets
+//! tests observe the configuration the kernel would have programmed into a
+//! real MPU.
+
+use core::cell::Cell;
+use core::cmp;
+use core::fmt;
+
+use kernel::mpu;
+use kernel::ProcessId;
+
+/// Maximum number of regions a single configuration can hold.
+const NUM_REGIONS: usize = 8;
+
+/// Alignment used for process memory blocks. This matches the host word size
+/// so that the kernel can place its data structures at the end of the block.
+const MEMORY_ALIGNMENT: usize = core::mem::size_of::<usize>();
+
+#[derive(Copy, Clone)]
+struct HostRegion {
+    start: usize,
+    size: usize,
+    writeable: bool,
+}
+
+/// Regions allocated for a single process. The app-owned memory region, once
+/// allocated, is always stored in slot 0.
+#[derive(Copy, Clone, Default)]
+pub struct HostMpuConfig {
+    regions: [Option<HostRegion>; NUM_REGIONS],
+}
+
+impl HostMpuConfig {
+    fn free_slot(&self) -> Option<usize> {
+        // Slot 0 is reserved for app-owned memory.
+        (1..NUM_REGIONS).find(|i| self.regions[*i].is_none())
+    }
+
+    fn overlaps(&self, start: usize, size: usize) -> bool {
+        self.regions
+            .iter()
+            .flatten()
+            .any(|region| start < region.start + region.size && region.start < start + size)
+    }
+
+    /// Whether `[start, start + size)` is covered by an allocated region that
+    /// the process may write.
+    pub fn is_writeable(&self, start: usize, size: usize) -> bool {
+        self.regions.iter().flatten().any(|region| {
+            region.writeable && start >= region.start && start + size <= region.start + region.size
+        })
+    }
+}
+
+impl fmt::Display for HostMpuConfig {
+    fn fmt(&self, f: &mut fmt::Formatter) -> fmt::Result {
+        write!(f, "\r\n Host MPU")?;
+        for (i, region) in self.regions.iter().enumerate() {
+            match region {
+                Some(region) => write!(
+                    f,
+                    "\r\n  Region {}: [{:#x}:{:#x}], length: {} bytes; {}",
+                    i,
+                    region.start,
+                    region.start + region.size,
+                    region.size,
+                    if region.writeable { "RW" } else { "R" },
+                )?,
+                None => write!(f, "\r\n  Region {}: Unused", i)?,
+            }
+        }
+        write!(f, "\r\n")
+    }
+}
+
+pub struct HostMpu {
+    enabled: Cell<bool>,
+    configured_for: Cell<Option<ProcessId>>,
+    active: Cell<HostMpuConfig>,
+}
+
+impl HostMpu {
+    pub const fn new() -> HostMpu {
+        HostMpu {
+            enabled: Cell::new(false),
+            configured_for: Cell::new(None),
+            active: Cell::new(HostMpuConfig {
+                regions: [None; NUM_REGIONS],
+            }),
+        }
+    }
+
+    /// Whether the MPU is currently enforcing the app configuration, i.e.
+    /// whether a process is executing.
+    pub fn is_enabled(&self) -> bool {
+        self.enabled.get()
+    }
+
+    /// The process the MPU was last configured for.
+    pub fn configured_for(&self) -> Option<ProcessId> {
+        self.configured_for.get()
+    }
+
+    /// The configuration most recently passed to `configure_mpu()`.
+    pub fn active_config(&self) -> HostMpuConfig {
+        self.active.get()
+    }
+}
+
+impl mpu::MPU for HostMpu {
+    type MpuConfig = HostMpuConfig;
+
+    fn clear_mpu(&self) {
+        self.active.set(HostMpuConfig::default());
+        self.configured_for.set(None);
+    }
+
+    fn enable_app_mpu(&self) {
+        self.enabled.set(true);
+    }
+
+    fn disable_app_mpu(&self) {
+        self.enabled.set(false);
+    }
+
+    fn number_total_regions(&self) -> usize {
+        NUM_REGIONS
+    }
+
+    fn allocate_region(
+        &self,
+        unallocated_memory_start: *const u8,
+        unallocated_memory_size: usize,
+        min_region_size: usize,
+        permissions: mpu::Permissions,
+        config: &mut HostMpuConfig,
+    ) -> Option<mpu::Region> {
+        let start = unallocated_memory_start as usize;
+        if min_region_size > unallocated_memory_size || config.overlaps(start, min_region_size) {
+            return None;
+        }
+        let slot = config.free_slot()?;
+        config.regions[slot] = Some(HostRegion {
+            start,
+            size: min_region_size,
+            writeable: is_writeable(permissions),
+        });
+        Some(mpu::Region::new(unallocated_memory_start, min_region_size))
+    }
+
+    fn allocate_app_memory_region(
+        &self,
+        unallocated_memory_start: *const u8,
+        unallocated_memory_size: usize,
+        min_memory_size: usize,
+        initial_app_memory_size: usize,
+        initial_kernel_memory_size: usize,
+        permissions: mpu::Permissions,
+        config: &mut HostMpuConfig,
+    ) -> Option<(*const u8, usize)> {
+        if config.regions[0].is_some() {
+            return None;
+        }
+
+        // Align both ends of the memory block so the kernel-owned structures
+        // at the end of the block are word aligned.
+        let unallocated_start = unallocated_memory_start as usize;
+        let start = align_up(unallocated_start);
+        let memory_size = align_up(cmp::max(
+            min_memory_size,
+            initial_app_memory_size + initial_kernel_memory_size,
+        ));
+        if start - unallocated_start + memory_size > unallocated_memory_size
+            || config.overlaps(start, memory_size)
+        {
+            return None;
+        }
+
+        config.regions[0] = Some(HostRegion {
+            start,
+            size: initial_app_memory_size,
+            writeable: is_writeable(permissions),
+        });
+        Some((start as *const u8, memory_size))
+    }
+
+    fn update_app_memory_region(
+        &self,
+        app_memory_break: *const u8,
+        kernel_memory_break: *const u8,
+        permissions: mpu::Permissions,
+        config: &mut HostMpuConfig,
+    ) -> Result<(), ()> {
+        if (app_memory_break as usize) > (kernel_memory_break as usize) {
+            return Err(());
+        }
+        let region = config.regions[0].as_mut().ok_or(())?;
+        if (app_memory_break as usize) < region.start {
+            return Err(());
+        }
+        region.size = app_memory_break as usize - region.start;
+        region.writeable = is_writeable(permissions);
+        Ok(())
+    }
+
+    fn configure_mpu(&self, config: &HostMpuConfig, app_id: &ProcessId) {
+        self.active.set(*config);
+        self.configured_for.set(Some(*app_id));
+    }
+}
+
+fn is_writeable(permissions: mpu::Permissions) -> bool {
+    match permissions {
+        mpu::Permissions::ReadWriteExecute | mpu::Permissions::ReadWriteOnly => true,
+        mpu::Permissions::ReadExecuteOnly
+        | mpu::Permissions::ReadOnly
+        | mpu::Permissions::ExecuteOnly => false,
+    }
+}
+
+fn align_up(value: usize) -> usize {
+    (value + MEMORY_ALIGNMENT - 1) & !(MEMORY_ALIGNMENT - 1)
+}
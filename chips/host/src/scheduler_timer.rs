@@ -0,0 +1,62 @@
+//! Scheduler timer measured with the host's monotonic clock.
+//!
+//! Host processes cannot be interrupted mid-execution, so this timer never
+//! fires an interrupt. The kernel instead observes an expired timeslice the
+//! next time it checks `get_remaining_us()`, which happens on every system
+//! call a process makes.
+
+use core::cell::Cell;
+use std::time::{Duration, Instant};
+
+use kernel::SchedulerTimer;
+
+pub struct HostSchedulerTimer {
+    deadline: Cell<Option<Instant>>,
+    armed: Cell<bool>,
+}
+
+impl HostSchedulerTimer {
+    pub const fn new() -> HostSchedulerTimer {
+        HostSchedulerTimer {
+            deadline: Cell::new(None),
+            armed: Cell::new(false),
+        }
+    }
+
+    /// Whether the timer is currently armed, i.e. a process is executing
+    /// under a timeslice.
+    pub fn is_armed(&self) -> bool {
+        self.armed.get()
+    }
+}
+
+impl SchedulerTimer for HostSchedulerTimer {
+    fn start(&self, us: u32) {
+        self.deadline
+            .set(Some(Instant::now() + Duration::from_micros(us.into())));
+    }
+
+    fn reset(&self) {
+        self.deadline.set(None);
+        self.armed.set(false);
+    }
+
+    fn arm(&self) {
+        self.armed.set(true);
+    }
+
+    fn disarm(&self) {
+        self.armed.set(false);
+    }
+
+    fn get_remaining_us(&self) -> Option<u32> {
+        self.deadline.get().and_then(|deadline| {
+            let now = Instant::now();
+            if now >= deadline {
+                None
+            } else {
+                Some((deadline - now).as_micros() as u32)
+            }
+        })
+    }
+}
@@ -0,0 +1,379 @@
+//! Kernel-userland system call interface for host processes.
+//!
+//! Every process is a host program (a Rust closure registered with
+//! `SysCall::register_program()`) that runs on its own OS thread. Switching to
+//! a process hands it the pending syscall return value or function call over a
+//! channel, then blocks the kernel thread until the process makes its next
+//! system call. The process thread, in turn, blocks whenever it is waiting on
+//! the kernel, so the kernel and processes never execute concurrently.
+//!
+//! A TBF image selects its program by storing the program identifier in the
+//! first word of the application binary (see `crate::tbf::TbfBuilder`). The
+//! process thread is started when the kernel sets up the initial function call
+//! for the process, and is torn down whenever the process is re-initialized
+//! (for example on restart).
+//!
+//! Threads created by a process with the thread syscalls run on OS threads of
+//! their own. Their entry point is a closure that `App::thread_create()`
+//! registers under an address in the application binary, so that the kernel
+//! can pass it around like any other function address.
+
+use core::cell::RefCell;
+use core::fmt::Write;
+use std::any::Any;
+use std::panic::{self, AssertUnwindSafe};
+use std::sync::mpsc::{channel, Receiver, Sender};
+use std::sync::Arc;
+use std::thread;
+
+use kernel::procs::{FunctionCall, FunctionCallSource};
+use kernel::syscall::{ContextSwitchReason, Syscall, SyscallReturn};
+use kernel::ErrorCode;
+
+use crate::app::{App, ThreadEntries};
+
+/// A host program that can be loaded as a process.
+pub type Program = Arc<dyn Fn(&App) + Send + Sync>;
+
+/// What a process thread should do when it is next switched to.
+#[derive(Copy, Clone)]
+pub(crate) enum Resume {
+    /// Return from the last system call with this value.
+    Return(SyscallReturn),
+    /// Execute a function call (the initial function or an upcall).
+    Call(FunctionCall),
+    /// Return from the last system call without a return value. This is the
+    /// case for a yield-no-wait when no upcalls were pending.
+    Continue,
+}
+
+/// Sent from the kernel to a process thread to resume it.
+pub(crate) struct ResumeMessage(pub(crate) Resume);
+
+// Safety: the pointers in a `SyscallReturn` are addresses in the process's
+// own memory. They are only dereferenced by the process thread, and only
+// while the kernel thread is blocked waiting for the next system call.
+unsafe impl Send for ResumeMessage {}
+
+/// Sent from a process thread to the kernel when it stops executing.
+pub(crate) enum ProcessEvent {
+    Syscall(Syscall),
+    Fault,
+}
+
+pub(crate) struct ProcessEventMessage(pub(crate) ProcessEvent);
+
+// Safety: as with `ResumeMessage`, pointers in a `Syscall` refer to process
+// memory and are validated by the kernel before use.
+unsafe impl Send for ProcessEventMessage {}
+
+/// Panic payload used to unwind a process thread when the kernel drops its
+/// end of the channels. This is not a fault of the process.
+pub(crate) struct ProcessTornDown;
+
+/// Host state for a single process.
+struct ProcessThread {
+    to_process: Option<Sender<ResumeMessage>>,
+    from_process: Option<Receiver<ProcessEventMessage>>,
+    pending: Option<Resume>,
+    switch_count: usize,
+    /// Whether this is a thread created by the process rather than its main
+    /// thread.
+    worker: bool,
+}
+
+impl ProcessThread {
+    fn tear_down(&mut self) {
+        // Dropping both ends of the channels causes the process thread to
+        // unwind the next time it talks to the kernel.
+        self.to_process = None;
+        self.from_process = None;
+        self.pending = None;
+    }
+}
+
+/// Per-process stored state. This is just an index into the process threads
+/// tracked by `SysCall`.
+#[derive(Copy, Clone, Default)]
+pub struct HostStoredState {
+    thread: Option<usize>,
+}
+
+pub struct SysCall {
+    programs: RefCell<Vec<Program>>,
+    threads: RefCell<Vec<ProcessThread>>,
+    thread_entries: ThreadEntries,
+}
+
+impl SysCall {
+    pub fn new() -> SysCall {
+        SysCall {
+            programs: RefCell::new(Vec::new()),
+            threads: RefCell::new(Vec::new()),
+            thread_entries: ThreadEntries::default(),
+        }
+    }
+
+    /// Register a program and return the identifier to place in a TBF
+    /// binary.
+    pub fn register_program<F>(&self, program: F) -> u32
+    where
+        F: Fn(&App) + Send + Sync + 'static,
+    {
+        let mut programs = self.programs.borrow_mut();
+        programs.push(Arc::new(program));
+        (programs.len() - 1) as u32
+    }
+
+    /// Number of times the kernel has switched to the process with this stored
+    /// state since it was last initialized.
+    pub fn switch_count(&self, state: &HostStoredState) -> usize {
+        state.thread.map_or(0, |index| {
+            self.threads
+                .borrow()
+                .get(index)
+                .map_or(0, |thread| thread.switch_count)
+        })
+    }
+
+    fn with_thread<F, R>(&self, state: &HostStoredState, f: F) -> Result<R, ()>
+    where
+        F: FnOnce(&mut ProcessThread) -> R,
+    {
+        let index = state.thread.ok_or(())?;
+        let mut threads = self.threads.borrow_mut();
+        threads.get_mut(index).map(f).ok_or(())
+    }
+
+    /// Start an OS thread for a process thread. The OS thread waits for the
+    /// initial function call, then passes it to `run` along with the
+    /// channels to the kernel.
+    fn spawn<F>(&self, thread: &mut ProcessThread, run: F)
+    where
+        F: FnOnce(Sender<ProcessEventMessage>, Receiver<ResumeMessage>, FunctionCall)
+            + Send
+            + 'static,
+    {
+        let (to_process, from_kernel) = channel::<ResumeMessage>();
+        let (to_kernel, from_process) = channel::<ProcessEventMessage>();
+
+        thread::spawn(move || {
+            let init = match from_kernel.recv() {
+                Ok(ResumeMessage(Resume::Call(init))) => init,
+                _ => return,
+            };
+            let to_kernel_fault = to_kernel.clone();
+            let result =
+                panic::catch_unwind(AssertUnwindSafe(|| run(to_kernel, from_kernel, init)));
+            if let Err(payload) = result {
+                if !is_torn_down(&payload) {
+                    let _ = to_kernel_fault.send(ProcessEventMessage(ProcessEvent::Fault));
+                }
+            }
+        });
+
+        thread.to_process = Some(to_process);
+        thread.from_process = Some(from_process);
+    }
+
+    /// Start the main thread of a process running `program`.
+    fn spawn_main(&self, program: Program, thread: &mut ProcessThread) {
+        let thread_entries = self.thread_entries.clone();
+        self.spawn(thread, move |to_kernel, from_kernel, init| {
+            let app = App::new(to_kernel, from_kernel, init, thread_entries);
+            program(&app);
+            // Returning from `main` is not an exit. Just like a real runtime,
+            // keep servicing upcalls forever.
+            loop {
+                app.yield_wait();
+            }
+        });
+    }
+
+    /// Start a thread created by a process, running the closure registered
+    /// for the entry point of `call`.
+    fn spawn_worker(
+        &self,
+        call: FunctionCall,
+        app_brk: *const u8,
+        thread: &mut ProcessThread,
+    ) -> Result<(), ()> {
+        let entry = self
+            .thread_entries
+            .lock()
+            .map_err(|_| ())?
+            .iter()
+            .find(|entry| entry.pc == call.pc)
+            .cloned()
+            .ok_or(())?;
+        let thread_entries = self.thread_entries.clone();
+        let app_brk = app_brk as usize;
+        self.spawn(thread, move |to_kernel, from_kernel, init| {
+            let app = App::new_thread(to_kernel, from_kernel, &entry, app_brk, thread_entries);
+            (entry.function)(&app, init.argument0);
+            app.thread_exit(0);
+        });
+        Ok(())
+    }
+}
+
+fn is_torn_down(payload: &Box<dyn Any + Send>) -> bool {
+    payload.is::<ProcessTornDown>()
+}
+
+impl kernel::syscall::UserspaceKernelBoundary for SysCall {
+    type StoredState = HostStoredState;
+
+    fn initial_process_app_brk_size(&self) -> usize {
+        // Reserve one word for the yield flag.
+        core::mem::size_of::<usize>()
+    }
+
+    unsafe fn initialize_process(
+        &self,
+        _accessible_memory_start: *const u8,
+        _app_brk: *const u8,
+        state: &mut Self::StoredState,
+    ) -> Result<(), ()> {
+        let fresh = ProcessThread {
+            to_process: None,
+            from_process: None,
+            pending: None,
+            switch_count: 0,
+            worker: false,
+        };
+
+        let mut threads = self.threads.borrow_mut();
+        match state.thread {
+            Some(index) => {
+                let thread = threads.get_mut(index).ok_or(())?;
+                thread.tear_down();
+                *thread = fresh;
+            }
+            None => {
+                threads.push(fresh);
+                state.thread = Some(threads.len() - 1);
+            }
+        }
+        Ok(())
+    }
+
+    unsafe fn initialize_thread(
+        &self,
+        accessible_memory_start: *const u8,
+        app_brk: *const u8,
+        _stack_pointer: *const u8,
+        state: &mut Self::StoredState,
+    ) -> Result<(), ErrorCode> {
+        // Host threads run on OS threads with their own stacks, so the stack
+        // pointer is not used.
+        self.initialize_process(accessible_memory_start, app_brk, state)
+            .and_then(|()| self.with_thread(state, |thread| thread.worker = true))
+            .map_err(|()| ErrorCode::FAIL)
+    }
+
+    unsafe fn set_syscall_return_value(
+        &self,
+        _accessible_memory_start: *const u8,
+        _app_brk: *const u8,
+        state: &mut Self::StoredState,
+        return_value: SyscallReturn,
+    ) -> Result<(), ()> {
+        self.with_thread(state, |thread| {
+            thread.pending = Some(Resume::Return(return_value));
+        })
+    }
+
+    unsafe fn set_process_function(
+        &self,
+        _accessible_memory_start: *const u8,
+        app_brk: *const u8,
+        state: &mut Self::StoredState,
+        upcall: FunctionCall,
+    ) -> Result<(), ()> {
+        let worker = self.with_thread(state, |thread| thread.worker)?;
+        if let (FunctionCallSource::Kernel, true) = (upcall.source, worker) {
+            // This is the entry point of a thread created by the process.
+            self.with_thread(state, |thread| {
+                thread.tear_down();
+                self.spawn_worker(upcall, app_brk, thread)
+            })??;
+        } else if let FunctionCallSource::Kernel = upcall.source {
+            // This is the initial function of the process. `argument0` is the
+            // start of the application binary, which holds the program id.
+            let program_id = core::ptr::read_unaligned(upcall.argument0 as *const u32);
+            let program = self
+                .programs
+                .borrow()
+                .get(program_id as usize)
+                .cloned()
+                .ok_or(())?;
+            self.with_thread(state, |thread| {
+                thread.tear_down();
+                self.spawn_main(program, thread);
+            })?;
+        }
+        self.with_thread(state, |thread| {
+            thread.pending = Some(Resume::Call(upcall));
+        })
+    }
+
+    unsafe fn switch_to_process(
+        &self,
+        _accessible_memory_start: *const u8,
+        _app_brk: *const u8,
+        state: &mut Self::StoredState,
+    ) -> (ContextSwitchReason, Option<*const u8>) {
+        // Take what we need out of the thread table so that it is not borrowed
+        // while the process runs.
+        let channels = self.with_thread(state, |thread| {
+            thread.switch_count += 1;
+            let resume = thread.pending.take().unwrap_or(Resume::Continue);
+            (resume, thread.to_process.clone())
+        });
+        let (resume, to_process) = match channels {
+            Ok((resume, Some(to_process))) => (resume, to_process),
+            _ => return (ContextSwitchReason::Fault, None),
+        };
+
+        if to_process.send(ResumeMessage(resume)).is_err() {
+            return (ContextSwitchReason::Fault, None);
+        }
+
+        let event = self.with_thread(state, |thread| {
+            thread
+                .from_process
+                .as_ref()
+                .map(|from_process| from_process.recv())
+        });
+        match event {
+            Ok(Some(Ok(ProcessEventMessage(ProcessEvent::Syscall(syscall))))) => {
+                (ContextSwitchReason::SyscallFired { syscall }, None)
+            }
+            _ => (ContextSwitchReason::Fault, None),
+        }
+    }
+
+    fn store_context(&self, _state: &HostStoredState, _out: &mut [u8]) -> Result<usize, ErrorCode> {
+        // Simulated processes run on host threads and have no registers the
+        // kernel can save.
+        Ok(0)
+    }
+
+    unsafe fn print_context(
+        &self,
+        _accessible_memory_start: *const u8,
+        _app_brk: *const u8,
+        state: &Self::StoredState,
+        writer: &mut dyn Write,
+    ) {
+        let _ = writer.write_fmt(format_args!(
+            "\
+             \r\n Host process thread: {:?}\
+             \r\n Context switches: {}\
+             \r\n",
+            state.thread,
+            self.switch_count(state),
+        ));
+    }
+}
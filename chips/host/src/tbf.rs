@@ -0,0 +1,269 @@
+//! Builder for Tock Binary Format images of host programs.
+//!
+//! The "binary" of a host application is just the identifier of the program
+//! registered with `SysCall::register_program()`, followed by padding. The
+//! padding gives the runtime addresses inside the application's flash region
+//! to use for upcalls.
+//!
+//! Images with footers use a Program TLV instead of the Main TLV, so that the
+//! kernel knows where the binary ends and the footer starts.
+
+/// TBF header version produced by the builder.
+const TBF_VERSION: u16 = 2;
+
+/// Size of the fixed part of a version 2 header.
+const TBF_BASE_SIZE: usize = 16;
+
+const TLV_MAIN: u16 = 1;
+const TLV_PACKAGE_NAME: u16 = 3;
+const TLV_STORAGE_PERMISSIONS: u16 = 7;
+const TLV_PROGRAM: u16 = 9;
+const TLV_SHORT_ID: u16 = 10;
+const TLV_REAL_TIME: u16 = 11;
+const TLV_PRIORITY: u16 = 12;
+const TLV_IPC_ACCESS: u16 = 13;
+const TLV_COMMAND_PERMISSIONS: u16 = 14;
+const TLV_CREDENTIALS: u16 = 128;
+
+/// Bit 0 of the flags field marks the application as enabled.
+const FLAG_ENABLED: u32 = 0x0000_0001;
+
+pub struct TbfBuilder {
+    program: u32,
+    package_name: Option<String>,
+    minimum_ram_size: u32,
+    binary_size: usize,
+    enabled: bool,
+    tlvs: Vec<(u16, Vec<u8>)>,
+    footers: Vec<(u32, usize, Box<Credential>)>,
+}
+
+/// Computes the data of a credential from the integrity region of the image.
+type Credential = dyn Fn(&[u8]) -> Vec<u8>;
+
+impl TbfBuilder {
+    /// Start building an image that runs the registered program `program`.
+    pub fn new(program: u32) -> TbfBuilder {
+        TbfBuilder {
+            program,
+            package_name: None,
+            minimum_ram_size: 1024,
+            binary_size: 512,
+            enabled: true,
+            tlvs: Vec::new(),
+            footers: Vec::new(),
+        }
+    }
+
+    pub fn package_name(mut self, name: &str) -> Self {
+        self.package_name = Some(name.to_string());
+        self
+    }
+
+    /// Amount of RAM the process requests in its Main TLV.
+    pub fn minimum_ram_size(mut self, size: u32) -> Self {
+        self.minimum_ram_size = size;
+        self
+    }
+
+    /// Size of the application binary following the header. This bounds the
+    /// number of distinct upcalls the process can subscribe.
+    pub fn binary_size(mut self, size: usize) -> Self {
+        self.binary_size = size;
+        self
+    }
+
+    pub fn enabled(mut self, enabled: bool) -> Self {
+        self.enabled = enabled;
+        self
+    }
+
+    /// Give the app a fixed short ID with a ShortId TLV.
+    pub fn short_id(self, short_id: u32) -> Self {
+        self.tlv(TLV_SHORT_ID, &short_id.to_le_bytes())
+    }
+
+    /// Restrict the app to the drivers in `permissions` with a Command
+    /// Permissions TLV. Each entry is a driver number, the block of 64 command
+    /// numbers, and the bitmask of allowed commands in that block.
+    pub fn permissions(self, permissions: &[(u32, u32, u64)]) -> Self {
+        let mut value = (permissions.len() as u16).to_le_bytes().to_vec();
+        for (driver_number, offset, allowed_commands) in permissions {
+            value.extend_from_slice(&driver_number.to_le_bytes());
+            value.extend_from_slice(&offset.to_le_bytes());
+            value.extend_from_slice(&allowed_commands.to_le_bytes());
+        }
+        self.tlv(TLV_COMMAND_PERMISSIONS, &value)
+    }
+
+    /// Declare the storage IDs the app writes, reads and modifies with a
+    /// Storage Permissions TLV.
+    pub fn storage_permissions(self, write_id: u32, read_ids: &[u32], modify_ids: &[u32]) -> Self {
+        let mut value = write_id.to_le_bytes().to_vec();
+        value.extend_from_slice(&(read_ids.len() as u16).to_le_bytes());
+        value.extend_from_slice(&(modify_ids.len() as u16).to_le_bytes());
+        for id in read_ids.iter().chain(modify_ids) {
+            value.extend_from_slice(&id.to_le_bytes());
+        }
+        self.tlv(TLV_STORAGE_PERMISSIONS, &value)
+    }
+
+    /// Reserve `budget_us` of every `period_us` for the app with a RealTime
+    /// TLV.
+    pub fn real_time(self, period_us: u32, budget_us: u32) -> Self {
+        let mut value = period_us.to_le_bytes().to_vec();
+        value.extend_from_slice(&budget_us.to_le_bytes());
+        self.tlv(TLV_REAL_TIME, &value)
+    }
+
+    /// Give the app an explicit scheduling priority with a Priority TLV.
+    pub fn priority(self, priority: u32) -> Self {
+        self.tlv(TLV_PRIORITY, &priority.to_le_bytes())
+    }
+
+    /// Only let the apps with the short IDs in `clients` connect to the app's
+    /// IPC service with an IPC Access TLV.
+    pub fn ipc_access(self, clients: &[u32]) -> Self {
+        let value: Vec<u8> = clients
+            .iter()
+            .flat_map(|client| client.to_le_bytes())
+            .collect();
+        self.tlv(TLV_IPC_ACCESS, &value)
+    }
+
+    /// Append an arbitrary TLV entry to the header.
+    pub fn tlv(mut self, tipe: u16, value: &[u8]) -> Self {
+        self.tlvs.push((tipe, value.to_vec()));
+        self
+    }
+
+    /// Append a credentials footer with the given format and data.
+    pub fn footer(self, format: u32, data: &[u8]) -> Self {
+        let data = data.to_vec();
+        self.footer_with(format, data.len(), move |_| data.clone())
+    }
+
+    /// Append a credentials footer of `len` bytes whose data is computed from
+    /// the integrity region (header and binary) of the finished image, for
+    /// example its hash.
+    pub fn footer_with<F: Fn(&[u8]) -> Vec<u8> + 'static>(
+        mut self,
+        format: u32,
+        len: usize,
+        credential: F,
+    ) -> Self {
+        self.footers.push((format, len, Box::new(credential)));
+        self
+    }
+
+    /// Produce the TBF image.
+    pub fn build(&self) -> Vec<u8> {
+        let mut tlvs = Vec::new();
+
+        let mut main = Vec::new();
+        main.extend_from_slice(&0u32.to_le_bytes()); // init_fn_offset
+        main.extend_from_slice(&0u32.to_le_bytes()); // protected_size
+        main.extend_from_slice(&self.minimum_ram_size.to_le_bytes());
+
+        // The header size does not depend on the values in the Program TLV,
+        // so the end of the binary is known before the TLV is written.
+        let binary_size = align4(core::cmp::max(self.binary_size, 4));
+        let program_tlv_size = 4 + main.len() + 8;
+        let tlvs_size = if self.footers.is_empty() {
+            4 + main.len()
+        } else {
+            program_tlv_size
+        } + self
+            .package_name
+            .as_ref()
+            .map_or(0, |n| 4 + align4(n.len()))
+            + self
+                .tlvs
+                .iter()
+                .map(|(_, value)| 4 + align4(value.len()))
+                .sum::<usize>();
+        let binary_end = TBF_BASE_SIZE + tlvs_size + binary_size;
+
+        if self.footers.is_empty() {
+            push_tlv(&mut tlvs, TLV_MAIN, &main);
+        } else {
+            main.extend_from_slice(&(binary_end as u32).to_le_bytes());
+            main.extend_from_slice(&0u32.to_le_bytes()); // version
+            push_tlv(&mut tlvs, TLV_PROGRAM, &main);
+        }
+
+        if let Some(name) = &self.package_name {
+            push_tlv(&mut tlvs, TLV_PACKAGE_NAME, name.as_bytes());
+        }
+        for (tipe, value) in self.tlvs.iter() {
+            push_tlv(&mut tlvs, *tipe, value);
+        }
+
+        let header_size = TBF_BASE_SIZE + tlvs.len();
+        debug_assert_eq!(header_size + binary_size, binary_end);
+        let footers_size: usize = self
+            .footers
+            .iter()
+            .map(|(_, len, _)| 4 + align4(4 + len))
+            .sum();
+        let total_size = header_size + binary_size + footers_size;
+        let flags = if self.enabled { FLAG_ENABLED } else { 0 };
+
+        let mut image = Vec::with_capacity(total_size);
+        image.extend_from_slice(&TBF_VERSION.to_le_bytes());
+        image.extend_from_slice(&(header_size as u16).to_le_bytes());
+        image.extend_from_slice(&(total_size as u32).to_le_bytes());
+        image.extend_from_slice(&flags.to_le_bytes());
+        image.extend_from_slice(&0u32.to_le_bytes()); // checksum, filled below
+        image.extend_from_slice(&tlvs);
+
+        let checksum = image
+            .chunks_exact(4)
+            .enumerate()
+            .filter(|(i, _)| *i != 3)
+            .fold(0, |acc, (_, word)| {
+                acc ^ u32::from_le_bytes([word[0], word[1], word[2], word[3]])
+            });
+        image[12..16].copy_from_slice(&checksum.to_le_bytes());
+
+        image.extend_from_slice(&self.program.to_le_bytes());
+        image.resize(binary_end, 0);
+
+        let mut footers = Vec::new();
+        for (format, _, credential) in self.footers.iter() {
+            let mut value = format.to_le_bytes().to_vec();
+            value.extend_from_slice(&credential(&image));
+            push_tlv(&mut footers, TLV_CREDENTIALS, &value);
+        }
+        image.extend_from_slice(&footers);
+        image.resize(total_size, 0);
+        image
+    }
+}
+
+fn align4(len: usize) -> usize {
+    (len + 3) & !3
+}
+
+fn push_tlv(buf: &mut Vec<u8>, tipe: u16, value: &[u8]) {
+    buf.extend_from_slice(&tipe.to_le_bytes());
+    buf.extend_from_slice(&(value.len() as u16).to_le_bytes());
+    buf.extend_from_slice(value);
+    buf.resize(align4(buf.len()), 0);
+}
+
+/// Concatenate TBF images into a flash region that lives for the rest of the
+/// program, suitable for passing to `kernel::procs::load_processes()`.
+pub fn flash_image(images: &[Vec<u8>]) -> &'static [u8] {
+    Box::leak(images.concat().into_boxed_slice())
+}
+
+/// Allocate zeroed, word-aligned memory for processes that lives for the rest
+/// of the program.
+pub fn process_memory(len: usize) -> &'static mut [u8] {
+    let word = core::mem::size_of::<usize>();
+    let words = Box::leak(vec![0usize; (len + word - 1) / word].into_boxed_slice());
+    // Safety: the allocation is at least `len` bytes and any byte pattern is a
+    // valid `u8`.
+    unsafe { core::slice::from_raw_parts_mut(words.as_mut_ptr() as *mut u8, len) }
+}
@@ -0,0 +1,357 @@
+//! Tests of installing and removing apps at runtime.
+
+use core::cell::Cell;
+use core::convert::TryInto;
+use std::rc::Rc;
+use std::sync::atomic::{AtomicUsize, Ordering};
+use std::sync::{Arc, Mutex};
+
+use kernel::hil::time::Alarm;
+use kernel::syscall::SyscallReturn;
+use kernel::{Chip, ErrorCode};
+use kernel::{CooperativeSched, EDFProcessNode, EDFSched, ProcessId};
+
+use crate::alarm::HostAlarm;
+use crate::tbf::TbfBuilder;
+
+use super::board::*;
+
+/// Build the image of a new app that adds one to `started` when it runs.
+fn counting_app(board: &TestBoard, started: &Arc<AtomicUsize>) -> Vec<u8> {
+    let started = started.clone();
+    let id = board
+        .chip
+        .userspace_kernel_boundary()
+        .register_program(move |app| {
+            started.fetch_add(1, Ordering::SeqCst);
+            app.exit_terminate(0)
+        });
+    TbfBuilder::new(id).package_name("new").build()
+}
+
+#[derive(Default)]
+struct LoaderClient {
+    writes: Cell<usize>,
+    unloads: Cell<usize>,
+    aborts: Cell<usize>,
+}
+
+impl capsules::app_loader::AppLoaderClient for LoaderClient {
+    fn write_done(&self, _buffer: &'static mut [u8], result: Result<(), ErrorCode>) {
+        assert_eq!(result, Ok(()));
+        self.writes.set(self.writes.get() + 1);
+    }
+
+    fn unload_done(&self, result: Result<(), ErrorCode>) {
+        assert_eq!(result, Ok(()));
+        self.unloads.set(self.unloads.get() + 1);
+    }
+
+    fn abort_done(&self, result: Result<(), ErrorCode>) {
+        assert_eq!(result, Ok(()));
+        self.aborts.set(self.aborts.get() + 1);
+    }
+}
+
+/// Install `image` with the app loader and start it.
+fn install(
+    board: &TestBoard,
+    scheduler: &'static CooperativeSched<'static>,
+    client: &LoaderClient,
+    image: Vec<u8>,
+) -> ProcessId {
+    let writes = client.writes.get();
+    let length = image.len();
+    board.app_loader.setup(length).unwrap();
+    board
+        .app_loader
+        .write(0, Box::leak(image.into_boxed_slice()), length)
+        .unwrap();
+    board.run_until(scheduler, true, || client.writes.get() == writes + 1);
+    board.app_loader.load().unwrap()
+}
+
+#[test]
+fn app_loader_installs_app_from_kernel() {
+    let board = TestBoard::boot(vec![program(|app| app.exit_terminate(0))]);
+    let scheduler = board.cooperative();
+    let client = leak(LoaderClient::default());
+    board.app_loader.set_client(client);
+
+    let started = Arc::new(AtomicUsize::new(0));
+    let image = counting_app(&board, &started);
+    board.app_loader.setup(image.len()).unwrap();
+    let length = image.len();
+    board
+        .app_loader
+        .write(0, Box::leak(image.into_boxed_slice()), length)
+        .unwrap();
+    board.run_until(scheduler, true, || client.writes.get() == 1);
+    assert!(board.processes[1].get().is_none());
+
+    let processid = board.app_loader.load().unwrap();
+    assert_eq!(board.process(1).processid(), processid);
+    assert_eq!(board.process(1).get_process_name(), "new");
+    board.run_until(scheduler, true, || started.load(Ordering::SeqCst) == 1);
+
+    // Space reserved after the new app is still erased, so there is nothing
+    // to load there.
+    board.app_loader.setup(length).unwrap();
+    assert_eq!(board.app_loader.load().map(|_| ()), Err(ErrorCode::FAIL));
+    board.run_until(scheduler, true, || client.aborts.get() == 1);
+}
+
+/// Write `image` with the app loader without loading it, and return its
+/// offset in app flash.
+fn write_image(
+    board: &TestBoard,
+    scheduler: &'static CooperativeSched<'static>,
+    client: &LoaderClient,
+    image: Vec<u8>,
+) -> usize {
+    let writes = client.writes.get();
+    let length = image.len();
+    board.app_loader.setup(length).unwrap();
+    board
+        .app_loader
+        .write(0, Box::leak(image.into_boxed_slice()), length)
+        .unwrap();
+    board.run_until(scheduler, true, || client.writes.get() == writes + 1);
+    let offset = board.process(0).flash_end() as usize - board.app_flash.as_ptr() as usize;
+    assert_ne!(board.app_flash[offset], 0xFF);
+    offset
+}
+
+#[test]
+fn app_loader_erases_aborted_app() {
+    let board = TestBoard::boot(vec![program(|app| app.exit_terminate(0))]);
+    let scheduler = board.cooperative();
+    let client = leak(LoaderClient::default());
+    board.app_loader.set_client(client);
+
+    let started = Arc::new(AtomicUsize::new(0));
+    let offset = write_image(&board, scheduler, client, counting_app(&board, &started));
+    board.app_loader.abort().unwrap();
+    assert_eq!(board.app_loader.load().map(|_| ()), Err(ErrorCode::RESERVE));
+    board.run_until(scheduler, true, || client.aborts.get() == 1);
+    assert!(board.app_flash[offset..].iter().all(|byte| *byte == 0xFF));
+    assert_eq!(started.load(Ordering::SeqCst), 0);
+}
+
+#[test]
+fn app_loader_erases_app_that_fails_to_load() {
+    let board = TestBoard::boot_with(
+        vec![program(|app| app.exit_terminate(0))],
+        BootOptions::new()
+            .image(|_, image| sha256_footer(image))
+            .checker(sha256_checker(true)),
+    );
+    let scheduler = board.cooperative();
+    let client = leak(LoaderClient::default());
+    board.app_loader.set_client(client);
+
+    // No credentials, so the checker refuses the new app.
+    let started = Arc::new(AtomicUsize::new(0));
+    let offset = write_image(&board, scheduler, client, counting_app(&board, &started));
+    assert_eq!(board.app_loader.load().map(|_| ()), Err(ErrorCode::FAIL));
+    assert!(board.processes[1].get().is_none());
+    board.run_until(scheduler, true, || client.aborts.get() == 1);
+    assert!(board.app_flash[offset..].iter().all(|byte| *byte == 0xFF));
+    assert_eq!(started.load(Ordering::SeqCst), 0);
+}
+
+#[test]
+fn app_loader_refuses_unprivileged_apps() {
+    const LOADER: usize = capsules::app_loader::DRIVER_NUM;
+
+    let victim = Arc::new(AtomicUsize::new(0));
+    let app_victim = victim.clone();
+    let results = Arc::new(Mutex::new(Vec::new()));
+    let app_results = results.clone();
+    let board = TestBoard::boot(vec![
+        program(move |app| {
+            let buffer = app.alloc(16).unwrap();
+            let allowed = app.allow_readonly(LOADER, 0, buffer, 16);
+            let mut results = app_results.lock().unwrap();
+            results.push(matches!(
+                allowed,
+                SyscallReturn::AllowReadOnlyFailure(ErrorCode::NOSUPPORT, _, _)
+            ));
+            for command in 0..6 {
+                results.push(matches!(
+                    app.command(LOADER, command, 16, 0),
+                    SyscallReturn::Failure(ErrorCode::NOSUPPORT)
+                ));
+            }
+            // Unloading another app.
+            results.push(matches!(
+                app.command(LOADER, 6, app_victim.load(Ordering::SeqCst), 0),
+                SyscallReturn::Failure(ErrorCode::NOSUPPORT)
+            ));
+            drop(results);
+            app.exit_terminate(0);
+        }),
+        program(|app| app.yield_for(|| false)),
+    ]);
+    victim.store(board.process(1).processid().id(), Ordering::SeqCst);
+
+    let scheduler = board.cooperative();
+    board.run_until(scheduler, true, || results.lock().unwrap().len() == 8);
+    assert!(results.lock().unwrap().iter().all(|refused| *refused));
+    assert!(board.processes[1].get().is_some());
+}
+
+#[test]
+fn app_loader_installs_app_from_userspace() {
+    const LOADER: usize = capsules::app_loader::DRIVER_NUM;
+
+    let image = Arc::new(Mutex::new(Vec::new()));
+    let installer_image = image.clone();
+    let loaded = Arc::new(Mutex::new(None));
+    let installer_loaded = loaded.clone();
+    let board = TestBoard::boot_with(
+        vec![program(move |app| {
+            let image = installer_image.lock().unwrap().clone();
+            // Chunks that do not line up with flash pages.
+            let chunk_size = 300;
+            assert!(matches!(
+                app.command(LOADER, 2, image.len(), 0),
+                SyscallReturn::Success
+            ));
+
+            let buffer = app.alloc(chunk_size).unwrap();
+            let done = Rc::new(Cell::new(false));
+            let upcall_done = done.clone();
+            app.subscribe(LOADER, 0, move |_, status, _, _| {
+                assert_eq!(status, 0);
+                upcall_done.set(true)
+            });
+            for (i, chunk) in image.chunks(chunk_size).enumerate() {
+                app.write_bytes(buffer, chunk);
+                app.allow_readonly(LOADER, 0, buffer, chunk.len());
+                done.set(false);
+                assert!(matches!(
+                    app.command(LOADER, 3, i * chunk_size, chunk.len()),
+                    SyscallReturn::Success
+                ));
+                app.yield_for(|| done.get());
+            }
+            let result = app.command(LOADER, 4, 0, 0);
+            *installer_loaded.lock().unwrap() =
+                Some(matches!(result, SyscallReturn::SuccessU32(_)));
+            app.exit_terminate(0);
+        })],
+        BootOptions::new()
+            .image(|_, image| sha256_footer(image.short_id(LOADER_SHORT_ID)))
+            .checker(sha256_checker(false)),
+    );
+
+    let started = Arc::new(AtomicUsize::new(0));
+    *image.lock().unwrap() = counting_app(&board, &started);
+
+    let scheduler = board.cooperative();
+    board.run_until(scheduler, true, || started.load(Ordering::SeqCst) == 1);
+    assert_eq!(*loaded.lock().unwrap(), Some(true));
+    assert_eq!(board.process(1).get_process_name(), "new");
+}
+
+#[test]
+fn app_loader_unloads_last_app() {
+    let board = TestBoard::boot(vec![program(|app| app.exit_terminate(0))]);
+    let scheduler = board.cooperative();
+    let client = leak(LoaderClient::default());
+    board.app_loader.set_client(client);
+
+    let started = Arc::new(AtomicUsize::new(0));
+    let processid = install(&board, scheduler, client, counting_app(&board, &started));
+    let flash_start = board.process(1).flash_start();
+    let offset = flash_start as usize - board.app_flash.as_ptr() as usize;
+    board.run_until(scheduler, true, || started.load(Ordering::SeqCst) == 1);
+
+    board.app_loader.unload(processid).unwrap();
+    assert!(board.processes[1].get().is_none());
+    board.run_until(scheduler, true, || client.unloads.get() == 1);
+    assert!(board.app_flash[offset..].iter().all(|byte| *byte == 0xFF));
+    assert_eq!(board.app_loader.unload(processid), Err(ErrorCode::INVAL));
+
+    // The slot and the flash are reused by the next app.
+    let processid = install(&board, scheduler, client, counting_app(&board, &started));
+    assert_eq!(board.process(1).processid(), processid);
+    assert_eq!(board.process(1).flash_start(), flash_start);
+    board.run_until(scheduler, true, || started.load(Ordering::SeqCst) == 2);
+}
+
+#[test]
+fn app_loader_replaces_unloaded_app_with_padding() {
+    let board = TestBoard::boot(vec![
+        program(|app| app.exit_terminate(0)),
+        program(|app| app.exit_terminate(0)),
+    ]);
+    let scheduler = board.cooperative();
+    let client = leak(LoaderClient::default());
+    board.app_loader.set_client(client);
+
+    let total_size =
+        board.process(0).flash_end() as usize - board.process(0).flash_start() as usize;
+    board
+        .app_loader
+        .unload(board.process(0).processid())
+        .unwrap();
+    assert!(board.processes[0].get().is_none());
+    board.run_until(scheduler, true, || client.unloads.get() == 1);
+
+    // The second app keeps its place in the list, behind a padding entry the
+    // size of the first app.
+    let header = &board.app_flash[..16];
+    let word = |i: usize| u32::from_le_bytes(header[i * 4..i * 4 + 4].try_into().unwrap());
+    assert_eq!(word(0), 2 | (16 << 16));
+    assert_eq!(word(1), total_size as u32);
+    assert_eq!(word(2), 0);
+    assert_eq!(word(3), word(0) ^ word(1) ^ word(2));
+    assert_eq!(board.process(1).get_process_name(), "app1");
+}
+
+#[test]
+fn edf_admits_app_loaded_into_empty_slot() {
+    let board = TestBoard::boot_with(
+        vec![program(|app| app.yield_for(|| false))],
+        BootOptions::new().image(|_, image| image.real_time(20_000, 4_000)),
+    );
+    let cooperative = board.cooperative();
+    let client = leak(LoaderClient::default());
+    board.app_loader.set_client(client);
+
+    let alarm = leak(HostAlarm::new(board.chip.interrupt_line(ALARM_INTERRUPT)));
+    board.interrupts.alarm.set(alarm);
+    let edf = leak(EDFSched::new(alarm, 100));
+    alarm.set_alarm_client(edf);
+    // Nodes for every slot, including the empty ones.
+    for process in board.processes.iter() {
+        assert!(edf.add_process(leak(EDFProcessNode::new(process))).is_ok());
+    }
+    assert_eq!(edf.utilization(), 200_000);
+
+    let started = Arc::new(AtomicUsize::new(0));
+    let app_started = started.clone();
+    let id = board
+        .chip
+        .userspace_kernel_boundary()
+        .register_program(move |app| {
+            app_started.fetch_add(1, Ordering::SeqCst);
+            app.yield_for(|| false)
+        });
+    let image = TbfBuilder::new(id)
+        .package_name("new")
+        .real_time(10_000, 5_000)
+        .build();
+    let processid = install(&board, cooperative, client, image);
+
+    // The new process goes through admission control when the scheduler
+    // next runs, and its reservation is released once it is unloaded.
+    board.run_until(edf, false, || started.load(Ordering::SeqCst) == 1);
+    assert_eq!(edf.utilization(), 700_000);
+    board.app_loader.unload(processid).unwrap();
+    board.run_until(edf, false, || {
+        client.unloads.get() == 1 && edf.utilization() == 200_000
+    });
+}
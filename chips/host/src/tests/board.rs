@@ -0,0 +1,622 @@
+//! The board the tests boot: the host chip with the kernel, a test driver
+//! and the capsules under test, and helpers to build and run processes.
+
+use core::fmt::Write;
+use core::num::NonZeroU32;
+use std::time::{Duration, Instant};
+
+use kernel::capabilities;
+use kernel::common::cells::OptionalCell;
+use kernel::create_capability;
+use kernel::hil::time::Alarm;
+use kernel::procs::TbfFooterV2CredentialsType;
+use kernel::procs::{AppCheckerVerify, AppCredentialsChecker, AppIdPolicy, CredentialsVerifier};
+use kernel::procs::{Process, ProcessFaultPolicy, ProcessSlot, ShortIdFromTbf};
+use kernel::sleep::{SleepPolicy, SleepState};
+use kernel::{Chip, ErrorCode, InterruptService, Kernel, Platform, Scheduler};
+use kernel::{CommandReturn, Driver, Grant, ProcessId, ShortID};
+use kernel::{CoopProcessNode, CooperativeSched, RoundRobinProcessNode, RoundRobinSched};
+use kernel::{ReadOnlyProcessBuffer, ReadWriteProcessBuffer};
+use kernel::{ReadableProcessBuffer, WriteableProcessBuffer};
+use kernel::{SyscallFilter, TbfHeaderFilterDefaultAllow};
+
+use crate::alarm::HostAlarm;
+use crate::flash::{HostFlash, HostPage, PAGE_SIZE};
+use crate::tbf::{self, TbfBuilder};
+use crate::{App, HostChip};
+
+pub(super) const NUM_PROCS: usize = 4;
+const NUM_UPCALLS_IPC: usize = NUM_PROCS + 1;
+/// Like the kernel's default, only verified apps get a fixed short ID.
+pub(super) const DEFAULT_ID_POLICY: &ShortIdFromTbf = &ShortIdFromTbf::new(true);
+/// Short IDs for unverified apps too, for tests that do not check credentials.
+pub(super) const UNVERIFIED_ID_POLICY: &ShortIdFromTbf = &ShortIdFromTbf::new(false);
+/// Short ID of the app allowed to use the app loader.
+pub(super) const LOADER_SHORT_ID: u32 = 0x10ad;
+pub(super) const TEST_DRIVER: usize = 0x9999;
+pub(super) const ALARM_INTERRUPT: u32 = 3;
+const FLASH_INTERRUPT: u32 = 4;
+const STORAGE_INTERRUPT: u32 = 5;
+const KV_INTERRUPT: u32 = 6;
+const CRASH_INTERRUPT: u32 = 7;
+const ALARM_DRIVER_INTERRUPT: u32 = 8;
+pub(super) const PREVIOUS_PANIC: &str =
+    "\r\npanicked at 'out of grant memory', kernel/src/grant.rs:42:9\r\n\tKernel version test\r\n";
+
+type TestAppLoader = capsules::app_loader::AppLoader<'static, HostFlash<'static>, Capability>;
+type TestAppWatchdog =
+    capsules::app_watchdog::AppWatchdog<'static, HostAlarm<'static>, Capability, NUM_PROCS>;
+type TestStorage = capsules::nonvolatile_storage_driver::NonvolatileStorage<'static>;
+type TestTicKV = capsules::tickv::TicKVStore<'static, HostFlash<'static>>;
+type TestKVStore = capsules::kv_store::KVStore<'static, TestTicKV, capsules::tickv::TicKVKeyType>;
+type TestKVDriver = capsules::kv_driver::KVStoreDriver<'static>;
+type TestCrashDump = capsules::crash_dump::CrashDump<'static, HostFlash<'static>>;
+type TestAlarmDriver = capsules::alarm::AlarmDriver<'static, HostAlarm<'static>>;
+type TestIPC = kernel::ipc::IPC<NUM_PROCS, NUM_UPCALLS_IPC>;
+
+pub(super) struct Capability;
+unsafe impl capabilities::ProcessManagementCapability for Capability {}
+
+#[derive(Default)]
+struct TestData {
+    rw: ReadWriteProcessBuffer,
+    ro: ReadOnlyProcessBuffer,
+}
+
+/// Minimal syscall driver used to exercise grants, upcalls and allow.
+struct TestDriver {
+    apps: Grant<TestData, 1>,
+    /// A second grant, allocated after `apps` on first use.
+    scratch: Grant<[usize; 16], 0>,
+}
+
+impl Driver for TestDriver {
+    fn command(&self, command_num: usize, r2: usize, r3: usize, appid: ProcessId) -> CommandReturn {
+        match command_num {
+            0 => CommandReturn::success(),
+            // Schedule upcall 0 with the two arguments.
+            1 => self
+                .apps
+                .enter(appid, |_, upcalls| {
+                    upcalls.schedule_upcall(0, r2, r3, 0).ok();
+                    CommandReturn::success()
+                })
+                .unwrap_or_else(|err| CommandReturn::failure(err.into())),
+            // Fill the read-write buffer with `r2`.
+            2 => self
+                .apps
+                .enter(appid, |app, _| {
+                    let len = app
+                        .rw
+                        .mut_enter(|buf| {
+                            buf.iter().for_each(|byte| byte.set(r2 as u8));
+                            buf.len()
+                        })
+                        .unwrap_or(0);
+                    CommandReturn::success_u32(len as u32)
+                })
+                .unwrap_or_else(|err| CommandReturn::failure(err.into())),
+            // Sum the bytes of the read-only buffer.
+            3 => self
+                .apps
+                .enter(appid, |app, _| {
+                    let sum = app
+                        .ro
+                        .enter(|buf| buf.iter().map(|byte| byte.get() as u32).sum())
+                        .unwrap_or(0);
+                    CommandReturn::success_u32(sum)
+                })
+                .unwrap_or_else(|err| CommandReturn::failure(err.into())),
+            // Free the grant.
+            4 => self.apps.free(appid).map_or_else(
+                |err| CommandReturn::failure(err.into()),
+                |()| CommandReturn::success(),
+            ),
+            // Store `r2` in the scratch grant.
+            5 => self
+                .scratch
+                .enter(appid, |scratch, _| {
+                    scratch[0] = r2;
+                    CommandReturn::success()
+                })
+                .unwrap_or_else(|err| CommandReturn::failure(err.into())),
+            // Read back the value stored in the scratch grant.
+            6 => self
+                .scratch
+                .enter(appid, |scratch, _| {
+                    CommandReturn::success_u32(scratch[0] as u32)
+                })
+                .unwrap_or_else(|err| CommandReturn::failure(err.into())),
+            // Free the scratch grant.
+            7 => self.scratch.free(appid).map_or_else(
+                |err| CommandReturn::failure(err.into()),
+                |()| CommandReturn::success(),
+            ),
+            _ => CommandReturn::failure(ErrorCode::NOSUPPORT),
+        }
+    }
+
+    fn allow_readwrite(
+        &self,
+        appid: ProcessId,
+        allow_num: usize,
+        mut slice: ReadWriteProcessBuffer,
+    ) -> Result<ReadWriteProcessBuffer, (ReadWriteProcessBuffer, ErrorCode)> {
+        if allow_num != 0 {
+            return Err((slice, ErrorCode::NOSUPPORT));
+        }
+        match self.apps.enter(appid, |app, _| {
+            core::mem::swap(&mut app.rw, &mut slice);
+        }) {
+            Ok(()) => Ok(slice),
+            Err(err) => Err((slice, err.into())),
+        }
+    }
+
+    fn allow_readonly(
+        &self,
+        appid: ProcessId,
+        allow_num: usize,
+        mut slice: ReadOnlyProcessBuffer,
+    ) -> Result<ReadOnlyProcessBuffer, (ReadOnlyProcessBuffer, ErrorCode)> {
+        if allow_num != 0 {
+            return Err((slice, ErrorCode::NOSUPPORT));
+        }
+        match self.apps.enter(appid, |app, _| {
+            core::mem::swap(&mut app.ro, &mut slice);
+        }) {
+            Ok(()) => Ok(slice),
+            Err(err) => Err((slice, err.into())),
+        }
+    }
+
+    fn allocate_grant(&self, processid: ProcessId) -> Result<(), kernel::procs::Error> {
+        self.apps.enter(processid, |_, _| {})
+    }
+}
+
+pub(super) struct TestPlatform {
+    driver: &'static TestDriver,
+    app_loader: &'static TestAppLoader,
+    storage: &'static TestStorage,
+    kv: &'static TestKVDriver,
+    pub(super) panic_report: &'static capsules::panic_report::PanicReport,
+    pub(super) ipc: &'static TestIPC,
+    alarm: &'static TestAlarmDriver,
+    pub(super) sleep_policy: OptionalCell<&'static dyn SleepPolicy>,
+    pub(super) app_watchdog: OptionalCell<&'static TestAppWatchdog>,
+}
+
+impl Platform for TestPlatform {
+    fn with_driver<F, R>(&self, driver_num: usize, f: F) -> R
+    where
+        F: FnOnce(Option<&dyn Driver>) -> R,
+    {
+        match driver_num {
+            TEST_DRIVER => f(Some(self.driver)),
+            capsules::app_loader::DRIVER_NUM => f(Some(self.app_loader)),
+            capsules::nonvolatile_storage_driver::DRIVER_NUM => f(Some(self.storage)),
+            capsules::kv_driver::DRIVER_NUM => f(Some(self.kv)),
+            capsules::panic_report::DRIVER_NUM => f(Some(self.panic_report)),
+            kernel::ipc::DRIVER_NUM => f(Some(self.ipc)),
+            capsules::alarm::DRIVER_NUM => f(Some(self.alarm)),
+            capsules::app_watchdog::DRIVER_NUM => f(self
+                .app_watchdog
+                .extract()
+                .map(|driver| driver as &dyn Driver)),
+            _ => f(None),
+        }
+    }
+
+    fn filter_syscall(
+        &self,
+        process: &dyn Process,
+        syscall: &kernel::syscall::Syscall,
+    ) -> Result<(), ErrorCode> {
+        TbfHeaderFilterDefaultAllow {}.filter_syscall(process, syscall)
+    }
+
+    fn select_sleep_state(&self, states: &[SleepState]) -> Option<usize> {
+        self.sleep_policy
+            .and_then(|policy| policy.select_sleep_state(states))
+    }
+
+    fn watchdog_healthy(&self) -> bool {
+        self.app_watchdog
+            .map_or(true, |watchdog| watchdog.healthy())
+    }
+}
+
+pub(super) struct TestInterrupts {
+    pub(super) alarm: OptionalCell<&'static HostAlarm<'static>>,
+    flash: OptionalCell<&'static HostFlash<'static>>,
+    storage: OptionalCell<&'static HostFlash<'static>>,
+    kv: OptionalCell<&'static HostFlash<'static>>,
+    crash: OptionalCell<&'static HostFlash<'static>>,
+    alarm_driver: OptionalCell<&'static HostAlarm<'static>>,
+}
+
+impl InterruptService for TestInterrupts {
+    unsafe fn service_interrupt(&self, interrupt: u32) -> bool {
+        match interrupt {
+            ALARM_INTERRUPT => self.alarm.map(|alarm| alarm.handle_interrupt()).is_some(),
+            FLASH_INTERRUPT => self.flash.map(|flash| flash.handle_interrupt()).is_some(),
+            STORAGE_INTERRUPT => self.storage.map(|flash| flash.handle_interrupt()).is_some(),
+            KV_INTERRUPT => self.kv.map(|flash| flash.handle_interrupt()).is_some(),
+            CRASH_INTERRUPT => self.crash.map(|flash| flash.handle_interrupt()).is_some(),
+            ALARM_DRIVER_INTERRUPT => self
+                .alarm_driver
+                .map(|alarm| alarm.handle_interrupt())
+                .is_some(),
+            _ => false,
+        }
+    }
+}
+
+/// How `TestBoard::boot_with()` boots the board. By default processes that
+/// fault are stopped, programs use the TBF `TbfBuilder` makes, and apps are
+/// loaded without checking their credentials.
+pub(super) struct BootOptions<'a> {
+    fault_policy: &'static dyn ProcessFaultPolicy,
+    image: Box<dyn Fn(usize, TbfBuilder) -> TbfBuilder + 'a>,
+    checker: Option<&'static dyn AppCredentialsChecker>,
+    id_policy: &'static dyn AppIdPolicy,
+}
+
+impl<'a> BootOptions<'a> {
+    pub(super) fn new() -> Self {
+        BootOptions {
+            fault_policy: leak(kernel::procs::StopFaultPolicy {}),
+            image: Box::new(|_, image| image),
+            checker: None,
+            id_policy: DEFAULT_ID_POLICY,
+        }
+    }
+
+    pub(super) fn fault_policy(mut self, fault_policy: &'static dyn ProcessFaultPolicy) -> Self {
+        self.fault_policy = fault_policy;
+        self
+    }
+
+    /// Let `image` customize the TBF of each program, by its index.
+    pub(super) fn image<F: Fn(usize, TbfBuilder) -> TbfBuilder + 'a>(mut self, image: F) -> Self {
+        self.image = Box::new(image);
+        self
+    }
+
+    /// Check app credentials with `checker`.
+    pub(super) fn checker(mut self, checker: &'static dyn AppCredentialsChecker) -> Self {
+        self.checker = Some(checker);
+        self
+    }
+
+    /// Assign short IDs with `id_policy`. Without a checker, apps loaded at
+    /// boot use the kernel's default policy instead.
+    pub(super) fn id_policy(mut self, id_policy: &'static dyn AppIdPolicy) -> Self {
+        self.id_policy = id_policy;
+        self
+    }
+}
+
+pub(super) struct TestBoard {
+    pub(super) kernel: &'static Kernel,
+    pub(super) chip: &'static HostChip<TestInterrupts>,
+    pub(super) interrupts: &'static TestInterrupts,
+    pub(super) platform: &'static TestPlatform,
+    pub(super) processes: &'static [ProcessSlot],
+    pub(super) app_flash: &'static [u8],
+    pub(super) app_loader: &'static TestAppLoader,
+    pub(super) storage: &'static TestStorage,
+    pub(super) crash_dump: &'static TestCrashDump,
+}
+
+pub(super) fn leak<T>(value: T) -> &'static mut T {
+    Box::leak(Box::new(value))
+}
+
+impl TestBoard {
+    /// Boot a board running `programs`, each as a separate process, with the
+    /// default `BootOptions`.
+    pub(super) fn boot(programs: Vec<Box<dyn Fn(&App) + Send + Sync>>) -> TestBoard {
+        TestBoard::boot_with(programs, BootOptions::new())
+    }
+
+    /// Boot a board running `programs`, each as a separate process.
+    pub(super) fn boot_with(
+        programs: Vec<Box<dyn Fn(&App) + Send + Sync>>,
+        options: BootOptions<'_>,
+    ) -> TestBoard {
+        let BootOptions {
+            fault_policy,
+            image,
+            checker,
+            id_policy,
+        } = options;
+        let interrupts = leak(TestInterrupts {
+            alarm: OptionalCell::empty(),
+            flash: OptionalCell::empty(),
+            storage: OptionalCell::empty(),
+            kv: OptionalCell::empty(),
+            crash: OptionalCell::empty(),
+            alarm_driver: OptionalCell::empty(),
+        });
+        let chip = leak(HostChip::new(&*interrupts));
+
+        let images: Vec<Vec<u8>> = programs
+            .into_iter()
+            .enumerate()
+            .map(|(i, program)| {
+                let id = chip
+                    .userspace_kernel_boundary()
+                    .register_program(move |app| program(app));
+                image(i, TbfBuilder::new(id).package_name(&format!("app{}", i))).build()
+            })
+            .collect();
+
+        let processes: &'static [ProcessSlot] = leak([ProcessSlot::EMPTY; NUM_PROCS]);
+        let kernel = leak(Kernel::new(processes));
+
+        let memory_allocation_cap = create_capability!(capabilities::MemoryAllocationCapability);
+        let process_management_cap = create_capability!(capabilities::ProcessManagementCapability);
+
+        // Faults are saved to a crash dump flash before `fault_policy` is
+        // applied.
+        let crash_flash = leak(HostFlash::new(
+            &[],
+            4 * PAGE_SIZE,
+            chip.interrupt_line(CRASH_INTERRUPT),
+        ));
+        interrupts.crash.set(crash_flash);
+        let crash_dump = leak(capsules::crash_dump::CrashDump::new(
+            &*crash_flash,
+            fault_policy,
+            crash_flash.region().as_ptr() as usize / PAGE_SIZE,
+            4,
+            leak(HostPage::default()),
+        ));
+        kernel::hil::flash::HasClient::set_client(&*crash_flash, &*crash_dump);
+        crash_dump.initialize().unwrap();
+        let fault_policy: &'static dyn ProcessFaultPolicy = crash_dump;
+
+        let driver = leak(TestDriver {
+            apps: kernel.create_grant(TEST_DRIVER, &memory_allocation_cap),
+            scratch: kernel.create_grant(TEST_DRIVER + 1, &memory_allocation_cap),
+        });
+
+        // Leave room in flash for apps installed at runtime.
+        let flash = leak(HostFlash::new(
+            &images.concat(),
+            64 * 1024,
+            chip.interrupt_line(FLASH_INTERRUPT),
+        ));
+        interrupts.flash.set(flash);
+        let app_flash = flash.region();
+        let app_memory = tbf::process_memory(64 * 1024);
+
+        let dynamic_loader = leak(kernel::procs::DynamicProcessLoader::new(
+            kernel,
+            &*chip,
+            tbf::process_memory(32 * 1024),
+            fault_policy,
+            checker,
+            id_policy,
+            &process_management_cap,
+        ));
+        let app_loader = leak(capsules::app_loader::AppLoader::new(
+            kernel,
+            &*flash,
+            dynamic_loader,
+            app_flash,
+            leak(HostPage::default()),
+            kernel.create_grant(capsules::app_loader::DRIVER_NUM, &memory_allocation_cap),
+            ShortID::Fixed(NonZeroU32::new(LOADER_SHORT_ID).unwrap()),
+            Capability,
+        ));
+        kernel::hil::flash::HasClient::set_client(&*flash, &*app_loader);
+
+        // Nonvolatile storage on a separate flash, split evenly between
+        // userspace and the kernel.
+        let storage_flash = leak(HostFlash::new(
+            &[],
+            8 * 1024,
+            chip.interrupt_line(STORAGE_INTERRUPT),
+        ));
+        interrupts.storage.set(storage_flash);
+        let pages = leak(capsules::nonvolatile_to_pages::NonvolatileToPages::new(
+            &*storage_flash,
+            leak(HostPage::default()),
+        ));
+        kernel::hil::flash::HasClient::set_client(&*storage_flash, &*pages);
+        let storage_start = storage_flash.region().as_ptr() as usize;
+        let storage = leak(
+            capsules::nonvolatile_storage_driver::NonvolatileStorage::new(
+                &*pages,
+                kernel.create_grant(
+                    capsules::nonvolatile_storage_driver::DRIVER_NUM,
+                    &memory_allocation_cap,
+                ),
+                storage_start,
+                4 * 1024,
+                storage_start + 4 * 1024,
+                4 * 1024,
+                leak([0; 512]),
+            ),
+        );
+        kernel::hil::nonvolatile_storage::NonvolatileStorage::set_client(&*pages, &*storage);
+
+        // A key-value store on TicKV, on another flash.
+        let kv_flash = leak(HostFlash::new(
+            &[],
+            4 * 1024,
+            chip.interrupt_line(KV_INTERRUPT),
+        ));
+        interrupts.kv.set(kv_flash);
+        let tickv = leak(capsules::tickv::TicKVStore::new(
+            &*kv_flash,
+            leak([0; 512]),
+            leak(HostPage::default()),
+            kv_flash.region().as_ptr() as usize / PAGE_SIZE,
+            kv_flash.region().len(),
+        ));
+        kernel::hil::flash::HasClient::set_client(&*kv_flash, &*tickv);
+        let kv_store: &'static TestKVStore = leak(capsules::kv_store::KVStore::new(
+            &*tickv,
+            leak([0; 8]),
+            leak([0; 256]),
+        ));
+        kernel::hil::kv_system::KVSystem::set_client(&*tickv, kv_store);
+        let kv = leak(capsules::kv_driver::KVStoreDriver::new(
+            kv_store,
+            kernel.create_grant(capsules::kv_driver::DRIVER_NUM, &memory_allocation_cap),
+            leak([0; 64]),
+            leak([0; 256]),
+        ));
+        kernel::hil::kv_store::KVStore::set_client(kv_store, &*kv);
+        tickv.initalise();
+
+        // The board is booted as if the kernel panicked before the reset.
+        let panic_record = leak(kernel::debug::PanicRecord::new());
+        let _ = panic_record.write_str(PREVIOUS_PANIC);
+        panic_record.seal();
+        let panic_report = leak(capsules::panic_report::PanicReport::new(
+            panic_record,
+            leak([0; kernel::debug::PANIC_RECORD_LENGTH]),
+            kernel.create_grant(capsules::panic_report::DRIVER_NUM, &memory_allocation_cap),
+        ));
+
+        let ipc = leak(kernel::ipc::IPC::new(
+            kernel,
+            kernel::ipc::DRIVER_NUM,
+            &memory_allocation_cap,
+        ));
+
+        // The userspace alarm driver has a timer of its own, so that tests can
+        // use `ALARM_INTERRUPT` directly.
+        let driver_alarm = leak(HostAlarm::new(chip.interrupt_line(ALARM_DRIVER_INTERRUPT)));
+        interrupts.alarm_driver.set(driver_alarm);
+        let alarm = leak(capsules::alarm::AlarmDriver::new(
+            &*driver_alarm,
+            kernel.create_grant(capsules::alarm::DRIVER_NUM, &memory_allocation_cap),
+        ));
+        driver_alarm.set_alarm_client(&*alarm);
+
+        let platform = leak(TestPlatform {
+            driver,
+            app_loader,
+            storage,
+            kv,
+            panic_report,
+            ipc,
+            alarm,
+            sleep_policy: OptionalCell::empty(),
+            app_watchdog: OptionalCell::empty(),
+        });
+
+        match checker {
+            Some(checker) => kernel::procs::load_and_check_processes(
+                kernel,
+                &*chip,
+                app_flash,
+                app_memory,
+                fault_policy,
+                checker,
+                id_policy,
+                &process_management_cap,
+            ),
+            None => kernel::procs::load_processes(
+                kernel,
+                &*chip,
+                app_flash,
+                app_memory,
+                fault_policy,
+                &process_management_cap,
+            ),
+        }
+        .unwrap();
+
+        TestBoard {
+            kernel,
+            chip,
+            interrupts,
+            platform,
+            processes,
+            app_flash,
+            app_loader,
+            storage,
+            crash_dump,
+        }
+    }
+
+    pub(super) fn cooperative(&self) -> &'static CooperativeSched<'static> {
+        let scheduler = leak(CooperativeSched::new());
+        for process in self.processes.iter() {
+            scheduler
+                .processes
+                .push_tail(leak(CoopProcessNode::new(process)));
+        }
+        scheduler
+    }
+
+    pub(super) fn round_robin(&self) -> &'static RoundRobinSched<'static> {
+        let scheduler = leak(RoundRobinSched::new());
+        for process in self.processes.iter() {
+            scheduler
+                .processes
+                .push_tail(leak(RoundRobinProcessNode::new(process)));
+        }
+        scheduler
+    }
+
+    pub(super) fn process(&self, index: usize) -> &'static dyn Process {
+        self.processes[index].get().unwrap()
+    }
+
+    /// Run the kernel loop until `done` returns true, or fail after a while.
+    pub(super) fn run_until<S, F>(&self, scheduler: &S, no_sleep: bool, done: F)
+    where
+        S: Scheduler<HostChip<TestInterrupts>>,
+        F: Fn() -> bool,
+    {
+        let main_loop_cap = create_capability!(capabilities::MainLoopCapability);
+        let deadline = Instant::now() + Duration::from_secs(10);
+        while !done() {
+            assert!(Instant::now() < deadline, "timed out running kernel loop");
+            self.kernel
+                .kernel_loop_operation::<_, _, _, NUM_PROCS, NUM_UPCALLS_IPC>(
+                    self.platform,
+                    self.chip,
+                    Some(self.platform.ipc),
+                    scheduler,
+                    no_sleep,
+                    &main_loop_cap,
+                );
+        }
+    }
+}
+
+pub(super) fn program<F: Fn(&App) + Send + Sync + 'static>(
+    f: F,
+) -> Box<dyn Fn(&App) + Send + Sync> {
+    Box::new(f)
+}
+
+pub(super) const CREDENTIALS_SHA256: u32 = TbfFooterV2CredentialsType::SHA256 as u32;
+
+pub(super) fn sha256_checker(require_credentials: bool) -> &'static dyn AppCredentialsChecker {
+    let verifier = leak(capsules::app_checker_sha256::AppCheckerSha256::new());
+    let verifiers: &'static [&'static dyn CredentialsVerifier] = leak([verifier as _]);
+    leak(AppCheckerVerify::new(verifiers, require_credentials))
+}
+
+/// A checker that loads every app, but verifies none.
+pub(super) fn unchecked() -> &'static dyn AppCredentialsChecker {
+    leak(AppCheckerVerify::new(&[], false))
+}
+
+pub(super) fn sha256_footer(image: TbfBuilder) -> TbfBuilder {
+    image.footer_with(CREDENTIALS_SHA256, 32, |region| {
+        capsules::app_checker_sha256::sha256(region).to_vec()
+    })
+}
+
+pub(super) const STORAGE: usize = capsules::nonvolatile_storage_driver::DRIVER_NUM;
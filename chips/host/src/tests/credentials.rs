@@ -0,0 +1,188 @@
+//! Tests of app credentials: SHA-256 and Ed25519 checking.
+
+use kernel::procs::{AppCheckerVerify, AppCredentialsChecker, CredentialsVerifier};
+use kernel::procs::{ProcessSlot, TbfFooterV2CredentialsType};
+
+use crate::tbf::TbfBuilder;
+
+use super::board::*;
+
+const CREDENTIALS_ED25519: u32 = TbfFooterV2CredentialsType::Ed25519 as u32;
+
+/// Boot three apps, each counting its start in `started`, and return which
+/// of them were loaded.
+fn boot_checked<F>(image: F, checker: &'static dyn AppCredentialsChecker) -> Vec<bool>
+where
+    F: Fn(usize, TbfBuilder) -> TbfBuilder,
+{
+    let board = TestBoard::boot_with(
+        (0..3)
+            .map(|_| program(|app| app.exit_terminate(0)))
+            .collect(),
+        BootOptions::new().image(image).checker(checker),
+    );
+    (0..3)
+        .map(|i| {
+            board
+                .processes
+                .iter()
+                .filter_map(ProcessSlot::get)
+                .any(|process| process.get_process_name() == format!("app{}", i))
+        })
+        .collect()
+}
+
+#[test]
+fn sha256_hash_matches() {
+    assert_eq!(
+        capsules::app_checker_sha256::sha256(b"abc")[..4],
+        [0xba, 0x78, 0x16, 0xbf]
+    );
+    // Two-block padding: 56 bytes of input leave no room for the length.
+    assert_eq!(
+        capsules::app_checker_sha256::sha256(&[b'a'; 56])[..4],
+        [0xb3, 0x54, 0x39, 0xa4]
+    );
+}
+
+/// Decode a hex string, as the RFC 8032 test vectors are written.
+fn hex(string: &str) -> Vec<u8> {
+    (0..string.len())
+        .step_by(2)
+        .map(|i| u8::from_str_radix(&string[i..i + 2], 16).unwrap())
+        .collect()
+}
+
+fn key(string: &str) -> [u8; 32] {
+    let mut key = [0; 32];
+    key.copy_from_slice(&hex(string));
+    key
+}
+
+/// Private key of test 1 of RFC 8032, section 7.1.
+const ED25519_SECRET_KEY: &str = "9d61b19deffd5a60ba844af492ec2cc44449c5697b326919703bac031cae7f60";
+
+#[test]
+fn ed25519_signatures_match() {
+    use capsules::app_checker_ed25519::{public_key, sign, verify};
+
+    let long_message: Vec<u8> = (0..300).map(|i| (i % 251) as u8).collect();
+    let vectors = [
+        // Tests 1 and 2 of RFC 8032, section 7.1.
+        (
+            ED25519_SECRET_KEY,
+            "d75a980182b10ab7d54bfed3c964073a0ee172f3daa62325af021a68f707511a",
+            Vec::new(),
+            "e5564300c360ac729086e2cc806e828a84877f1eb8e5d974d873e065224901555fb8821590a33bacc61e39701cf9b46bd25bf5f0595bbe24655141438e7a100b",
+        ),
+        (
+            "4ccd089b28ff96da9db6c346ec114e0f5b8a319f35aba624da8cf6ed4fb8a6fb",
+            "3d4017c3e843895a92b70aa74d1b7ebc9c982ccf2ec4968cc0cd55f12af4660c",
+            vec![0x72],
+            "92a009a9f0d4cab8720e820b5f642540a2b27b5416503f8fb3762223ebdb69da085ac1e43e15996e458f3613d0f11d8c387b2eaeb4302aeeb00d291612bb0c00",
+        ),
+        // A message longer than two SHA-512 blocks.
+        (
+            ED25519_SECRET_KEY,
+            "d75a980182b10ab7d54bfed3c964073a0ee172f3daa62325af021a68f707511a",
+            long_message,
+            "34dd30465444c93a243e43161f92dac95c300f9e7f442a270729eb820bf0e0c9d7873790ba729a21627ef65da1bf31b8b462066c011bbbeeee3ef0ec5c907202",
+        ),
+    ];
+    for (secret_key, public, message, signature) in vectors.iter() {
+        let (secret_key, public) = (key(secret_key), key(public));
+        let signature = hex(signature);
+        assert_eq!(public_key(&secret_key), public);
+        assert_eq!(sign(&secret_key, message)[..], signature[..]);
+        assert!(verify(&public, message, &signature));
+
+        let mut modified = message.clone();
+        modified.push(0);
+        assert!(!verify(&public, &modified, &signature));
+        let mut forged = signature.clone();
+        forged[0] ^= 1;
+        assert!(!verify(&public, message, &forged));
+    }
+
+    // S must be less than the group order, so adding the order to S does
+    // not give a second valid signature.
+    let (secret_key, public) = (key(ED25519_SECRET_KEY), key(vectors[0].1));
+    let mut signature = sign(&secret_key, b"");
+    signature[63] = signature[63].wrapping_add(0x10);
+    assert!(!verify(&public, b"", &signature));
+}
+
+#[test]
+fn apps_with_valid_signatures_load() {
+    let secret_key = key(ED25519_SECRET_KEY);
+    let other_key = [7; 32];
+    let verifier = leak(capsules::app_checker_ed25519::AppCheckerEd25519::new(
+        capsules::app_checker_ed25519::public_key(&secret_key),
+    ));
+    let verifiers: &'static [&'static dyn CredentialsVerifier] = leak([verifier as _]);
+    let checker = leak(AppCheckerVerify::new(verifiers, true));
+
+    // The first app is signed with the key the board trusts, the second with
+    // another key, and the third app was modified after it was signed.
+    let loaded = boot_checked(
+        move |i, image| {
+            image.footer_with(CREDENTIALS_ED25519, 64, move |region| match i {
+                0 => capsules::app_checker_ed25519::sign(&secret_key, region).to_vec(),
+                1 => capsules::app_checker_ed25519::sign(&other_key, region).to_vec(),
+                _ => {
+                    let mut modified = region.to_vec();
+                    modified[region.len() - 1] ^= 1;
+                    capsules::app_checker_ed25519::sign(&secret_key, &modified).to_vec()
+                }
+            })
+        },
+        checker,
+    );
+    assert_eq!(loaded, [true, false, false]);
+}
+
+#[test]
+fn apps_with_valid_credentials_load() {
+    let loaded = boot_checked(|_, image| sha256_footer(image), sha256_checker(true));
+    assert_eq!(loaded, [true, true, true]);
+}
+
+#[test]
+fn apps_without_credentials_refused_when_required() {
+    let loaded = boot_checked(
+        |i, image| if i == 1 { image } else { sha256_footer(image) },
+        sha256_checker(true),
+    );
+    assert_eq!(loaded, [true, false, true]);
+
+    let loaded = boot_checked(|_, image| image, sha256_checker(false));
+    assert_eq!(loaded, [true, true, true]);
+}
+
+#[test]
+fn apps_with_bad_credentials_refused() {
+    // A hash that does not match the image, as if the binary was modified
+    // after the footer was computed.
+    let loaded = boot_checked(
+        |i, image| {
+            if i == 0 {
+                image.footer(CREDENTIALS_SHA256, &[0; 32])
+            } else {
+                sha256_footer(image)
+            }
+        },
+        sha256_checker(false),
+    );
+    assert_eq!(loaded, [false, true, true]);
+}
+
+#[test]
+fn unverifiable_credentials_are_skipped() {
+    // No verifier for Ed25519 is configured, so the hash that follows it
+    // decides.
+    let loaded = boot_checked(
+        |_, image| sha256_footer(image.footer(CREDENTIALS_ED25519, &[0; 64])),
+        sha256_checker(true),
+    );
+    assert_eq!(loaded, [true, true, true]);
+}
@@ -0,0 +1,130 @@
+//! Tests of deferred call priorities.
+
+use core::cell::{Cell, RefCell};
+use std::rc::Rc;
+use std::sync::atomic::{AtomicU32, Ordering};
+
+use super::board::*;
+
+/// A deferred call client that logs when it runs, and can set itself again.
+struct DeferredWorker {
+    deferred_call: kernel::common::deferred_call::DeferredCall,
+    registry: &'static kernel::common::deferred_call::DeferredCallRegistry,
+    name: &'static str,
+    log: Rc<RefCell<Vec<&'static str>>>,
+    again: Cell<bool>,
+}
+
+impl DeferredWorker {
+    fn new(
+        name: &'static str,
+        priority: kernel::common::deferred_call::Priority,
+        registry: &'static kernel::common::deferred_call::DeferredCallRegistry,
+        log: &Rc<RefCell<Vec<&'static str>>>,
+    ) -> DeferredWorker {
+        DeferredWorker {
+            deferred_call: kernel::common::deferred_call::DeferredCall::with_priority(priority),
+            registry,
+            name,
+            log: log.clone(),
+            again: Cell::new(false),
+        }
+    }
+}
+
+impl kernel::common::deferred_call::DeferredCallClient for DeferredWorker {
+    fn handle_deferred_call(&self) {
+        self.log.borrow_mut().push(self.name);
+        if self.again.take() {
+            self.deferred_call.set();
+        }
+    }
+
+    fn register(&'static self) {
+        self.registry.register(&self.deferred_call, self);
+    }
+}
+
+/// A clock for deferred call statistics that only moves when told to, in
+/// microsecond ticks.
+struct FakeClock(AtomicU32);
+
+impl kernel::common::deferred_call::DeferredCallClock for FakeClock {
+    fn timestamp(&self) -> u32 {
+        self.0.load(Ordering::Relaxed)
+    }
+
+    fn elapsed_us(&self, start: u32, end: u32) -> u32 {
+        end.wrapping_sub(start)
+    }
+}
+
+#[test]
+fn deferred_calls_run_by_priority_and_record_pending_time() {
+    use kernel::common::deferred_call::{DeferredCallClient, DeferredCallRegistry, Priority};
+
+    // A registry of its own, so that kernel loops of other tests don't run
+    // these clients.
+    let registry = leak(DeferredCallRegistry::new());
+    let clock = leak(FakeClock(AtomicU32::new(0)));
+    registry.set_clock(&*clock);
+
+    let log = Rc::new(RefCell::new(Vec::new()));
+    let low = leak(DeferredWorker::new("low", Priority::Low, registry, &log));
+    let normal = leak(DeferredWorker::new(
+        "normal",
+        Priority::Normal,
+        registry,
+        &log,
+    ));
+    let high = leak(DeferredWorker::new("high", Priority::High, registry, &log));
+    // A call set before it is registered is serviced once it is.
+    low.deferred_call.set();
+    low.register();
+    normal.register();
+    high.register();
+    // Registering again does not link the call into the list twice.
+    high.register();
+    assert!(registry.has_tasks());
+
+    clock.0.store(100, Ordering::Relaxed);
+    normal.deferred_call.set();
+    high.deferred_call.set();
+    // Setting a pending call again does not restart its pending time.
+    clock.0.store(200, Ordering::Relaxed);
+    high.deferred_call.set();
+    normal.again.set(true);
+
+    // Calls run highest priority first, and a call that sets itself again
+    // waits for the next pass instead of starving lower priorities.
+    clock.0.store(250, Ordering::Relaxed);
+    registry.service_while(|| true);
+    assert_eq!(*log.borrow(), ["high", "normal", "low"]);
+    assert!(normal.deferred_call.is_pending());
+    assert!(!low.deferred_call.is_pending());
+    assert_eq!(high.deferred_call.stats().last_pending_us, 150);
+    assert_eq!(low.deferred_call.stats().last_pending_us, 250);
+
+    clock.0.store(300, Ordering::Relaxed);
+    registry.service_while(|| true);
+    assert_eq!(*log.borrow(), ["high", "normal", "low", "normal"]);
+    let stats = normal.deferred_call.stats();
+    assert_eq!(stats.priority, Priority::Normal);
+    assert_eq!(stats.calls, 2);
+    assert_eq!(stats.last_pending_us, 50);
+    assert_eq!(stats.max_pending_us, 150);
+
+    // Servicing stops as soon as the predicate fails, leaving lower priority
+    // calls pending for a later pass.
+    log.borrow_mut().clear();
+    low.deferred_call.set();
+    high.deferred_call.set();
+    let budget = Cell::new(1);
+    registry.service_while(|| budget.replace(0) == 1);
+    assert_eq!(*log.borrow(), ["high"]);
+    assert!(low.deferred_call.is_pending());
+    registry.service_while(|| true);
+    assert_eq!(*log.borrow(), ["high", "low"]);
+    assert!(!low.deferred_call.is_pending());
+    assert!(!registry.has_tasks());
+}
@@ -0,0 +1,284 @@
+//! Tests of process faults, crash dumps, restarts and the app watchdog.
+
+use core::cell::{Cell, RefCell};
+use core::num::NonZeroU32;
+use std::sync::atomic::{AtomicUsize, Ordering};
+use std::sync::{Arc, Mutex};
+use std::time::{Duration, Instant};
+
+use kernel::common::cells::OptionalCell;
+use kernel::hil::time::Alarm;
+use kernel::procs::{Process, ProcessFaultPolicy, State};
+use kernel::syscall::{SyscallClass, SyscallReturn};
+use kernel::ShortID;
+use kernel::{Chip, ErrorCode};
+
+use capsules::crash_dump::{CrashDumpClient, CrashDumpReader, CrashRecord};
+
+use crate::alarm::HostAlarm;
+
+use super::board::*;
+
+#[test]
+fn panicking_process_faults() {
+    let board = TestBoard::boot(vec![program(|app| {
+        app.command(TEST_DRIVER, 0, 0, 0);
+        panic!("simulated process fault");
+    })]);
+
+    let scheduler = board.cooperative();
+    board.run_until(scheduler, true, || {
+        board.process(0).get_state() == State::Faulted
+    });
+}
+
+/// Sequence, name, state, restart count and last syscall of a crash dump.
+type CrashSummary = (u32, String, Option<State>, u16, Option<(u8, u32, u32)>);
+
+/// Collects crash dumps read back from flash.
+#[derive(Default)]
+struct CrashDumpLog {
+    records: RefCell<Vec<CrashSummary>>,
+    done: Cell<bool>,
+}
+
+impl CrashDumpClient for CrashDumpLog {
+    fn crash_dump(&self, record: &CrashRecord) {
+        self.records.borrow_mut().push((
+            record.sequence,
+            record.process_name.to_string(),
+            record.state,
+            record.restart_count,
+            record
+                .last_syscall
+                .map(|(class, arg0, arg1)| (class as u8, arg0, arg1)),
+        ));
+    }
+
+    fn crash_dumps_done(&self, result: Result<(), ErrorCode>) {
+        assert!(result.is_ok());
+        self.done.set(true);
+    }
+}
+
+#[test]
+fn crash_dumps_saved_to_flash() {
+    let board = TestBoard::boot_with(
+        vec![program(|app| {
+            app.command(TEST_DRIVER, 0, 0, 0);
+            panic!("simulated process fault");
+        })],
+        BootOptions::new().fault_policy(leak(kernel::procs::ThresholdRestartFaultPolicy::new(4))),
+    );
+
+    // The process faults six times, and only the newest four crash dumps fit
+    // in flash.
+    let scheduler = board.cooperative();
+    board.run_until(scheduler, true, || {
+        board.process(0).get_state() == State::Faulted
+    });
+    let log = leak(CrashDumpLog::default());
+    board.crash_dump.set_client(log);
+    board.run_until(scheduler, true, || {
+        board.crash_dump.read_crash_dumps().is_ok()
+    });
+    board.run_until(scheduler, true, || log.done.get());
+
+    assert_eq!(board.crash_dump.dropped_count(), 0);
+    let command = Some((SyscallClass::Command as u8, TEST_DRIVER as u32, 0));
+    assert_eq!(
+        *log.records.borrow(),
+        (2..6)
+            .map(|i| (
+                i,
+                "app0".to_string(),
+                Some(State::Running),
+                i as u16,
+                command
+            ))
+            .collect::<Vec<_>>()
+    );
+}
+
+#[test]
+fn panic_report_returns_previous_panic() {
+    let report = Arc::new(Mutex::new(Vec::new()));
+    let app_report = report.clone();
+    let board = TestBoard::boot(vec![program(move |app| {
+        let length = match app.command(capsules::panic_report::DRIVER_NUM, 1, 0, 0) {
+            SyscallReturn::SuccessU32(length) => length as usize,
+            _ => panic!("no panic report"),
+        };
+        let buffer = app.alloc(length).unwrap();
+        app.allow_readwrite(capsules::panic_report::DRIVER_NUM, 0, buffer, length);
+        app.command(capsules::panic_report::DRIVER_NUM, 2, 0, 0);
+        *app_report.lock().unwrap() = app.read_bytes(buffer, length);
+        app.exit_terminate(0);
+    })]);
+
+    let scheduler = board.cooperative();
+    board.run_until(scheduler, true, || {
+        board.process(0).get_state() == State::Terminated
+    });
+    assert_eq!(*report.lock().unwrap(), PREVIOUS_PANIC.as_bytes());
+    assert_eq!(
+        board.platform.panic_report.reason(),
+        Some(&b"panicked at 'out of grant memory', kernel/src/grant.rs:42:9"[..])
+    );
+}
+
+#[test]
+fn exit_restart_restarts_process() {
+    let starts = Arc::new(AtomicUsize::new(0));
+    let app_starts = starts.clone();
+    let board = TestBoard::boot(vec![program(move |app| {
+        if app_starts.fetch_add(1, Ordering::SeqCst) == 0 {
+            app.exit_restart(0);
+        }
+        app.exit_terminate(0);
+    })]);
+
+    let scheduler = board.cooperative();
+    board.run_until(scheduler, true, || {
+        board.process(0).get_state() == State::Terminated
+    });
+    assert_eq!(starts.load(Ordering::SeqCst), 2);
+    assert_eq!(board.process(0).get_restart_count(), 1);
+}
+
+#[test]
+fn app_watchdog_restarts_hung_processes() {
+    let starts = Arc::new(AtomicUsize::new(0));
+    let app_starts = starts.clone();
+    let spoofed = Arc::new(Mutex::new(None));
+    let app_spoofed = spoofed.clone();
+    let board = TestBoard::boot_with(
+        vec![
+            // Hangs on its first start, then stops the watchdog and exits.
+            program(move |app| {
+                if app_starts.fetch_add(1, Ordering::SeqCst) == 0 {
+                    app.command(capsules::app_watchdog::DRIVER_NUM, 1, 2, 0);
+                    app.command(capsules::app_watchdog::DRIVER_NUM, 2, 0, 0);
+                    loop {
+                        app.yield_wait();
+                    }
+                }
+                assert!(matches!(
+                    app.command(capsules::app_watchdog::DRIVER_NUM, 3, 0, 0),
+                    SyscallReturn::Success
+                ));
+                app.exit_terminate(0);
+            }),
+            // A critical process that always hangs.
+            program(|app| {
+                app.command(capsules::app_watchdog::DRIVER_NUM, 1, 2, 0);
+                loop {
+                    app.yield_wait();
+                }
+            }),
+            // Claims the short ID of the first app, but is not signed.
+            program(move |app| {
+                *app_spoofed.lock().unwrap() = Some(matches!(
+                    app.command(capsules::app_watchdog::DRIVER_NUM, 3, 0, 0),
+                    SyscallReturn::Failure(ErrorCode::NOSUPPORT)
+                ));
+                app.exit_terminate(0);
+            }),
+        ],
+        BootOptions::new()
+            .image(|i, image| match i {
+                2 => image.short_id(0x100),
+                _ => sha256_footer(image.short_id(0x100 + i as u32)),
+            })
+            .checker(sha256_checker(false)),
+    );
+    let alarm = leak(HostAlarm::new(board.chip.interrupt_line(ALARM_INTERRUPT)));
+    board.interrupts.alarm.set(alarm);
+    let watchdog = leak(capsules::app_watchdog::AppWatchdog::new(
+        board.kernel,
+        &*alarm,
+        leak([ShortID::Fixed(NonZeroU32::new(0x101).unwrap())]),
+        Capability,
+    ));
+    alarm.set_alarm_client(watchdog);
+    board.platform.app_watchdog.set(watchdog);
+
+    let scheduler = board.cooperative();
+    board.run_until(scheduler, false, || {
+        board.process(0).get_state() == State::Terminated
+    });
+    assert_eq!(starts.load(Ordering::SeqCst), 2);
+    assert_eq!(*spoofed.lock().unwrap(), Some(true));
+    assert_eq!(board.process(0).get_restart_count(), 1);
+    assert!(board.chip.watchdog().tickles() > 0);
+
+    // The critical process is restarted too, but once it misses its heartbeat
+    // a second time in a row the kernel stops tickling the watchdog.
+    board.run_until(scheduler, false, || !watchdog.healthy());
+    let restarts = board.process(1).get_restart_count();
+    assert!(restarts >= 2);
+    let tickles = board.chip.watchdog().tickles();
+    board.run_until(scheduler, false, || {
+        board.process(1).get_restart_count() > restarts
+    });
+    assert_eq!(board.chip.watchdog().tickles(), tickles);
+}
+
+/// Fault policy set after boot, once the kernel and alarm it needs exist.
+struct LateFaultPolicy(OptionalCell<&'static dyn ProcessFaultPolicy>);
+
+impl ProcessFaultPolicy for LateFaultPolicy {
+    fn action(&self, process: &dyn Process) -> kernel::procs::FaultAction {
+        self.0.map_or(kernel::procs::FaultAction::Stop, |policy| {
+            policy.action(process)
+        })
+    }
+}
+
+#[test]
+fn fault_policies_back_off_and_limit_restart_rate() {
+    let starts = Arc::new(Mutex::new(Vec::new()));
+    let app_starts = starts.clone();
+    let late = leak(LateFaultPolicy(OptionalCell::empty()));
+    let board = TestBoard::boot_with(
+        vec![
+            program(move |_| {
+                app_starts.lock().unwrap().push(Instant::now());
+                panic!("simulated process fault");
+            }),
+            program(|_| panic!("simulated process fault")),
+        ],
+        BootOptions::new().fault_policy(&*late),
+    );
+    let alarm = leak(HostAlarm::new(board.chip.interrupt_line(ALARM_INTERRUPT)));
+    board.interrupts.alarm.set(alarm);
+    let backoff = leak(
+        kernel::procs::BackoffRestartFaultPolicy::<_, NUM_PROCS>::new(board.kernel, &*alarm, 2, 8),
+    );
+    alarm.set_alarm_client(backoff);
+    let rate = leak(kernel::procs::RestartRateFaultPolicy::<_, NUM_PROCS>::new(
+        &*alarm, 2, 10,
+    ));
+    let policies: &'static [(&str, &dyn ProcessFaultPolicy)] = leak([
+        ("app0", &*backoff as &dyn ProcessFaultPolicy),
+        ("app1", &*rate),
+    ]);
+    late.0.set(leak(kernel::procs::PerAppFaultPolicy::new(
+        leak(kernel::procs::StopFaultPolicy {}),
+        policies,
+    )));
+
+    // The second app is restarted twice, then stopped on its third fault
+    // within the window.
+    let scheduler = board.cooperative();
+    board.run_until(scheduler, false, || {
+        board.process(1).get_state() == State::Faulted && starts.lock().unwrap().len() > 4
+    });
+    assert_eq!(board.process(1).get_restart_count(), 2);
+
+    // The first app waits twice as long before each restart, up to 8 ms.
+    let starts = starts.lock().unwrap();
+    for (i, delay_ms) in [2, 4, 8, 8].iter().enumerate() {
+        assert!(starts[i + 1] - starts[i] >= Duration::from_millis(*delay_ms));
+    }
+}
@@ -0,0 +1,112 @@
+//! Tests of freeing grants and returning their memory to processes.
+
+use core::cell::Cell;
+use std::rc::Rc;
+use std::sync::{Arc, Mutex};
+
+use kernel::procs::State;
+use kernel::syscall::SyscallReturn;
+
+use crate::App;
+
+use super::board::*;
+
+/// The highest break the process can move to, which is the kernel memory
+/// break. Leaves the break where it was.
+fn highest_break(app: &App) -> usize {
+    let original = app.app_break();
+    let word = core::mem::size_of::<usize>();
+    let (mut low, mut high) = (original, app.memory_start() + app.memory_len());
+    while high - low > word {
+        let middle = (low + (high - low) / 2) & !(word - 1);
+        match app.brk(middle) {
+            SyscallReturn::Success => low = middle,
+            _ => high = middle,
+        }
+    }
+    app.brk(original);
+    low
+}
+
+#[test]
+fn freed_grants_are_reclaimed() {
+    let breaks = Arc::new(Mutex::new(Vec::new()));
+    let app_breaks = breaks.clone();
+    let board = TestBoard::boot(vec![program(move |app| {
+        let initial = highest_break(app);
+        app.command(TEST_DRIVER, 2, 0, 0);
+        app.command(TEST_DRIVER, 5, 0x1234, 0);
+        let allocated = highest_break(app);
+
+        // Freeing the first grant moves the scratch grant up, and gives
+        // the memory back to the process.
+        assert!(matches!(
+            app.command(TEST_DRIVER, 4, 0, 0),
+            SyscallReturn::Success
+        ));
+        assert!(matches!(
+            app.command(TEST_DRIVER, 6, 0, 0),
+            SyscallReturn::SuccessU32(0x1234)
+        ));
+        let compacted = highest_break(app);
+
+        // Freeing a grant that is not allocated does nothing.
+        assert!(matches!(
+            app.command(TEST_DRIVER, 4, 0, 0),
+            SyscallReturn::Success
+        ));
+        app_breaks
+            .lock()
+            .unwrap()
+            .extend_from_slice(&[initial, allocated, compacted]);
+    })]);
+    let scheduler = board.cooperative();
+    let process = board.process(0);
+    board.run_until(scheduler, true, || {
+        process.get_state() == State::Yielded && !breaks.lock().unwrap().is_empty()
+    });
+    let breaks = breaks.lock().unwrap();
+    let (initial, allocated, compacted) = (breaks[0], breaks[1], breaks[2]);
+    assert!(allocated < compacted && compacted < initial);
+    assert_eq!(process.kernel_memory_break() as usize, compacted);
+
+    // Only the scratch grant is left.
+    let mut map = String::new();
+    process.print_memory_map(&mut map);
+    assert!(!map.contains(&format!("{:#07x}", TEST_DRIVER)));
+    assert!(map.contains(&format!("{:#07x}", TEST_DRIVER + 1)));
+}
+
+#[test]
+fn alarm_grant_freed_after_unsubscribe() {
+    let breaks = Arc::new(Mutex::new(Vec::new()));
+    let app_breaks = breaks.clone();
+    let board = TestBoard::boot(vec![program(move |app| {
+        let initial = highest_break(app);
+        let fired = Rc::new(Cell::new(false));
+        let app_fired = fired.clone();
+        app.subscribe(capsules::alarm::DRIVER_NUM, 0, move |_, _, _, _| {
+            app_fired.set(true)
+        });
+        app.command(capsules::alarm::DRIVER_NUM, 5, 100, 0);
+        app.yield_for(|| fired.get());
+        let allocated = highest_break(app);
+
+        // The alarm fired, so unsubscribing leaves nothing in the grant.
+        app.unsubscribe(capsules::alarm::DRIVER_NUM, 0);
+        let freed = highest_break(app);
+        app_breaks
+            .lock()
+            .unwrap()
+            .extend_from_slice(&[initial, allocated, freed]);
+    })]);
+    let scheduler = board.cooperative();
+    let process = board.process(0);
+    board.run_until(scheduler, true, || {
+        process.get_state() == State::Yielded && !breaks.lock().unwrap().is_empty()
+    });
+    let breaks = breaks.lock().unwrap();
+    assert!(breaks[1] < breaks[0]);
+    assert_eq!(breaks[2], breaks[0]);
+    assert_eq!(process.kernel_memory_break() as usize, breaks[0]);
+}
@@ -0,0 +1,363 @@
+//! Tests of IPC services, messages and priority inheritance.
+
+use core::cell::Cell;
+use core::num::NonZeroU32;
+use std::rc::Rc;
+use std::sync::atomic::{AtomicUsize, Ordering};
+use std::sync::{Arc, Mutex};
+use std::time::Duration;
+
+use kernel::ipc::ServiceBinding;
+use kernel::procs::State;
+use kernel::syscall::SyscallReturn;
+use kernel::{ErrorCode, ShortID, TbfPrioritySched};
+
+use crate::App;
+
+use super::board::*;
+
+/// Find the IPC service of the app named `name`.
+fn ipc_discover(app: &App, name: &str) -> usize {
+    let buffer = app.alloc(name.len()).unwrap();
+    app.write_bytes(buffer, name.as_bytes());
+    app.allow_readonly(kernel::ipc::DRIVER_NUM, 0, buffer, name.len());
+    match app.command(kernel::ipc::DRIVER_NUM, 1, 0, 0) {
+        SyscallReturn::SuccessU32(service) => service as usize,
+        _ => panic!("no IPC service named {}", name),
+    }
+}
+
+#[test]
+fn ipc_service_inherits_client_priority() {
+    let log = Arc::new(Mutex::new(Vec::new()));
+    let (client_log, hog_log, service_log) = (log.clone(), log.clone(), log.clone());
+    let programs = vec![
+        // High priority client that blocks on the service.
+        program(move |app| {
+            let service = ipc_discover(app, "app2");
+            let notifications = Rc::new(Cell::new(0));
+            let count = notifications.clone();
+            app.subscribe(kernel::ipc::DRIVER_NUM, 3, move |_, _, _, _| {
+                count.set(count.get() + 1)
+            });
+            // Wait for the service to be ready, then make a request.
+            app.yield_for(|| notifications.get() == 1);
+            app.command(kernel::ipc::DRIVER_NUM, 2, service, 0);
+            app.yield_for(|| notifications.get() == 2);
+            client_log.lock().unwrap().push("reply");
+            app.exit_terminate(0);
+        }),
+        // Medium priority process that keeps the CPU busy once started.
+        program(move |app| {
+            ipc_discover(app, "app2");
+            let started = Rc::new(Cell::new(false));
+            let start = started.clone();
+            app.subscribe(kernel::ipc::DRIVER_NUM, 3, move |_, _, _, _| {
+                start.set(true)
+            });
+            app.yield_for(|| started.get());
+            for _ in 0..20 {
+                std::thread::sleep(Duration::from_millis(1));
+                app.yield_no_wait();
+            }
+            hog_log.lock().unwrap().push("hog done");
+            app.exit_terminate(0);
+        }),
+        // Low priority service, which starts the busy process while it
+        // handles the request.
+        program(move |app| {
+            let client = ipc_discover(app, "app0");
+            let hog = ipc_discover(app, "app1");
+            let request = Rc::new(Cell::new(0));
+            let from = request.clone();
+            app.subscribe(kernel::ipc::DRIVER_NUM, 0, move |_, client, _, _| {
+                from.set(client)
+            });
+            app.command(kernel::ipc::DRIVER_NUM, 3, client, 0);
+            app.yield_for(|| request.get() != 0);
+            app.command(kernel::ipc::DRIVER_NUM, 3, hog, 0);
+            for _ in 0..2 {
+                std::thread::sleep(Duration::from_millis(1));
+                app.yield_no_wait();
+            }
+            service_log.lock().unwrap().push("service done");
+            app.command(kernel::ipc::DRIVER_NUM, 3, request.get(), 0);
+            app.exit_terminate(0);
+        }),
+    ];
+    let board = TestBoard::boot_with(
+        programs,
+        BootOptions::new().image(|i, image| match i {
+            0 => image.priority(0),
+            2 => image.priority(10),
+            _ => image,
+        }),
+    );
+    let scheduler: &'static TbfPrioritySched<NUM_PROCS> =
+        leak(TbfPrioritySched::new(board.kernel, 5, Some(1)));
+    board.platform.ipc.set_observer(scheduler);
+
+    // The client asks for a higher priority than the board allows, and the
+    // busy process has no Priority TLV.
+    assert_eq!(scheduler.base_priority(board.process(0)), 1);
+    assert_eq!(scheduler.base_priority(board.process(1)), 5);
+    assert_eq!(scheduler.base_priority(board.process(2)), 10);
+
+    board.run_until(scheduler, true, || {
+        (0..3).all(|i| board.process(i).get_state() == State::Terminated)
+    });
+
+    // Without inheritance the busy process would preempt the service and
+    // finish before the client gets its reply.
+    assert_eq!(
+        *log.lock().unwrap(),
+        vec!["service done", "reply", "hog done"]
+    );
+}
+
+#[test]
+fn ipc_priority_returned_when_client_restarts() {
+    let starts = Arc::new(AtomicUsize::new(0));
+    let app_starts = starts.clone();
+    let programs = vec![
+        // High priority client that restarts without waiting for its reply,
+        // and then only waits.
+        program(move |app| {
+            if app_starts.fetch_add(1, Ordering::SeqCst) == 0 {
+                let service = ipc_discover(app, "app1");
+                app.command(kernel::ipc::DRIVER_NUM, 2, service, 0);
+                app.exit_restart(0);
+            }
+            loop {
+                app.yield_wait();
+            }
+        }),
+        // Low priority service that never answers.
+        program(move |app| loop {
+            app.yield_wait();
+        }),
+    ];
+    let board = TestBoard::boot_with(
+        programs,
+        BootOptions::new().image(|i, image| match i {
+            0 => image.priority(1),
+            _ => image.priority(10),
+        }),
+    );
+    let scheduler: &'static TbfPrioritySched<NUM_PROCS> =
+        leak(TbfPrioritySched::new(board.kernel, 5, None));
+    board.platform.ipc.set_observer(scheduler);
+
+    board.run_until(scheduler, true, || {
+        starts.load(Ordering::SeqCst) == 2 && board.process(0).get_state() == State::Yielded
+    });
+    // The request of the first run of the client is gone with it.
+    assert_eq!(scheduler.priority(board.process(1)), 10);
+}
+
+/// Allow `message` as the IPC send buffer, and return its address.
+fn ipc_allow_message(app: &App, message: &[u8]) -> usize {
+    let buffer = app.alloc(message.len()).unwrap();
+    app.write_bytes(buffer, message);
+    app.allow_readonly(kernel::ipc::DRIVER_NUM, 1, buffer, message.len());
+    buffer
+}
+
+#[test]
+fn ipc_messages_are_copied_to_mailboxes() {
+    let log = Arc::new(Mutex::new(Vec::new()));
+    let (client_log, service_log) = (log.clone(), log.clone());
+    let programs = vec![
+        program(move |app| {
+            let service = ipc_discover(app, "app1");
+            app.subscribe(kernel::ipc::DRIVER_NUM, service, |_, _, _, _| {});
+            let receive = app.alloc(16).unwrap();
+            app.allow_readwrite(kernel::ipc::DRIVER_NUM, 0, receive, 16);
+
+            let message = ipc_allow_message(app, b"ping");
+            assert!(matches!(
+                app.command(kernel::ipc::DRIVER_NUM, 4, service, 5),
+                SyscallReturn::Failure(ErrorCode::SIZE)
+            ));
+            assert!(matches!(
+                app.command(kernel::ipc::DRIVER_NUM, 4, service, 4),
+                SyscallReturn::Success
+            ));
+            // Changing the send buffer does not change the sent message.
+            app.write_bytes(message, b"PING");
+
+            match app.yield_wait_for(kernel::ipc::DRIVER_NUM, service) {
+                SyscallReturn::SuccessU32U32U32(sender, 4, tag)
+                    if sender as usize == service
+                        && tag == kernel::ipc::MESSAGE_UPCALL_TAG as u32 => {}
+                rval => panic!("no reply: {:?}", rval),
+            }
+            match app.command(kernel::ipc::DRIVER_NUM, 6, 0, 0) {
+                SyscallReturn::SuccessU32U32U32(sender, 4, 1) if sender as usize == service => {}
+                rval => panic!("receive failed: {:?}", rval),
+            }
+            client_log
+                .lock()
+                .unwrap()
+                .push(String::from_utf8(app.read_bytes(receive, 4)).unwrap());
+            assert!(matches!(
+                app.command(kernel::ipc::DRIVER_NUM, 6, 0, 0),
+                SyscallReturn::Failure(ErrorCode::FAIL)
+            ));
+            app.exit_terminate(0);
+        }),
+        program(move |app| {
+            let service = ipc_discover(app, "app1");
+            app.subscribe(kernel::ipc::DRIVER_NUM, 0, |_, _, _, _| {});
+            let mut receive = app.alloc(2).unwrap();
+            app.allow_readwrite(kernel::ipc::DRIVER_NUM, 0, receive, 2);
+
+            // The request may have arrived before the upcall was subscribed.
+            let client = loop {
+                match app.command(kernel::ipc::DRIVER_NUM, 6, 0, 0) {
+                    SyscallReturn::SuccessU32U32U32(client, 4, 0) => break client as usize,
+                    SyscallReturn::Failure(ErrorCode::FAIL) => {
+                        app.yield_wait_for(kernel::ipc::DRIVER_NUM, 0);
+                    }
+                    SyscallReturn::FailureU32(ErrorCode::SIZE, 4) => {
+                        receive = app.alloc(16).unwrap();
+                        app.allow_readwrite(kernel::ipc::DRIVER_NUM, 0, receive, 16);
+                    }
+                    rval => panic!("receive failed: {:?}", rval),
+                }
+            };
+            service_log
+                .lock()
+                .unwrap()
+                .push(String::from_utf8(app.read_bytes(receive, 4)).unwrap());
+
+            ipc_allow_message(app, b"pong");
+            assert!(matches!(
+                app.command(kernel::ipc::DRIVER_NUM, 5, client, 4),
+                SyscallReturn::Success
+            ));
+            // Messages queue until the sender used up its share of the
+            // mailbox.
+            for _ in 0..kernel::ipc::MAILBOX_LEN_PER_SENDER {
+                assert!(matches!(
+                    app.command(kernel::ipc::DRIVER_NUM, 4, service, 4),
+                    SyscallReturn::Success
+                ));
+            }
+            assert!(matches!(
+                app.command(kernel::ipc::DRIVER_NUM, 4, service, 4),
+                SyscallReturn::Failure(ErrorCode::BUSY)
+            ));
+            app.exit_terminate(0);
+        }),
+    ];
+    let board = TestBoard::boot(programs);
+
+    let scheduler = board.cooperative();
+    board.run_until(scheduler, true, || {
+        (0..2).all(|i| board.process(i).get_state() == State::Terminated)
+    });
+    assert_eq!(*log.lock().unwrap(), ["ping", "pong"]);
+}
+
+/// Put `name` in the IPC search buffer.
+fn ipc_allow_name(app: &App, name: &str) {
+    let buffer = app.alloc(name.len()).unwrap();
+    app.write_bytes(buffer, name.as_bytes());
+    app.allow_readonly(kernel::ipc::DRIVER_NUM, 0, buffer, name.len());
+}
+
+#[test]
+fn ipc_registry_enforces_access_lists() {
+    let log = Arc::new(Mutex::new(Vec::new()));
+    let (service_log, client_log) = (log.clone(), log.clone());
+    let programs = vec![
+        // Service that only app1 may connect to.
+        program(move |app| {
+            ipc_allow_name(app, "echo");
+            assert!(matches!(
+                app.command(kernel::ipc::DRIVER_NUM, 7, 2, 0),
+                SyscallReturn::Success
+            ));
+            app.subscribe(kernel::ipc::DRIVER_NUM, 0, |_, _, _, _| {});
+            app.yield_wait_for(kernel::ipc::DRIVER_NUM, 0);
+            service_log.lock().unwrap().push("request");
+        }),
+        // Allowed client.
+        program(move |app| {
+            ipc_allow_name(app, "echo");
+            let service = match app.command(kernel::ipc::DRIVER_NUM, 8, 1, 0) {
+                SyscallReturn::SuccessU32U32(service, 2) => service as usize,
+                rval => panic!("lookup failed: {:?}", rval),
+            };
+            assert!(matches!(
+                app.command(kernel::ipc::DRIVER_NUM, 8, 3, 0),
+                SyscallReturn::FailureU32(ErrorCode::NOSUPPORT, 2)
+            ));
+            ipc_allow_message(app, b"hi");
+            assert!(matches!(
+                app.command(kernel::ipc::DRIVER_NUM, 4, service, 2),
+                SyscallReturn::Success
+            ));
+            client_log.lock().unwrap().push("sent");
+            app.exit_terminate(0);
+        }),
+        // Client that claims the short ID of app1, but is not signed.
+        program(move |app| {
+            ipc_allow_name(app, "echo");
+            assert!(matches!(
+                app.command(kernel::ipc::DRIVER_NUM, 8, 0, 0),
+                SyscallReturn::Failure(ErrorCode::NOACK)
+            ));
+            // The board binds the name to another app.
+            assert!(matches!(
+                app.command(kernel::ipc::DRIVER_NUM, 7, 1, 0),
+                SyscallReturn::Failure(ErrorCode::RESERVE)
+            ));
+            // Guessing the descriptor does not get around the access list.
+            ipc_allow_name(app, "app0");
+            assert!(matches!(
+                app.command(kernel::ipc::DRIVER_NUM, 1, 0, 0),
+                SyscallReturn::Failure(ErrorCode::NOACK)
+            ));
+            // The first process loaded has identifier 0.
+            let service = 1;
+            assert!(matches!(
+                app.command(kernel::ipc::DRIVER_NUM, 2, service, 0),
+                SyscallReturn::Failure(ErrorCode::NOACK)
+            ));
+            ipc_allow_message(app, b"hi");
+            assert!(matches!(
+                app.command(kernel::ipc::DRIVER_NUM, 4, service, 2),
+                SyscallReturn::Failure(ErrorCode::NOACK)
+            ));
+            ipc_allow_name(app, "none");
+            assert!(matches!(
+                app.command(kernel::ipc::DRIVER_NUM, 8, 0, 0),
+                SyscallReturn::Failure(ErrorCode::NODEVICE)
+            ));
+            app.exit_terminate(0);
+        }),
+    ];
+    let board = TestBoard::boot_with(
+        programs,
+        BootOptions::new()
+            .image(|i, image| match i {
+                0 => sha256_footer(image.short_id(0x10).ipc_access(&[0x11])),
+                1 => sha256_footer(image.short_id(0x11)),
+                _ => image.short_id(0x11),
+            })
+            .checker(sha256_checker(false)),
+    );
+    board.platform.ipc.set_services(leak([ServiceBinding {
+        name: "echo",
+        short_id: ShortID::Fixed(NonZeroU32::new(0x10).unwrap()),
+    }]));
+
+    let scheduler = board.cooperative();
+    board.run_until(scheduler, true, || {
+        log.lock().unwrap().len() == 2
+            && (1..3).all(|i| board.process(i).get_state() == State::Terminated)
+    });
+    assert_eq!(*log.lock().unwrap(), ["sent", "request"]);
+}
@@ -0,0 +1,16 @@
+//! Tests that boot the kernel on the host chip and run simulated processes.
+//!
+//! Each module tests one feature on the board from `board`.
+
+mod app_loader;
+mod board;
+mod credentials;
+mod deferred_call;
+mod faults;
+mod grants;
+mod ipc;
+mod power;
+mod scheduling;
+mod short_ids;
+mod storage;
+mod syscalls;
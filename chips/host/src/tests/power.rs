@@ -0,0 +1,184 @@
+//! Tests of alarms, sleep states and shared clocks.
+
+use core::cell::Cell;
+
+use kernel::hil::time::{Alarm, AlarmClient, Ticks, Time};
+use kernel::sleep::{ClockRequirement, DeadlineSleepPolicy, SleepPolicy};
+
+use crate::alarm::HostAlarm;
+
+use super::board::*;
+
+struct AlarmCount(Cell<usize>);
+
+impl AlarmClient for AlarmCount {
+    fn alarm(&self) {
+        self.0.set(self.0.get() + 1);
+    }
+}
+
+#[test]
+fn alarm_wakes_sleeping_kernel() {
+    let board = TestBoard::boot(Vec::new());
+    let alarm = leak(HostAlarm::new(board.chip.interrupt_line(ALARM_INTERRUPT)));
+    board.interrupts.alarm.set(alarm);
+    let client = leak(AlarmCount(Cell::new(0)));
+    alarm.set_alarm_client(client);
+
+    let start = alarm.now();
+    alarm.set_alarm(start, 5000.into());
+
+    // With no processes the scheduler puts the chip to sleep, so this only
+    // makes progress if the alarm interrupt wakes the kernel.
+    let scheduler = board.cooperative();
+    board.run_until(scheduler, false, || client.0.get() == 1);
+    assert!(alarm.now().into_u32().wrapping_sub(start.into_u32()) >= 5000);
+    assert!(!alarm.is_armed());
+}
+
+/// Clock of a peripheral that tests turn on and off.
+#[derive(Default)]
+struct TestClock(Cell<bool>);
+
+impl kernel::ClockInterface for TestClock {
+    fn is_enabled(&self) -> bool {
+        self.0.get()
+    }
+    fn enable(&self) {
+        self.0.set(true);
+    }
+    fn disable(&self) {
+        self.0.set(false);
+    }
+}
+
+#[test]
+fn sleep_policy_picks_deepest_state_in_time_for_alarm() {
+    let board = TestBoard::boot(Vec::new());
+    let alarm = leak(HostAlarm::new(board.chip.interrupt_line(ALARM_INTERRUPT)));
+    board.interrupts.alarm.set(alarm);
+    let client = leak(AlarmCount(Cell::new(0)));
+    alarm.set_alarm_client(client);
+    // The peripheral needs its clock, which "standby" stops.
+    let clock = leak(TestClock::default());
+    let requirements = leak([ClockRequirement::new(&*clock, 1)]);
+    let policy = leak(DeadlineSleepPolicy::new(&*alarm, &*requirements));
+    board.platform.sleep_policy.set(policy);
+    let states = &crate::chip::SLEEP_STATES;
+
+    // With no alarm the kernel can sleep as deeply as the chip allows.
+    assert_eq!(policy.select_sleep_state(states), Some(2));
+    clock.0.set(true);
+    assert_eq!(policy.select_sleep_state(states), Some(1));
+    clock.0.set(false);
+
+    // Waking up from "standby" takes too long for an alarm 2 ms away.
+    alarm.set_alarm(alarm.now(), 2000.into());
+    assert_eq!(policy.select_sleep_state(states), Some(1));
+    alarm.disarm().unwrap();
+
+    // The kernel sleeps in "standby" until an alarm 20 ms away.
+    alarm.set_alarm(alarm.now(), 20000.into());
+    let scheduler = board.cooperative();
+    board.run_until(scheduler, false, || client.0.get() == 1);
+    assert!(board.chip.sleep_count(2) >= 1);
+    assert_eq!(board.chip.sleep_count(0), 0);
+}
+
+/// I2C controller that holds on to the buffer of the transfer in progress.
+struct TestI2C {
+    buffer: kernel::common::cells::TakeCell<'static, [u8]>,
+}
+
+impl kernel::hil::i2c::I2CMaster for TestI2C {
+    fn set_master_client(&self, _: &'static dyn kernel::hil::i2c::I2CHwMasterClient) {}
+    fn enable(&self) {}
+    fn disable(&self) {}
+    fn write_read(
+        &self,
+        _: u8,
+        data: &'static mut [u8],
+        _: u8,
+        _: u8,
+    ) -> Result<(), (kernel::hil::i2c::Error, &'static mut [u8])> {
+        self.buffer.replace(data);
+        Ok(())
+    }
+    fn write(
+        &self,
+        _: u8,
+        data: &'static mut [u8],
+        _: u8,
+    ) -> Result<(), (kernel::hil::i2c::Error, &'static mut [u8])> {
+        self.buffer.replace(data);
+        Ok(())
+    }
+    fn read(
+        &self,
+        _: u8,
+        buffer: &'static mut [u8],
+        _: u8,
+    ) -> Result<(), (kernel::hil::i2c::Error, &'static mut [u8])> {
+        self.buffer.replace(buffer);
+        Ok(())
+    }
+}
+
+#[derive(Default)]
+struct I2CCompletions(Cell<usize>);
+
+impl kernel::hil::i2c::I2CClient for I2CCompletions {
+    fn command_complete(&self, _: &'static mut [u8], _: Result<(), kernel::hil::i2c::Error>) {
+        self.0.set(self.0.get() + 1);
+    }
+}
+
+#[test]
+fn shared_clock_gated_when_last_user_releases() {
+    use kernel::hil::i2c::{I2CDevice, I2CHwMasterClient};
+    use kernel::power::{PowerRequest, PowerResource, SharedClock};
+
+    let domain_clock = leak(TestClock::default());
+    let domain = leak(SharedClock::new(&*domain_clock, None));
+    let bus_clock = leak(TestClock::default());
+    let bus = leak(SharedClock::new(&*bus_clock, Some(&*domain)));
+
+    let i2c = leak(TestI2C {
+        buffer: kernel::common::cells::TakeCell::empty(),
+    });
+    let mux = leak(capsules::virtual_i2c::MuxI2C::new(&*i2c, None));
+    mux.set_power_resource(&*bus);
+    let device = leak(capsules::virtual_i2c::I2CDevice::new(mux, 0x40));
+    let completions = leak(I2CCompletions::default());
+    device.set_client(&*completions);
+
+    // Another capsule uses the same clock for a while.
+    let other = PowerRequest::new();
+    other.set_resource(&*bus);
+    other.acquire();
+    other.acquire();
+    assert!(bus_clock.0.get() && domain_clock.0.get());
+    assert_eq!(bus.users(), 1);
+
+    // The mux holds the clock while its transfer is in flight, so the other
+    // capsule cannot gate it.
+    assert!(device.write(leak([0; 4]), 4).is_ok());
+    assert_eq!(bus.users(), 2);
+    other.relinquish();
+    assert!(bus_clock.0.get());
+    assert_eq!(domain.users(), 1);
+
+    // Once the transfer completes nothing needs the bus or its domain.
+    mux.command_complete(i2c.buffer.take().unwrap(), Ok(()));
+    assert_eq!(completions.0.get(), 1);
+    assert!(!bus_clock.0.get() && !domain_clock.0.get());
+    assert_eq!(bus.users(), 0);
+
+    // Releasing again does not disable the clock under a new user.
+    bus.request();
+    other.relinquish();
+    bus.release();
+    bus.release();
+    assert_eq!(bus.users(), 0);
+    assert!(!bus_clock.0.get());
+}
@@ -0,0 +1,233 @@
+//! Tests of timeslices, CPU time accounting, tracing and the EDF scheduler.
+
+use core::cell::RefCell;
+use std::sync::{Arc, Mutex};
+use std::time::Duration;
+
+use kernel::capabilities;
+use kernel::create_capability;
+use kernel::hil::time::Alarm;
+use kernel::procs::State;
+use kernel::syscall::SyscallClass;
+use kernel::trace::{TraceEvent, TraceEventKind, Tracer};
+use kernel::{EDFProcessNode, EDFSched, OverrunClient, StoppedExecutingReason};
+use kernel::{ErrorCode, ProcessId};
+
+use crate::alarm::HostAlarm;
+
+use super::board::*;
+
+#[test]
+fn timeslice_expiration_preempts_at_syscalls() {
+    let log = Arc::new(Mutex::new(Vec::new()));
+    let programs = (0..2)
+        .map(|id| {
+            let app_log = log.clone();
+            program(move |app| {
+                for _ in 0..20 {
+                    app_log.lock().unwrap().push(id);
+                    std::thread::sleep(Duration::from_millis(2));
+                    app.yield_no_wait();
+                }
+                app.exit_terminate(0);
+            })
+        })
+        .collect();
+    let board = TestBoard::boot(programs);
+
+    let scheduler = board.round_robin();
+    board.run_until(scheduler, true, || {
+        board.process(0).get_state() == State::Terminated
+            && board.process(1).get_state() == State::Terminated
+    });
+
+    // The round robin timeslice is 10 ms, so each process must have been
+    // preempted before finishing its 40 ms of work.
+    let log = log.lock().unwrap();
+    let switches = log.windows(2).filter(|pair| pair[0] != pair[1]).count();
+    assert!(switches >= 2, "processes did not interleave: {:?}", *log);
+}
+
+#[test]
+fn cpu_time_charged_to_processes() {
+    let programs = vec![
+        program(|app| {
+            for _ in 0..20 {
+                std::thread::sleep(Duration::from_millis(1));
+                app.yield_no_wait();
+            }
+            app.exit_terminate(0);
+        }),
+        program(|app| {
+            app.subscribe(TEST_DRIVER, 0, |_, _, _, _| {});
+            for _ in 0..5 {
+                app.command(TEST_DRIVER, 1, 0, 0);
+                app.yield_wait();
+            }
+            app.exit_terminate(0);
+        }),
+    ];
+    let board = TestBoard::boot(programs);
+
+    let scheduler = board.round_robin();
+    board.run_until(scheduler, true, || {
+        board.process(0).get_state() == State::Terminated
+            && board.process(1).get_state() == State::Terminated
+    });
+
+    let capability = Capability;
+    let info = kernel::introspection::KernelInfo::new(board.kernel);
+    let (busy, waiting) = (board.process(0).processid(), board.process(1).processid());
+    let busy_us = info.app_cpu_time_us(busy, &capability);
+    assert!(busy_us >= 15_000, "only {} us charged", busy_us);
+    assert!(info.app_kernel_time_us(busy, &capability) < busy_us);
+    assert_eq!(
+        info.cpu_time_us(&capability),
+        busy_us + info.app_cpu_time_us(waiting, &capability)
+    );
+    assert_eq!(info.number_app_wakeups(busy, &capability), 0);
+    assert_eq!(info.number_app_wakeups(waiting, &capability), 5);
+}
+
+#[test]
+fn tracer_records_events_of_selected_process() {
+    let programs = vec![
+        program(|app| {
+            app.subscribe(TEST_DRIVER, 0, |_, _, _, _| {});
+            app.command(TEST_DRIVER, 1, 7, 9);
+            app.yield_wait();
+            app.exit_terminate(0);
+        }),
+        program(|app| {
+            app.command(TEST_DRIVER, 0, 0, 0);
+            app.exit_terminate(0);
+        }),
+    ];
+    let board = TestBoard::boot(programs);
+    let tracer = leak(Tracer::new(leak([TraceEvent::EMPTY; 64])));
+    let traced = board.process(0).processid();
+    tracer.set_process_filter(Some(traced));
+    tracer.enable();
+    board.kernel.set_tracer(
+        tracer,
+        &create_capability!(capabilities::MainLoopCapability),
+    );
+
+    let scheduler = board.round_robin();
+    board.run_until(scheduler, true, || {
+        board.process(0).get_state() == State::Terminated
+            && board.process(1).get_state() == State::Terminated
+    });
+
+    let mut events = Vec::new();
+    while let Some(event) = tracer.pop() {
+        events.push(event);
+    }
+    assert_eq!(tracer.dropped(), 0);
+    assert!(events.iter().all(|e| e.process == traced.id() as u32));
+    assert!(events
+        .windows(2)
+        .all(|pair| pair[0].time_us <= pair[1].time_us));
+    let has = |kind, detail, driver, arg0, arg1: Option<u32>| {
+        events.iter().any(|e| {
+            e.kind == kind
+                && e.detail == detail
+                && e.driver == driver
+                && e.arg0 == arg0
+                && arg1.map_or(true, |arg1| e.arg1 == arg1)
+        })
+    };
+    let test_driver = TEST_DRIVER as u32;
+    let command = SyscallClass::Command as u8;
+    assert!(has(
+        TraceEventKind::Syscall,
+        command,
+        test_driver,
+        1,
+        Some(7)
+    ));
+    assert!(has(TraceEventKind::Upcall, 0, test_driver, 0, Some(7)));
+    assert!(has(TraceEventKind::Upcall, 1, kernel::trace::NONE, 0, None));
+    assert!(!has(TraceEventKind::Syscall, command, test_driver, 0, None));
+    assert!(events.iter().any(|e| e.kind == TraceEventKind::Scheduled));
+    assert!(events
+        .iter()
+        .any(|e| e.kind == TraceEventKind::ContextSwitch));
+
+    let bytes = events[0].to_bytes();
+    assert_eq!(bytes[4], events[0].kind as u8);
+    assert_eq!(bytes[8..12], (traced.id() as u32).to_le_bytes());
+}
+
+/// Records the overruns reported by a real-time scheduler.
+#[derive(Default)]
+struct OverrunLog(RefCell<Vec<(ProcessId, StoppedExecutingReason)>>);
+
+impl OverrunClient for OverrunLog {
+    fn overrun(&self, process: ProcessId, reason: StoppedExecutingReason) {
+        self.0.borrow_mut().push((process, reason));
+    }
+}
+
+#[test]
+fn edf_throttles_real_time_process_to_budget() {
+    let programs = (0..3)
+        .map(|_| {
+            program(|app| {
+                for _ in 0..30 {
+                    std::thread::sleep(Duration::from_millis(1));
+                    app.yield_no_wait();
+                }
+                app.exit_terminate(0);
+            })
+        })
+        .collect();
+    let board = TestBoard::boot_with(
+        programs,
+        BootOptions::new().image(|i, image| match i {
+            0 => image.real_time(20_000, 4_000),
+            2 => image.real_time(10_000, 9_000),
+            _ => image,
+        }),
+    );
+    let alarm = leak(HostAlarm::new(board.chip.interrupt_line(ALARM_INTERRUPT)));
+    board.interrupts.alarm.set(alarm);
+    let scheduler = leak(EDFSched::new(alarm, 100));
+    alarm.set_alarm_client(scheduler);
+    let overruns = leak(OverrunLog::default());
+    scheduler.set_overrun_client(overruns);
+
+    let nodes: Vec<&'static EDFProcessNode> = board
+        .processes
+        .iter()
+        .map(|process| &*leak(EDFProcessNode::new(process)))
+        .collect();
+    assert!(scheduler.add_process(nodes[0]).is_ok());
+    assert!(scheduler.add_process(nodes[1]).is_ok());
+    // The third process would need 90% of the CPU on top of the 20% of the
+    // first one, so it is not admitted.
+    assert!(matches!(
+        scheduler.add_process(nodes[2]),
+        Err(ErrorCode::SIZE)
+    ));
+    assert_eq!(scheduler.utilization(), 200_000);
+
+    board.run_until(scheduler, false, || {
+        board.process(0).get_state() == State::Terminated
+            && board.process(1).get_state() == State::Terminated
+    });
+
+    // The real-time process needs 30 ms of CPU time but only gets 4 ms every
+    // 20 ms, so it overruns its budget, and the best-effort process runs in
+    // between.
+    let real_time = board.process(0).processid();
+    assert!(nodes[0].overrun_count() >= 2);
+    assert!(overruns
+        .0
+        .borrow()
+        .iter()
+        .all(|(process, reason)| *process == real_time
+            && *reason == StoppedExecutingReason::BudgetOverrun));
+    assert_eq!(overruns.0.borrow().len(), nodes[0].overrun_count());
+    assert_eq!(board.process(2).get_state(), State::Unstarted);
+}
@@ -0,0 +1,108 @@
+//! Tests of short IDs assigned to apps.
+
+use core::num::NonZeroU32;
+use std::sync::atomic::{AtomicUsize, Ordering};
+use std::sync::Arc;
+
+use kernel::procs::ShortIdFromTbf;
+use kernel::ShortID;
+
+use super::board::*;
+
+#[test]
+fn short_ids_identify_apps() {
+    let starts = Arc::new(AtomicUsize::new(0));
+    let app_starts = starts.clone();
+    let board = TestBoard::boot_with(
+        vec![
+            program(move |app| {
+                if app_starts.fetch_add(1, Ordering::SeqCst) == 0 {
+                    app.exit_restart(0);
+                }
+                app.exit_terminate(0);
+            }),
+            program(|app| app.exit_terminate(0)),
+            program(|app| app.exit_terminate(0)),
+        ],
+        BootOptions::new()
+            .image(|i, image| match i {
+                0 => image.short_id(0x1234),
+                // Same package name as the second app.
+                2 => image.package_name("app1"),
+                _ => image,
+            })
+            .checker(unchecked())
+            .id_policy(UNVERIFIED_ID_POLICY),
+    );
+
+    let short_id = |i| board.process(i).processid().short_app_id();
+    assert_eq!(
+        short_id(0),
+        ShortID::Fixed(NonZeroU32::new(0x1234).unwrap())
+    );
+    // Derived from the package name, so the same on every boot.
+    let derived = short_id(1);
+    assert!(matches!(derived, ShortID::Fixed(_)));
+    assert_eq!(
+        TestBoard::boot_with(
+            (0..2)
+                .map(|_| program(|app| app.exit_terminate(0)))
+                .collect(),
+            BootOptions::new()
+                .checker(unchecked())
+                .id_policy(UNVERIFIED_ID_POLICY)
+        )
+        .process(1)
+        .short_app_id(),
+        derived
+    );
+    // Two processes never share a short ID, the app that claims a short ID
+    // in use is not loaded.
+    assert!(board.processes[2].get().is_none());
+
+    // Restarting changes the process identifier but not the short ID.
+    let processid = board.process(0).processid();
+    let scheduler = board.cooperative();
+    board.run_until(scheduler, true, || starts.load(Ordering::SeqCst) == 2);
+    assert_ne!(board.process(0).processid(), processid);
+    assert_eq!(
+        short_id(0),
+        ShortID::Fixed(NonZeroU32::new(0x1234).unwrap())
+    );
+    assert_eq!(processid.short_app_id(), ShortID::LocallyUnique);
+}
+
+#[test]
+fn unverified_apps_have_no_short_id_by_default() {
+    let board = TestBoard::boot_with(
+        vec![program(|app| app.exit_terminate(0))],
+        BootOptions::new().image(|_, image| image.short_id(0x1234)),
+    );
+    assert_eq!(board.process(0).short_app_id(), ShortID::LocallyUnique);
+}
+
+#[test]
+fn short_ids_require_verified_credentials() {
+    let board = TestBoard::boot_with(
+        vec![
+            program(|app| app.exit_terminate(0)),
+            program(|app| app.exit_terminate(0)),
+        ],
+        BootOptions::new()
+            .image(|i, image| {
+                let image = image.short_id(0x100 + i as u32);
+                if i == 0 {
+                    sha256_footer(image)
+                } else {
+                    image
+                }
+            })
+            .checker(sha256_checker(false))
+            .id_policy(leak(ShortIdFromTbf::new(true))),
+    );
+    assert_eq!(
+        board.process(0).short_app_id(),
+        ShortID::Fixed(NonZeroU32::new(0x100).unwrap())
+    );
+    assert_eq!(board.process(1).short_app_id(), ShortID::LocallyUnique);
+}
@@ -0,0 +1,236 @@
+//! Tests of nonvolatile storage permissions and the key-value store.
+
+use core::cell::Cell;
+use std::rc::Rc;
+use std::sync::atomic::{AtomicUsize, Ordering};
+use std::sync::{Arc, Mutex};
+
+use kernel::syscall::SyscallReturn;
+use kernel::ErrorCode;
+
+use crate::App;
+
+use super::board::*;
+
+/// Select the app region of `storage_id` in nonvolatile storage, or the app's
+/// own region for `0`.
+fn storage_select(app: &App, storage_id: u32) {
+    assert!(matches!(
+        app.command(STORAGE, 4, storage_id as usize, 0),
+        SyscallReturn::Success
+    ));
+}
+
+/// Write `data` to nonvolatile storage at `offset` and wait for the write to
+/// finish, returning the number of bytes written.
+fn storage_write(app: &App, offset: usize, data: &[u8]) -> Result<usize, ErrorCode> {
+    let buffer = app.alloc(data.len()).unwrap();
+    app.write_bytes(buffer, data);
+    app.allow_readonly(STORAGE, 0, buffer, data.len());
+    storage_wait(app, 1, || app.command(STORAGE, 3, offset, data.len()))
+}
+
+/// Read `len` bytes of nonvolatile storage at `offset`.
+fn storage_read(app: &App, offset: usize, len: usize) -> Result<Vec<u8>, ErrorCode> {
+    let buffer = app.alloc(len).unwrap();
+    app.allow_readwrite(STORAGE, 0, buffer, len);
+    let read = storage_wait(app, 0, || app.command(STORAGE, 2, offset, len))?;
+    Ok(app.read_bytes(buffer, read))
+}
+
+/// Start a storage operation with `start` and wait for the upcall on
+/// `subscribe` that reports its length.
+fn storage_wait<F>(app: &App, subscribe: usize, start: F) -> Result<usize, ErrorCode>
+where
+    F: Fn() -> SyscallReturn,
+{
+    let done = Rc::new(Cell::new(None));
+    let upcall_done = done.clone();
+    app.subscribe(STORAGE, subscribe, move |_, length, _, _| {
+        upcall_done.set(Some(length))
+    });
+    match start() {
+        SyscallReturn::Success => {}
+        SyscallReturn::Failure(err) => return Err(err),
+        other => panic!("unexpected storage result {:?}", other),
+    }
+    app.yield_for(|| done.get().is_some());
+    Ok(done.get().unwrap())
+}
+
+#[test]
+fn storage_permissions_isolate_app_regions() {
+    let done = Arc::new(AtomicUsize::new(0));
+    let results = Arc::new(Mutex::new([Vec::new(), Vec::new(), Vec::new()]));
+    let (writer_done, writer_results) = (done.clone(), results.clone());
+    let (reader_done, reader_results) = (done.clone(), results.clone());
+    let (unsigned_done, unsigned_results) = (done.clone(), results.clone());
+    let board = TestBoard::boot_with(
+        vec![
+            program(move |app| {
+                let written = storage_write(app, 4, b"hello").map(|_| Vec::new());
+                // The reader's data is off limits.
+                storage_select(app, 0x20);
+                let read = storage_read(app, 0, 4);
+                writer_results.lock().unwrap()[0] = vec![written, read];
+                writer_done.fetch_add(1, Ordering::SeqCst);
+                app.exit_terminate(0);
+            }),
+            program(move |app| {
+                // Runs once the writer is waiting for its write, so the
+                // driver queues this read after it.
+                storage_select(app, 0x10);
+                let shared = storage_read(app, 4, 5);
+                let modified = storage_write(app, 4, b"bye").map(|_| Vec::new());
+                // The reader's own region has never been written.
+                storage_select(app, 0);
+                let own = storage_read(app, 4, 5);
+                reader_results.lock().unwrap()[1] = vec![shared, modified, own];
+                reader_done.fetch_add(1, Ordering::SeqCst);
+                app.exit_terminate(0);
+            }),
+            program(move |app| {
+                // Asks for the same permissions as the reader, but is not
+                // signed.
+                storage_select(app, 0x10);
+                let shared = storage_read(app, 4, 5);
+                unsigned_results.lock().unwrap()[2] = vec![shared];
+                unsigned_done.fetch_add(1, Ordering::SeqCst);
+                app.exit_terminate(0);
+            }),
+        ],
+        BootOptions::new()
+            .image(|i, image| match i {
+                0 => sha256_footer(image.short_id(0x10)),
+                1 => sha256_footer(image.short_id(0x20).storage_permissions(0x20, &[0x10], &[])),
+                _ => image.short_id(0x30).storage_permissions(0x30, &[0x10], &[]),
+            })
+            .checker(sha256_checker(false))
+            .id_policy(UNVERIFIED_ID_POLICY),
+    );
+    board.storage.set_app_regions(1024, leak([0; 4]));
+
+    let scheduler = board.cooperative();
+    board.run_until(scheduler, true, || done.load(Ordering::SeqCst) == 3);
+    let results = results.lock().unwrap();
+    assert_eq!(results[0], vec![Ok(Vec::new()), Err(ErrorCode::NOSUPPORT)]);
+    assert_eq!(
+        results[1],
+        vec![
+            Ok(b"hello".to_vec()),
+            Err(ErrorCode::NOSUPPORT),
+            Err(ErrorCode::INVAL),
+        ]
+    );
+    assert_eq!(results[2], vec![Err(ErrorCode::NOSUPPORT)]);
+}
+
+const KV: usize = capsules::kv_driver::DRIVER_NUM;
+
+/// Run KV store command `command` on `key` with `value`, returning the value
+/// that was read or the status code the driver reported.
+fn kv_command(app: &App, command: usize, key: &[u8], value: &[u8]) -> Result<Vec<u8>, usize> {
+    let key_buffer = app.alloc(key.len()).unwrap();
+    app.write_bytes(key_buffer, key);
+    app.allow_readonly(KV, 0, key_buffer, key.len());
+    let value_buffer = app.alloc(value.len().max(1)).unwrap();
+    app.write_bytes(value_buffer, value);
+    app.allow_readonly(KV, 1, value_buffer, value.len());
+    let output = app.alloc(64).unwrap();
+    app.allow_readwrite(KV, 0, output, 64);
+
+    let done = Rc::new(Cell::new(None));
+    let upcall_done = done.clone();
+    app.subscribe(KV, 0, move |_, status, length, _| {
+        upcall_done.set(Some((status, length)))
+    });
+    match app.command(KV, command, 0, 0) {
+        SyscallReturn::Success => {}
+        SyscallReturn::Failure(err) => return Err(err as usize),
+        other => panic!("unexpected KV store result {:?}", other),
+    }
+    app.yield_for(|| done.get().is_some());
+    match done.get().unwrap() {
+        (0, length) => Ok(app.read_bytes(output, length)),
+        (status, _) => Err(status),
+    }
+}
+
+#[test]
+fn kv_store_namespaces_keys_per_app() {
+    let done = Arc::new(AtomicUsize::new(0));
+    let results = Arc::new(Mutex::new([Vec::new(), Vec::new()]));
+    let (owner_done, owner_results) = (done.clone(), results.clone());
+    let (reader_done, reader_results) = (done.clone(), results.clone());
+    let board = TestBoard::boot_with(
+        vec![
+            program(move |app| {
+                let set = kv_command(app, 2, b"greeting", b"hello");
+                let added = kv_command(app, 3, b"greeting", b"hi");
+                let read = kv_command(app, 1, b"greeting", b"");
+                let missing = kv_command(app, 4, b"count", b"1");
+                let _ = kv_command(app, 3, b"count", b"1");
+                let updated = kv_command(app, 4, b"count", b"2");
+                let count = kv_command(app, 1, b"count", b"");
+                let deleted = kv_command(app, 5, b"count", b"");
+                let gone = kv_command(app, 1, b"count", b"");
+                owner_results.lock().unwrap()[0] =
+                    vec![set, added, read, missing, updated, count, deleted, gone];
+                owner_done.fetch_add(1, Ordering::SeqCst);
+                app.exit_terminate(0);
+            }),
+            program(move |app| {
+                // Queued behind the owner's first write if that is still in
+                // progress.
+                app.command(KV, 6, 0x10, 0);
+                let shared = kv_command(app, 1, b"greeting", b"");
+                let modified = kv_command(app, 2, b"greeting", b"bye");
+                // The same key names a different value in the app's own
+                // namespace.
+                app.command(KV, 6, 0, 0);
+                let missing = kv_command(app, 1, b"greeting", b"");
+                let set = kv_command(app, 2, b"greeting", b"mine");
+                let own = kv_command(app, 1, b"greeting", b"");
+                reader_results.lock().unwrap()[1] = vec![shared, modified, missing, set, own];
+                reader_done.fetch_add(1, Ordering::SeqCst);
+                app.exit_terminate(0);
+            }),
+        ],
+        BootOptions::new()
+            .image(|i, image| {
+                sha256_footer(match i {
+                    0 => image.short_id(0x10),
+                    _ => image.short_id(0x20).storage_permissions(0x20, &[0x10], &[]),
+                })
+            })
+            .checker(sha256_checker(false)),
+    );
+
+    let scheduler = board.cooperative();
+    board.run_until(scheduler, true, || done.load(Ordering::SeqCst) == 2);
+    let results = results.lock().unwrap();
+    let (nosupport, fail) = (ErrorCode::NOSUPPORT as usize, ErrorCode::FAIL as usize);
+    assert_eq!(
+        results[0],
+        vec![
+            Ok(Vec::new()),
+            Err(nosupport),
+            Ok(b"hello".to_vec()),
+            Err(nosupport),
+            Ok(Vec::new()),
+            Ok(b"2".to_vec()),
+            Ok(Vec::new()),
+            Err(nosupport),
+        ]
+    );
+    assert_eq!(
+        results[1],
+        vec![
+            Ok(b"hello".to_vec()),
+            Err(fail),
+            Err(nosupport),
+            Ok(Vec::new()),
+            Ok(b"mine".to_vec()),
+        ]
+    );
+}
@@ -0,0 +1,263 @@
+//! Tests of the system call interface: commands, upcalls, allow and threads.
+
+use core::cell::Cell;
+use std::rc::Rc;
+use std::sync::atomic::{AtomicUsize, Ordering};
+use std::sync::{Arc, Mutex};
+
+use kernel::procs::State;
+use kernel::syscall::SyscallReturn;
+use kernel::ErrorCode;
+
+use super::board::*;
+
+#[test]
+fn command_and_upcall() {
+    let log = Arc::new(Mutex::new(Vec::new()));
+    let app_log = log.clone();
+    let board = TestBoard::boot(vec![program(move |app| {
+        assert!(matches!(
+            app.command(TEST_DRIVER, 0, 0, 0),
+            SyscallReturn::Success
+        ));
+
+        let args = Rc::new(Cell::new((0, 0)));
+        let upcall_args = args.clone();
+        app.subscribe(TEST_DRIVER, 0, move |_, a, b, _| upcall_args.set((a, b)));
+        app.command(TEST_DRIVER, 1, 7, 9);
+        app.yield_wait();
+        app_log.lock().unwrap().push(args.get());
+        app.exit_terminate(0);
+    })]);
+
+    let scheduler = board.cooperative();
+    board.run_until(scheduler, true, || {
+        board.process(0).get_state() == State::Terminated
+    });
+    assert_eq!(*log.lock().unwrap(), vec![(7, 9)]);
+}
+
+#[test]
+fn allow_buffers() {
+    let results = Arc::new(Mutex::new(Vec::new()));
+    let app_results = results.clone();
+    let board = TestBoard::boot(vec![program(move |app| {
+        let rw = app.alloc(16).unwrap();
+        app.allow_readwrite(TEST_DRIVER, 0, rw, 16);
+        if let SyscallReturn::SuccessU32(len) = app.command(TEST_DRIVER, 2, 0xab, 0) {
+            app_results.lock().unwrap().push(len);
+        }
+        assert_eq!(app.read_bytes(rw, 16), vec![0xab; 16]);
+
+        let ro = app.alloc(4).unwrap();
+        app.write_bytes(ro, &[1, 2, 3, 4]);
+        app.allow_readonly(TEST_DRIVER, 0, ro, 4);
+        if let SyscallReturn::SuccessU32(sum) = app.command(TEST_DRIVER, 3, 0, 0) {
+            app_results.lock().unwrap().push(sum);
+        }
+        app.exit_terminate(0);
+    })]);
+
+    let scheduler = board.cooperative();
+    board.run_until(scheduler, true, || {
+        board.process(0).get_state() == State::Terminated
+    });
+    assert_eq!(*results.lock().unwrap(), vec![16, 10]);
+}
+
+#[test]
+fn tbf_permissions_filter_syscalls() {
+    let results = Arc::new(Mutex::new([Vec::new(), Vec::new()]));
+    let done = Arc::new(AtomicUsize::new(0));
+    let (restricted_results, restricted_done) = (results.clone(), done.clone());
+    let (unrestricted_results, unrestricted_done) = (results.clone(), done.clone());
+    let board = TestBoard::boot_with(
+        vec![
+            program(move |app| {
+                let allowed = |rval| !matches!(rval, SyscallReturn::Failure(ErrorCode::NODEVICE));
+                let buffer = app.alloc(4).unwrap();
+                let mut results = vec![
+                    allowed(app.command(TEST_DRIVER, 0, 0, 0)),
+                    allowed(app.subscribe(TEST_DRIVER, 0, |_, _, _, _| {})),
+                    allowed(app.allow_readonly(TEST_DRIVER, 0, buffer, 4)),
+                    allowed(app.command(TEST_DRIVER, 2, 0, 0)),
+                    allowed(app.command(TEST_DRIVER, 64 + 3, 0, 0)),
+                    allowed(app.command(TEST_DRIVER, 64 + 4, 0, 0)),
+                    allowed(app.command(STORAGE, 0, 0, 0)),
+                    allowed(app.allow_readonly(STORAGE, 0, buffer, 4)),
+                    // Thread operations are filtered like commands.
+                    allowed(app.thread_yield()),
+                    app.thread_id() == 0,
+                ];
+                // Yield, exit and memop are never filtered.
+                results.push(matches!(app.memop(1, 0), SyscallReturn::SuccessU32(_)));
+                restricted_results.lock().unwrap()[0] = results;
+                restricted_done.fetch_add(1, Ordering::SeqCst);
+                app.exit_terminate(0);
+            }),
+            program(move |app| {
+                let results = vec![
+                    matches!(
+                        app.command(TEST_DRIVER, 2, 0, 0),
+                        SyscallReturn::SuccessU32(_)
+                    ),
+                    matches!(app.command(STORAGE, 0, 0, 0), SyscallReturn::Success),
+                ];
+                unrestricted_results.lock().unwrap()[1] = results;
+                unrestricted_done.fetch_add(1, Ordering::SeqCst);
+                app.exit_terminate(0);
+            }),
+        ],
+        BootOptions::new().image(|i, image| match i {
+            0 => image.permissions(&[
+                (TEST_DRIVER as u32, 0, 0b1),
+                (TEST_DRIVER as u32, 1, 0b1000),
+                (kernel::syscall::THREAD_DRIVER_NUM as u32, 0, 0b10000),
+            ]),
+            _ => image,
+        }),
+    );
+
+    let scheduler = board.cooperative();
+    board.run_until(scheduler, true, || done.load(Ordering::SeqCst) == 2);
+    let results = results.lock().unwrap();
+    assert_eq!(
+        results[0],
+        vec![true, true, true, false, true, false, false, false, false, true, true]
+    );
+    assert_eq!(results[1], vec![true, true]);
+}
+
+#[test]
+fn allow_outside_process_memory_rejected() {
+    let results = Arc::new(Mutex::new(Vec::new()));
+    let app_results = results.clone();
+    let board = TestBoard::boot(vec![program(move |app| {
+        // Memory allocated with `brk` is valid for allow, but the kernel
+        // must reject an allow that extends past the break.
+        let buf = app.alloc(8).unwrap();
+        let rval = app.allow_readwrite(TEST_DRIVER, 0, buf, 4096);
+        app_results.lock().unwrap().push(matches!(
+            rval,
+            SyscallReturn::AllowReadWriteFailure(ErrorCode::INVAL, _, _)
+        ));
+        app.exit_terminate(0);
+    })]);
+
+    let scheduler = board.cooperative();
+    board.run_until(scheduler, true, || {
+        board.process(0).get_state() == State::Terminated
+    });
+    assert_eq!(*results.lock().unwrap(), vec![true]);
+}
+
+#[test]
+fn threads_share_the_process_and_join() {
+    let log = Arc::new(Mutex::new(Vec::new()));
+    let app_log = log.clone();
+    let board = TestBoard::boot(vec![program(move |app| {
+        assert_eq!(app.thread_id(), 0);
+        let stack = app.alloc(512).unwrap();
+        let worker_log = app_log.clone();
+        let worker = match app.thread_create(stack + 256, 7, move |app, argument| {
+            worker_log
+                .lock()
+                .unwrap()
+                .push(format!("worker {} {}", app.thread_id(), argument));
+            app.thread_yield();
+            worker_log
+                .lock()
+                .unwrap()
+                .push("worker resumed".to_string());
+            app.thread_exit(argument as u32 * 6);
+        }) {
+            SyscallReturn::SuccessU32(thread) => thread as usize,
+            rval => panic!("thread create failed: {:?}", rval),
+        };
+        // A thread that returns from its closure exits with 0.
+        let idle = match app.thread_create(stack + 512, 0, |_, _| {}) {
+            SyscallReturn::SuccessU32(thread) => thread as usize,
+            rval => panic!("thread create failed: {:?}", rval),
+        };
+        app_log.lock().unwrap().push("main".to_string());
+        app.thread_yield();
+        app_log.lock().unwrap().push("main resumed".to_string());
+
+        assert!(matches!(
+            app.thread_join(worker),
+            SyscallReturn::SuccessU32(42)
+        ));
+        assert!(matches!(
+            app.thread_join(idle),
+            SyscallReturn::SuccessU32(0)
+        ));
+        // Joined threads are gone, and the main thread cannot be joined.
+        assert!(matches!(
+            app.thread_join(worker),
+            SyscallReturn::Failure(ErrorCode::INVAL)
+        ));
+        assert!(matches!(
+            app.thread_join(0),
+            SyscallReturn::Failure(ErrorCode::INVAL)
+        ));
+        app.exit_terminate(0);
+    })]);
+
+    let scheduler = board.cooperative();
+    board.run_until(scheduler, false, || {
+        board.process(0).get_state() == State::Terminated
+    });
+    assert_eq!(
+        *log.lock().unwrap(),
+        ["main", "worker 1 7", "main resumed", "worker resumed"]
+    );
+}
+
+#[test]
+fn yield_wait_for_returns_upcall_arguments() {
+    let log = Arc::new(Mutex::new(Vec::new()));
+    let app_log = log.clone();
+    let board = TestBoard::boot(vec![program(move |app| {
+        let upcall_log = app_log.clone();
+        app.subscribe(TEST_DRIVER, 0, move |_, a, b, _| {
+            upcall_log
+                .lock()
+                .unwrap()
+                .push(format!("upcall {} {}", a, b))
+        });
+        // An upcall that is already queued is returned immediately.
+        app.command(TEST_DRIVER, 1, 1, 2);
+        match app.yield_wait_for(TEST_DRIVER, 0) {
+            SyscallReturn::SuccessU32U32U32(a, b, _) => {
+                app_log.lock().unwrap().push(format!("queued {} {}", a, b))
+            }
+            rval => panic!("yield-wait-for failed: {:?}", rval),
+        }
+
+        // Without an upcall function the process can still wait for the
+        // upcall, which here a worker thread schedules while the main
+        // thread is blocked.
+        app.unsubscribe(TEST_DRIVER, 0);
+        let stack = app.alloc(256).unwrap();
+        app.thread_create(stack + 256, 0, |app, _| {
+            assert!(matches!(
+                app.yield_wait_for(TEST_DRIVER, 0),
+                SyscallReturn::Failure(ErrorCode::INVAL)
+            ));
+            app.command(TEST_DRIVER, 1, 3, 4);
+        });
+        match app.yield_wait_for(TEST_DRIVER, 0) {
+            SyscallReturn::SuccessU32U32U32(a, b, _) => {
+                app_log.lock().unwrap().push(format!("blocked {} {}", a, b))
+            }
+            rval => panic!("yield-wait-for failed: {:?}", rval),
+        }
+        app.exit_terminate(0);
+    })]);
+
+    let scheduler = board.cooperative();
+    board.run_until(scheduler, false, || {
+        board.process(0).get_state() == State::Terminated
+    });
+    assert_eq!(*log.lock().unwrap(), ["queued 1 2", "blocked 3 4"]);
+}
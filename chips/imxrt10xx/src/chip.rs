@@ -7,14 +7,14 @@ use kernel::{Chip, InterruptService};
 
 use crate::nvic;
 
-pub struct Imxrt10xx<I: InterruptService<()> + 'static> {
+pub struct Imxrt10xx<I: InterruptService + 'static> {
     mpu: cortexm7::mpu::MPU,
     userspace_kernel_boundary: cortexm7::syscall::SysCall,
     scheduler_timer: cortexm7::systick::SysTick,
     interrupt_service: &'static I,
 }
 
-impl<I: InterruptService<()> + 'static> Imxrt10xx<I> {
+impl<I: InterruptService + 'static> Imxrt10xx<I> {
     pub unsafe fn new(interrupt_service: &'static I) -> Self {
         Imxrt10xx {
             mpu: cortexm7::mpu::MPU::new(),
@@ -59,7 +59,7 @@ impl Imxrt10xxDefaultPeripherals {
     }
 }
 
-impl InterruptService<()> for Imxrt10xxDefaultPeripherals {
+impl InterruptService for Imxrt10xxDefaultPeripherals {
     unsafe fn service_interrupt(&self, interrupt: u32) -> bool {
         match interrupt {
             nvic::LPUART1 => self.lpuart1.handle_interrupt(),
@@ -98,13 +98,9 @@ impl InterruptService<()> for Imxrt10xxDefaultPeripherals {
         }
         true
     }
-
-    unsafe fn service_deferred_call(&self, _: ()) -> bool {
-        false
-    }
 }
 
-impl<I: InterruptService<()> + 'static> Chip for Imxrt10xx<I> {
+impl<I: InterruptService + 'static> Chip for Imxrt10xx<I> {
     type MPU = cortexm7::mpu::MPU;
     type UserspaceKernelBoundary = cortexm7::syscall::SysCall;
     type SchedulerTimer = cortexm7::systick::SysTick;
@@ -19,7 +19,7 @@ static mut INTERRUPT_CONTROLLER: VexRiscvInterruptController = VexRiscvInterrupt
 // The VexRiscv "Secure" variant of
 // [pythondata-cpu-vexriscv](https://github.com/litex-hub/pythondata-cpu-vexriscv)
 // has 16 PMP slots
-pub struct LiteXVexRiscv<A: 'static + Alarm<'static>, I: 'static + InterruptService<()>> {
+pub struct LiteXVexRiscv<A: 'static + Alarm<'static>, I: 'static + InterruptService> {
     soc_identifier: &'static str,
     userspace_kernel_boundary: SysCall,
     interrupt_controller: &'static VexRiscvInterruptController,
@@ -28,7 +28,7 @@ pub struct LiteXVexRiscv<A: 'static + Alarm<'static>, I: 'static + InterruptServ
     interrupt_service: &'static I,
 }
 
-impl<A: 'static + Alarm<'static>, I: 'static + InterruptService<()>> LiteXVexRiscv<A, I> {
+impl<A: 'static + Alarm<'static>, I: 'static + InterruptService> LiteXVexRiscv<A, I> {
     pub unsafe fn new(
         soc_identifier: &'static str,
         alarm: &'static A,
@@ -58,7 +58,7 @@ impl<A: 'static + Alarm<'static>, I: 'static + InterruptService<()>> LiteXVexRis
     }
 }
 
-impl<A: 'static + Alarm<'static>, I: 'static + InterruptService<()>> kernel::Chip
+impl<A: 'static + Alarm<'static>, I: 'static + InterruptService> kernel::Chip
     for LiteXVexRiscv<A, I>
 {
     type MPU = PMP<8>;
@@ -2,9 +2,7 @@
 
 use core::cell::Cell;
 use kernel::common::cells::{OptionalCell, TakeCell};
-use kernel::common::dynamic_deferred_call::{
-    DeferredCallHandle, DynamicDeferredCall, DynamicDeferredCallClient,
-};
+use kernel::common::deferred_call::{DeferredCall, DeferredCallClient};
 use kernel::common::leasable_buffer::LeasableBuffer;
 use kernel::common::registers::interfaces::{ReadWriteable, Readable, Writeable};
 use kernel::common::registers::{
@@ -88,15 +86,11 @@ pub struct Otbn<'a> {
     out_buffer: TakeCell<'static, [u8; 1024]>,
 
     add_data_deferred_call: Cell<bool>,
-    deferred_caller: &'static DynamicDeferredCall,
-    deferred_handle: OptionalCell<DeferredCallHandle>,
+    deferred_call: DeferredCall,
 }
 
 impl<'a> Otbn<'a> {
-    pub const fn new(
-        base: StaticRef<OtbnRegisters>,
-        deferred_caller: &'static DynamicDeferredCall,
-    ) -> Self {
+    pub fn new(base: StaticRef<OtbnRegisters>) -> Self {
         Otbn {
             registers: base,
             client: OptionalCell::empty(),
@@ -104,8 +98,7 @@ impl<'a> Otbn<'a> {
             out_buffer: TakeCell::empty(),
 
             add_data_deferred_call: Cell::new(false),
-            deferred_caller,
-            deferred_handle: OptionalCell::empty(),
+            deferred_call: DeferredCall::new(),
         }
     }
 
@@ -130,10 +123,6 @@ impl<'a> Otbn<'a> {
         self.registers.intr_state.set(0xFFFF_FFFF);
     }
 
-    pub fn initialise(&self, deferred_call_handle: DeferredCallHandle) {
-        self.deferred_handle.set(deferred_call_handle);
-    }
-
     /// Set the client instance which will receive
     pub fn set_client(&'a self, client: &'a dyn Client<'a, 1024>) {
         self.client.set(client);
@@ -173,8 +162,7 @@ impl<'a> Otbn<'a> {
         // Schedule a deferred call as there are no interrupts to monitor
         // the binary loading.
         self.add_data_deferred_call.set(true);
-        self.deferred_handle
-            .map(|handle| self.deferred_caller.set(*handle));
+        self.deferred_call.set();
 
         Ok(())
     }
@@ -229,8 +217,8 @@ impl<'a> Otbn<'a> {
     pub fn clear_data(&self) {}
 }
 
-impl<'a> DynamicDeferredCallClient for Otbn<'a> {
-    fn call(&self, _handle: DeferredCallHandle) {
+impl<'a> DeferredCallClient for Otbn<'a> {
+    fn handle_deferred_call(&self) {
         if self.add_data_deferred_call.get() {
             self.add_data_deferred_call.set(false);
 
@@ -241,4 +229,8 @@ impl<'a> DynamicDeferredCallClient for Otbn<'a> {
             });
         }
     }
+
+    fn register(&'static self) {
+        self.deferred_call.register(self);
+    }
 }
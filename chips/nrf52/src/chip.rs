@@ -1,18 +1,17 @@
-use crate::deferred_call_tasks::DeferredCallTask;
 use core::fmt::Write;
 use cortexm4::{self, nvic};
-use kernel::common::deferred_call;
+use kernel::common::deferred_call::DeferredCallClient;
 use kernel::hil::time::Alarm;
 use kernel::InterruptService;
 
-pub struct NRF52<'a, I: InterruptService<DeferredCallTask> + 'a> {
+pub struct NRF52<'a, I: InterruptService + 'a> {
     mpu: cortexm4::mpu::MPU,
     userspace_kernel_boundary: cortexm4::syscall::SysCall,
     scheduler_timer: cortexm4::systick::SysTick,
     interrupt_service: &'a I,
 }
 
-impl<'a, I: InterruptService<DeferredCallTask> + 'a> NRF52<'a, I> {
+impl<'a, I: InterruptService + 'a> NRF52<'a, I> {
     pub unsafe fn new(interrupt_service: &'a I) -> Self {
         Self {
             mpu: cortexm4::mpu::MPU::new(),
@@ -80,12 +79,13 @@ impl<'a> Nrf52DefaultPeripherals<'a> {
         }
     }
     // Necessary for setting up circular dependencies
-    pub fn init(&'a self) {
+    pub fn init(&'static self) {
         self.ieee802154_radio.set_timer_ref(&self.timer0);
         self.timer0.set_alarm_client(&self.ieee802154_radio);
+        self.nvmc.register();
     }
 }
-impl<'a> kernel::InterruptService<DeferredCallTask> for Nrf52DefaultPeripherals<'a> {
+impl<'a> kernel::InterruptService for Nrf52DefaultPeripherals<'a> {
     unsafe fn service_interrupt(&self, interrupt: u32) -> bool {
         match interrupt {
             crate::peripheral_interrupts::COMP => self.acomp.handle_interrupt(),
@@ -145,15 +145,9 @@ impl<'a> kernel::InterruptService<DeferredCallTask> for Nrf52DefaultPeripherals<
         }
         true
     }
-    unsafe fn service_deferred_call(&self, task: DeferredCallTask) -> bool {
-        match task {
-            DeferredCallTask::Nvmc => self.nvmc.handle_interrupt(),
-        }
-        true
-    }
 }
 
-impl<'a, I: InterruptService<DeferredCallTask> + 'a> kernel::Chip for NRF52<'a, I> {
+impl<'a, I: InterruptService + 'a> kernel::Chip for NRF52<'a, I> {
     type MPU = cortexm4::mpu::MPU;
     type UserspaceKernelBoundary = cortexm4::syscall::SysCall;
     type SchedulerTimer = cortexm4::systick::SysTick;
@@ -178,11 +172,7 @@ impl<'a, I: InterruptService<DeferredCallTask> + 'a> kernel::Chip for NRF52<'a,
     fn service_pending_interrupts(&self) {
         unsafe {
             loop {
-                if let Some(task) = deferred_call::DeferredCall::next_pending() {
-                    if !self.interrupt_service.service_deferred_call(task) {
-                        panic!("unhandled deferred call task");
-                    }
-                } else if let Some(interrupt) = nvic::next_pending() {
+                if let Some(interrupt) = nvic::next_pending() {
                     if !self.interrupt_service.service_interrupt(interrupt) {
                         panic!("unhandled interrupt {}", interrupt);
                     }
@@ -197,7 +187,7 @@ impl<'a, I: InterruptService<DeferredCallTask> + 'a> kernel::Chip for NRF52<'a,
     }
 
     fn has_pending_interrupts(&self) -> bool {
-        unsafe { nvic::has_pending() || deferred_call::has_tasks() }
+        unsafe { nvic::has_pending() }
     }
 
     fn sleep(&self) {
@@ -9,7 +9,6 @@ pub mod ble_radio;
 pub mod chip;
 pub mod clock;
 pub mod crt1;
-pub mod deferred_call_tasks;
 pub mod ficr;
 pub mod i2c;
 pub mod ieee802154_radio;
@@ -7,15 +7,13 @@ use core::ops::{Index, IndexMut};
 use kernel::common::cells::OptionalCell;
 use kernel::common::cells::TakeCell;
 use kernel::common::cells::VolatileCell;
-use kernel::common::deferred_call::DeferredCall;
+use kernel::common::deferred_call::{DeferredCall, DeferredCallClient, Priority};
 use kernel::common::registers::interfaces::{Readable, Writeable};
 use kernel::common::registers::{register_bitfields, ReadOnly, ReadWrite};
 use kernel::common::StaticRef;
 use kernel::hil;
 use kernel::ErrorCode;
 
-use crate::deferred_call_tasks::DeferredCallTask;
-
 const NVMC_BASE: StaticRef<NvmcRegisters> =
     unsafe { StaticRef::new(0x4001E400 as *const NvmcRegisters) };
 
@@ -137,11 +135,6 @@ register_bitfields! [u32,
     ]
 ];
 
-/// This mechanism allows us to schedule "interrupts" even if the hardware
-/// does not support them.
-static DEFERRED_CALL: DeferredCall<DeferredCallTask> =
-    unsafe { DeferredCall::new(DeferredCallTask::Nvmc) };
-
 const PAGE_SIZE: usize = 4096;
 
 /// This is a wrapper around a u8 array that is sized to a single page for the
@@ -206,15 +199,19 @@ pub struct Nvmc {
     client: OptionalCell<&'static dyn hil::flash::Client<Nvmc>>,
     buffer: TakeCell<'static, NrfPage>,
     state: Cell<FlashState>,
+    /// This mechanism allows us to schedule "interrupts" even if the hardware
+    /// does not support them.
+    deferred_call: DeferredCall,
 }
 
 impl Nvmc {
-    pub const fn new() -> Nvmc {
+    pub fn new() -> Nvmc {
         Nvmc {
             registers: NVMC_BASE,
             client: OptionalCell::empty(),
             buffer: TakeCell::empty(),
             state: Cell::new(FlashState::Ready),
+            deferred_call: DeferredCall::with_priority(Priority::High),
         }
     }
 
@@ -304,7 +301,7 @@ impl Nvmc {
         // Mark the need for an interrupt so we can call the read done
         // callback.
         self.state.set(FlashState::Read);
-        DEFERRED_CALL.set();
+        self.deferred_call.set();
 
         Ok(())
     }
@@ -341,7 +338,7 @@ impl Nvmc {
         // Mark the need for an interrupt so we can call the write done
         // callback.
         self.state.set(FlashState::Write);
-        DEFERRED_CALL.set();
+        self.deferred_call.set();
 
         Ok(())
     }
@@ -353,12 +350,22 @@ impl Nvmc {
         // Mark that we want to trigger a pseudo interrupt so that we can issue
         // the callback even though the NVMC is completely blocking.
         self.state.set(FlashState::Erase);
-        DEFERRED_CALL.set();
+        self.deferred_call.set();
 
         Ok(())
     }
 }
 
+impl DeferredCallClient for Nvmc {
+    fn handle_deferred_call(&self) {
+        self.handle_interrupt();
+    }
+
+    fn register(&'static self) {
+        self.deferred_call.register(self);
+    }
+}
+
 impl<C: hil::flash::Client<Self>> hil::flash::HasClient<'static, C> for Nvmc {
     fn set_client(&self, client: &'static C) {
         self.client.set(client);
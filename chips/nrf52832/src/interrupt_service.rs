@@ -1,4 +1,3 @@
-use crate::deferred_call_tasks::DeferredCallTask;
 use nrf52::chip::Nrf52DefaultPeripherals;
 
 /// This struct, when initialized, instantiates all peripheral drivers for the nrf52840.
@@ -17,11 +16,11 @@ impl<'a> Nrf52832DefaultPeripherals<'a> {
         }
     }
     // Necessary for setting up circular dependencies
-    pub fn init(&'a self) {
+    pub fn init(&'static self) {
         self.nrf52.init();
     }
 }
-impl<'a> kernel::InterruptService<DeferredCallTask> for Nrf52832DefaultPeripherals<'a> {
+impl<'a> kernel::InterruptService for Nrf52832DefaultPeripherals<'a> {
     unsafe fn service_interrupt(&self, interrupt: u32) -> bool {
         match interrupt {
             nrf52::peripheral_interrupts::GPIOTE => self.gpio_port.handle_interrupt(),
@@ -29,7 +28,4 @@ impl<'a> kernel::InterruptService<DeferredCallTask> for Nrf52832DefaultPeriphera
         }
         true
     }
-    unsafe fn service_deferred_call(&self, task: DeferredCallTask) -> bool {
-        self.nrf52.service_deferred_call(task)
-    }
 }
@@ -1,4 +1,3 @@
-use crate::deferred_call_tasks::DeferredCallTask;
 use nrf52::chip::Nrf52DefaultPeripherals;
 
 /// This struct, when initialized, instantiates all peripheral drivers for the nrf52840.
@@ -21,13 +20,13 @@ impl<'a> Nrf52840DefaultPeripherals<'a> {
         }
     }
     // Necessary for setting up circular dependencies
-    pub fn init(&'a self) {
+    pub fn init(&'static self) {
         self.nrf52.pwr_clk.set_usb_client(&self.usbd);
         self.usbd.set_power_ref(&self.nrf52.pwr_clk);
         self.nrf52.init();
     }
 }
-impl<'a> kernel::InterruptService<DeferredCallTask> for Nrf52840DefaultPeripherals<'a> {
+impl<'a> kernel::InterruptService for Nrf52840DefaultPeripherals<'a> {
     unsafe fn service_interrupt(&self, interrupt: u32) -> bool {
         match interrupt {
             crate::peripheral_interrupts::USBD => self.usbd.handle_interrupt(),
@@ -36,7 +35,4 @@ impl<'a> kernel::InterruptService<DeferredCallTask> for Nrf52840DefaultPeriphera
         }
         true
     }
-    unsafe fn service_deferred_call(&self, task: DeferredCallTask) -> bool {
-        self.nrf52.service_deferred_call(task)
-    }
 }
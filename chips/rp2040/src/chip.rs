@@ -1,7 +1,6 @@
 //! Chip trait setup.
 
 use core::fmt::Write;
-use kernel::common::deferred_call;
 use kernel::Chip;
 use kernel::InterruptService;
 
@@ -24,7 +23,7 @@ pub enum Processor {
     Processor1 = 1,
 }
 
-pub struct Rp2040<'a, I: InterruptService<()> + 'a> {
+pub struct Rp2040<'a, I: InterruptService + 'a> {
     mpu: cortexm0p::mpu::MPU,
     userspace_kernel_boundary: cortexm0p::syscall::SysCall,
     scheduler_timer: cortexm0p::systick::SysTick,
@@ -34,7 +33,7 @@ pub struct Rp2040<'a, I: InterruptService<()> + 'a> {
     processor1_interrupt_mask: (u128, u128),
 }
 
-impl<'a, I: InterruptService<()>> Rp2040<'a, I> {
+impl<'a, I: InterruptService> Rp2040<'a, I> {
     pub unsafe fn new(interrupt_service: &'a I, sio: &'a SIO) -> Self {
         Self {
             mpu: cortexm0p::mpu::MPU::new(),
@@ -48,7 +47,7 @@ impl<'a, I: InterruptService<()>> Rp2040<'a, I> {
     }
 }
 
-impl<'a, I: InterruptService<()>> Chip for Rp2040<'a, I> {
+impl<'a, I: InterruptService> Chip for Rp2040<'a, I> {
     type MPU = cortexm0p::mpu::MPU;
     type UserspaceKernelBoundary = cortexm0p::syscall::SysCall;
     type SchedulerTimer = cortexm0p::systick::SysTick;
@@ -86,7 +85,7 @@ impl<'a, I: InterruptService<()>> Chip for Rp2040<'a, I> {
             Processor::Processor0 => self.processor0_interrupt_mask,
             Processor::Processor1 => self.processor1_interrupt_mask,
         };
-        unsafe { cortexm0p::nvic::has_pending_with_mask(mask) || deferred_call::has_tasks() }
+        unsafe { cortexm0p::nvic::has_pending_with_mask(mask) }
     }
 
     fn mpu(&self) -> &Self::MPU {
@@ -159,7 +158,7 @@ impl<'a> Rp2040DefaultPeripherals<'a> {
     }
 }
 
-impl InterruptService<()> for Rp2040DefaultPeripherals<'_> {
+impl InterruptService for Rp2040DefaultPeripherals<'_> {
     unsafe fn service_interrupt(&self, interrupt: u32) -> bool {
         match interrupt {
             interrupts::TIMER_IRQ_0 => {
@@ -193,8 +192,4 @@ impl InterruptService<()> for Rp2040DefaultPeripherals<'_> {
             _ => false,
         }
     }
-
-    unsafe fn service_deferred_call(&self, _task: ()) -> bool {
-        false
-    }
 }
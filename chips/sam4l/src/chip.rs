@@ -1,14 +1,24 @@
 //! Interrupt mapping and DMA channel setup.
 
-use crate::deferred_call_tasks::Task;
 use crate::pm;
 
 use core::fmt::Write;
 use cortexm4;
-use kernel::common::deferred_call;
+use kernel::common::deferred_call::DeferredCallClient;
+use kernel::sleep::SleepState;
 use kernel::{Chip, InterruptService};
 
-pub struct Sam4l<I: InterruptService<Task> + 'static> {
+/// Sleep states of the SAM4L. In "sleep" the CPU clock is stopped and every
+/// peripheral keeps running. "deepsleep" also stops the high speed clocks,
+/// which have to be restarted on wakeup, so it is only entered if no
+/// peripheral that needs them is enabled (see `pm::deep_sleep_ready()`).
+/// The latencies are conservative estimates.
+pub const SLEEP_STATES: [SleepState; 2] = [
+    SleepState::new("sleep", 0, 0),
+    SleepState::new("deepsleep", 10, 100),
+];
+
+pub struct Sam4l<I: InterruptService + 'static> {
     mpu: cortexm4::mpu::MPU,
     userspace_kernel_boundary: cortexm4::syscall::SysCall,
     scheduler_timer: cortexm4::systick::SysTick,
@@ -16,7 +26,7 @@ pub struct Sam4l<I: InterruptService<Task> + 'static> {
     interrupt_service: &'static I,
 }
 
-impl<I: InterruptService<Task> + 'static> Sam4l<I> {
+impl<I: InterruptService + 'static> Sam4l<I> {
     pub unsafe fn new(pm: &'static crate::pm::PowerManager, interrupt_service: &'static I) -> Self {
         Self {
             mpu: cortexm4::mpu::MPU::new(),
@@ -114,8 +124,12 @@ impl Sam4lDefaultPeripherals {
     // Sam4l was the only chip that partially initialized some drivers in new, I
     // have moved that initialization to this helper function.
     // TODO: Delete explanation
+    // This also registers the peripherals that use deferred calls.
     pub fn setup_dma(&'static self) {
         use crate::dma;
+        self.flash_controller.register();
+        self.crccu.register();
+
         self.usart0
             .set_dma(&self.dma_channels[0], &self.dma_channels[1]);
         self.dma_channels[0].initialize(&self.usart0, dma::DMAWidth::Width8Bit);
@@ -154,7 +168,7 @@ impl Sam4lDefaultPeripherals {
         self.dma_channels[13].initialize(&self.adc, dma::DMAWidth::Width16Bit);
     }
 }
-impl kernel::InterruptService<Task> for Sam4lDefaultPeripherals {
+impl kernel::InterruptService for Sam4lDefaultPeripherals {
     unsafe fn service_interrupt(&self, interrupt: u32) -> bool {
         use crate::nvic;
         match interrupt {
@@ -227,16 +241,9 @@ impl kernel::InterruptService<Task> for Sam4lDefaultPeripherals {
         }
         true
     }
-    unsafe fn service_deferred_call(&self, task: Task) -> bool {
-        match task {
-            crate::deferred_call_tasks::Task::Flashcalw => self.flash_controller.handle_interrupt(),
-            crate::deferred_call_tasks::Task::CRCCU => self.crccu.handle_deferred_call(),
-        }
-        true
-    }
 }
 
-impl<I: InterruptService<Task> + 'static> Chip for Sam4l<I> {
+impl<I: InterruptService + 'static> Chip for Sam4l<I> {
     type MPU = cortexm4::mpu::MPU;
     type UserspaceKernelBoundary = cortexm4::syscall::SysCall;
     type SchedulerTimer = cortexm4::systick::SysTick;
@@ -245,12 +252,7 @@ impl<I: InterruptService<Task> + 'static> Chip for Sam4l<I> {
     fn service_pending_interrupts(&self) {
         unsafe {
             loop {
-                if let Some(task) = deferred_call::DeferredCall::next_pending() {
-                    match self.interrupt_service.service_deferred_call(task) {
-                        true => {}
-                        false => panic!("unhandled deferred call task"),
-                    }
-                } else if let Some(interrupt) = cortexm4::nvic::next_pending() {
+                if let Some(interrupt) = cortexm4::nvic::next_pending() {
                     match self.interrupt_service.service_interrupt(interrupt) {
                         true => {}
                         false => panic!("unhandled interrupt"),
@@ -266,7 +268,7 @@ impl<I: InterruptService<Task> + 'static> Chip for Sam4l<I> {
     }
 
     fn has_pending_interrupts(&self) -> bool {
-        unsafe { cortexm4::nvic::has_pending() || deferred_call::has_tasks() }
+        unsafe { cortexm4::nvic::has_pending() }
     }
 
     fn mpu(&self) -> &cortexm4::mpu::MPU {
@@ -286,7 +288,15 @@ impl<I: InterruptService<Task> + 'static> Chip for Sam4l<I> {
     }
 
     fn sleep(&self) {
-        if pm::deep_sleep_ready() {
+        self.sleep_in_state(SLEEP_STATES.len() - 1);
+    }
+
+    fn sleep_states(&self) -> &[SleepState] {
+        &SLEEP_STATES
+    }
+
+    fn sleep_in_state(&self, state: usize) {
+        if state > 0 && pm::deep_sleep_ready() {
             unsafe {
                 cortexm4::scb::set_sleepdeep();
             }
@@ -48,15 +48,15 @@
 //
 // - Support continuous-mode CRC
 
-use crate::deferred_call_tasks::Task;
 use crate::pm::{disable_clock, enable_clock, Clock, HSBClock, PBBClock};
 use core::cell::Cell;
 use kernel::common::cells::OptionalCell;
+use kernel::common::deferred_call::{DeferredCall, DeferredCallClient, Priority};
 use kernel::common::registers::interfaces::{Readable, Writeable};
 use kernel::common::registers::{
     register_bitfields, FieldValue, InMemoryRegister, ReadOnly, ReadWrite, WriteOnly,
 };
-use kernel::common::{deferred_call::DeferredCall, leasable_buffer::LeasableBuffer, StaticRef};
+use kernel::common::{leasable_buffer::LeasableBuffer, StaticRef};
 use kernel::hil::crc::{Client, Crc, CrcAlgorithm, CrcOutput};
 use kernel::ErrorCode;
 
@@ -64,8 +64,6 @@ use kernel::ErrorCode;
 pub const BASE_ADDRESS: StaticRef<CrccuRegisters> =
     unsafe { StaticRef::new(0x400A4000 as *const CrccuRegisters) };
 
-static DEFERRED_CALL: DeferredCall<Task> = unsafe { DeferredCall::new(Task::CRCCU) };
-
 #[repr(C)]
 pub struct CrccuRegisters {
     // From page 1005 of SAM4L manual
@@ -263,6 +261,8 @@ pub struct Crccu<'a> {
     // Must be aligned to a 512-byte boundary, which is guaranteed by
     // the struct definition.
     descriptor: Descriptor,
+
+    deferred_call: DeferredCall,
 }
 
 impl Crccu<'_> {
@@ -275,6 +275,7 @@ impl Crccu<'_> {
             current_full_buffer: Cell::new((0 as *mut u8, 0)),
             compute_requested: Cell::new(false),
             descriptor: Descriptor::new(),
+            deferred_call: DeferredCall::with_priority(Priority::High),
         }
     }
 
@@ -363,7 +364,7 @@ impl Crccu<'_> {
         }
     }
 
-    pub fn handle_deferred_call(&self) {
+    fn compute_done(&self) {
         // A deferred call is currently only issued on a call to
         // compute, in which case we need to provide the CRC to the
         // client
@@ -384,6 +385,16 @@ impl Crccu<'_> {
     }
 }
 
+impl DeferredCallClient for Crccu<'_> {
+    fn handle_deferred_call(&self) {
+        self.compute_done();
+    }
+
+    fn register(&'static self) {
+        self.deferred_call.register(self);
+    }
+}
+
 // Implement the generic CRC interface with the CRCCU
 impl<'a> Crc<'a> for Crccu<'a> {
     /// Set a client to receive results from the CRCCU
@@ -531,7 +542,7 @@ impl<'a> Crc<'a> for Crccu<'a> {
 
         // Request a deferred call such that we can provide the result
         // back to the client
-        DEFERRED_CALL.set();
+        self.deferred_call.set();
 
         Ok(())
     }
@@ -20,12 +20,11 @@
 //! - Author:  Kevin Baichoo <kbaichoo@cs.stanford.edu>
 //! - Date: July 27, 2016
 
-use crate::deferred_call_tasks::Task;
 use crate::pm;
 use core::cell::Cell;
 use core::ops::{Index, IndexMut};
 use kernel::common::cells::{OptionalCell, TakeCell};
-use kernel::common::deferred_call::DeferredCall;
+use kernel::common::deferred_call::{DeferredCall, DeferredCallClient, Priority};
 use kernel::common::registers::interfaces::{ReadWriteable, Readable, Writeable};
 use kernel::common::registers::{register_bitfields, ReadOnly, ReadWrite, WriteOnly};
 use kernel::common::StaticRef;
@@ -318,8 +317,6 @@ enum RegKey {
     GPFRLO,
 }
 
-static DEFERRED_CALL: DeferredCall<Task> = unsafe { DeferredCall::new(Task::Flashcalw) };
-
 /// There are 18 recognized commands for the flash. These are "bare-bones"
 /// commands and values that are written to the Flash's command register to
 /// inform the flash what to do. Table 14-5.
@@ -417,6 +414,7 @@ pub struct FLASHCALW {
     client: OptionalCell<&'static dyn hil::flash::Client<FLASHCALW>>,
     current_state: Cell<FlashState>,
     buffer: TakeCell<'static, Sam4lPage>,
+    deferred_call: DeferredCall,
 }
 
 // Few constants relating to module configuration.
@@ -435,11 +433,7 @@ const FREQ_PS1_FWS_0_MAX_FREQ: u32 = 8000000;
 const FREQ_PS2_FWS_0_MAX_FREQ: u32 = 24000000;
 
 impl FLASHCALW {
-    pub const fn new(
-        ahb_clk: pm::HSBClock,
-        hramc1_clk: pm::HSBClock,
-        pb_clk: pm::PBBClock,
-    ) -> FLASHCALW {
+    pub fn new(ahb_clk: pm::HSBClock, hramc1_clk: pm::HSBClock, pb_clk: pm::PBBClock) -> FLASHCALW {
         FLASHCALW {
             registers: FLASHCALW_ADDRESS,
             ahb_clock: pm::Clock::HSB(ahb_clk),
@@ -448,6 +442,7 @@ impl FLASHCALW {
             client: OptionalCell::empty(),
             current_state: Cell::new(FlashState::Unconfigured),
             buffer: TakeCell::empty(),
+            deferred_call: DeferredCall::with_priority(Priority::High),
         }
     }
 
@@ -858,7 +853,7 @@ impl FLASHCALW {
         // This is kind of strange, but because read() in this case is
         // synchronous, we still need to schedule as if we had an interrupt so
         // we can allow this function to return and then call the callback.
-        DEFERRED_CALL.set();
+        self.deferred_call.set();
 
         Ok(())
     }
@@ -905,6 +900,16 @@ impl FLASHCALW {
     }
 }
 
+impl DeferredCallClient for FLASHCALW {
+    fn handle_deferred_call(&self) {
+        self.handle_interrupt();
+    }
+
+    fn register(&'static self) {
+        self.deferred_call.register(self);
+    }
+}
+
 impl<C: hil::flash::Client<Self>> hil::flash::HasClient<'static, C> for FLASHCALW {
     fn set_client(&self, client: &'static C) {
         self.client.set(client);
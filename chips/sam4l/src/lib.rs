@@ -7,8 +7,6 @@
 #![feature(const_fn_trait_bound)]
 #![no_std]
 
-pub mod deferred_call_tasks;
-
 pub mod acifc;
 pub mod adc;
 pub mod aes;
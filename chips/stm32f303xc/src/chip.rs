@@ -2,15 +2,14 @@
 
 use core::fmt::Write;
 use cortexm4;
-use kernel::common::deferred_call;
+use kernel::common::deferred_call::DeferredCallClient;
 use kernel::Chip;
 use kernel::InterruptService;
 
-use crate::deferred_call_tasks::DeferredCallTask;
 use crate::nvic;
 use crate::wdt;
 
-pub struct Stm32f3xx<'a, I: InterruptService<DeferredCallTask> + 'a> {
+pub struct Stm32f3xx<'a, I: InterruptService + 'a> {
     mpu: cortexm4::mpu::MPU,
     userspace_kernel_boundary: cortexm4::syscall::SysCall,
     scheduler_timer: cortexm4::systick::SysTick,
@@ -49,12 +48,13 @@ impl<'a> Stm32f3xxDefaultPeripherals<'a> {
         }
     }
 
-    pub fn setup_circular_deps(&'a self) {
+    pub fn setup_circular_deps(&'static self) {
         self.gpio_ports.setup_circular_deps();
+        self.flash.register();
     }
 }
 
-impl<'a> InterruptService<DeferredCallTask> for Stm32f3xxDefaultPeripherals<'a> {
+impl<'a> InterruptService for Stm32f3xxDefaultPeripherals<'a> {
     unsafe fn service_interrupt(&self, interrupt: u32) -> bool {
         match interrupt {
             nvic::USART1 => self.usart1.handle_interrupt(),
@@ -80,16 +80,9 @@ impl<'a> InterruptService<DeferredCallTask> for Stm32f3xxDefaultPeripherals<'a>
         }
         true
     }
-
-    unsafe fn service_deferred_call(&self, task: DeferredCallTask) -> bool {
-        match task {
-            DeferredCallTask::Flash => self.flash.handle_interrupt(),
-        }
-        true
-    }
 }
 
-impl<'a, I: InterruptService<DeferredCallTask> + 'a> Stm32f3xx<'a, I> {
+impl<'a, I: InterruptService + 'a> Stm32f3xx<'a, I> {
     pub unsafe fn new(interrupt_service: &'a I, rcc: &'a crate::rcc::Rcc) -> Self {
         Self {
             mpu: cortexm4::mpu::MPU::new(),
@@ -105,7 +98,7 @@ impl<'a, I: InterruptService<DeferredCallTask> + 'a> Stm32f3xx<'a, I> {
     }
 }
 
-impl<'a, I: InterruptService<DeferredCallTask> + 'a> Chip for Stm32f3xx<'a, I> {
+impl<'a, I: InterruptService + 'a> Chip for Stm32f3xx<'a, I> {
     type MPU = cortexm4::mpu::MPU;
     type UserspaceKernelBoundary = cortexm4::syscall::SysCall;
     type SchedulerTimer = cortexm4::systick::SysTick;
@@ -114,11 +107,7 @@ impl<'a, I: InterruptService<DeferredCallTask> + 'a> Chip for Stm32f3xx<'a, I> {
     fn service_pending_interrupts(&self) {
         unsafe {
             loop {
-                if let Some(task) = deferred_call::DeferredCall::next_pending() {
-                    if !self.interrupt_service.service_deferred_call(task) {
-                        panic!("unhandled deferred call");
-                    }
-                } else if let Some(interrupt) = cortexm4::nvic::next_pending() {
+                if let Some(interrupt) = cortexm4::nvic::next_pending() {
                     if !self.interrupt_service.service_interrupt(interrupt) {
                         panic!("unhandled interrupt {}", interrupt);
                     }
@@ -133,7 +122,7 @@ impl<'a, I: InterruptService<DeferredCallTask> + 'a> Chip for Stm32f3xx<'a, I> {
     }
 
     fn has_pending_interrupts(&self) -> bool {
-        unsafe { cortexm4::nvic::has_pending() || deferred_call::has_tasks() }
+        unsafe { cortexm4::nvic::has_pending() }
     }
 
     fn mpu(&self) -> &cortexm4::mpu::MPU {
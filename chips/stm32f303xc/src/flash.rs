@@ -14,7 +14,7 @@ use core::ops::{Index, IndexMut};
 use kernel::common::cells::OptionalCell;
 use kernel::common::cells::TakeCell;
 use kernel::common::cells::VolatileCell;
-use kernel::common::deferred_call::DeferredCall;
+use kernel::common::deferred_call::{DeferredCall, DeferredCallClient, Priority};
 use kernel::common::registers::interfaces::{ReadWriteable, Readable, Writeable};
 use kernel::common::registers::register_bitfields;
 use kernel::common::registers::{ReadOnly, ReadWrite, WriteOnly};
@@ -22,8 +22,6 @@ use kernel::common::StaticRef;
 use kernel::hil;
 use kernel::ErrorCode;
 
-use crate::deferred_call_tasks::DeferredCallTask;
-
 const FLASH_BASE: StaticRef<FlashRegisters> =
     unsafe { StaticRef::new(0x40022000 as *const FlashRegisters) };
 
@@ -207,9 +205,6 @@ register_bitfields! [u32,
     ]
 ];
 
-static DEFERRED_CALL: DeferredCall<DeferredCallTask> =
-    unsafe { DeferredCall::new(DeferredCallTask::Flash) };
-
 const PAGE_SIZE: usize = 2048;
 
 /// Address of the first flash page.
@@ -288,10 +283,11 @@ pub struct Flash {
     state: Cell<FlashState>,
     write_counter: Cell<usize>,
     page_number: Cell<usize>,
+    deferred_call: DeferredCall,
 }
 
 impl Flash {
-    pub const fn new() -> Flash {
+    pub fn new() -> Flash {
         Flash {
             registers: FLASH_BASE,
             client: OptionalCell::empty(),
@@ -299,6 +295,7 @@ impl Flash {
             state: Cell::new(FlashState::Ready),
             write_counter: Cell::new(0),
             page_number: Cell::new(0),
+            deferred_call: DeferredCall::with_priority(Priority::High),
         }
     }
 
@@ -556,7 +553,7 @@ impl Flash {
 
         self.buffer.replace(buffer);
         self.state.set(FlashState::Read);
-        DEFERRED_CALL.set();
+        self.deferred_call.set();
 
         Ok(())
     }
@@ -604,6 +601,16 @@ impl Flash {
     }
 }
 
+impl DeferredCallClient for Flash {
+    fn handle_deferred_call(&self) {
+        self.handle_interrupt();
+    }
+
+    fn register(&'static self) {
+        self.deferred_call.register(self);
+    }
+}
+
 impl<C: hil::flash::Client<Self>> hil::flash::HasClient<'static, C> for Flash {
     fn set_client(&self, client: &'static C) {
         self.client.set(client);
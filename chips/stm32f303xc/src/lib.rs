@@ -8,7 +8,6 @@
 #![no_std]
 
 pub mod chip;
-mod deferred_call_tasks;
 pub mod nvic;
 
 // Peripherals
@@ -247,6 +247,12 @@ impl<'a> Spi<'a> {
         self.clock.disable();
     }
 
+    /// The peripheral clock, for boards that share it with a
+    /// `kernel::power::SharedClock`.
+    pub fn clock(&self) -> &dyn ClockInterface {
+        &self.clock
+    }
+
     pub fn handle_interrupt(&self) {
         if self.registers.sr.is_set(SR::TXE) {
             if self.tx_buffer.is_some() && self.tx_position.get() < self.len.get() {
@@ -1,6 +1,5 @@
 use crate::stm32f412g_nvic;
 use stm32f4xx::chip::Stm32f4xxDefaultPeripherals;
-use stm32f4xx::deferred_calls::DeferredCallTask;
 
 pub struct Stm32f412gDefaultPeripherals<'a> {
     pub stm32f4: Stm32f4xxDefaultPeripherals<'a>,
@@ -20,11 +19,11 @@ impl<'a> Stm32f412gDefaultPeripherals<'a> {
         }
     }
     // Necessary for setting up circular dependencies
-    pub fn init(&'a self) {
+    pub fn init(&'static self) {
         self.stm32f4.setup_circular_deps();
     }
 }
-impl<'a> kernel::InterruptService<DeferredCallTask> for Stm32f412gDefaultPeripherals<'a> {
+impl<'a> kernel::InterruptService for Stm32f412gDefaultPeripherals<'a> {
     unsafe fn service_interrupt(&self, interrupt: u32) -> bool {
         match interrupt {
             // put Stm32f412g specific interrupts here
@@ -35,7 +34,4 @@ impl<'a> kernel::InterruptService<DeferredCallTask> for Stm32f412gDefaultPeriphe
             _ => self.stm32f4.service_interrupt(interrupt),
         }
     }
-    unsafe fn service_deferred_call(&self, task: DeferredCallTask) -> bool {
-        self.stm32f4.service_deferred_call(task)
-    }
 }
@@ -1,5 +1,4 @@
 use stm32f4xx::chip::Stm32f4xxDefaultPeripherals;
-use stm32f4xx::deferred_calls::DeferredCallTask;
 
 pub struct Stm32f429ziDefaultPeripherals<'a> {
     pub stm32f4: Stm32f4xxDefaultPeripherals<'a>,
@@ -17,18 +16,15 @@ impl<'a> Stm32f429ziDefaultPeripherals<'a> {
         }
     }
     // Necessary for setting up circular dependencies
-    pub fn init(&'a self) {
+    pub fn init(&'static self) {
         self.stm32f4.setup_circular_deps();
     }
 }
-impl<'a> kernel::InterruptService<DeferredCallTask> for Stm32f429ziDefaultPeripherals<'a> {
+impl<'a> kernel::InterruptService for Stm32f429ziDefaultPeripherals<'a> {
     unsafe fn service_interrupt(&self, interrupt: u32) -> bool {
         match interrupt {
             // put Stm32f429zi specific interrupts here
             _ => self.stm32f4.service_interrupt(interrupt),
         }
     }
-    unsafe fn service_deferred_call(&self, task: DeferredCallTask) -> bool {
-        self.stm32f4.service_deferred_call(task)
-    }
 }
@@ -2,16 +2,14 @@
 
 use core::fmt::Write;
 use cortexm4;
-use kernel::common::deferred_call;
+use kernel::common::deferred_call::DeferredCallClient;
 use kernel::Chip;
 use kernel::InterruptService;
 
 use crate::dma1;
 use crate::nvic;
 
-use crate::deferred_calls::DeferredCallTask;
-
-pub struct Stm32f4xx<'a, I: InterruptService<DeferredCallTask> + 'a> {
+pub struct Stm32f4xx<'a, I: InterruptService + 'a> {
     mpu: cortexm4::mpu::MPU,
     userspace_kernel_boundary: cortexm4::syscall::SysCall,
     scheduler_timer: cortexm4::systick::SysTick,
@@ -67,12 +65,13 @@ impl<'a> Stm32f4xxDefaultPeripherals<'a> {
         }
     }
 
-    pub fn setup_circular_deps(&'a self) {
+    pub fn setup_circular_deps(&'static self) {
         self.gpio_ports.setup_circular_deps();
+        self.fsmc.register();
     }
 }
 
-impl<'a> InterruptService<DeferredCallTask> for Stm32f4xxDefaultPeripherals<'a> {
+impl<'a> InterruptService for Stm32f4xxDefaultPeripherals<'a> {
     unsafe fn service_interrupt(&self, interrupt: u32) -> bool {
         match interrupt {
             nvic::DMA1_Stream1 => self.dma_streams
@@ -118,16 +117,9 @@ impl<'a> InterruptService<DeferredCallTask> for Stm32f4xxDefaultPeripherals<'a>
         }
         true
     }
-
-    unsafe fn service_deferred_call(&self, task: DeferredCallTask) -> bool {
-        match task {
-            DeferredCallTask::Fsmc => self.fsmc.handle_interrupt(),
-        }
-        true
-    }
 }
 
-impl<'a, I: InterruptService<DeferredCallTask> + 'a> Stm32f4xx<'a, I> {
+impl<'a, I: InterruptService + 'a> Stm32f4xx<'a, I> {
     pub unsafe fn new(interrupt_service: &'a I) -> Self {
         Self {
             mpu: cortexm4::mpu::MPU::new(),
@@ -138,7 +130,7 @@ impl<'a, I: InterruptService<DeferredCallTask> + 'a> Stm32f4xx<'a, I> {
     }
 }
 
-impl<'a, I: InterruptService<DeferredCallTask> + 'a> Chip for Stm32f4xx<'a, I> {
+impl<'a, I: InterruptService + 'a> Chip for Stm32f4xx<'a, I> {
     type MPU = cortexm4::mpu::MPU;
     type UserspaceKernelBoundary = cortexm4::syscall::SysCall;
     type SchedulerTimer = cortexm4::systick::SysTick;
@@ -147,11 +139,7 @@ impl<'a, I: InterruptService<DeferredCallTask> + 'a> Chip for Stm32f4xx<'a, I> {
     fn service_pending_interrupts(&self) {
         unsafe {
             loop {
-                if let Some(task) = deferred_call::DeferredCall::next_pending() {
-                    if !self.interrupt_service.service_deferred_call(task) {
-                        panic!("Unhandled deferred call");
-                    }
-                } else if let Some(interrupt) = cortexm4::nvic::next_pending() {
+                if let Some(interrupt) = cortexm4::nvic::next_pending() {
                     if !self.interrupt_service.service_interrupt(interrupt) {
                         panic!("unhandled interrupt {}", interrupt);
                     }
@@ -167,7 +155,7 @@ impl<'a, I: InterruptService<DeferredCallTask> + 'a> Chip for Stm32f4xx<'a, I> {
     }
 
     fn has_pending_interrupts(&self) -> bool {
-        unsafe { cortexm4::nvic::has_pending() || deferred_call::has_tasks() }
+        unsafe { cortexm4::nvic::has_pending() }
     }
 
     fn mpu(&self) -> &cortexm4::mpu::MPU {
@@ -1,7 +1,7 @@
 use crate::rcc;
 use core::cell::Cell;
 use kernel::common::cells::{OptionalCell, TakeCell};
-use kernel::common::deferred_call::DeferredCall;
+use kernel::common::deferred_call::{DeferredCall, DeferredCallClient, Priority};
 use kernel::common::registers::interfaces::{ReadWriteable, Readable, Writeable};
 use kernel::common::registers::{register_bitfields, ReadWrite};
 use kernel::common::StaticRef;
@@ -9,8 +9,6 @@ use kernel::hil::bus8080::{Bus8080, BusWidth, Client};
 use kernel::ClockInterface;
 use kernel::ErrorCode;
 
-use crate::deferred_calls::DeferredCallTask;
-
 /// FSMC peripheral interface
 #[repr(C)]
 struct FsmcBankRegisters {
@@ -128,11 +126,6 @@ register_bitfields![u32,
     ]
 ];
 
-/// This mechanism allows us to schedule "interrupts" even if the hardware
-/// does not support them.
-static DEFERRED_CALL: DeferredCall<DeferredCallTask> =
-    unsafe { DeferredCall::new(DeferredCallTask::Fsmc) };
-
 const FSMC_BASE: StaticRef<FsmcBankRegisters> =
     unsafe { StaticRef::new(0xA000_0000 as *const FsmcBankRegisters) };
 
@@ -170,10 +163,14 @@ pub struct Fsmc<'a> {
     buffer: TakeCell<'static, [u8]>,
     bus_width: Cell<usize>,
     len: Cell<usize>,
+
+    /// This mechanism allows us to schedule "interrupts" even if the hardware
+    /// does not support them.
+    deferred_call: DeferredCall,
 }
 
 impl<'a> Fsmc<'a> {
-    pub const fn new(bank_addr: [Option<StaticRef<FsmcBank>>; 4], rcc: &'a rcc::Rcc) -> Self {
+    pub fn new(bank_addr: [Option<StaticRef<FsmcBank>>; 4], rcc: &'a rcc::Rcc) -> Self {
         Self {
             registers: FSMC_BASE,
             bank: bank_addr,
@@ -186,6 +183,7 @@ impl<'a> Fsmc<'a> {
             buffer: TakeCell::empty(),
             bus_width: Cell::new(1),
             len: Cell::new(0),
+            deferred_call: DeferredCall::with_priority(Priority::High),
         }
     }
 
@@ -293,12 +291,22 @@ impl ClockInterface for FsmcClock<'_> {
     }
 }
 
+impl DeferredCallClient for Fsmc<'_> {
+    fn handle_deferred_call(&self) {
+        self.handle_interrupt();
+    }
+
+    fn register(&'static self) {
+        self.deferred_call.register(self);
+    }
+}
+
 impl Bus8080<'static> for Fsmc<'_> {
     fn set_addr(&self, addr_width: BusWidth, addr: usize) -> Result<(), ErrorCode> {
         match addr_width {
             BusWidth::Bits8 => {
                 self.write_reg(FsmcBanks::Bank1, addr as u16);
-                DEFERRED_CALL.set();
+                self.deferred_call.set();
                 Ok(())
             }
             _ => Err(ErrorCode::NOSUPPORT),
@@ -329,7 +337,7 @@ impl Bus8080<'static> for Fsmc<'_> {
             self.buffer.replace(buffer);
             self.bus_width.set(bytes);
             self.len.set(len);
-            DEFERRED_CALL.set();
+            self.deferred_call.set();
             Ok(())
         } else {
             Err((ErrorCode::NOMEM, buffer))
@@ -360,7 +368,7 @@ impl Bus8080<'static> for Fsmc<'_> {
             self.buffer.replace(buffer);
             self.bus_width.set(bytes);
             self.len.set(len);
-            DEFERRED_CALL.set();
+            self.deferred_call.set();
             Ok(())
         } else {
             Err((ErrorCode::NOMEM, buffer))
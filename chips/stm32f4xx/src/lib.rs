@@ -14,7 +14,6 @@ pub mod nvic;
 // Peripherals
 pub mod adc;
 pub mod dbg;
-pub mod deferred_calls;
 pub mod dma1;
 pub mod exti;
 pub mod fsmc;
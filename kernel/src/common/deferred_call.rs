@@ -1,84 +1,361 @@
 //! Deferred call mechanism.
 //!
-//! This is a tool to allow chip peripherals to schedule "interrupts"
-//! in the chip scheduler if the hardware doesn't support interrupts where
-//! they are needed.
-
-use core::cell::UnsafeCell;
-use core::convert::Into;
-use core::convert::TryFrom;
-use core::convert::TryInto;
-use core::intrinsics;
-use core::marker::Copy;
-use core::marker::Sync;
-
-/// AtomicUsize with no CAS operations that works on targets that have "no atomic
-/// support" according to their specification. This makes it work on thumbv6
-/// platforms.
+//! A deferred call lets a kernel component schedule a call to itself from the
+//! kernel main loop instead of calling its client directly. Chip peripherals
+//! use them when the hardware doesn't raise an interrupt where one is needed,
+//! virtualizers use them to issue callbacks outside of the caller's stack,
+//! and long-running in-kernel algorithms use them to yield to the scheduler
+//! and be called again later.
+//!
+//! Any component can create a [`DeferredCall`] in its constructor. The
+//! deferred call is stored in the component itself and the kernel links
+//! registered deferred calls into a list, so there is no array for the board
+//! to size, no limit on the number of deferred calls, and nothing to free. The
+//! component implements [`DeferredCallClient`], and the code that sets the
+//! component up (a component in `boards/components`, or the chip's peripheral
+//! initialization) calls [`register()`](DeferredCallClient::register) once it
+//! has a `'static` reference. A deferred call that is set before it is
+//! registered stays pending and is serviced once it is registered.
+//!
+//! Every deferred call has a [`Priority`]. When the kernel services deferred
+//! calls it runs all pending `High` calls before `Normal` ones, and `Normal`
+//! ones before `Low` ones. Each pending call runs at most once per pass of the
+//! kernel loop, so a component that sets its deferred call again from within
+//! `handle_deferred_call()` cannot starve the rest of the system. Servicing
+//! stops as soon as an interrupt is pending, so a low priority call may wait
+//! several passes; [`stats()`] reports how long each call was pending so that
+//! this starvation can be observed.
+//!
+//! Usage
+//! -----
+//!
+//! ```
+//! use kernel::common::deferred_call::{DeferredCall, DeferredCallClient};
+//!
+//! struct SomeCapsule {
+//!     deferred_call: DeferredCall,
+//! }
+//!
+//! impl SomeCapsule {
+//!     pub fn new() -> Self {
+//!         SomeCapsule {
+//!             deferred_call: DeferredCall::new(),
+//!         }
+//!     }
+//!
+//!     pub fn start(&self) {
+//!         // `handle_deferred_call()` runs from the main loop later.
+//!         self.deferred_call.set();
+//!     }
+//! }
+//!
+//! impl DeferredCallClient for SomeCapsule {
+//!     fn handle_deferred_call(&self) {
+//!         // Issue callbacks, continue work, etc.
+//!     }
+//!
+//!     fn register(&'static self) {
+//!         self.deferred_call.register(self);
+//!     }
+//! }
+//!
+//! let some_capsule = unsafe { kernel::static_init!(SomeCapsule, SomeCapsule::new()) };
+//! some_capsule.register();
+//! some_capsule.start();
+//! ```
+
+use core::cell::Cell;
+
+use crate::common::cells::OptionalCell;
+use crate::common::list::{List, ListLink, ListNode};
+use crate::hil::time::{Frequency, Ticks, Time};
+
+/// The order in which pending deferred calls are serviced.
+#[derive(Copy, Clone, Debug, PartialEq, Eq)]
+pub enum Priority {
+    /// Serviced before all other deferred calls. Chip peripherals use this
+    /// for deferred calls that stand in for hardware interrupts.
+    High = 0,
+    /// The default for capsules.
+    Normal = 1,
+    /// Only serviced once no higher priority deferred call is pending, for
+    /// background work that can wait.
+    Low = 2,
+}
+
+/// Client of a [`DeferredCall`].
+pub trait DeferredCallClient {
+    /// Called from the kernel main loop after the deferred call was set.
+    fn handle_deferred_call(&self);
+
+    /// Register this client with its deferred call, typically by calling
+    /// [`DeferredCall::register()`]. This is done by the code that sets up
+    /// the client, once it has a `'static` reference to it.
+    fn register(&'static self);
+}
+
+/// A clock used to measure how long deferred calls are pending.
+///
+/// This is implemented for every [`Time`], so boards can pass any alarm or
+/// counter to [`set_clock()`].
+pub trait DeferredCallClock {
+    /// The current time, in ticks that may wrap.
+    fn timestamp(&self) -> u32;
+
+    /// Microseconds elapsed between the tick values `start` and `end`.
+    fn elapsed_us(&self, start: u32, end: u32) -> u32;
+}
+
+impl<T: Time> DeferredCallClock for T {
+    fn timestamp(&self) -> u32 {
+        self.now().into_u32()
+    }
+
+    fn elapsed_us(&self, start: u32, end: u32) -> u32 {
+        let ticks = T::Ticks::from(end)
+            .wrapping_sub(T::Ticks::from(start))
+            .into_u32();
+        (ticks as u64 * 1_000_000 / T::Frequency::frequency() as u64) as u32
+    }
+}
+
+/// How often a deferred call ran and how long it waited to do so.
 ///
-/// Borrowed from https://github.com/japaric/heapless/blob/master/src/ring_buffer/mod.rs
-/// See: https://github.com/japaric/heapless/commit/37c8b5b63780ed8811173dc1ec8859cd99efa9ad
-struct AtomicUsize {
-    v: UnsafeCell<usize>,
+/// Pending times are only measured once a clock has been provided with
+/// [`set_clock()`], and are zero until then.
+#[derive(Copy, Clone, Debug)]
+pub struct DeferredCallStats {
+    pub priority: Priority,
+    /// Number of times the deferred call has been serviced.
+    pub calls: usize,
+    /// Microseconds between the most recent `set()` and its service.
+    pub last_pending_us: u32,
+    /// The longest time the deferred call has been pending.
+    pub max_pending_us: u32,
+}
+
+/// A deferred call, serviced by the kernel main loop once it is set.
+pub struct DeferredCall {
+    priority: Priority,
+    client: OptionalCell<&'static dyn DeferredCallClient>,
+    registry: OptionalCell<&'static DeferredCallRegistry>,
+    pending: Cell<bool>,
+    /// Whether the call was pending when the current service pass started.
+    queued: Cell<bool>,
+    pending_since: Cell<u32>,
+    calls: Cell<usize>,
+    last_pending_us: Cell<u32>,
+    max_pending_us: Cell<u32>,
+    next: ListLink<'static, DeferredCall>,
+}
+
+impl ListNode<'static, DeferredCall> for DeferredCall {
+    fn next(&'static self) -> &'static ListLink<'static, DeferredCall> {
+        &self.next
+    }
 }
 
-impl AtomicUsize {
-    pub(crate) const fn new(v: usize) -> AtomicUsize {
-        AtomicUsize {
-            v: UnsafeCell::new(v),
+impl DeferredCall {
+    /// Create a new deferred call with `Normal` priority.
+    pub const fn new() -> DeferredCall {
+        DeferredCall::with_priority(Priority::Normal)
+    }
+
+    /// Create a new deferred call with the given priority.
+    pub const fn with_priority(priority: Priority) -> DeferredCall {
+        DeferredCall {
+            priority,
+            client: OptionalCell::empty(),
+            registry: OptionalCell::empty(),
+            pending: Cell::new(false),
+            queued: Cell::new(false),
+            pending_since: Cell::new(0),
+            calls: Cell::new(0),
+            last_pending_us: Cell::new(0),
+            max_pending_us: Cell::new(0),
+            next: ListLink::empty(),
         }
     }
 
-    pub(crate) fn load_relaxed(&self) -> usize {
-        unsafe { intrinsics::atomic_load_relaxed(self.v.get()) }
+    /// Set the client that is called when this deferred call is serviced, and
+    /// add the deferred call to the ones the kernel services. Registering a
+    /// deferred call again has no effect.
+    pub fn register(&'static self, client: &'static dyn DeferredCallClient) {
+        registry().register(self, client);
+    }
+
+    /// Mark the deferred call as pending. Setting a call that is already
+    /// pending has no effect; its client is still called once.
+    pub fn set(&self) {
+        if !self.pending.get() {
+            self.start_pending_time();
+            self.pending.set(true);
+            self.registry.map(|registry| registry.pending_added());
+        }
     }
 
-    pub(crate) fn store_relaxed(&self, val: usize) {
-        unsafe { intrinsics::atomic_store_relaxed(self.v.get(), val) }
+    fn start_pending_time(&self) {
+        self.registry.map(|registry| {
+            registry
+                .clock
+                .map(|clock| self.pending_since.set(clock.timestamp()))
+        });
     }
 
-    pub(crate) fn fetch_or_relaxed(&self, val: usize) {
-        unsafe { intrinsics::atomic_store_relaxed(self.v.get(), self.load_relaxed() | val) }
+    /// Whether the deferred call is set and has not been serviced yet.
+    pub fn is_pending(&self) -> bool {
+        self.pending.get()
     }
-}
 
-unsafe impl Sync for AtomicUsize {}
+    /// Statistics for this deferred call.
+    pub fn stats(&self) -> DeferredCallStats {
+        DeferredCallStats {
+            priority: self.priority,
+            calls: self.calls.get(),
+            last_pending_us: self.last_pending_us.get(),
+            max_pending_us: self.max_pending_us.get(),
+        }
+    }
+}
 
-static DEFERRED_CALL: AtomicUsize = AtomicUsize::new(0);
+impl Default for DeferredCall {
+    fn default() -> DeferredCall {
+        DeferredCall::new()
+    }
+}
 
-/// Are there any pending `DeferredCall`s?
-pub fn has_tasks() -> bool {
-    DEFERRED_CALL.load_relaxed() != 0
+/// A set of registered deferred calls, serviced together.
+///
+/// The kernel services the deferred calls registered with
+/// [`DeferredCall::register()`]; the free functions of this module operate on
+/// those. Other registries are only useful for testing.
+pub struct DeferredCallRegistry {
+    /// The registered deferred calls of each priority level.
+    calls: [List<'static, DeferredCall>; 3],
+    /// The number of registered deferred calls that are pending.
+    pending: Cell<usize>,
+    clock: OptionalCell<&'static dyn DeferredCallClock>,
 }
 
-/// Represents a way to generate an asynchronous call without a hardware
-/// interrupt. Supports up to 32 possible deferrable tasks.
-pub struct DeferredCall<T>(T);
+impl DeferredCallRegistry {
+    pub const fn new() -> DeferredCallRegistry {
+        DeferredCallRegistry {
+            calls: [List::new(), List::new(), List::new()],
+            pending: Cell::new(0),
+            clock: OptionalCell::empty(),
+        }
+    }
+
+    /// Register `call` with `client`. A deferred call can only be registered
+    /// once, with a single registry; registering it again has no effect.
+    pub fn register(
+        &'static self,
+        call: &'static DeferredCall,
+        client: &'static dyn DeferredCallClient,
+    ) {
+        if call.registry.is_some() {
+            // Linking the call into a list again would create a cycle.
+            return;
+        }
+        call.client.set(client);
+        call.registry.set(self);
+        if call.pending.get() {
+            // Set before it was registered: it has been pending since now.
+            call.start_pending_time();
+            self.pending_added();
+        }
+        self.calls[call.priority as usize].push_tail(call);
+    }
 
-impl<T: Into<usize> + TryFrom<usize> + Copy> DeferredCall<T> {
-    /// Creates a new DeferredCall
-    ///
-    /// Only create one per task, preferably in the module that it will be used
-    /// in.
-    pub const unsafe fn new(task: T) -> Self {
-        DeferredCall(task)
+    /// Provide the clock used to measure how long deferred calls are pending.
+    pub fn set_clock(&self, clock: &'static dyn DeferredCallClock) {
+        self.clock.set(clock);
     }
 
-    /// Set the `DeferredCall` as pending
-    pub fn set(&self) {
-        DEFERRED_CALL.fetch_or_relaxed(1 << self.0.into() as usize);
-    }
-
-    /// Gets and clears the next pending `DeferredCall`
-    pub fn next_pending() -> Option<T> {
-        let val = DEFERRED_CALL.load_relaxed();
-        if val == 0 {
-            None
-        } else {
-            let bit = val.trailing_zeros() as usize;
-            let new_val = val & !(1 << bit);
-            DEFERRED_CALL.store_relaxed(new_val);
-            bit.try_into().ok()
+    fn pending_added(&self) {
+        self.pending.set(self.pending.get() + 1);
+    }
+
+    fn iter(&self) -> impl Iterator<Item = &'static DeferredCall> + '_ {
+        self.calls.iter().flat_map(|calls| calls.iter())
+    }
+
+    /// Are there any pending deferred calls?
+    pub fn has_tasks(&self) -> bool {
+        self.pending.get() > 0
+    }
+
+    /// Statistics for each registered deferred call, highest priority first.
+    pub fn stats(&self) -> impl Iterator<Item = DeferredCallStats> + '_ {
+        self.iter().map(|call| call.stats())
+    }
+
+    /// Service deferred calls that were pending when this was called, in
+    /// priority order, while the supplied predicate returns `true`. Deferred
+    /// calls set while servicing are left for the next call.
+    pub fn service_while<F: Fn() -> bool>(&self, f: F) {
+        for call in self.iter() {
+            call.queued.set(call.pending.get());
+        }
+        for call in self.iter() {
+            if !call.queued.get() {
+                continue;
+            }
+            if !f() {
+                return;
+            }
+            call.queued.set(false);
+            call.pending.set(false);
+            self.pending.set(self.pending.get() - 1);
+
+            self.clock.map(|clock| {
+                let pending_us = clock.elapsed_us(call.pending_since.get(), clock.timestamp());
+                call.last_pending_us.set(pending_us);
+                if pending_us > call.max_pending_us.get() {
+                    call.max_pending_us.set(pending_us);
+                }
+            });
+            call.calls.set(call.calls.get() + 1);
+            call.client.map(|client| client.handle_deferred_call());
         }
     }
 }
+
+/// The deferred calls serviced by the kernel.
+struct KernelRegistry(DeferredCallRegistry);
+
+// Safety: the registry is made of `Cell`s, which are not `Sync`. The kernel
+// runs on a single thread, and interrupt handlers only mark interrupts as
+// pending for the kernel loop to service, so deferred calls are only set and
+// serviced from that thread and the registry is never accessed concurrently.
+unsafe impl Sync for KernelRegistry {}
+
+static KERNEL_REGISTRY: KernelRegistry = KernelRegistry(DeferredCallRegistry::new());
+
+fn registry() -> &'static DeferredCallRegistry {
+    &KERNEL_REGISTRY.0
+}
+
+/// Provide the clock used to measure how long deferred calls are pending.
+pub fn set_clock(clock: &'static dyn DeferredCallClock) {
+    registry().set_clock(clock);
+}
+
+/// Are there any pending `DeferredCall`s?
+pub fn has_tasks() -> bool {
+    registry().has_tasks()
+}
+
+/// Statistics for each registered deferred call, highest priority first.
+pub fn stats() -> impl Iterator<Item = DeferredCallStats> {
+    registry().stats()
+}
+
+/// Service deferred calls that were pending when this was called, in priority
+/// order, while the supplied predicate returns `true`.
+///
+/// This gets called by the kernel scheduler automatically. Deferred calls set
+/// while servicing are left for the next call.
+pub fn service_while<F: Fn() -> bool>(f: F) {
+    registry().service_while(f)
+}
@@ -22,11 +22,52 @@
 //! constants throughout the code, so for example a boolean condition used in an `if` block will in
 //! principle have a zero cost on the resulting binary - as if a Cargo feature was used instead.
 //! Some simple experiments on generated Tock code have confirmed this zero cost in practice.
+//!
+//! Boards still need to choose the options without editing the kernel crate, so the values in
+//! `CONFIG` come from Cargo features of the kernel crate, read with `cfg!()`. This keeps the
+//! benefits above: `cfg!()` only produces a `bool`, so the code of disabled options is still
+//! type-checked. A board enables options in its `Cargo.toml`:
+//!
+//! ```toml
+//! kernel = { path = "../../kernel", features = ["debug_process_faults", "panic_summary"] }
+//! ```
+//!
+//! Since Cargo unifies features, all boards built in the same `cargo` invocation get the options
+//! any of them enables. Boards are normally built one at a time, so this only matters when
+//! building the whole workspace.
+
+/// How much the kernel prints when it panics.
+#[derive(Copy, Clone, PartialEq, Eq)]
+pub(crate) enum PanicVerbosity {
+    /// Print the panic message, the debug buffer, the CPU state and the memory map and registers
+    /// of every process.
+    Full,
+    /// Print the panic message, the debug buffer, the CPU state and one line per process. Enabled
+    /// by the `panic_summary` feature.
+    Summary,
+    /// Print only the panic message and the debug buffer. Enabled by the `panic_banner_only`
+    /// feature.
+    Banner,
+}
+
+/// How much the kernel prints when a process faults. Faults that panic the kernel are reported
+/// by the panic handler instead.
+#[derive(Copy, Clone, PartialEq, Eq)]
+pub(crate) enum FaultDetail {
+    /// Print nothing.
+    None,
+    /// Print the name of the process, how often it restarted and what the fault policy decided.
+    /// Enabled by the `debug_process_faults` feature.
+    Summary,
+    /// Also print the memory map and registers of the process. Enabled by the
+    /// `debug_process_faults_full` feature.
+    Full,
+}
 
 /// Data structure holding compile-time configuration options.
 ///
-/// To change the configuration, modify the relevant values in the `CONFIG` constant object defined
-/// at the end of this file.
+/// Each option is set by the Cargo feature of the kernel crate of the same name, unless
+/// documented otherwise.
 pub(crate) struct Config {
     /// Whether the kernel should trace syscalls to the debug output.
     ///
@@ -40,11 +81,44 @@ pub(crate) struct Config {
     /// into which SRAM addresses. This can be useful to debug whether the kernel could
     /// successfully load processes, and whether the allocated SRAM is as expected.
     pub(crate) debug_load_processes: bool,
+
+    /// Whether the kernel should show debugging output about grants.
+    ///
+    /// If enabled, the kernel will print each grant it allocates or frees for a process, and
+    /// grants it cannot allocate because the grant region is full. Drivers which do not allocate
+    /// their grant when a process subscribes to one of their upcalls are reported with
+    /// `trace_syscalls`.
+    pub(crate) debug_grants: bool,
+
+    /// Whether the kernel should show the system calls the platform's syscall filter denies.
+    pub(crate) debug_syscall_filter: bool,
+
+    /// What the kernel prints when a process faults.
+    pub(crate) process_fault_detail: FaultDetail,
+
+    /// What the kernel prints when it panics.
+    pub(crate) panic_verbosity: PanicVerbosity,
 }
 
 /// A unique instance of `Config` where compile-time configuration options are defined. These
 /// options are available in the kernel crate to be used for relevant configuration.
 pub(crate) const CONFIG: Config = Config {
-    trace_syscalls: false,
-    debug_load_processes: false,
+    trace_syscalls: cfg!(feature = "trace_syscalls"),
+    debug_load_processes: cfg!(feature = "debug_load_processes"),
+    debug_grants: cfg!(feature = "debug_grants"),
+    debug_syscall_filter: cfg!(feature = "debug_syscall_filter"),
+    process_fault_detail: if cfg!(feature = "debug_process_faults_full") {
+        FaultDetail::Full
+    } else if cfg!(feature = "debug_process_faults") {
+        FaultDetail::Summary
+    } else {
+        FaultDetail::None
+    },
+    panic_verbosity: if cfg!(feature = "panic_banner_only") {
+        PanicVerbosity::Banner
+    } else if cfg!(feature = "panic_summary") {
+        PanicVerbosity::Summary
+    } else {
+        PanicVerbosity::Full
+    },
 };
@@ -51,6 +51,7 @@
 
 use crate::ErrorCode;
 use core::cell::Cell;
+use core::cmp;
 use core::fmt::{write, Arguments, Result, Write};
 use core::panic::PanicInfo;
 use core::str;
@@ -59,8 +60,9 @@ use crate::common::cells::NumericCellExt;
 use crate::common::cells::{MapCell, TakeCell};
 use crate::common::queue::Queue;
 use crate::common::ring_buffer::RingBuffer;
+use crate::config::{self, PanicVerbosity};
 use crate::hil;
-use crate::process::Process;
+use crate::process::{Process, ProcessSlot};
 use crate::Chip;
 
 /// This trait is similar to std::io::Write in that it takes bytes instead of a string (contrary to
@@ -92,7 +94,8 @@ pub trait IoWrite {
 ///
 /// This is useful for boards which do not feature LEDs to blink or
 /// want to implement their own behaviour. This method returns after
-/// performing the panic dump.
+/// performing the panic dump. How much is printed depends on the
+/// `panic_summary` and `panic_banner_only` features of the kernel.
 ///
 /// After this method returns, the system is no longer in a
 /// well-defined state. Care must be taken on how one interacts with
@@ -103,15 +106,63 @@ pub unsafe fn panic_print<W: Write + IoWrite, C: Chip>(
     writer: &mut W,
     panic_info: &PanicInfo,
     nop: &dyn Fn(),
-    processes: &'static [Option<&'static dyn Process>],
+    processes: &'static [ProcessSlot],
     chip: &'static Option<&'static C>,
 ) {
     panic_begin(nop);
-    panic_banner(writer, panic_info);
+    // The banner and CPU state are saved in the panic record as they are
+    // printed, so that the chip state is only read once.
+    let mut record = PANIC_RECORD.as_deref_mut();
+    if let Some(record) = record.as_mut() {
+        record.clear();
+    }
+    panic_banner(
+        &mut PanicRecordWriter {
+            writer: &mut *writer,
+            record: record.as_deref_mut(),
+        },
+        panic_info,
+    );
     // Flush debug buffer if needed
     flush(writer);
-    panic_cpu_state(chip, writer);
-    panic_process_info(processes, writer);
+    let mut tee = PanicRecordWriter {
+        writer: &mut *writer,
+        record: record.as_deref_mut(),
+    };
+    match config::CONFIG.panic_verbosity {
+        PanicVerbosity::Full => {
+            panic_cpu_state(chip, &mut tee);
+            panic_process_info(processes, writer);
+        }
+        PanicVerbosity::Summary => {
+            panic_cpu_state(chip, &mut tee);
+            panic_process_summary(processes, writer);
+        }
+        PanicVerbosity::Banner => {
+            if let Some(record) = record.as_deref_mut() {
+                panic_cpu_state(chip, record);
+            }
+        }
+    }
+    if let Some(record) = record {
+        record.seal();
+    }
+}
+
+/// Writes panic output both to the board's writer and to the panic record,
+/// if there is one.
+struct PanicRecordWriter<'a, W: Write> {
+    writer: &'a mut W,
+    record: Option<&'a mut PanicRecord>,
+}
+
+impl<W: Write> Write for PanicRecordWriter<'_, W> {
+    fn write_str(&mut self, s: &str) -> Result {
+        if let Some(record) = self.record.as_mut() {
+            let _ = record.write_str(s);
+        }
+        self.writer.write_str(s)
+    }
 }
 
 /// Tock default panic routine.
@@ -122,7 +173,7 @@ pub unsafe fn panic<L: hil::led::Led, W: Write + IoWrite, C: Chip>(
     writer: &mut W,
     panic_info: &PanicInfo,
     nop: &dyn Fn(),
-    processes: &'static [Option<&'static dyn Process>],
+    processes: &'static [ProcessSlot],
     chip: &'static Option<&'static C>,
 ) -> ! {
     // Call `panic_print` first which will print out the panic
@@ -176,17 +227,150 @@ pub unsafe fn panic_cpu_state<W: Write, C: Chip>(
 /// More detailed prints about all processes.
 ///
 /// **NOTE:** The supplied `writer` must be synchronous.
-pub unsafe fn panic_process_info<W: Write>(
-    procs: &'static [Option<&'static dyn Process>],
-    writer: &mut W,
-) {
+pub unsafe fn panic_process_info<W: Write>(procs: &'static [ProcessSlot], writer: &mut W) {
     // print data about each process
     let _ = writer.write_fmt(format_args!("\r\n---| App Status |---\r\n"));
-    for idx in 0..procs.len() {
-        procs[idx].as_ref().map(|process| {
-            process.print_full_process(writer);
-        });
+    for process in procs.iter().filter_map(ProcessSlot::get) {
+        process.print_full_process(writer);
+    }
+}
+
+/// One line about each process: its name, state and how often it restarted.
+///
+/// **NOTE:** The supplied `writer` must be synchronous.
+pub unsafe fn panic_process_summary<W: Write>(procs: &'static [ProcessSlot], writer: &mut W) {
+    let _ = writer.write_fmt(format_args!("\r\n---| App Status |---\r\n"));
+    for process in procs.iter().filter_map(ProcessSlot::get) {
+        let _ = writer.write_fmt(format_args!(
+            "{:<20} {:?}, {} restarts\r\n",
+            process.get_process_name(),
+            process.get_state(),
+            process.get_restart_count(),
+        ));
+    }
+}
+
+/// Print the memory map and registers of `process` to the debug output, if
+/// the board set a debug writer.
+pub(crate) fn debug_process(process: &dyn Process) {
+    if let Some(writer) = unsafe { try_get_debug_writer() } {
+        process.print_full_process(writer);
+        writer.publish_bytes();
+    }
+}
+
+/// Save the panic banner and CPU state in the panic record, if the board set
+/// one with `set_panic_record()`.
+///
+/// `panic_print()` already saves its output in the record, so this is only
+/// for boards with a panic routine of their own.
+pub unsafe fn panic_record<C: Chip>(panic_info: &PanicInfo, chip: &'static Option<&'static C>) {
+    if let Some(record) = PANIC_RECORD.as_mut() {
+        record.clear();
+        panic_banner(*record, panic_info);
+        panic_cpu_state(chip, *record);
+        record.seal();
+    }
+}
+
+/// Number of bytes of panic output kept by a `PanicRecord`.
+pub const PANIC_RECORD_LENGTH: usize = 1024;
+
+const PANIC_RECORD_MAGIC: u32 = 0x434e_4150;
+
+/// The output of a kernel panic, kept in memory that is not initialized at
+/// boot so it can be reported after the board resets.
+///
+/// Boards place the record in the `.noinit` section of the kernel linker
+/// script:
+///
+/// ```ignore
+/// #[link_section = ".noinit"]
+/// static mut PANIC_RECORD: kernel::debug::PanicRecord = kernel::debug::PanicRecord::new();
+/// ```
+///
+/// read the panic from before the reset with `text()`, and then pass the record
+/// to `set_panic_record()`. Keeping a record is opt-in: boards that never call
+/// `set_panic_record()` only print their panics. The record holds the panic
+/// message and location, which for kernel faults include the faulting PC and
+/// LR, and the output of `Chip::print_state()`, truncated to
+/// `PANIC_RECORD_LENGTH` bytes. A checksum detects records that were never
+/// written or did not survive the reset, such as after a power cycle.
+#[repr(C)]
+pub struct PanicRecord {
+    magic: u32,
+    length: u32,
+    checksum: u32,
+    text: [u8; PANIC_RECORD_LENGTH],
+}
+
+impl PanicRecord {
+    pub const fn new() -> PanicRecord {
+        PanicRecord {
+            magic: 0,
+            length: 0,
+            checksum: 0,
+            text: [0; PANIC_RECORD_LENGTH],
+        }
+    }
+
+    /// The saved panic output, if the record holds a complete panic.
+    pub fn text(&self) -> Option<&[u8]> {
+        let length = self.length as usize;
+        if self.magic == PANIC_RECORD_MAGIC
+            && length <= PANIC_RECORD_LENGTH
+            && self.checksum == self.compute_checksum()
+        {
+            Some(&self.text[..length])
+        } else {
+            None
+        }
     }
+
+    /// Erase the record so it no longer holds a panic.
+    pub fn clear(&mut self) {
+        self.magic = 0;
+        self.length = 0;
+        self.checksum = 0;
+    }
+
+    /// Mark the text written to the record as a complete panic.
+    pub fn seal(&mut self) {
+        self.checksum = self.compute_checksum();
+        self.magic = PANIC_RECORD_MAGIC;
+    }
+
+    /// FNV-1a hash of the length and text.
+    fn compute_checksum(&self) -> u32 {
+        let length = cmp::min(self.length as usize, PANIC_RECORD_LENGTH);
+        self.length
+            .to_le_bytes()
+            .iter()
+            .chain(self.text[..length].iter())
+            .fold(0x811c_9dc5, |hash, byte| {
+                (hash ^ *byte as u32).wrapping_mul(0x0100_0193)
+            })
+    }
+}
+
+impl Write for PanicRecord {
+    /// Appends to the text, silently dropping whatever does not fit.
+    fn write_str(&mut self, s: &str) -> Result {
+        let start = cmp::min(self.length as usize, PANIC_RECORD_LENGTH);
+        let count = cmp::min(s.len(), PANIC_RECORD_LENGTH - start);
+        self.text[start..start + count].copy_from_slice(&s.as_bytes()[..count]);
+        self.length = (start + count) as u32;
+        Ok(())
+    }
+}
+
+static mut PANIC_RECORD: Option<&'static mut PanicRecord> = None;
+
+/// Save the output of future panics in `record`. This clears the record, so
+/// any panic it holds from before the last reset must be read first.
+pub unsafe fn set_panic_record(record: &'static mut PanicRecord) {
+    record.clear();
+    PANIC_RECORD = Some(record);
 }
 
 /// Blinks a recognizable pattern forever.
@@ -223,6 +223,14 @@ pub trait Driver {
         Err((slice, ErrorCode::NOSUPPORT))
     }
 
+    /// Notification that a process unsubscribed upcall `subscribe_num` of
+    /// this driver by subscribing a null upcall.
+    ///
+    /// Subscriptions are handled by the core kernel, so this is how a capsule
+    /// learns about them. A capsule that holds no other state for the process
+    /// can give the memory of its grant back with `Grant::free()`.
+    fn unsubscribed(&self, process_id: ProcessId, subscribe_num: usize) {}
+
     /// Request to allocate a capsule's grant for a specific process.
     ///
     /// The core kernel uses this function to instruct a capsule to ensure its
@@ -35,6 +35,11 @@
 //! memory for their operation, they can use an `Allocator` to request
 //! additional memory from the process's grant region.
 //!
+//! A capsule that no longer needs its state for a process can free the grant
+//! with `Grant::free()`. The kernel then compacts the grant region, moving the
+//! grants allocated after it up to reclaim the memory. Only grants allocated
+//! after the last custom grant of the process can be moved.
+//!
 //! ```text,ignore
 //!                            ┌──────────────────┐
 //!                            │                  │
@@ -127,7 +132,7 @@ use core::ops::{Deref, DerefMut};
 use core::ptr::{write, NonNull};
 use core::slice;
 
-use crate::process::{Error, Process, ProcessCustomGrantIdentifer, ProcessId};
+use crate::process::{Error, Process, ProcessCustomGrantIdentifer, ProcessId, ProcessSlot};
 use crate::sched::Kernel;
 use crate::upcall::{Upcall, UpcallError, UpcallId};
 use crate::ErrorCode;
@@ -208,6 +213,13 @@ impl<'a> GrantUpcallTable<'a> {
         }
     }
 
+    /// Whether the process has subscribed any of the upcalls of this grant.
+    pub fn has_subscriptions(&self) -> bool {
+        self.upcalls
+            .iter()
+            .any(|saved_upcall| saved_upcall.fn_ptr.is_some())
+    }
+
     /// Schedule the specified upcall for the process with r0, r1, r2 as
     /// provided values.
     ///
@@ -449,9 +461,9 @@ impl<'a, T: Default, const NUM_UPCALLS: usize> ProcessGrant<'a, T, NUM_UPCALLS>
                     //            │   NumUpcalls (usize)                 y
                     // 0x003FF20  └────────────────────────────────────  1
                     //
-                    // Note: This allocation is intentionally never freed. A
-                    // grant region is valid once allocated for the lifetime of
-                    // the process.
+                    // Note: This allocation stays valid until the capsule
+                    // frees it with `Grant::free()`, or for the lifetime of
+                    // the process otherwise.
                     //
                     // If the grant could not be allocated this will cause the
                     // `new()` function to return with an error.
@@ -1347,6 +1359,26 @@ impl<T: Default, const NUM_UPCALLS: usize> Grant<T, NUM_UPCALLS> {
         Ok(pg.enter_with_allocator(fun))
     }
 
+    /// Free the grant for a specific process, once the capsule no longer needs
+    /// any state for it, for example after the process unsubscribed (see
+    /// `Driver::unsubscribed()`) and unallowed everything. The memory is
+    /// returned to the grant region of the process, and is allocated again the
+    /// next time the grant is entered.
+    ///
+    /// Upcalls the process subscribed to this grant are forgotten, and any
+    /// that are pending are removed. `T` is not dropped.
+    pub fn free(&self, processid: ProcessId) -> Result<(), Error> {
+        let process = self.kernel.get_process(processid).ok_or(Error::NoSuchApp)?;
+        process.free_grant(self.grant_num)?;
+        for subscribe_num in 0..NUM_UPCALLS {
+            process.remove_pending_upcalls(UpcallId {
+                driver_num: self.driver_num,
+                subscribe_num,
+            });
+        }
+        Ok(())
+    }
+
     /// Run a function on the grant for each active process if the grant has
     /// been allocated for that process.
     ///
@@ -1388,8 +1420,8 @@ pub struct Iter<'a, T: 'a + Default, const NUM_UPCALLS: usize> {
 
     /// Iterator over valid processes.
     subiter: core::iter::FilterMap<
-        core::slice::Iter<'a, Option<&'static dyn Process>>,
-        fn(&Option<&'static dyn Process>) -> Option<&'static dyn Process>,
+        core::slice::Iter<'a, ProcessSlot>,
+        fn(&ProcessSlot) -> Option<&'static dyn Process>,
     >,
 }
 
@@ -0,0 +1,141 @@
+//! Interface for Key-Value (KV) Stores
+//!
+//! This is the third level of the KV store stack described in
+//! `hil::kv_system`. It stores values under unhashed keys of any length and
+//! hashes the keys by calling into the KV system below it.
+//!
+//! This level is in charge of enforcing storage permissions. Every value is
+//! stored together with a header that records the write ID of the process
+//! that stored it (see `StoragePermissions`). A value can only be read by a
+//! caller that may read that write ID, and only be overwritten or deleted by a
+//! caller that may modify it.
+//!
+//! The header is stored in the same buffer as the value, so buffers holding
+//! values must reserve `header_size()` bytes in front of the value. Before
+//! calling `set()`, `add()` or `update()`, slice the value buffer to
+//! `header_size()..header_size() + length`. After `get()` completes, the value
+//! buffer is sliced to the value that was read in the same way.
+
+use crate::common::leasable_buffer::LeasableBuffer;
+use crate::ErrorCode;
+use crate::StoragePermissions;
+
+/// Implement this trait and use `set_client()` in order to receive callbacks.
+pub trait StoreClient {
+    /// This callback is called when the get operation completes
+    ///
+    /// `result`: Nothing on success, 'ErrorCode' on error
+    /// `key`: The key buffer
+    /// `value`: The value buffer, sliced to the value that was read
+    fn get_complete(
+        &self,
+        result: Result<(), ErrorCode>,
+        key: LeasableBuffer<'static, u8>,
+        value: LeasableBuffer<'static, u8>,
+    );
+
+    /// This callback is called when the set, add or update operation
+    /// completes
+    ///
+    /// `result`: Nothing on success, 'ErrorCode' on error
+    /// `key`: The key buffer
+    /// `value`: The value buffer
+    fn set_complete(
+        &self,
+        result: Result<(), ErrorCode>,
+        key: LeasableBuffer<'static, u8>,
+        value: LeasableBuffer<'static, u8>,
+    );
+
+    /// This callback is called when the delete operation completes
+    ///
+    /// `result`: Nothing on success, 'ErrorCode' on error
+    /// `key`: The key buffer
+    fn delete_complete(&self, result: Result<(), ErrorCode>, key: LeasableBuffer<'static, u8>);
+}
+
+/// A key-value store that checks the storage permissions of its callers.
+///
+/// All operations return `BUSY` if an operation is already in progress. The
+/// other errors, returned either immediately or through the `StoreClient`,
+/// are:
+///    `INVAL`: An invalid parameter was passed
+///    `NOSUPPORT`: The key could not be found, or for `add()`, already exists
+///    `NOMEM`: The value could not be stored due to no more space
+///    `SIZE`: The value does not fit in the buffer
+///    `FAIL`: `permissions` do not allow the operation
+pub trait KVStore<'a> {
+    /// Set the client
+    fn set_client(&self, client: &'a dyn StoreClient);
+
+    /// The number of bytes value buffers must reserve in front of the value
+    /// for the header.
+    fn header_size(&self) -> usize;
+
+    /// Retrieve the value stored under `key` into `value`.
+    fn get(
+        &self,
+        key: LeasableBuffer<'static, u8>,
+        value: LeasableBuffer<'static, u8>,
+        permissions: StoragePermissions,
+    ) -> Result<
+        (),
+        (
+            LeasableBuffer<'static, u8>,
+            LeasableBuffer<'static, u8>,
+            ErrorCode,
+        ),
+    >;
+
+    /// Store `value` under `key`, replacing any existing value.
+    fn set(
+        &self,
+        key: LeasableBuffer<'static, u8>,
+        value: LeasableBuffer<'static, u8>,
+        permissions: StoragePermissions,
+    ) -> Result<
+        (),
+        (
+            LeasableBuffer<'static, u8>,
+            LeasableBuffer<'static, u8>,
+            ErrorCode,
+        ),
+    >;
+
+    /// Store `value` under `key` if the key does not exist yet.
+    fn add(
+        &self,
+        key: LeasableBuffer<'static, u8>,
+        value: LeasableBuffer<'static, u8>,
+        permissions: StoragePermissions,
+    ) -> Result<
+        (),
+        (
+            LeasableBuffer<'static, u8>,
+            LeasableBuffer<'static, u8>,
+            ErrorCode,
+        ),
+    >;
+
+    /// Replace the value stored under `key`, which must already exist.
+    fn update(
+        &self,
+        key: LeasableBuffer<'static, u8>,
+        value: LeasableBuffer<'static, u8>,
+        permissions: StoragePermissions,
+    ) -> Result<
+        (),
+        (
+            LeasableBuffer<'static, u8>,
+            LeasableBuffer<'static, u8>,
+            ErrorCode,
+        ),
+    >;
+
+    /// Delete the value stored under `key`.
+    fn delete(
+        &self,
+        key: LeasableBuffer<'static, u8>,
+        permissions: StoragePermissions,
+    ) -> Result<(), (LeasableBuffer<'static, u8>, ErrorCode)>;
+}
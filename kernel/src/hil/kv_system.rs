@@ -30,7 +30,7 @@
 //! This level is also in charge of generating the key hash by calling into
 //! level 2.
 //!
-//! There is currently no implementation of this HIL in Tock.
+//! This level is described by `hil::kv_store`.
 //!
 //! The expected setup inside Tock will look like this:
 //! +-----------------------+
@@ -39,7 +39,7 @@
 //! |                       |
 //! +-----------------------+
 //!
-//!    hil::kv_store (not written yet)
+//!    hil::kv_store
 //!
 //! +-----------------------+
 //! |                       |
@@ -47,7 +47,7 @@
 //! |                       |
 //! +-----------------------+
 //!
-//!    hil::kv_system (this PR)
+//!    hil::kv_system (this file)
 //!
 //! +-----------------------+
 //! |                       |
@@ -67,18 +67,6 @@ impl KeyType for [u8; 8] {}
 
 /// Implement this trait and use `set_client()` in order to receive callbacks.
 pub trait Client<K: KeyType> {
-    /// This callback is called when the append_key operation completes
-    ///
-    /// `result`: Nothing on success, 'ErrorCode' on error
-    /// `unhashed_key`: The unhashed_key buffer
-    /// `key_buf`: The key_buf buffer
-    fn generate_key_complete(
-        &self,
-        result: Result<(), ErrorCode>,
-        unhashed_key: &'static [u8],
-        key_buf: &'static K,
-    );
-
     /// This callback is called when the append_key operation completes
     ///
     /// `result`: Nothing on success, 'ErrorCode' on error
@@ -88,7 +76,7 @@ pub trait Client<K: KeyType> {
         &self,
         result: Result<(), ErrorCode>,
         key: &'static mut K,
-        value: &'static [u8],
+        value: &'static mut [u8],
     );
 
     /// This callback is called when the get_value operation completes
@@ -127,26 +115,23 @@ pub trait KVSystem<'a> {
     /// `unhashed_key`: A unhashed key that should be hashed.
     /// `key_buf`: A buffer to store the hashed key output.
     ///
-    /// On success returns nothing.
-    /// On error the unhashed_key, key_buf and `Result<(), ErrorCode>` will be returned.
-    fn generate_key(
-        &self,
-        unhashed_key: &'static mut [u8],
-        key_buf: &'static mut Self::K,
-    ) -> Result<
-        (),
-        (
-            &'static mut [u8],
-            &'static mut Self::K,
-            Result<(), ErrorCode>,
-        ),
-    >;
+    /// Unlike the other operations this completes immediately: the key is
+    /// only borrowed while it is hashed, so the caller keeps both buffers
+    /// and can pass `key_buf` on to the operation that needs the hash.
+    ///
+    /// On success returns nothing and `key_buf` holds the hashed key.
+    /// On error a `ErrorCode` will be returned.
+    fn generate_key(&self, unhashed_key: &[u8], key_buf: &mut Self::K) -> Result<(), ErrorCode>;
 
     /// Appends the key/value pair.
     ///
     /// `key`: A hashed key. This key will be used in future to retrieve
     ///        or remove the `value`.
-    /// `value`: A buffer containing the data to be stored to flash.
+    /// `value`: A buffer containing the data to be stored to flash. It is
+    ///          handed back to the client in `append_key_complete()`, so
+    ///          the caller can reuse it.
+    /// `length`: The number of bytes at the start of `value` to store, so
+    ///           that `value` can be larger than the data it holds.
     ///
     /// On success nothing will be returned.
     /// On error the key, value and a `Result<(), ErrorCode>` will be returned.
@@ -160,8 +145,16 @@ pub trait KVSystem<'a> {
     fn append_key(
         &self,
         key: &'static mut Self::K,
-        value: &'static [u8],
-    ) -> Result<(), (&'static mut Self::K, &'static [u8], Result<(), ErrorCode>)>;
+        value: &'static mut [u8],
+        length: usize,
+    ) -> Result<
+        (),
+        (
+            &'static mut Self::K,
+            &'static mut [u8],
+            Result<(), ErrorCode>,
+        ),
+    >;
 
     /// Retrieves the value from a specified key.
     ///
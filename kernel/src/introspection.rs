@@ -123,6 +123,40 @@ impl KernelInfo {
             .process_map_or(0, app, |process| process.debug_timeslice_expiration_count())
     }
 
+    /// Returns how many microseconds this app has been scheduled for, including
+    /// the time the kernel spent on its behalf. Only time the app ran under a
+    /// timeslice is measured.
+    pub fn app_cpu_time_us(
+        &self,
+        app: ProcessId,
+        _capability: &dyn ProcessManagementCapability,
+    ) -> u64 {
+        self.kernel
+            .process_map_or(0, app, |process| process.debug_execution_time_us())
+    }
+
+    /// Returns how many of the microseconds this app has been scheduled for
+    /// were spent in the kernel, handling its syscalls and switching to it.
+    pub fn app_kernel_time_us(
+        &self,
+        app: ProcessId,
+        _capability: &dyn ProcessManagementCapability,
+    ) -> u64 {
+        self.kernel
+            .process_map_or(0, app, |process| process.debug_kernel_time_us())
+    }
+
+    /// Returns the number of times this app was resumed with an upcall after
+    /// it yielded.
+    pub fn number_app_wakeups(
+        &self,
+        app: ProcessId,
+        _capability: &dyn ProcessManagementCapability,
+    ) -> usize {
+        self.kernel
+            .process_map_or(0, app, |process| process.debug_wakeup_count())
+    }
+
     /// Returns a tuple of the (the number of grants in the grant region this
     /// app has allocated, total number of grants that exist in the system).
     pub fn number_app_grant_uses(
@@ -152,4 +186,14 @@ impl KernelInfo {
         });
         count.get()
     }
+
+    /// Returns the total number of microseconds all processes have been
+    /// scheduled for.
+    pub fn cpu_time_us(&self, _capability: &dyn ProcessManagementCapability) -> u64 {
+        let total: Cell<u64> = Cell::new(0);
+        self.kernel.process_each(|proc| {
+            total.set(total.get() + proc.debug_execution_time_us());
+        });
+        total.get()
+    }
 }
@@ -1,21 +1,49 @@
 //! Inter-process communication mechanism for Tock.
 //!
 //! This is a special syscall driver that allows userspace applications to
-//! share memory.
+//! share memory, or to exchange bounded messages that the kernel copies from
+//! the sender into a mailbox of the receiver.
+//!
+//! A process offers a service by registering it under a name and version.
+//! The board decides which app may register each name, by its short ID.
+//! Clients look services up by name. The IPC access list in the TBF header of
+//! a service's app lists the short IDs of the apps that may look up, notify or
+//! send requests to its service; other apps are refused with `NOACK`. As with
+//! any use of short IDs, boards should only give them to verified apps.
 
 use crate::capabilities::MemoryAllocationCapability;
+use crate::common::cells::OptionalCell;
 use crate::grant::Grant;
 use crate::process;
-use crate::process::ProcessId;
+use crate::process::{ProcessId, ShortID};
 use crate::sched::Kernel;
 use crate::{
     CommandReturn, Driver, ErrorCode, ReadOnlyProcessBuffer, ReadWriteProcessBuffer,
-    ReadableProcessBuffer,
+    ReadableProcessBuffer, WriteableProcessBuffer,
 };
 
 /// Syscall number
 pub const DRIVER_NUM: usize = 0x10000;
 
+/// Largest message, in bytes, that a process can send.
+pub const MAX_MESSAGE_LEN: usize = 32;
+
+/// Number of messages that can wait in the mailbox of a process to be
+/// received.
+pub const MAILBOX_LEN: usize = 4;
+
+/// Number of messages from the same sender that can wait in the mailbox of a
+/// process, so that a single sender cannot fill the mailbox by itself.
+pub const MAILBOX_LEN_PER_SENDER: usize = 2;
+
+/// Third argument of the upcall scheduled for a message. A notify passes the
+/// address of the shared buffer there instead, or 0, which is never this
+/// value.
+pub const MESSAGE_UPCALL_TAG: usize = usize::MAX;
+
+/// Longest name, in bytes, a service can be registered under.
+pub const MAX_SERVICE_NAME_LEN: usize = 16;
+
 /// Enum to mark which type of upcall is scheduled for the IPC mechanism.
 #[derive(Copy, Clone, Debug)]
 pub enum IPCUpcallType {
@@ -27,12 +55,76 @@ pub enum IPCUpcallType {
     Client,
 }
 
+/// Observes the notifications that processes send each other over IPC, for
+/// example so that a scheduler can implement priority inheritance.
+pub trait IPCObserver {
+    /// `client` notified `service`, and is likely to wait for a response.
+    fn service_notified(&self, client: ProcessId, service: ProcessId);
+
+    /// `service` notified `client`, typically in response to a request.
+    fn client_notified(&self, service: ProcessId, client: ProcessId);
+}
+
+/// Whether a message is a request to a service or a reply to a client.
+#[derive(Copy, Clone, Debug, PartialEq)]
+pub enum MessageKind {
+    Request = 0,
+    Reply = 1,
+}
+
+/// A message copied out of the memory of its sender, waiting in the mailbox
+/// of its receiver.
+#[derive(Copy, Clone)]
+struct Message {
+    /// The identifier of the sender, as passed to notify.
+    sender: usize,
+    kind: MessageKind,
+    len: usize,
+    data: [u8; MAX_MESSAGE_LEN],
+}
+
+impl Message {
+    const EMPTY: Message = Message {
+        sender: 0,
+        kind: MessageKind::Request,
+        len: 0,
+        data: [0; MAX_MESSAGE_LEN],
+    };
+}
+
+/// Binds the name of a service to the app allowed to register it.
+#[derive(Copy, Clone)]
+pub struct ServiceBinding {
+    /// Name of the service.
+    pub name: &'static str,
+    /// Short ID of the app that may register the service.
+    pub short_id: ShortID,
+}
+
+/// A service a process registered.
+#[derive(Copy, Clone)]
+struct Service {
+    name: [u8; MAX_SERVICE_NAME_LEN],
+    name_len: usize,
+    version: u32,
+}
+
 /// State that is stored in each process's grant region to support IPC.
 struct IPCData<const NUM_PROCS: usize> {
     /// An array of process buffers that this application has shared
     /// with other applications.
     shared_memory: [ReadWriteProcessBuffer; NUM_PROCS],
     search_buf: ReadOnlyProcessBuffer,
+    /// The buffer messages are sent from.
+    send_buf: ReadOnlyProcessBuffer,
+    /// The buffer received messages are copied into.
+    receive_buf: ReadWriteProcessBuffer,
+    /// Messages sent to this process, oldest first. Only the first
+    /// `mailbox_len` are valid.
+    mailbox: [Message; MAILBOX_LEN],
+    mailbox_len: usize,
+    /// The service this process registered, if any.
+    service: Option<Service>,
 }
 
 impl<const NUM_PROCS: usize> Default for IPCData<NUM_PROCS> {
@@ -41,6 +133,11 @@ impl<const NUM_PROCS: usize> Default for IPCData<NUM_PROCS> {
         IPCData {
             shared_memory: [DEFAULT_RW_PROC_BUF; NUM_PROCS],
             search_buf: ReadOnlyProcessBuffer::default(),
+            send_buf: ReadOnlyProcessBuffer::default(),
+            receive_buf: ReadWriteProcessBuffer::default(),
+            mailbox: [Message::EMPTY; MAILBOX_LEN],
+            mailbox_len: 0,
+            service: None,
         }
     }
 }
@@ -71,6 +168,10 @@ const CLIENT_UPCALL_NUM_BASE: usize = 1;
 pub struct IPC<const NUM_PROCS: usize, const NUM_UPCALLS: usize> {
     /// The grant regions for each process that holds the per-process IPC data.
     data: Grant<IPCData<NUM_PROCS>, NUM_UPCALLS>,
+    /// Optional observer of notifications between processes.
+    observer: OptionalCell<&'static dyn IPCObserver>,
+    /// Which app may register each service name.
+    services: OptionalCell<&'static [ServiceBinding]>,
 }
 
 impl<const NUM_PROCS: usize, const NUM_UPCALLS: usize> IPC<NUM_PROCS, NUM_UPCALLS> {
@@ -81,9 +182,22 @@ impl<const NUM_PROCS: usize, const NUM_UPCALLS: usize> IPC<NUM_PROCS, NUM_UPCALL
     ) -> Self {
         Self {
             data: kernel.create_grant(driver_num, capability),
+            observer: OptionalCell::empty(),
+            services: OptionalCell::empty(),
         }
     }
 
+    /// Set the observer told about every notification that is delivered.
+    pub fn set_observer(&self, observer: &'static dyn IPCObserver) {
+        self.observer.set(observer);
+    }
+
+    /// Set the names services can be registered under, and which app may
+    /// register each of them. Without bindings no service can be registered.
+    pub fn set_services(&self, services: &'static [ServiceBinding]) {
+        self.services.set(services);
+    }
+
     /// Schedule an IPC upcall for a process. This is called by the main
     /// scheduler loop if an IPC task was queued for the process.
     pub(crate) unsafe fn schedule_upcall(
@@ -154,6 +268,243 @@ impl<const NUM_PROCS: usize, const NUM_UPCALLS: usize> IPC<NUM_PROCS, NUM_UPCALL
             })
             .and_then(|x| x)
     }
+
+    /// Whether the IPC access list of `service` lets `client` connect to it.
+    /// A process may always connect to itself.
+    fn client_allowed(&self, client: ProcessId, service: &dyn process::Process) -> bool {
+        client == service.processid() || service.ipc_client_allowed(client.short_app_id())
+    }
+
+    /// Whether the process `service` lets `client` connect to its service.
+    fn service_allows(&self, service: ProcessId, client: ProcessId) -> bool {
+        self.data.kernel.process_map_or(false, service, |service| {
+            self.client_allowed(client, service)
+        })
+    }
+
+    /// Copy the service name in the search buffer of `appid`.
+    fn service_name(
+        &self,
+        appid: ProcessId,
+    ) -> Result<([u8; MAX_SERVICE_NAME_LEN], usize), ErrorCode> {
+        let res = self.data.enter(appid, |data, _upcalls| {
+            data.search_buf.enter(|slice| {
+                if slice.len() == 0 {
+                    return Err(ErrorCode::INVAL);
+                }
+                if slice.len() > MAX_SERVICE_NAME_LEN {
+                    return Err(ErrorCode::SIZE);
+                }
+                let mut name = [0; MAX_SERVICE_NAME_LEN];
+                slice.copy_to_slice(&mut name[..slice.len()]);
+                Ok((name, slice.len()))
+            })
+        });
+        match res {
+            Ok(Ok(name)) => name,
+            Ok(Err(e)) | Err(e) => Err(e.into()),
+        }
+    }
+
+    /// Find the process that registered the service `name`, and the version
+    /// it registered.
+    fn find_service(&self, name: &[u8]) -> Option<(ProcessId, u32)> {
+        self.data.iter().find_map(|process_grant| {
+            let processid = process_grant.processid();
+            process_grant.enter(|data, _upcalls| {
+                data.service
+                    .filter(|service| &service.name[..service.name_len] == name)
+                    .map(|service| (processid, service.version))
+            })
+        })
+    }
+
+    /// Whether the board binds the service `name` to `appid`.
+    fn service_bound(&self, name: &[u8], appid: ProcessId) -> bool {
+        let short_id = appid.short_app_id();
+        match short_id {
+            ShortID::Fixed(_) => self.services.map_or(false, |services| {
+                services
+                    .iter()
+                    .any(|binding| binding.name.as_bytes() == name && binding.short_id == short_id)
+            }),
+            ShortID::LocallyUnique => false,
+        }
+    }
+
+    /// Register `appid` as the service named in its search buffer, replacing
+    /// any service it registered before.
+    fn register_service(&self, appid: ProcessId, version: u32) -> CommandReturn {
+        let (name, name_len) = match self.service_name(appid) {
+            Ok(name) => name,
+            Err(e) => return CommandReturn::failure(e),
+        };
+        if !self.service_bound(&name[..name_len], appid) {
+            return CommandReturn::failure(ErrorCode::RESERVE);
+        }
+        if self
+            .find_service(&name[..name_len])
+            .map_or(false, |(service, _)| service != appid)
+        {
+            return CommandReturn::failure(ErrorCode::BUSY);
+        }
+        self.data
+            .enter(appid, |data, _upcalls| {
+                data.service = Some(Service {
+                    name,
+                    name_len,
+                    version,
+                });
+                CommandReturn::success()
+            })
+            .unwrap_or_else(|err| CommandReturn::failure(err.into()))
+    }
+
+    /// Look up the service named in the search buffer of `appid`, which must
+    /// have at least version `min_version`.
+    fn lookup_service(&self, appid: ProcessId, min_version: usize) -> CommandReturn {
+        let (name, name_len) = match self.service_name(appid) {
+            Ok(name) => name,
+            Err(e) => return CommandReturn::failure(e),
+        };
+        match self.find_service(&name[..name_len]) {
+            None => CommandReturn::failure(ErrorCode::NODEVICE),
+            Some((service, version)) => {
+                if !self.service_allows(service, appid) {
+                    CommandReturn::failure(ErrorCode::NOACK)
+                } else if (version as usize) < min_version {
+                    CommandReturn::failure_u32(ErrorCode::NOSUPPORT, version)
+                } else {
+                    CommandReturn::success_u32_u32(service.id() as u32 + 1, version)
+                }
+            }
+        }
+    }
+
+    /// Copy the first `len` bytes of the send buffer of `appid` into the
+    /// mailbox of the process `target_id` refers to, and schedule the
+    /// service upcall (for a request) or the client upcall for `appid` (for
+    /// a reply) of that process. The upcall is passed the identifier of the
+    /// sender, the length of the message and `MESSAGE_UPCALL_TAG`.
+    fn send_message(
+        &self,
+        appid: ProcessId,
+        target_id: usize,
+        len: usize,
+        kind: MessageKind,
+    ) -> CommandReturn {
+        let otherapp = match target_id
+            .checked_sub(1)
+            .and_then(|app_identifier| self.data.kernel.lookup_app_by_identifier(app_identifier))
+        {
+            Some(otherapp) => otherapp,
+            None => return CommandReturn::failure(ErrorCode::INVAL),
+        };
+        if kind == MessageKind::Request && !self.service_allows(otherapp, appid) {
+            return CommandReturn::failure(ErrorCode::NOACK);
+        }
+        if len > MAX_MESSAGE_LEN {
+            return CommandReturn::failure(ErrorCode::SIZE);
+        }
+        let upcall_num = match kind {
+            MessageKind::Request => SERVICE_UPCALL_NUM,
+            MessageKind::Reply => match appid.index() {
+                Some(i) => i + CLIENT_UPCALL_NUM_BASE,
+                None => return CommandReturn::failure(ErrorCode::INVAL),
+            },
+        };
+
+        // Copy the message out of the sender first, so that a process can
+        // also send a message to itself.
+        let message = self.data.enter(appid, |data, _upcalls| {
+            data.send_buf.enter(|buf| {
+                if len > buf.len() {
+                    return None;
+                }
+                let mut message = Message {
+                    sender: appid.id() + 1,
+                    kind,
+                    len,
+                    data: [0; MAX_MESSAGE_LEN],
+                };
+                buf[..len].copy_to_slice(&mut message.data[..len]);
+                Some(message)
+            })
+        });
+        let message = match message {
+            Ok(Ok(Some(message))) => message,
+            Ok(Ok(None)) => return CommandReturn::failure(ErrorCode::SIZE),
+            Ok(Err(e)) | Err(e) => return CommandReturn::failure(e.into()),
+        };
+
+        let res = self.data.enter(otherapp, |data, upcalls| {
+            let count = data.mailbox_len;
+            if count == MAILBOX_LEN {
+                return Err(ErrorCode::NOMEM);
+            }
+            let queued = data.mailbox[..count]
+                .iter()
+                .filter(|queued| queued.sender == message.sender)
+                .count();
+            if queued == MAILBOX_LEN_PER_SENDER {
+                return Err(ErrorCode::BUSY);
+            }
+            data.mailbox[count] = message;
+            data.mailbox_len = count + 1;
+            upcalls
+                .schedule_upcall(upcall_num, message.sender, message.len, MESSAGE_UPCALL_TAG)
+                .ok();
+            Ok(())
+        });
+        match res {
+            Ok(Ok(())) => {
+                self.observer.map(|observer| match kind {
+                    MessageKind::Request => observer.service_notified(appid, otherapp),
+                    MessageKind::Reply => observer.client_notified(appid, otherapp),
+                });
+                CommandReturn::success()
+            }
+            Ok(Err(e)) => CommandReturn::failure(e),
+            Err(e) => CommandReturn::failure(e.into()),
+        }
+    }
+
+    /// Copy the oldest message in the mailbox of `appid` into its receive
+    /// buffer and remove it from the mailbox.
+    fn receive_message(&self, appid: ProcessId) -> CommandReturn {
+        self.data
+            .enter(appid, |data, _upcalls| {
+                let count = data.mailbox_len;
+                if count == 0 {
+                    return CommandReturn::failure(ErrorCode::FAIL);
+                }
+                let message = data.mailbox[0];
+                let copied = data
+                    .receive_buf
+                    .mut_enter(|buf| {
+                        if message.len > buf.len() {
+                            false
+                        } else {
+                            buf[..message.len].copy_from_slice(&message.data[..message.len]);
+                            true
+                        }
+                    })
+                    .unwrap_or(false);
+                if !copied {
+                    // Leave the message in the mailbox so that the process
+                    // can retry with a larger buffer.
+                    return CommandReturn::failure_u32(ErrorCode::SIZE, message.len as u32);
+                }
+                data.mailbox[..count].rotate_left(1);
+                data.mailbox_len = count - 1;
+                CommandReturn::success_u32_u32_u32(
+                    message.sender as u32,
+                    message.len as u32,
+                    message.kind as u32,
+                )
+            })
+            .unwrap_or_else(|err| CommandReturn::failure(err.into()))
+    }
 }
 
 impl<const NUM_PROCS: usize, const NUM_UPCALLS: usize> Driver for IPC<NUM_PROCS, NUM_UPCALLS> {
@@ -172,17 +523,39 @@ impl<const NUM_PROCS: usize, const NUM_UPCALLS: usize> Driver for IPC<NUM_PROCS,
     /// - `0`: Driver check, always returns Ok(())
     /// - `1`: Perform discovery on the package name passed to `allow_readonly`. Returns the
     ///        service descriptor if the service is found, otherwise returns an error.
+    ///        Returns `NOACK` if the access list of the app does not allow this process.
     /// - `2`: Notify a service previously discovered to have the service descriptor in
     ///        `target_id`. Returns an error if `target_id` refers to an invalid service or the
-    ///        notify fails to enqueue.
+    ///        notify fails to enqueue, and `NOACK` if the service does not allow this process.
     /// - `3`: Notify a client with descriptor `target_id`, typically in response to a previous
     ///        notify from the client. Returns an error if `target_id` refers to an invalid client
     ///        or the notify fails to enqueue.
+    /// - `4`: Send a request of `len` bytes from the send buffer to the service with descriptor
+    ///        `target_id`, and schedule its service upcall with the descriptor of this process,
+    ///        the length of the message and `MESSAGE_UPCALL_TAG`. Returns `SIZE` if the message
+    ///        is longer than `MAX_MESSAGE_LEN` or the send buffer, `NOMEM` if the mailbox of the
+    ///        service is full, and `BUSY` if `MAILBOX_LEN_PER_SENDER` messages from this
+    ///        process are already waiting in it.
+    /// - `5`: Reply with `len` bytes from the send buffer to the client with descriptor
+    ///        `target_id`, and schedule its client upcall for this process. Fails as `4` does.
+    /// - `6`: Receive the oldest message in the mailbox into the receive buffer. Returns the
+    ///        descriptor of the sender, the length of the message, and whether it is a request
+    ///        (`0`) or a reply (`1`). Returns `FAIL` if the mailbox is empty, and `SIZE` with the
+    ///        length of the message if it does not fit in the receive buffer.
+    /// - `7`: Register this process as the service with the name passed to `allow_readonly`
+    ///        and version `target_id`. Returns `RESERVE` if the board does not bind the name
+    ///        to this app, `BUSY` if another process registered the name, and `SIZE` if the
+    ///        name is longer than `MAX_SERVICE_NAME_LEN`.
+    /// - `8`: Look up the registered service with the name passed to `allow_readonly`. Returns
+    ///        the service descriptor and version. Returns `NODEVICE` if no process registered
+    ///        the name, `NOACK` if the service does not allow this process, and `NOSUPPORT`
+    ///        with the version if it is older than `target_id`.
+    /// - `9`: Unregister the service of this process.
     fn command(
         &self,
         command_number: usize,
         target_id: usize,
-        _: usize,
+        len: usize,
         appid: ProcessId,
     ) -> CommandReturn {
         match command_number {
@@ -204,9 +577,13 @@ impl<const NUM_PROCS: usize, const NUM_UPCALLS: usize> Driver for IPC<NUM_PROCS,
                                                 .zip(slice.iter())
                                                 .all(|(c1, c2)| *c1 == c2.get())
                                         {
-                                            Some(CommandReturn::success_u32(
-                                                p.processid().id() as u32 + 1,
-                                            ))
+                                            if self.client_allowed(appid, p) {
+                                                Some(CommandReturn::success_u32(
+                                                    p.processid().id() as u32 + 1,
+                                                ))
+                                            } else {
+                                                Some(CommandReturn::failure(ErrorCode::NOACK))
+                                            }
                                         } else {
                                             None
                                         }
@@ -231,9 +608,17 @@ impl<const NUM_PROCS: usize, const NUM_UPCALLS: usize> Driver for IPC<NUM_PROCS,
                             CommandReturn::failure(ErrorCode::INVAL),
                             otherapp,
                             |target| {
+                                if !self.client_allowed(appid, target) {
+                                    return CommandReturn::failure(ErrorCode::NOACK);
+                                }
                                 let ret = target.enqueue_task(process::Task::IPC((appid, cb_type)));
                                 match ret {
-                                    Ok(()) => CommandReturn::success(),
+                                    Ok(()) => {
+                                        self.observer.map(|observer| {
+                                            observer.service_notified(appid, otherapp)
+                                        });
+                                        CommandReturn::success()
+                                    }
                                     Err(e) => match e {
                                         // The other side has a null upcall, choosing to ignore
                                         ErrorCode::OFF => CommandReturn::success(),
@@ -260,7 +645,12 @@ impl<const NUM_PROCS: usize, const NUM_UPCALLS: usize> Driver for IPC<NUM_PROCS,
                             |target| {
                                 let ret = target.enqueue_task(process::Task::IPC((appid, cb_type)));
                                 match ret {
-                                    Ok(()) => CommandReturn::success(),
+                                    Ok(()) => {
+                                        self.observer.map(|observer| {
+                                            observer.client_notified(appid, otherapp)
+                                        });
+                                        CommandReturn::success()
+                                    }
                                     Err(e) => match e {
                                         // The other side has a null upcall, choosing to ignore
                                         ErrorCode::OFF => CommandReturn::success(),
@@ -271,29 +661,45 @@ impl<const NUM_PROCS: usize, const NUM_UPCALLS: usize> Driver for IPC<NUM_PROCS,
                         )
                     })
             }
+            4 => self.send_message(appid, target_id, len, MessageKind::Request),
+            5 => self.send_message(appid, target_id, len, MessageKind::Reply),
+            6 => self.receive_message(appid),
+            7 => self.register_service(appid, target_id as u32),
+            8 => self.lookup_service(appid, target_id),
+            9 => self
+                .data
+                .enter(appid, |data, _upcalls| {
+                    data.service = None;
+                    CommandReturn::success()
+                })
+                .unwrap_or_else(|err| CommandReturn::failure(err.into())),
             _ => CommandReturn::failure(ErrorCode::NOSUPPORT),
         }
     }
 
     /// allow_readonly with subdriver number `0` stores the provided buffer for service discovery.
-    /// The buffer should contain the package name of a process that exports an IPC service.
+    /// The buffer should contain the package name of a process that exports an IPC service, or
+    /// the name of a service to register or look up.
+    ///
+    /// allow_readonly with subdriver number `1` stores the buffer messages are sent from.
     fn allow_readonly(
         &self,
         appid: ProcessId,
         subdriver: usize,
         mut buffer: ReadOnlyProcessBuffer,
     ) -> Result<ReadOnlyProcessBuffer, (ReadOnlyProcessBuffer, ErrorCode)> {
-        if subdriver == 0 {
+        if subdriver > 1 {
+            return Err((buffer, ErrorCode::NOSUPPORT));
+        }
+        let res = self.data.enter(appid, |data, _upcalls| match subdriver {
             // Package name for discovery
-            let res = self.data.enter(appid, |data, _upcalls| {
-                core::mem::swap(&mut data.search_buf, &mut buffer);
-            });
-            match res {
-                Ok(_) => Ok(buffer),
-                Err(e) => Err((buffer, e.into())),
-            }
-        } else {
-            Err((buffer, ErrorCode::NOSUPPORT))
+            0 => core::mem::swap(&mut data.search_buf, &mut buffer),
+            // Message to send
+            _ => core::mem::swap(&mut data.send_buf, &mut buffer),
+        });
+        match res {
+            Ok(_) => Ok(buffer),
+            Err(e) => Err((buffer, e.into())),
         }
     }
 
@@ -305,7 +711,8 @@ impl<const NUM_PROCS: usize, const NUM_UPCALLS: usize> Driver for IPC<NUM_PROCS,
     /// specified by the target_id). allow() simply allows both processes to
     /// access the buffer, it does not signal the service.
     ///
-    /// target_id == 0 is currently unsupported and reserved for future use.
+    /// If allow is called with target_id == 0, the buffer is where received
+    /// messages are copied to.
     fn allow_readwrite(
         &self,
         appid: ProcessId,
@@ -313,7 +720,12 @@ impl<const NUM_PROCS: usize, const NUM_UPCALLS: usize> Driver for IPC<NUM_PROCS,
         mut buffer: ReadWriteProcessBuffer,
     ) -> Result<ReadWriteProcessBuffer, (ReadWriteProcessBuffer, ErrorCode)> {
         if target_id == 0 {
-            Err((buffer, ErrorCode::NOSUPPORT))
+            match self.data.enter(appid, |data, _upcalls| {
+                core::mem::swap(&mut data.receive_buf, &mut buffer);
+            }) {
+                Ok(_) => Ok(buffer),
+                Err(e) => Err((buffer, e.into())),
+            }
         } else {
             match self.data.enter(appid, |data, _upcalls| {
                 // Lookup the index of the app based on the passed in
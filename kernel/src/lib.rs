@@ -97,6 +97,7 @@ pub mod hil;
 pub mod introspection;
 pub mod ipc;
 pub mod syscall;
+pub mod trace;
 
 mod config;
 mod driver;
@@ -106,10 +107,13 @@ mod mem;
 mod memop;
 mod platform;
 mod process;
+mod process_checker;
 mod process_policies;
 mod process_standard;
 mod process_utilities;
 mod sched;
+mod storage_permissions;
+mod thread;
 mod upcall;
 
 pub use crate::driver::{CommandReturn, Driver};
@@ -120,16 +124,22 @@ pub use crate::mem::{
     ReadOnlyProcessBuffer, ReadWriteProcessBuffer, ReadableProcessBuffer, ReadableProcessByte,
     ReadableProcessSlice, WriteableProcessBuffer, WriteableProcessSlice,
 };
+pub use crate::platform::power;
 pub use crate::platform::scheduler_timer::{SchedulerTimer, VirtualSchedulerTimer};
+pub use crate::platform::sleep;
 pub use crate::platform::watchdog;
 pub use crate::platform::{mpu, Chip, InterruptService, Platform};
 pub use crate::platform::{ClockInterface, NoClockControl, NO_CLOCK_CONTROL};
-pub use crate::process::ProcessId;
+pub use crate::platform::{SyscallFilter, TbfHeaderFilterDefaultAllow};
+pub use crate::process::{ProcessId, ShortID};
 pub use crate::sched::cooperative::{CoopProcessNode, CooperativeSched};
+pub use crate::sched::edf::{EDFProcessNode, EDFSched, OverrunClient};
 pub use crate::sched::mlfq::{MLFQProcessNode, MLFQSched};
 pub use crate::sched::priority::PrioritySched;
 pub use crate::sched::round_robin::{RoundRobinProcessNode, RoundRobinSched};
-pub use crate::sched::{Kernel, Scheduler};
+pub use crate::sched::tbf_priority::TbfPrioritySched;
+pub use crate::sched::{Kernel, Scheduler, StoppedExecutingReason};
+pub use crate::storage_permissions::StoragePermissions;
 pub use crate::upcall::UpcallError;
 
 // Export only select items from the process module. To remove the name conflict
@@ -139,13 +149,23 @@ pub use crate::upcall::UpcallError;
 /// Publicly available process-related objects.
 pub mod procs {
     pub use crate::process::{
-        Error, FaultAction, FunctionCall, FunctionCallSource, Process, State, Task,
+        Error, FaultAction, FunctionCall, FunctionCallSource, Process, ProcessSlot, State, Task,
+        ThreadState, MAX_THREADS,
+    };
+    pub use crate::process_checker::{
+        AppCheckerVerify, AppCredentialsChecker, AppIdPolicy, CheckResult, CredentialsVerifier,
+        ShortIdFromTbf,
     };
     pub use crate::process_policies::{
-        PanicFaultPolicy, ProcessFaultPolicy, RestartFaultPolicy, StopFaultPolicy,
-        StopWithDebugFaultPolicy, ThresholdRestartFaultPolicy,
-        ThresholdRestartThenPanicFaultPolicy,
+        BackoffRestartFaultPolicy, PanicFaultPolicy, PerAppFaultPolicy, ProcessFaultPolicy,
+        RestartFaultPolicy, RestartRateFaultPolicy, StopFaultPolicy, StopWithDebugFaultPolicy,
+        ThresholdRestartFaultPolicy, ThresholdRestartThenPanicFaultPolicy,
     };
     pub use crate::process_standard::ProcessStandard;
-    pub use crate::process_utilities::{load_processes, ProcessLoadError};
+    pub use crate::process_utilities::{
+        load_and_check_processes, load_processes, DynamicProcessLoader, DynamicProcessLoading,
+        ProcessLoadError,
+    };
+    pub use tock_tbf::types::CommandPermissions;
+    pub use tock_tbf::types::{TbfFooterV2Credentials, TbfFooterV2CredentialsType, TbfHeader};
 }
@@ -5,9 +5,13 @@ use crate::errorcode;
 use crate::process;
 use crate::syscall;
 use core::fmt::Write;
+use sleep::SleepState;
+use tock_tbf::types::CommandPermissions;
 
 pub mod mpu;
+pub mod power;
 pub(crate) mod scheduler_timer;
+pub mod sleep;
 pub mod watchdog;
 
 /// Interface for individual boards.
@@ -53,6 +57,9 @@ pub trait Platform {
     /// be returned to the calling application. The default implementation
     /// allows all system calls.
     ///
+    /// Boards can implement this with a reusable `SyscallFilter`, such as
+    /// `TbfHeaderFilterDefaultAllow`.
+    ///
     /// This API should be considered unstable, and is likely to change in the
     /// future.
     fn filter_syscall(
@@ -99,6 +106,97 @@ pub trait Platform {
     fn process_fault_hook(&self, process: &dyn process::Process) -> Result<(), ()> {
         Err(())
     }
+
+    /// Choose the sleep state to enter when the kernel has nothing to do, as
+    /// an index into `states`, the sleep states of the chip. Returning `None`,
+    /// the default, makes the kernel call `Chip::sleep()`.
+    ///
+    /// Boards can implement this with a reusable `SleepPolicy`, such as
+    /// `DeadlineSleepPolicy`.
+    #[allow(unused_variables)]
+    fn select_sleep_state(&self, states: &[SleepState]) -> Option<usize> {
+        None
+    }
+
+    /// Whether the board is healthy enough for the kernel to tickle the
+    /// hardware watchdog. Returning `false` lets the watchdog expire and reset
+    /// the chip. Boards can use this to reset when a critical process stops
+    /// responding, for example with `capsules::app_watchdog::AppWatchdog`.
+    fn watchdog_healthy(&self) -> bool {
+        true
+    }
+}
+
+/// A policy for which system calls processes may make, which a `Platform` can
+/// use to implement `filter_syscall()`.
+///
+/// ## Example
+///
+/// ```ignore
+/// impl Platform for Hail {
+///     fn filter_syscall(
+///         &self,
+///         process: &dyn kernel::procs::Process,
+///         syscall: &kernel::syscall::Syscall,
+///     ) -> Result<(), kernel::ErrorCode> {
+///         kernel::TbfHeaderFilterDefaultAllow {}.filter_syscall(process, syscall)
+///     }
+/// }
+/// ```
+pub trait SyscallFilter {
+    /// Return `Ok(())` if `process` may make `syscall`, or the `ErrorCode` to
+    /// return to the process otherwise.
+    fn filter_syscall(
+        &self,
+        process: &dyn process::Process,
+        syscall: &syscall::Syscall,
+    ) -> Result<(), errorcode::ErrorCode>;
+}
+
+/// Filter system calls by the permissions in each app's TBF header.
+///
+/// Apps whose header lists permissions may only subscribe, allow and call
+/// commands of the drivers listed, and may only call the commands allowed for
+/// each driver. Thread system calls are checked like commands of
+/// `syscall::THREAD_DRIVER_NUM`, with the operation as the command number. Any
+/// other system call returns `NODEVICE`. Apps without permissions in their
+/// header may make any system call.
+pub struct TbfHeaderFilterDefaultAllow {}
+
+impl SyscallFilter for TbfHeaderFilterDefaultAllow {
+    fn filter_syscall(
+        &self,
+        process: &dyn process::Process,
+        syscall: &syscall::Syscall,
+    ) -> Result<(), errorcode::ErrorCode> {
+        let (driver_number, command_number) = match *syscall {
+            syscall::Syscall::Command {
+                driver_number,
+                subdriver_number,
+                ..
+            } => (driver_number, Some(subdriver_number)),
+            syscall::Syscall::Thread { operation, .. } => {
+                (syscall::THREAD_DRIVER_NUM, Some(operation))
+            }
+            syscall::Syscall::Subscribe { driver_number, .. }
+            | syscall::Syscall::ReadWriteAllow { driver_number, .. }
+            | syscall::Syscall::ReadOnlyAllow { driver_number, .. } => (driver_number, None),
+            _ => return Ok(()),
+        };
+
+        // Upcalls and buffers are allowed for any driver the app may call.
+        let offset = command_number.map_or(0, |command| command / 64);
+        match process.get_command_permissions(driver_number, offset) {
+            CommandPermissions::NoPermsAtAll => Ok(()),
+            CommandPermissions::NoPermsThisDriver => Err(errorcode::ErrorCode::NODEVICE),
+            CommandPermissions::Mask(allowed) => match command_number {
+                Some(command) if allowed & (1 << (command % 64)) == 0 => {
+                    Err(errorcode::ErrorCode::NODEVICE)
+                }
+                _ => Ok(()),
+            },
+        }
+    }
 }
 
 /// Interface for individual MCUs.
@@ -158,6 +256,22 @@ pub trait Chip {
     /// chip and resumes the scheduler.
     fn sleep(&self);
 
+    /// The sleep states this chip supports, ordered from the lightest to the
+    /// deepest. The lightest state must be safe to enter at any time. Chips
+    /// that only support `sleep()` can use the default, which lists no
+    /// states.
+    fn sleep_states(&self) -> &[SleepState] {
+        &[]
+    }
+
+    /// Enter the sleep state at index `state` of `sleep_states()`. As with
+    /// `sleep()`, interrupts must still wake the chip and resume the
+    /// scheduler.
+    #[allow(unused_variables)]
+    fn sleep_in_state(&self, state: usize) {
+        self.sleep()
+    }
+
     /// Run a function in an atomic state, which means that interrupts are
     /// disabled so that an interrupt will not fire during the passed in
     /// function's execution.
@@ -214,13 +328,10 @@ pub trait Chip {
 /// where the kernel instructs the `nrf52` crate to handle interrupts, and if
 /// there is an interrupt ready then that interrupt is passed through the InterruptService objects
 /// until something can service it.
-pub trait InterruptService<T> {
+pub trait InterruptService {
     /// Service an interrupt, if supported by this chip. If this interrupt number is not supported,
     /// return false.
     unsafe fn service_interrupt(&self, interrupt: u32) -> bool;
-
-    /// Service a deferred call. If this task is not supported, return false.
-    unsafe fn service_deferred_call(&self, task: T) -> bool;
 }
 
 /// Generic operations that clock-like things are expected to support.
@@ -0,0 +1,137 @@
+//! Reference counting for clocks and power domains shared by several users.
+//!
+//! A `ClockInterface` can only be enabled and disabled, so two capsules that
+//! share a peripheral clock can disable it while the other is still using it.
+//! Wrapping the clock in a `SharedClock` counts the users that requested it:
+//! the clock is enabled on the first request and disabled again, gating the
+//! peripheral, once the last user releases it. A `SharedClock` can sit inside
+//! a parent power domain, which is itself a `PowerResource` that is requested
+//! while the clock is enabled.
+//!
+//! Users hold their request with a `PowerRequest`, so that acquiring or
+//! relinquishing it more than once does not unbalance the count.
+//!
+//! ## Example
+//!
+//! ```ignore
+//! let domain = static_init!(SharedClock, SharedClock::new(&peripherals.domain, None));
+//! let spi_clock = static_init!(
+//!     SharedClock,
+//!     SharedClock::new(&peripherals.spi_clock, Some(domain))
+//! );
+//! mux_spi.set_power_resource(spi_clock);
+//! ```
+
+use core::cell::Cell;
+
+use crate::common::cells::OptionalCell;
+use crate::platform::ClockInterface;
+
+/// A clock or power domain that users request while they need it.
+pub trait PowerResource {
+    /// Request the resource, enabling it if no one else holds it.
+    fn request(&self);
+
+    /// Release a previous request, disabling the resource if this was the last
+    /// one.
+    fn release(&self);
+}
+
+/// A reference-counted clock, optionally inside a parent power domain.
+pub struct SharedClock<'a> {
+    clock: &'a dyn ClockInterface,
+    parent: Option<&'a dyn PowerResource>,
+    users: Cell<usize>,
+}
+
+impl<'a> SharedClock<'a> {
+    pub const fn new(clock: &'a dyn ClockInterface, parent: Option<&'a dyn PowerResource>) -> Self {
+        SharedClock {
+            clock,
+            parent,
+            users: Cell::new(0),
+        }
+    }
+
+    /// Number of outstanding requests for the clock.
+    pub fn users(&self) -> usize {
+        self.users.get()
+    }
+}
+
+impl PowerResource for SharedClock<'_> {
+    fn request(&self) {
+        if self.users.get() == 0 {
+            if let Some(parent) = self.parent {
+                parent.request();
+            }
+            self.clock.enable();
+        }
+        self.users.set(self.users.get() + 1);
+    }
+
+    fn release(&self) {
+        match self.users.get() {
+            // Unbalanced release, which must not disable the clock under
+            // another user.
+            0 => {}
+            1 => {
+                self.users.set(0);
+                self.clock.disable();
+                if let Some(parent) = self.parent {
+                    parent.release();
+                }
+            }
+            users => self.users.set(users - 1),
+        }
+    }
+}
+
+/// A single user's request for a `PowerResource`.
+pub struct PowerRequest<'a> {
+    resource: OptionalCell<&'a dyn PowerResource>,
+    held: Cell<bool>,
+}
+
+impl<'a> PowerRequest<'a> {
+    pub const fn new() -> Self {
+        PowerRequest {
+            resource: OptionalCell::empty(),
+            held: Cell::new(false),
+        }
+    }
+
+    /// Set the resource to request. A request held for a previous resource is
+    /// moved to the new one.
+    pub fn set_resource(&self, resource: &'a dyn PowerResource) {
+        let held = self.held.get();
+        self.relinquish();
+        self.resource.set(resource);
+        if held {
+            self.acquire();
+        }
+    }
+
+    /// Request the resource, unless this user already holds it.
+    pub fn acquire(&self) {
+        if !self.held.get() {
+            self.resource.map(|resource| {
+                resource.request();
+                self.held.set(true);
+            });
+        }
+    }
+
+    /// Release the resource, if this user holds it.
+    pub fn relinquish(&self) {
+        if self.held.get() {
+            self.held.set(false);
+            self.resource.map(|resource| resource.release());
+        }
+    }
+
+    /// Whether this user holds the resource.
+    pub fn is_held(&self) -> bool {
+        self.held.get()
+    }
+}
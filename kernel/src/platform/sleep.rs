@@ -0,0 +1,141 @@
+//! Interface for choosing how deeply a chip sleeps when it is idle.
+//!
+//! Chips list the sleep states they support with `Chip::sleep_states()`,
+//! ordered from the lightest to the deepest. Deeper states save more power,
+//! but take longer to enter and to wake up from, and may stop clocks that
+//! peripherals need. When there is nothing to run, the kernel asks the
+//! board's `Platform::select_sleep_state()` which state to enter. Boards can
+//! implement this with a reusable `SleepPolicy`, such as
+//! `DeadlineSleepPolicy`.
+//!
+//! ## Example
+//!
+//! ```ignore
+//! impl Platform for Board {
+//!     fn select_sleep_state(&self, states: &[SleepState]) -> Option<usize> {
+//!         self.sleep_policy.select_sleep_state(states)
+//!     }
+//! }
+//! ```
+
+use crate::hil::time::{self, Frequency, Ticks};
+use crate::platform::ClockInterface;
+
+/// A sleep state supported by a chip.
+#[derive(Clone, Copy, Debug)]
+pub struct SleepState {
+    /// Name of the state, for debugging.
+    pub name: &'static str,
+    /// Time it takes to enter the state, in microseconds.
+    pub entry_latency_us: u32,
+    /// Time it takes to wake up from the state and resume the kernel, in
+    /// microseconds.
+    pub exit_latency_us: u32,
+}
+
+impl SleepState {
+    pub const fn new(name: &'static str, entry_latency_us: u32, exit_latency_us: u32) -> Self {
+        SleepState {
+            name,
+            entry_latency_us,
+            exit_latency_us,
+        }
+    }
+
+    /// Total time lost by going through this state, in microseconds.
+    pub fn latency_us(&self) -> u32 {
+        self.entry_latency_us.saturating_add(self.exit_latency_us)
+    }
+}
+
+/// A policy for picking the sleep state to enter when the kernel is idle.
+pub trait SleepPolicy {
+    /// Return the index in `states` of the sleep state to enter, or `None` to
+    /// use `Chip::sleep()`.
+    fn select_sleep_state(&self, states: &[SleepState]) -> Option<usize>;
+}
+
+/// The deepest sleep state a peripheral tolerates while its clock is enabled.
+pub struct ClockRequirement<'a> {
+    clock: &'a dyn ClockInterface,
+    deepest_state: usize,
+}
+
+impl<'a> ClockRequirement<'a> {
+    /// While `clock` is enabled, the chip may not sleep deeper than the state
+    /// at index `deepest_state` of `Chip::sleep_states()`.
+    pub const fn new(clock: &'a dyn ClockInterface, deepest_state: usize) -> Self {
+        ClockRequirement {
+            clock,
+            deepest_state,
+        }
+    }
+}
+
+/// Sleep policy that picks the deepest state the chip can wake up from in time
+/// for the next alarm, and that keeps the clocks of active peripherals
+/// running.
+///
+/// `alarm` should be the hardware alarm that all virtual alarms are
+/// multiplexed on, so that its deadline is the next time the kernel has work
+/// to do. Interrupts from other peripherals may still arrive earlier.
+pub struct DeadlineSleepPolicy<'a, A: time::Alarm<'a>> {
+    alarm: &'a A,
+    requirements: &'a [ClockRequirement<'a>],
+}
+
+impl<'a, A: time::Alarm<'a>> DeadlineSleepPolicy<'a, A> {
+    pub const fn new(alarm: &'a A, requirements: &'a [ClockRequirement<'a>]) -> Self {
+        DeadlineSleepPolicy {
+            alarm,
+            requirements,
+        }
+    }
+
+    /// Time until the alarm fires, in microseconds, or `None` if the alarm is
+    /// not armed.
+    pub fn time_to_deadline_us(&self) -> Option<u64> {
+        if !self.alarm.is_armed() {
+            return None;
+        }
+        let now = self.alarm.now();
+        // A deadline more than half the range of the counter away has
+        // already passed, so the alarm is about to fire.
+        let remaining = self.alarm.get_alarm().wrapping_sub(now).into_u32();
+        let ticks = if remaining > A::Ticks::max_value().into_u32() / 2 {
+            0
+        } else {
+            remaining as u64
+        };
+        Some(ticks * 1_000_000 / A::Frequency::frequency() as u64)
+    }
+
+    /// The deepest state that the peripherals with enabled clocks allow.
+    pub fn deepest_allowed_state(&self) -> usize {
+        self.requirements
+            .iter()
+            .filter(|requirement| requirement.clock.is_enabled())
+            .map(|requirement| requirement.deepest_state)
+            .min()
+            .unwrap_or(usize::MAX)
+    }
+}
+
+impl<'a, A: time::Alarm<'a>> SleepPolicy for DeadlineSleepPolicy<'a, A> {
+    fn select_sleep_state(&self, states: &[SleepState]) -> Option<usize> {
+        if states.is_empty() {
+            return None;
+        }
+        let budget_us = self.time_to_deadline_us().unwrap_or(u64::MAX);
+        let deepest = self.deepest_allowed_state();
+        // The lightest state is always allowed, as it stops no clocks.
+        Some(
+            states
+                .iter()
+                .enumerate()
+                .rev()
+                .find(|(index, state)| *index <= deepest && state.latency_us() as u64 <= budget_us)
+                .map_or(0, |(index, _)| index),
+        )
+    }
+}
@@ -3,6 +3,7 @@
 use core::cell::Cell;
 use core::fmt;
 use core::fmt::Write;
+use core::num::NonZeroU32;
 use core::ptr::NonNull;
 use core::str;
 
@@ -12,8 +13,65 @@ use crate::ipc;
 use crate::mem::{ReadOnlyProcessBuffer, ReadWriteProcessBuffer};
 use crate::platform::mpu::{self};
 use crate::sched::Kernel;
+use crate::storage_permissions::StoragePermissions;
 use crate::syscall::{self, Syscall, SyscallReturn};
 use crate::upcall::UpcallId;
+use tock_tbf::types::CommandPermissions;
+
+/// Persistent identifier of the application a process runs.
+///
+/// Unlike `ProcessId::id()`, which changes every time a process starts, the
+/// short ID of an app is derived from its TBF header and stays the same when
+/// the process restarts, the board reboots or the app is reinstalled. Capsules
+/// can use it to tell that two processes run the same app, for example to key
+/// the data an app stores in nonvolatile storage.
+///
+/// How short IDs are assigned is decided by the board's `AppIdPolicy`. No two
+/// processes have the same `Fixed` short ID at the same time.
+#[derive(Clone, Copy, Debug, PartialEq, Eq)]
+pub enum ShortID {
+    /// The app has no persistent identity. It is only identified by its
+    /// `ProcessId`, and should not be given access to persistent data.
+    LocallyUnique,
+    /// The app has this persistent identifier.
+    Fixed(NonZeroU32),
+}
+
+impl fmt::Display for ShortID {
+    fn fmt(&self, f: &mut fmt::Formatter) -> fmt::Result {
+        match self {
+            ShortID::LocallyUnique => write!(f, "Unique"),
+            ShortID::Fixed(id) => write!(f, "{:#010x}", id),
+        }
+    }
+}
+
+/// An entry in the kernel's processes array.
+///
+/// The array is shared by the kernel, the scheduler and the board's panic
+/// handler, and processes can be added or removed after boot. Slots therefore
+/// hold their process in a `Cell`, and are only changed through the `Kernel`.
+pub struct ProcessSlot {
+    process: Cell<Option<&'static dyn Process>>,
+}
+
+impl ProcessSlot {
+    /// An empty slot, used to initialize the processes array.
+    // Only used as an array initializer, each copy is a distinct slot.
+    #[allow(clippy::declare_interior_mutable_const)]
+    pub const EMPTY: ProcessSlot = ProcessSlot {
+        process: Cell::new(None),
+    };
+
+    /// The process in this slot, if there is one.
+    pub fn get(&self) -> Option<&'static dyn Process> {
+        self.process.get()
+    }
+
+    pub(crate) fn set(&self, process: Option<&'static dyn Process>) {
+        self.process.set(process);
+    }
+}
 
 /// Userspace process identifier.
 ///
@@ -147,6 +205,29 @@ impl ProcessId {
         self.identifier
     }
 
+    /// Get the persistent identifier of the app this `ProcessId` refers to.
+    ///
+    /// The short ID is the same for every process that runs the app, so it
+    /// can be used to associate persistent state with an app. If the process
+    /// no longer exists this returns `ShortID::LocallyUnique`.
+    pub fn short_app_id(&self) -> ShortID {
+        self.kernel
+            .process_map_or(ShortID::LocallyUnique, *self, |process| {
+                process.short_app_id()
+            })
+    }
+
+    /// Get the permissions of the process to persistent storage, or `None` if
+    /// the process no longer exists.
+    ///
+    /// Capsules that store data for processes must check these before giving
+    /// a process access to stored data.
+    pub fn get_storage_permissions(&self) -> Option<StoragePermissions> {
+        self.kernel.process_map_or(None, *self, |process| {
+            Some(process.get_storage_permissions())
+        })
+    }
+
     /// Returns the full address of the start and end of the flash region that
     /// the app owns and can write to. This includes the app's code and data and
     /// any padding at the end of the app. It does not include the TBF header,
@@ -207,6 +288,18 @@ pub trait Process {
     /// running.
     fn set_yielded_state(&self);
 
+    /// Block the main thread of the process until the upcall `upcall_id` is
+    /// scheduled, and return the arguments of the upcall as the return value
+    /// of the system call instead of running the upcall function. Other
+    /// upcalls stay queued until the process yields for them.
+    ///
+    /// If the upcall is already queued it is removed from the queue and the
+    /// process keeps running.
+    fn set_yielded_for_state(&self, upcall_id: UpcallId);
+
+    /// The upcall the process is blocked on in a yield-wait-for, if any.
+    fn yielded_for(&self) -> Option<UpcallId>;
+
     /// Move this process from running or yielded state into the stopped state.
     ///
     /// This will fail (i.e. not do anything) if the process was not either
@@ -229,6 +322,30 @@ pub trait Process {
     /// Get the name of the process. Used for IPC.
     fn get_process_name(&self) -> &'static str;
 
+    /// Get the persistent identifier of the app this process runs.
+    fn short_app_id(&self) -> ShortID;
+
+    /// Get the permissions of this process to persistent storage.
+    fn get_storage_permissions(&self) -> StoragePermissions;
+
+    /// Get the commands of driver `driver_num` in the block of 64 command
+    /// numbers starting at `offset * 64` that the permissions in this
+    /// process's TBF header allow.
+    fn get_command_permissions(&self, driver_num: usize, offset: usize) -> CommandPermissions;
+
+    /// Get the real-time reservation in this process's TBF header, as its
+    /// period and budget in microseconds.
+    fn get_real_time_reservation(&self) -> Option<(u32, u32)>;
+
+    /// Get the scheduling priority in this process's TBF header. Lower
+    /// values are higher priorities.
+    fn get_priority(&self) -> Option<u32>;
+
+    /// Whether the IPC access list in this process's TBF header lets the app
+    /// with short ID `client` connect to the IPC service of this process.
+    /// Apps without a fixed short ID are only allowed if there is no list.
+    fn ipc_client_allowed(&self, client: ShortID) -> bool;
+
     /// Stop and clear a process's state, putting it into the `Terminated`
     /// state.
     ///
@@ -261,6 +378,52 @@ pub trait Process {
     /// different application's `_start` function.
     fn try_restart(&self, completion_code: u32);
 
+    // thread operations
+
+    /// Identifier of the thread that runs when the process is next switched
+    /// to. The main thread, which runs the process's `_start` function and all
+    /// of its upcalls, is thread 0.
+    fn current_thread(&self) -> usize;
+
+    /// Number of threads of the process, including the main thread and
+    /// threads that exited but were not joined yet.
+    fn thread_count(&self) -> usize;
+
+    /// Create a thread that shares the memory and grants of the process. The
+    /// thread starts executing `entry` with `argument` and its own identifier
+    /// as arguments, on the stack that grows down from `stack_pointer`. The new
+    /// thread runs once the current thread blocks or yields.
+    ///
+    /// Returns the identifier of the new thread, or an error if the process
+    /// has no free thread slots (`NOMEM`), the entry point or stack pointer is
+    /// not in process memory (`INVAL`), or the architecture cannot run the
+    /// thread (`FAIL`).
+    fn create_thread(
+        &self,
+        entry: *const u8,
+        stack_pointer: *const u8,
+        argument: usize,
+    ) -> Result<usize, ErrorCode>;
+
+    /// Block the current thread until `thread` exits, then return the
+    /// completion code of `thread` to the current thread as the return value
+    /// of its system call.
+    ///
+    /// Fails with `INVAL` if `thread` does not exist, is the main thread or is
+    /// the current thread, and with `BUSY` if another thread already waits
+    /// for `thread` or if waiting would deadlock.
+    fn join_thread(&self, thread: usize) -> Result<(), ErrorCode>;
+
+    /// Let the other runnable threads of the process run before the current
+    /// thread continues. If the main thread is waiting for upcalls and one is
+    /// pending, the main thread runs it first.
+    fn yield_thread(&self);
+
+    /// Exit the current thread with `completion_code`, waking the thread
+    /// waiting for it, if any. Fails with `INVAL` for the main thread, which
+    /// must exit the process instead.
+    fn exit_thread(&self, completion_code: u32) -> Result<(), ErrorCode>;
+
     // memop operations
 
     /// Change the location of the program break and reallocate the MPU region
@@ -446,6 +609,17 @@ pub trait Process {
         align: usize,
     ) -> Option<(ProcessCustomGrantIdentifer, NonNull<u8>)>;
 
+    /// Free the memory of the grant based on `grant_num` for this process, so
+    /// that it can be used for other grants or given to the process with
+    /// `brk()`. The remaining grants are compacted to reclaim the memory. Grant
+    /// memory is not dropped, so capsules must not store anything in a grant
+    /// that needs to be.
+    ///
+    /// This returns `Ok` if the grant is not allocated, and an `Err` if the
+    /// process is inactive, if the `grant_num` is invalid, or if the grant is
+    /// entered.
+    fn free_grant(&self, grant_num: usize) -> Result<(), Error>;
+
     /// Enter the grant based on `grant_num` for this process.
     ///
     /// Entering a grant means getting access to the actual memory for the
@@ -536,6 +710,10 @@ pub trait Process {
     /// context, and the state of the memory protection unit (MPU).
     fn print_full_process(&self, writer: &mut dyn Write);
 
+    /// Store the architecture specific registers of the process in `out`.
+    /// Returns the number of bytes written, or `SIZE` if `out` is too small.
+    fn get_stored_state(&self, out: &mut [u8]) -> Result<usize, ErrorCode>;
+
     // debug
 
     /// Returns how many syscalls this app has called.
@@ -550,6 +728,27 @@ pub trait Process {
     /// Increment the number of times the process has exceeded its timeslice.
     fn debug_timeslice_expired(&self);
 
+    /// Add to the time the process was scheduled for, `kernel_time_us` of
+    /// which the kernel spent on behalf of the process.
+    fn debug_executed(&self, execution_time_us: u32, kernel_time_us: u32);
+
+    /// Returns how many microseconds this process has been scheduled for.
+    /// Only time under a timeslice is measured, so processes scheduled
+    /// cooperatively are not accounted for.
+    fn debug_execution_time_us(&self) -> u64;
+
+    /// Returns how many of the microseconds this process has been scheduled
+    /// for were spent in the kernel, rather than running the process.
+    fn debug_kernel_time_us(&self) -> u64;
+
+    /// Increment the number of times the process was resumed with an upcall
+    /// after it yielded.
+    fn debug_woken_up(&self);
+
+    /// Returns how many times this process was resumed with an upcall after
+    /// it yielded.
+    fn debug_wakeup_count(&self) -> usize;
+
     /// Increment the number of times the process called a syscall and record
     /// the last syscall that was called.
     fn debug_syscall_called(&self, last_syscall: Syscall);
@@ -562,6 +761,14 @@ pub trait Process {
 
     /// Return the lowest recorded address of the process stack, if known.
     fn debug_stack_end(&self) -> Option<*const u8>;
+
+    /// Return the last syscall the process called, if any.
+    fn debug_last_syscall(&self) -> Option<Syscall>;
+
+    /// Copy the process stack into `out`, starting at the lowest recorded
+    /// address of the stack. Returns the number of bytes copied, which is
+    /// zero if the stack is not known.
+    fn debug_copy_stack(&self, out: &mut [u8]) -> usize;
 }
 
 /// Opaque identifier for custom grants allocated dynamically from a process's
@@ -665,6 +872,30 @@ pub enum State {
     Unstarted,
 }
 
+/// Maximum number of threads of a process, including its main thread.
+pub const MAX_THREADS: usize = 4;
+
+/// State of a single thread of a process.
+#[derive(Copy, Clone, Debug, PartialEq, Eq)]
+pub enum ThreadState {
+    /// The thread slot is not in use.
+    Free,
+
+    /// The thread can run. Only one runnable thread runs at a time, whenever
+    /// the process is scheduled.
+    Runnable,
+
+    /// The main thread called `yield` and waits for an upcall.
+    Waiting,
+
+    /// The thread waits for the thread with this identifier to exit.
+    Joining(usize),
+
+    /// The thread exited with this completion code, and no thread has joined
+    /// it yet.
+    Exited(u32),
+}
+
 /// A wrapper around `Cell<State>` is used by `Process` to prevent bugs arising from
 /// the state duplication in the kernel work tracking and process state tracking.
 pub(crate) struct ProcessStateCell<'a> {
@@ -705,7 +936,7 @@ impl<'a> ProcessStateCell<'a> {
 ///
 /// The actions are separate from the policy on deciding which action to take. A
 /// separate process-specific policy should determine which action to take.
-#[derive(Copy, Clone)]
+#[derive(Copy, Clone, Debug)]
 pub enum FaultAction {
     /// Generate a `panic!()` call and crash the entire system. This is useful
     /// for debugging applications as the error is displayed immediately after
@@ -738,7 +969,7 @@ pub enum Task {
 /// implementation.
 ///
 /// An example of a kernel function is the application entry point.
-#[derive(Copy, Clone, Debug)]
+#[derive(Copy, Clone, Debug, PartialEq)]
 pub enum FunctionCallSource {
     /// For functions coming directly from the kernel, such as `init_fn`.
     Kernel,
@@ -0,0 +1,213 @@
+//! Checking the credentials of Tock applications before they are loaded.
+//!
+//! A TBF can carry credentials, such as a hash or a signature, in its footer,
+//! which is the region of the TBF between the end of the application binary
+//! and the end of the TBF. Credentials cover the app's integrity region: the
+//! TBF header and the application binary.
+//!
+//! When a board loads processes with `load_and_check_processes()`, each
+//! enabled app is passed to an `AppCredentialsChecker` before a process is
+//! created for it. Apps the checker does not approve are not loaded.
+//!
+//! Each process is also given a `ShortID` by the board's `AppIdPolicy`, which
+//! may depend on whether the app's credentials were accepted.
+
+use core::num::NonZeroU32;
+use tock_tbf::types::{TbfFooterV2Credentials, TbfFooterV2CredentialsType, TbfHeader};
+
+use crate::config;
+use crate::debug;
+use crate::process::ShortID;
+
+/// Result of checking a single credential of an app.
+#[derive(Clone, Copy, Debug, PartialEq, Eq)]
+pub enum CheckResult {
+    /// The credential is valid and the app should be loaded.
+    Accept,
+    /// The checker has no opinion on this credential, for example because it
+    /// does not support its format. The next credential is checked.
+    Pass,
+    /// The credential is invalid and the app must not be loaded.
+    Reject,
+}
+
+/// Policy deciding whether an app may be loaded based on the credentials in
+/// its footer.
+///
+/// The credentials are checked in the order they appear in the footer. The
+/// first credential that is accepted or rejected decides whether the app is
+/// loaded. If every credential is passed, the app is loaded only if
+/// `require_credentials()` returns false.
+pub trait AppCredentialsChecker {
+    /// Whether apps that no credential vouches for should be refused.
+    fn require_credentials(&self) -> bool;
+
+    /// Check one credential against the integrity region of the app.
+    fn check_credentials(
+        &self,
+        credentials: &TbfFooterV2Credentials,
+        integrity_region: &'static [u8],
+    ) -> CheckResult;
+}
+
+/// Verifier for one credentials format.
+///
+/// Implementations are usually backed by a hash or signature implementation
+/// the board provides, and hold the key the credential must be signed with.
+/// Verification happens while processes are loaded at boot, so it is
+/// synchronous.
+pub trait CredentialsVerifier {
+    /// The credentials format this verifier checks.
+    fn format(&self) -> TbfFooterV2CredentialsType;
+
+    /// Returns whether `credential` is a valid credential for
+    /// `integrity_region`.
+    fn verify(&self, integrity_region: &[u8], credential: &[u8]) -> bool;
+}
+
+/// Credentials checker that accepts an app if one of its credentials verifies
+/// with the verifier for that credential's format, and rejects it if a
+/// credential does not verify. Credentials without a matching verifier are
+/// ignored.
+pub struct AppCheckerVerify<'a> {
+    verifiers: &'a [&'a dyn CredentialsVerifier],
+    require_credentials: bool,
+}
+
+impl<'a> AppCheckerVerify<'a> {
+    pub fn new(
+        verifiers: &'a [&'a dyn CredentialsVerifier],
+        require_credentials: bool,
+    ) -> AppCheckerVerify<'a> {
+        AppCheckerVerify {
+            verifiers,
+            require_credentials,
+        }
+    }
+}
+
+impl AppCredentialsChecker for AppCheckerVerify<'_> {
+    fn require_credentials(&self) -> bool {
+        self.require_credentials
+    }
+
+    fn check_credentials(
+        &self,
+        credentials: &TbfFooterV2Credentials,
+        integrity_region: &'static [u8],
+    ) -> CheckResult {
+        match self
+            .verifiers
+            .iter()
+            .find(|verifier| verifier.format() == credentials.format())
+        {
+            Some(verifier) => {
+                if verifier.verify(integrity_region, credentials.data()) {
+                    CheckResult::Accept
+                } else {
+                    CheckResult::Reject
+                }
+            }
+            None => CheckResult::Pass,
+        }
+    }
+}
+
+/// Policy deciding the short ID of each app.
+///
+/// The policy is asked once, when a process is created for the app. If the
+/// short ID it returns is already used by another process, the app is not
+/// loaded.
+pub trait AppIdPolicy {
+    /// Return the short ID of the app with TBF header `header`. `verified` is
+    /// whether one of the app's credentials was accepted by the board's
+    /// credentials checker.
+    fn short_id(&self, header: &TbfHeader, verified: bool) -> ShortID;
+}
+
+/// Short ID policy using the `ShortId` element of the TBF header, or a hash of
+/// the package name for apps without one. Apps with neither are
+/// `ShortID::LocallyUnique`.
+///
+/// If `verified_only` is set, only apps whose credentials were accepted get a
+/// fixed short ID. This binds an app's identity to the keys the board's
+/// verifiers trust, so an unsigned app cannot claim the short ID (and with it
+/// the stored data) of another app.
+pub struct ShortIdFromTbf {
+    verified_only: bool,
+}
+
+impl ShortIdFromTbf {
+    pub const fn new(verified_only: bool) -> ShortIdFromTbf {
+        ShortIdFromTbf { verified_only }
+    }
+}
+
+impl AppIdPolicy for ShortIdFromTbf {
+    fn short_id(&self, header: &TbfHeader, verified: bool) -> ShortID {
+        if self.verified_only && !verified {
+            return ShortID::LocallyUnique;
+        }
+        if let Some(short_id) = header.get_fixed_short_id() {
+            return ShortID::Fixed(short_id);
+        }
+        match header.get_package_name() {
+            Some(name) if !name.is_empty() => {
+                // 32 bit FNV-1a.
+                let hash = name.bytes().fold(0x811c9dc5u32, |hash, byte| {
+                    (hash ^ byte as u32).wrapping_mul(0x01000193)
+                });
+                NonZeroU32::new(hash).map_or(ShortID::LocallyUnique, ShortID::Fixed)
+            }
+            _ => ShortID::LocallyUnique,
+        }
+    }
+}
+
+/// The policy used by boards that do not choose one. As these boards do not
+/// check credentials, all their apps are `ShortID::LocallyUnique`.
+pub(crate) static DEFAULT_APP_ID_POLICY: ShortIdFromTbf = ShortIdFromTbf::new(true);
+
+/// Decide whether the app in `app_flash`, whose parsed header is `header`,
+/// may be loaded. `app_flash` covers the entire TBF.
+///
+/// Returns `Accept` if a credential vouches for the app, `Pass` if no
+/// credential decided but the checker does not require one, and `Reject` if
+/// the app must not be loaded.
+pub(crate) fn check_app_credentials(
+    checker: &dyn AppCredentialsChecker,
+    header: &TbfHeader,
+    app_flash: &'static [u8],
+) -> CheckResult {
+    let binary_end = header.get_binary_end() as usize;
+    let total_size = header.get_total_size() as usize;
+    if binary_end > total_size || total_size > app_flash.len() {
+        return CheckResult::Reject;
+    }
+    let integrity_region = &app_flash[..binary_end];
+
+    let mut footers = &app_flash[binary_end..total_size];
+    while !footers.is_empty() {
+        let (credentials, entry_length) = match tock_tbf::parse::parse_tbf_footer(footers) {
+            Ok(footer) => footer,
+            Err(err) => {
+                if config::CONFIG.debug_load_processes {
+                    debug!("Could not parse TBF footer: {:?}", err);
+                }
+                // A malformed footer means we cannot trust anything after it.
+                break;
+            }
+        };
+        match checker.check_credentials(&credentials, integrity_region) {
+            CheckResult::Pass => {}
+            result => return result,
+        }
+        footers = footers.get(entry_length as usize..).unwrap_or(&[]);
+    }
+
+    if checker.require_credentials() {
+        CheckResult::Reject
+    } else {
+        CheckResult::Pass
+    }
+}
@@ -4,8 +4,13 @@
 //! kernel can use when managing processes. For example, these policies control
 //! decisions such as whether a specific process should be restarted.
 
+use core::cell::Cell;
+
+use crate::hil::time::{self, Alarm, Ticks, Time};
 use crate::process;
-use crate::process::Process;
+use crate::process::{Process, ProcessId, State};
+use crate::process_standard::COMPLETION_FAULT;
+use crate::sched::Kernel;
 
 /// Generic trait for implementing a policy on what to do when a process faults.
 ///
@@ -105,3 +110,292 @@ impl ProcessFaultPolicy for ThresholdRestartThenPanicFaultPolicy {
         }
     }
 }
+
+/// Implementation of `ProcessFaultPolicy` that restarts a faulted process
+/// after a delay that doubles with every consecutive fault, so that a process
+/// that keeps faulting does not monopolise the CPU.
+///
+/// The first restart happens after `initial_delay_ms`, and the delay doubles up
+/// to `max_delay_ms`. Once a process runs for `max_delay_ms` without faulting,
+/// its next fault is restarted after `initial_delay_ms` again. While waiting
+/// to be restarted the process is in the `Faulted` state.
+///
+/// The policy must be set as the client of `alarm`. Delays are measured in
+/// ticks of the alarm, at its own width, so `max_delay_ms` must be shorter
+/// than half the time it takes the alarm counter to wrap around. Processes
+/// beyond the first `NUM_PROCS` slots of the processes array are restarted
+/// immediately.
+///
+/// ```rust,ignore
+/// let fault_policy = static_init!(
+///     BackoffRestartFaultPolicy<'static, VirtualMuxAlarm<'static, Rtc>, NUM_PROCS>,
+///     BackoffRestartFaultPolicy::new(board_kernel, fault_alarm, 100, 60_000)
+/// );
+/// fault_alarm.set_alarm_client(fault_policy);
+/// ```
+pub struct BackoffRestartFaultPolicy<'a, A: Alarm<'a>, const NUM_PROCS: usize> {
+    kernel: &'static Kernel,
+    alarm: &'a A,
+    initial_delay_ms: u32,
+    max_delay_ms: u32,
+    backoffs: [Cell<Option<Backoff<A::Ticks>>>; NUM_PROCS],
+}
+
+#[derive(Clone, Copy)]
+struct Backoff<T: Ticks> {
+    /// The faulted process, which is only restarted if it was not restarted
+    /// in some other way in the meantime.
+    processid: ProcessId,
+    /// Delay before the restart, in milliseconds.
+    delay_ms: u32,
+    /// When the process faulted, in ticks of the alarm.
+    faulted_at: T,
+    /// Whether the restart is still to happen.
+    pending: bool,
+}
+
+impl<T: Ticks> Backoff<T> {
+    // Only used as an array initializer, each copy is a distinct slot.
+    #[allow(clippy::declare_interior_mutable_const)]
+    const NONE: Cell<Option<Backoff<T>>> = Cell::new(None);
+}
+
+impl<'a, A: Alarm<'a>, const NUM_PROCS: usize> BackoffRestartFaultPolicy<'a, A, NUM_PROCS> {
+    pub fn new(
+        kernel: &'static Kernel,
+        alarm: &'a A,
+        initial_delay_ms: u32,
+        max_delay_ms: u32,
+    ) -> BackoffRestartFaultPolicy<'a, A, NUM_PROCS> {
+        BackoffRestartFaultPolicy {
+            kernel,
+            alarm,
+            initial_delay_ms,
+            max_delay_ms: core::cmp::max(initial_delay_ms, max_delay_ms),
+            backoffs: [Backoff::<A::Ticks>::NONE; NUM_PROCS],
+        }
+    }
+
+    /// The delay before `process` is restarted, in milliseconds, if it is
+    /// waiting to be restarted.
+    pub fn pending_delay_ms(&self, process: &dyn Process) -> Option<u32> {
+        let processid = process.processid();
+        processid
+            .index()
+            .and_then(|index| self.backoffs.get(index))
+            .and_then(|backoff| backoff.get())
+            .filter(|backoff| backoff.pending && backoff.processid == processid)
+            .map(|backoff| backoff.delay_ms)
+    }
+
+    /// Set the alarm for the earliest pending restart.
+    fn reset_alarm(&self) {
+        let now = self.alarm.now();
+        let next = self
+            .backoffs
+            .iter()
+            .filter_map(|backoff| backoff.get())
+            .filter(|backoff| backoff.pending)
+            .map(|backoff| {
+                let elapsed = now.wrapping_sub(backoff.faulted_at);
+                let delay = A::ticks_from_ms(backoff.delay_ms);
+                if elapsed < delay {
+                    delay.wrapping_sub(elapsed)
+                } else {
+                    A::Ticks::from(0)
+                }
+            })
+            .min();
+        match next {
+            Some(remaining) => self.alarm.set_alarm(now, remaining),
+            None => {
+                let _ = self.alarm.disarm();
+            }
+        }
+    }
+}
+
+impl<'a, A: Alarm<'a>, const NUM_PROCS: usize> ProcessFaultPolicy
+    for BackoffRestartFaultPolicy<'a, A, NUM_PROCS>
+{
+    fn action(&self, process: &dyn Process) -> process::FaultAction {
+        let processid = process.processid();
+        let slot = match processid.index().and_then(|index| self.backoffs.get(index)) {
+            Some(slot) => slot,
+            None => return process::FaultAction::Restart,
+        };
+        let now = self.alarm.now();
+        let delay_ms = match slot.get() {
+            Some(last) => {
+                // Time the process ran since it was last restarted.
+                let elapsed = now.wrapping_sub(last.faulted_at);
+                let delay = A::ticks_from_ms(last.delay_ms);
+                let ran = if elapsed > delay {
+                    elapsed.wrapping_sub(delay)
+                } else {
+                    A::Ticks::from(0)
+                };
+                if ran >= A::ticks_from_ms(self.max_delay_ms) {
+                    self.initial_delay_ms
+                } else {
+                    core::cmp::min(last.delay_ms.saturating_mul(2), self.max_delay_ms)
+                }
+            }
+            None => self.initial_delay_ms,
+        };
+        slot.set(Some(Backoff {
+            processid,
+            delay_ms,
+            faulted_at: now,
+            pending: true,
+        }));
+        self.reset_alarm();
+        process::FaultAction::Stop
+    }
+}
+
+impl<'a, A: Alarm<'a>, const NUM_PROCS: usize> time::AlarmClient
+    for BackoffRestartFaultPolicy<'a, A, NUM_PROCS>
+{
+    fn alarm(&self) {
+        let now = self.alarm.now();
+        for slot in self.backoffs.iter() {
+            if let Some(backoff) = slot.get().filter(|backoff| backoff.pending) {
+                let elapsed = now.wrapping_sub(backoff.faulted_at);
+                if elapsed < A::ticks_from_ms(backoff.delay_ms) {
+                    continue;
+                }
+                slot.set(Some(Backoff {
+                    pending: false,
+                    ..backoff
+                }));
+                self.kernel
+                    .process_map_or((), backoff.processid, |process| {
+                        if process.get_state() == State::Faulted {
+                            process.try_restart(COMPLETION_FAULT);
+                        }
+                    });
+            }
+        }
+        self.reset_alarm();
+    }
+}
+
+/// Implementation of `ProcessFaultPolicy` that restarts a process unless it
+/// faulted more than `max_faults` times within a window of `window_s` seconds,
+/// in which case the process is stopped and no longer scheduled.
+///
+/// A window starts at the first fault after the previous window ended. Windows
+/// are measured in ticks of `time`, at its own width, so they must be shorter
+/// than the time it takes its counter to wrap around. Processes beyond the
+/// first `NUM_PROCS` slots of the processes array are always restarted.
+pub struct RestartRateFaultPolicy<'a, T: Time, const NUM_PROCS: usize> {
+    time: &'a T,
+    max_faults: usize,
+    window_s: u32,
+    windows: [Cell<Option<Window<T::Ticks>>>; NUM_PROCS],
+}
+
+/// The faults of a process in its current window.
+#[derive(Clone, Copy)]
+struct Window<T: Ticks> {
+    /// When the window started, in ticks.
+    start: T,
+    /// The number of faults in the window.
+    faults: usize,
+}
+
+impl<T: Ticks> Window<T> {
+    // Only used as an array initializer, each copy is a distinct slot.
+    #[allow(clippy::declare_interior_mutable_const)]
+    const NONE: Cell<Option<Window<T>>> = Cell::new(None);
+}
+
+impl<'a, T: Time, const NUM_PROCS: usize> RestartRateFaultPolicy<'a, T, NUM_PROCS> {
+    pub fn new(
+        time: &'a T,
+        max_faults: usize,
+        window_s: u32,
+    ) -> RestartRateFaultPolicy<'a, T, NUM_PROCS> {
+        RestartRateFaultPolicy {
+            time,
+            max_faults,
+            window_s,
+            windows: [Window::<T::Ticks>::NONE; NUM_PROCS],
+        }
+    }
+}
+
+impl<'a, T: Time, const NUM_PROCS: usize> ProcessFaultPolicy
+    for RestartRateFaultPolicy<'a, T, NUM_PROCS>
+{
+    fn action(&self, process: &dyn Process) -> process::FaultAction {
+        let window = match process
+            .processid()
+            .index()
+            .and_then(|index| self.windows.get(index))
+        {
+            Some(window) => window,
+            None => return process::FaultAction::Restart,
+        };
+        let now = self.time.now();
+        let length = T::ticks_from_seconds(self.window_s);
+        let current = match window.get() {
+            Some(current) if now.wrapping_sub(current.start) < length => Window {
+                faults: current.faults + 1,
+                ..current
+            },
+            _ => Window {
+                start: now,
+                faults: 1,
+            },
+        };
+        window.set(Some(current));
+        if current.faults <= self.max_faults {
+            process::FaultAction::Restart
+        } else {
+            process::FaultAction::Stop
+        }
+    }
+}
+
+/// Implementation of `ProcessFaultPolicy` that applies a different policy to
+/// each app, chosen by its package name. Apps without their own policy use
+/// `default`.
+///
+/// ```rust,ignore
+/// let fault_policy = static_init!(
+///     PerAppFaultPolicy<'static>,
+///     PerAppFaultPolicy::new(
+///         &STOP_FAULT_POLICY,
+///         &[("radio", &RESTART_FAULT_POLICY), ("shell", backoff_policy)],
+///     )
+/// );
+/// ```
+pub struct PerAppFaultPolicy<'a> {
+    default: &'a dyn ProcessFaultPolicy,
+    policies: &'a [(&'a str, &'a dyn ProcessFaultPolicy)],
+}
+
+impl<'a> PerAppFaultPolicy<'a> {
+    pub const fn new(
+        default: &'a dyn ProcessFaultPolicy,
+        policies: &'a [(&'a str, &'a dyn ProcessFaultPolicy)],
+    ) -> PerAppFaultPolicy<'a> {
+        PerAppFaultPolicy { default, policies }
+    }
+
+    /// The policy that applies to the app named `name`.
+    pub fn policy(&self, name: &str) -> &'a dyn ProcessFaultPolicy {
+        self.policies
+            .iter()
+            .find(|(app, _)| *app == name)
+            .map_or(self.default, |(_, policy)| *policy)
+    }
+}
+
+impl ProcessFaultPolicy for PerAppFaultPolicy<'_> {
+    fn action(&self, process: &dyn Process) -> process::FaultAction {
+        self.policy(process.get_process_name()).action(process)
+    }
+}
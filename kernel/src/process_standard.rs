@@ -11,22 +11,31 @@ use core::{mem, ptr, slice, str};
 
 use crate::common::cells::{MapCell, NumericCellExt};
 use crate::common::{Queue, RingBuffer};
-use crate::config;
+use crate::config::{self, FaultDetail};
 use crate::debug;
 use crate::errorcode::ErrorCode;
 use crate::mem::{ReadOnlyProcessBuffer, ReadWriteProcessBuffer};
 use crate::platform::mpu::{self, MPU};
 use crate::platform::Chip;
 use crate::process::{Error, FunctionCall, FunctionCallSource, Process, State, Task};
-use crate::process::{FaultAction, ProcessCustomGrantIdentifer, ProcessId, ProcessStateCell};
+use crate::process::{
+    FaultAction, ProcessCustomGrantIdentifer, ProcessId, ProcessStateCell, ShortID,
+};
+use crate::process::{ThreadState, MAX_THREADS};
 use crate::process_policies::ProcessFaultPolicy;
 use crate::process_utilities::ProcessLoadError;
 use crate::sched::Kernel;
+use crate::storage_permissions::StoragePermissions;
 use crate::syscall::{self, Syscall, SyscallReturn, UserspaceKernelBoundary};
 use crate::upcall::UpcallId;
+use tock_tbf::types::CommandPermissions;
 
 // The completion code for a process if it faulted.
-const COMPLETION_FAULT: u32 = 0xffffffff;
+pub(crate) const COMPLETION_FAULT: u32 = 0xffffffff;
+
+/// State the architecture saves for each thread of a process.
+type StoredState<C> =
+    <<C as Chip>::UserspaceKernelBoundary as UserspaceKernelBoundary>::StoredState;
 
 /// State for helping with debugging apps.
 ///
@@ -67,6 +76,18 @@ struct ProcessStandardDebug {
     /// How many times this process has been paused because it exceeded its
     /// timeslice.
     timeslice_expiration_count: usize,
+
+    /// Total time the process was scheduled for, in microseconds, as measured
+    /// by the scheduler timer.
+    execution_time_us: u64,
+
+    /// The part of `execution_time_us` the kernel spent on behalf of the
+    /// process, handling its syscalls and switching to it.
+    kernel_time_us: u64,
+
+    /// How many times the process was resumed with an upcall after it
+    /// yielded.
+    wakeup_count: usize,
 }
 
 /// Entry that is stored in the grant pointer table at the top of process
@@ -88,6 +109,13 @@ struct GrantPointerEntry {
     /// The start of the memory location where the grant has been allocated, or
     /// null if the grant has not been allocated.
     grant_ptr: *mut u8,
+
+    /// The size of the allocation, so that the grant can be moved when the
+    /// grant region is compacted.
+    size: usize,
+
+    /// The alignment of the allocation.
+    align: usize,
 }
 
 /// A type for userspace processes in Tock.
@@ -154,6 +182,11 @@ pub struct ProcessStandard<'a, C: 'static + Chip> {
     /// Pointer to the end of the allocated (and MPU protected) grant region.
     kernel_memory_break: Cell<*const u8>,
 
+    /// Lowest address of any custom grant. Custom grants are referenced by
+    /// their address, so only the grants below this can be moved when the
+    /// grant region is compacted.
+    custom_grant_break: Cell<*const u8>,
+
     /// Pointer to the end of process RAM that has been sbrk'd to the process.
     app_break: Cell<*const u8>,
 
@@ -168,9 +201,23 @@ pub struct ProcessStandard<'a, C: 'static + Chip> {
     header: tock_tbf::types::TbfHeader,
 
     /// State saved on behalf of the process each time the app switches to the
-    /// kernel.
-    stored_state:
-        MapCell<<<C as Chip>::UserspaceKernelBoundary as UserspaceKernelBoundary>::StoredState>,
+    /// kernel. This is the state of the main thread.
+    stored_state: MapCell<StoredState<C>>,
+
+    /// State saved for each of the other threads of the process. The state is
+    /// allocated in the grant region the first time the thread slot is used.
+    thread_stored_states: [Cell<Option<NonNull<MapCell<StoredState<C>>>>>; MAX_THREADS - 1],
+
+    /// The state of each thread, starting with the main thread.
+    threads: [Cell<ThreadState>; MAX_THREADS],
+
+    /// The thread that runs when the process is switched to. Whenever the
+    /// process is not `Running`, this is the main thread.
+    current_thread: Cell<usize>,
+
+    /// The upcall the main thread waits for in a yield-wait-for. Other
+    /// upcalls are not delivered while it waits.
+    yielded_for: Cell<Option<UpcallId>>,
 
     /// The current state of the app. The scheduler uses this to determine
     /// whether it can schedule this app to execute.
@@ -204,6 +251,12 @@ pub struct ProcessStandard<'a, C: 'static + Chip> {
     /// Name of the app.
     process_name: &'static str,
 
+    /// Persistent identifier of the app, which is kept across restarts.
+    short_id: ShortID,
+
+    /// Storage identifiers the process may read, write and modify.
+    storage_permissions: StoragePermissions,
+
     /// Values kept so that we can print useful debug messages when apps fault.
     debug: MapCell<ProcessStandardDebug>,
 }
@@ -220,6 +273,16 @@ impl<C: Chip> Process for ProcessStandard<'_, C> {
             return Err(ErrorCode::NODEVICE);
         }
 
+        // The upcall the process waits for is returned to it directly.
+        if let Task::FunctionCall(function_call) = task {
+            if let FunctionCallSource::Driver(upcall_id) = function_call.source {
+                if self.yielded_for.get() == Some(upcall_id) {
+                    self.resume_yielded_for(function_call);
+                    return Ok(());
+                }
+            }
+        }
+
         let ret = self.tasks.map_or(Err(ErrorCode::FAIL), |tasks| {
             match tasks.enqueue(task) {
                 true => {
@@ -248,8 +311,7 @@ impl<C: Chip> Process for ProcessStandard<'_, C> {
     }
 
     fn ready(&self) -> bool {
-        self.tasks.map_or(false, |ring_buf| ring_buf.has_elements())
-            || self.state.get() == State::Running
+        self.upcalls_deliverable() || self.state.get() == State::Running
     }
 
     fn remove_pending_upcalls(&self, upcall_id: UpcallId) {
@@ -290,10 +352,46 @@ impl<C: Chip> Process for ProcessStandard<'_, C> {
 
     fn set_yielded_state(&self) {
         if self.state.get() == State::Running {
-            self.state.update(State::Yielded);
+            self.threads[self.current_thread.get()].set(ThreadState::Waiting);
+            self.switch_thread();
         }
     }
 
+    fn set_yielded_for_state(&self, upcall_id: UpcallId) {
+        if self.state.get() != State::Running {
+            return;
+        }
+
+        // Take the oldest matching upcall if one is already queued.
+        let queued = Cell::new(None);
+        self.tasks.map(|tasks| {
+            tasks.retain(|task| match task {
+                Task::FunctionCall(function_call)
+                    if queued.get().is_none()
+                        && function_call.source == FunctionCallSource::Driver(upcall_id) =>
+                {
+                    self.kernel.decrement_work();
+                    queued.set(Some(*function_call));
+                    false
+                }
+                _ => true,
+            });
+        });
+        match queued.get() {
+            Some(function_call) => {
+                self.set_syscall_return_value(upcall_return_value(function_call));
+            }
+            None => {
+                self.yielded_for.set(Some(upcall_id));
+                self.set_yielded_state();
+            }
+        }
+    }
+
+    fn yielded_for(&self) -> Option<UpcallId> {
+        self.yielded_for.get()
+    }
+
     fn stop(&self) {
         match self.state.get() {
             State::Running => self.state.update(State::StoppedRunning),
@@ -315,6 +413,21 @@ impl<C: Chip> Process for ProcessStandard<'_, C> {
         // should take since the process faulted.
         let action = self.fault_policy.action(self);
 
+        // Faults that panic the kernel are reported by the panic handler.
+        if config::CONFIG.process_fault_detail != FaultDetail::None
+            && !matches!(action, FaultAction::Panic)
+        {
+            debug!(
+                "Process {} faulted after {} restarts, action: {:?}",
+                self.process_name,
+                self.restart_count.get(),
+                action
+            );
+            if config::CONFIG.process_fault_detail == FaultDetail::Full {
+                debug::debug_process(self);
+            }
+        }
+
         match action {
             FaultAction::Panic => {
                 // process faulted. Panic and print status
@@ -367,6 +480,9 @@ impl<C: Chip> Process for ProcessStandard<'_, C> {
             self.grant_ptrs_reset();
         }
 
+        // Only the main thread is left, which is started again on restart.
+        self.reset_threads();
+
         // Mark the app as stopped so the scheduler won't try to run it.
         self.state.update(State::Terminated);
     }
@@ -380,6 +496,11 @@ impl<C: Chip> Process for ProcessStandard<'_, C> {
     }
 
     fn dequeue_task(&self) -> Option<Task> {
+        // Queued tasks wait until the process stops waiting for a specific
+        // upcall.
+        if self.yielded_for.get().is_some() {
+            return None;
+        }
         self.tasks.map_or(None, |tasks| {
             tasks.dequeue().map(|cb| {
                 self.kernel.decrement_work();
@@ -491,12 +612,146 @@ impl<C: Chip> Process for ProcessStandard<'_, C> {
         self.brk(new_break)
     }
 
+    fn current_thread(&self) -> usize {
+        self.current_thread.get()
+    }
+
+    fn thread_count(&self) -> usize {
+        self.threads
+            .iter()
+            .filter(|thread| thread.get() != ThreadState::Free)
+            .count()
+    }
+
+    fn create_thread(
+        &self,
+        entry: *const u8,
+        stack_pointer: *const u8,
+        argument: usize,
+    ) -> Result<usize, ErrorCode> {
+        let valid_entry = NonNull::new(entry as *mut ())
+            .map_or(false, |entry| self.is_valid_upcall_function_pointer(entry));
+        if !valid_entry || stack_pointer < self.mem_start() || stack_pointer > self.app_break.get()
+        {
+            return Err(ErrorCode::INVAL);
+        }
+        let thread = (1..MAX_THREADS)
+            .find(|thread| self.threads[*thread].get() == ThreadState::Free)
+            .ok_or(ErrorCode::NOMEM)?;
+
+        // The thread starts like an upcall on its own stack. We guarantee that
+        // the memory bounds and stack pointer passed to the UKB are valid.
+        let stored_state = self
+            .thread_stored_state_or_allocate(thread)
+            .ok_or(ErrorCode::NOMEM)?;
+        let ukb = self.chip.userspace_kernel_boundary();
+        // ### Safety
+        //
+        // `stored_state` points to the pinned state of this thread slot in the
+        // grant region, see `thread_stored_state_or_allocate`.
+        unsafe { stored_state.as_ref() }.map_or(Err(ErrorCode::FAIL), |stored_state| unsafe {
+            ukb.initialize_thread(
+                self.mem_start(),
+                self.app_break.get(),
+                stack_pointer,
+                stored_state,
+            )?;
+            ukb.set_process_function(
+                self.mem_start(),
+                self.app_break.get(),
+                stored_state,
+                FunctionCall {
+                    source: FunctionCallSource::Kernel,
+                    pc: entry as usize,
+                    argument0: argument,
+                    argument1: thread,
+                    argument2: 0,
+                    argument3: 0,
+                },
+            )
+            .map_err(|()| ErrorCode::FAIL)
+        })?;
+        self.threads[thread].set(ThreadState::Runnable);
+        Ok(thread)
+    }
+
+    fn join_thread(&self, thread: usize) -> Result<(), ErrorCode> {
+        let current = self.current_thread.get();
+        if thread == 0 || thread == current || thread >= MAX_THREADS {
+            return Err(ErrorCode::INVAL);
+        }
+        match self.threads[thread].get() {
+            ThreadState::Free => Err(ErrorCode::INVAL),
+            ThreadState::Exited(completion_code) => {
+                self.threads[thread].set(ThreadState::Free);
+                self.set_syscall_return_value(SyscallReturn::SuccessU32(completion_code));
+                Ok(())
+            }
+            _ => {
+                if self
+                    .threads
+                    .iter()
+                    .any(|other| other.get() == ThreadState::Joining(thread))
+                {
+                    return Err(ErrorCode::BUSY);
+                }
+                // Waiting would deadlock if `thread` waits, directly or
+                // through other threads, for the current thread.
+                let mut next = thread;
+                while let ThreadState::Joining(joined) = self.threads[next].get() {
+                    if joined == current {
+                        return Err(ErrorCode::BUSY);
+                    }
+                    next = joined;
+                }
+                self.threads[current].set(ThreadState::Joining(thread));
+                self.switch_thread();
+                Ok(())
+            }
+        }
+    }
+
+    fn yield_thread(&self) {
+        if self.state.get() == State::Running {
+            self.switch_thread();
+        }
+    }
+
+    fn exit_thread(&self, completion_code: u32) -> Result<(), ErrorCode> {
+        let current = self.current_thread.get();
+        if current == 0 {
+            return Err(ErrorCode::INVAL);
+        }
+        match self
+            .threads
+            .iter()
+            .position(|thread| thread.get() == ThreadState::Joining(current))
+        {
+            Some(joiner) => {
+                self.threads[current].set(ThreadState::Free);
+                self.threads[joiner].set(ThreadState::Runnable);
+                self.set_thread_syscall_return_value(
+                    joiner,
+                    SyscallReturn::SuccessU32(completion_code),
+                );
+            }
+            None => self.threads[current].set(ThreadState::Exited(completion_code)),
+        }
+        self.switch_thread();
+        Ok(())
+    }
+
     fn brk(&self, new_break: *const u8) -> Result<*const u8, Error> {
         // Do not modify an inactive process.
         if !self.is_active() {
             return Err(Error::InactiveApp);
         }
 
+        // Reclaim the memory of freed grants before giving up.
+        if new_break > self.kernel_memory_break.get() {
+            self.compact_grants();
+        }
+
         self.mpu_config
             .map_or(Err(Error::KernelError), |mut config| {
                 if new_break < self.allow_high_water_mark.get() || new_break >= self.mem_end() {
@@ -742,6 +997,19 @@ impl<C: Chip> Process for ProcessStandard<'_, C> {
                         // Actually set the driver num and grant pointer.
                         grant_entry.driver_num = driver_num;
                         grant_entry.grant_ptr = grant_ptr.as_ptr() as *mut u8;
+                        grant_entry.size = size;
+                        grant_entry.align = align;
+
+                        if config::CONFIG.debug_grants {
+                            debug!(
+                                "[{:?}] grant {} for driver {:#x}: {} bytes at {:?}",
+                                self.processid(),
+                                grant_num,
+                                driver_num,
+                                size,
+                                grant_ptr
+                            );
+                        }
 
                         // If all of this worked, return the allocated pointer.
                         Some(grant_ptr)
@@ -749,6 +1017,15 @@ impl<C: Chip> Process for ProcessStandard<'_, C> {
             })
         } else {
             // Could not allocate the memory for the grant region.
+            if config::CONFIG.debug_grants {
+                debug!(
+                    "[{:?}] no memory for grant {} for driver {:#x}: {} bytes",
+                    self.processid(),
+                    grant_num,
+                    driver_num,
+                    size
+                );
+            }
             None
         }
     }
@@ -766,6 +1043,11 @@ impl<C: Chip> Process for ProcessStandard<'_, C> {
         // Use the shared grant allocator function to actually allocate memory.
         // Returns `None` if the allocation cannot be created.
         if let Some(ptr) = self.allocate_in_grant_region_internal(size, align) {
+            // Grants allocated before this one can no longer be moved.
+            if (ptr.as_ptr() as *const u8) < self.custom_grant_break.get() {
+                self.custom_grant_break.set(ptr.as_ptr());
+            }
+
             // Create the identifier that the caller will use to get access to
             // this custom grant in the future.
             let identifier = self.create_custom_grant_identifier(ptr);
@@ -777,6 +1059,42 @@ impl<C: Chip> Process for ProcessStandard<'_, C> {
         }
     }
 
+    fn free_grant(&self, grant_num: usize) -> Result<(), Error> {
+        // Do not modify an inactive process.
+        if !self.is_active() {
+            return Err(Error::InactiveApp);
+        }
+
+        let freed = self
+            .grant_pointers
+            .map_or(Err(Error::KernelError), |grant_pointers| {
+                match grant_pointers.get_mut(grant_num) {
+                    Some(grant_entry) => {
+                        if grant_entry.grant_ptr.is_null() {
+                            Ok(false)
+                        } else if (grant_entry.grant_ptr as usize) & 0x1 == 0x1 {
+                            // The capsule is using the grant right now.
+                            Err(Error::AlreadyInUse)
+                        } else {
+                            grant_entry.driver_num = 0;
+                            grant_entry.grant_ptr = ptr::null_mut();
+                            grant_entry.size = 0;
+                            grant_entry.align = 0;
+                            Ok(true)
+                        }
+                    }
+                    None => Err(Error::KernelError),
+                }
+            })?;
+        if freed {
+            if config::CONFIG.debug_grants {
+                debug!("[{:?}] freed grant {}", self.processid(), grant_num);
+            }
+            self.compact_grants();
+        }
+        Ok(())
+    }
+
     fn enter_grant(&self, grant_num: usize) -> Result<*mut u8, Error> {
         // Do not try to access the grant region of inactive process.
         if !self.is_active() {
@@ -903,40 +1221,36 @@ impl<C: Chip> Process for ProcessStandard<'_, C> {
         self.process_name
     }
 
-    fn set_syscall_return_value(&self, return_value: SyscallReturn) {
-        match self.stored_state.map(|stored_state| unsafe {
-            // Actually set the return value for a particular process.
-            //
-            // The UKB implementation uses the bounds of process-accessible
-            // memory to verify that any memory changes are valid. Here, the
-            // unsafe promise we are making is that the bounds passed to the UKB
-            // are correct.
-            self.chip
-                .userspace_kernel_boundary()
-                .set_syscall_return_value(
-                    self.mem_start(),
-                    self.app_break.get(),
-                    stored_state,
-                    return_value,
-                )
-        }) {
-            Some(Ok(())) => {
-                // If we get an `Ok` we are all set.
-            }
+    fn short_app_id(&self) -> ShortID {
+        self.short_id
+    }
 
-            Some(Err(())) => {
-                // If we get an `Err`, then the UKB implementation could not set
-                // the return value, likely because the process's stack is no
-                // longer accessible to it. All we can do is fault.
-                self.set_fault_state();
-            }
+    fn get_storage_permissions(&self) -> StoragePermissions {
+        self.storage_permissions
+    }
 
-            None => {
-                // We should never be here since `stored_state` should always be
-                // occupied.
-                self.set_fault_state();
-            }
-        }
+    fn get_command_permissions(&self, driver_num: usize, offset: usize) -> CommandPermissions {
+        self.header.get_command_permissions(driver_num, offset)
+    }
+
+    fn get_real_time_reservation(&self) -> Option<(u32, u32)> {
+        self.header.get_real_time_reservation()
+    }
+
+    fn get_priority(&self) -> Option<u32> {
+        self.header.get_priority()
+    }
+
+    fn ipc_client_allowed(&self, client: ShortID) -> bool {
+        let short_id = match client {
+            ShortID::Fixed(short_id) => short_id.get(),
+            ShortID::LocallyUnique => 0,
+        };
+        self.header.ipc_client_allowed(short_id)
+    }
+
+    fn set_syscall_return_value(&self, return_value: SyscallReturn) {
+        self.set_thread_syscall_return_value(self.current_thread.get(), return_value);
     }
 
     fn set_process_function(&self, callback: FunctionCall) {
@@ -946,7 +1260,8 @@ impl<C: Chip> Process for ProcessStandard<'_, C> {
         //
         // This can fail, for example if the process does not have enough memory
         // remaining.
-        match self.stored_state.map(|stored_state| {
+        let current = self.current_thread.get();
+        match self.map_thread_stored_state(current, |stored_state| {
             // Let the UKB implementation handle setting the process's PC so
             // that the process executes the upcall function. We encapsulate
             // unsafe here because we are guaranteeing that the memory bounds
@@ -963,6 +1278,7 @@ impl<C: Chip> Process for ProcessStandard<'_, C> {
             Some(Ok(())) => {
                 // If we got an `Ok` we are all set and should mark that this
                 // process is ready to be scheduled.
+                self.threads[current].set(ThreadState::Runnable);
 
                 // Move this process to the "running" state so the scheduler
                 // will schedule it.
@@ -992,8 +1308,20 @@ impl<C: Chip> Process for ProcessStandard<'_, C> {
             return None;
         }
 
-        let (switch_reason, stack_pointer) =
-            self.stored_state.map_or((None, None), |stored_state| {
+        // An upcall for the main thread preempts the other threads, as the
+        // main thread would have run it already had it been the one running.
+        // The other thread was interrupted before it could run.
+        let current = self.current_thread.get();
+        if current != 0
+            && self.threads[0].get() == ThreadState::Waiting
+            && self.upcalls_deliverable()
+        {
+            self.switch_thread();
+            return Some(syscall::ContextSwitchReason::Interrupted);
+        }
+
+        let (switch_reason, stack_pointer) = self
+            .map_thread_stored_state(current, |stored_state| {
                 // Switch to the process. We guarantee that the memory pointers
                 // we pass are valid, ensuring this context switch is safe.
                 // Therefore we encapsulate the `unsafe`.
@@ -1004,11 +1332,13 @@ impl<C: Chip> Process for ProcessStandard<'_, C> {
                         .switch_to_process(self.mem_start(), self.app_break.get(), stored_state);
                     (Some(switch_reason), optional_stack_pointer)
                 }
-            });
+            })
+            .unwrap_or((None, None));
 
         // If the UKB implementation passed us a stack pointer, update our
-        // debugging state. This is completely optional.
-        stack_pointer.map(|sp| {
+        // debugging state. This is completely optional, and only tracks the
+        // stack of the main thread.
+        stack_pointer.filter(|_| current == 0).map(|sp| {
             self.debug.map(|debug| {
                 match debug.app_stack_min_pointer {
                     None => debug.app_stack_min_pointer = Some(sp),
@@ -1043,6 +1373,29 @@ impl<C: Chip> Process for ProcessStandard<'_, C> {
             .map(|debug| debug.timeslice_expiration_count += 1);
     }
 
+    fn debug_executed(&self, execution_time_us: u32, kernel_time_us: u32) {
+        self.debug.map(|debug| {
+            debug.execution_time_us += execution_time_us as u64;
+            debug.kernel_time_us += kernel_time_us as u64;
+        });
+    }
+
+    fn debug_execution_time_us(&self) -> u64 {
+        self.debug.map_or(0, |debug| debug.execution_time_us)
+    }
+
+    fn debug_kernel_time_us(&self) -> u64 {
+        self.debug.map_or(0, |debug| debug.kernel_time_us)
+    }
+
+    fn debug_woken_up(&self) {
+        self.debug.map(|debug| debug.wakeup_count += 1);
+    }
+
+    fn debug_wakeup_count(&self) -> usize {
+        self.debug.map_or(0, |debug| debug.wakeup_count)
+    }
+
     fn debug_syscall_called(&self, last_syscall: Syscall) {
         self.debug.map(|debug| {
             debug.syscall_count += 1;
@@ -1065,6 +1418,28 @@ impl<C: Chip> Process for ProcessStandard<'_, C> {
             .map_or(None, |debug| debug.app_stack_min_pointer.map(|p| p))
     }
 
+    fn debug_last_syscall(&self) -> Option<Syscall> {
+        self.debug.map_or(None, |debug| debug.last_syscall)
+    }
+
+    fn debug_copy_stack(&self, out: &mut [u8]) -> usize {
+        let (start, end) = match (self.debug_stack_start(), self.debug_stack_end()) {
+            (Some(start), Some(end)) => (start as usize, end as usize),
+            _ => return 0,
+        };
+        // Only copy memory the process can access.
+        if end < self.mem_start() as usize || start > self.app_break.get() as usize || end > start {
+            return 0;
+        }
+        let length = cmp::min(start - end, out.len());
+        // Safety: the range is inside the process's accessible memory, which
+        // the kernel does not hold any references into while the process is
+        // not running.
+        let stack = unsafe { slice::from_raw_parts(end as *const u8, length) };
+        out[..length].copy_from_slice(stack);
+        length
+    }
+
     fn print_memory_map(&self, writer: &mut dyn Write) {
         // Flash
         let flash_end = self.flash.as_ptr().wrapping_add(self.flash.len()) as usize;
@@ -1103,13 +1478,14 @@ impl<C: Chip> Process for ProcessStandard<'_, C> {
             "\
              𝐀𝐩𝐩: {}   -   [{:?}]\
              \r\n Events Queued: {}   Syscall Count: {}   Dropped Upcall Count: {}\
-             \r\n Restart Count: {}\r\n",
+             \r\n Restart Count: {}   Short ID: {}\r\n",
             self.process_name,
             self.state.get(),
             events_queued,
             syscall_count,
             dropped_upcall_count,
             restart_count,
+            self.short_id,
         ));
 
         let _ = match last_syscall {
@@ -1123,16 +1499,32 @@ impl<C: Chip> Process for ProcessStandard<'_, C> {
              \r\n ╔═══════════╤══════════════════════════════════════════╗\
              \r\n ║  Address  │ Region Name    Used | Allocated (bytes)  ║\
              \r\n ╚{:#010X}═╪══════════════════════════════════════════╝\
-             \r\n             │ ▼ Grant      {:6} | {:6}{}\
-             \r\n  {:#010X} ┼───────────────────────────────────────────\
-             \r\n             │ Unused\
-             \r\n  {:#010X} ┼───────────────────────────────────────────",
+             \r\n             │ ▼ Grant      {:6} | {:6}{}",
             sram_end,
             sram_grant_size,
             sram_grant_allocated,
             exceeded_check(sram_grant_size, sram_grant_allocated),
-            sram_grant_start,
-            sram_heap_end,
+        ));
+
+        // Break the grant region down by the driver each grant belongs to.
+        self.grant_pointers.map(|grant_pointers| {
+            for grant_entry in grant_pointers
+                .iter()
+                .filter(|grant_entry| !grant_entry.grant_ptr.is_null())
+            {
+                let _ = writer.write_fmt(format_args!(
+                    "\r\n             │   {:#07x}    {:6}",
+                    grant_entry.driver_num, grant_entry.size,
+                ));
+            }
+        });
+
+        let _ = writer.write_fmt(format_args!(
+            "\
+             \r\n  {:#010X} ┼───────────────────────────────────────────\
+             \r\n             │ Unused\
+             \r\n  {:#010X} ┼───────────────────────────────────────────",
+            sram_grant_start, sram_heap_end,
         ));
 
         match sram_heap_start {
@@ -1224,10 +1616,25 @@ impl<C: Chip> Process for ProcessStandard<'_, C> {
         ));
     }
 
+    fn get_stored_state(&self, out: &mut [u8]) -> Result<usize, ErrorCode> {
+        self.map_thread_stored_state(self.current_thread.get(), |stored_state| {
+            self.chip
+                .userspace_kernel_boundary()
+                .store_context(stored_state, out)
+        })
+        .unwrap_or(Err(ErrorCode::FAIL))
+    }
+
     fn print_full_process(&self, writer: &mut dyn Write) {
         self.print_memory_map(writer);
 
-        self.stored_state.map(|stored_state| {
+        let _ = writer.write_fmt(format_args!(
+            "\r\n Thread {} of {}\r\n",
+            self.current_thread.get(),
+            self.thread_count(),
+        ));
+
+        self.map_thread_stored_state(self.current_thread.get(), |stored_state| {
             // We guarantee the memory bounds pointers provided to the UKB are
             // correct.
             unsafe {
@@ -1339,6 +1746,8 @@ impl<C: 'static + Chip> ProcessStandard<'_, C> {
         app_version: u16,
         remaining_memory: &'a mut [u8],
         fault_policy: &'static dyn ProcessFaultPolicy,
+        short_id: ShortID,
+        verified: bool,
         index: usize,
     ) -> Result<(Option<&'static dyn Process>, &'a mut [u8]), ProcessLoadError> {
         // Get a slice for just the app header.
@@ -1602,6 +2011,8 @@ impl<C: 'static + Chip> ProcessStandard<'_, C> {
         for grant_entry in grant_pointers.iter_mut() {
             grant_entry.driver_num = 0;
             grant_entry.grant_ptr = ptr::null_mut();
+            grant_entry.size = 0;
+            grant_entry.align = 0;
         }
 
         // Now that we know we have the space we can setup the memory for the
@@ -1649,12 +2060,22 @@ impl<C: 'static + Chip> ProcessStandard<'_, C> {
         process.memory_len = app_memory.len();
         process.header = tbf_header;
         process.kernel_memory_break = Cell::new(kernel_memory_break);
+        process.custom_grant_break = Cell::new(kernel_memory_break);
         process.app_break = Cell::new(initial_app_brk);
         process.grant_pointers = MapCell::new(grant_pointers);
 
         process.flash = app_flash;
 
         process.stored_state = MapCell::new(Default::default());
+        process.thread_stored_states = [Cell::new(None), Cell::new(None), Cell::new(None)];
+        process.threads = [
+            Cell::new(ThreadState::Waiting),
+            Cell::new(ThreadState::Free),
+            Cell::new(ThreadState::Free),
+            Cell::new(ThreadState::Free),
+        ];
+        process.current_thread = Cell::new(0);
+        process.yielded_for = Cell::new(None);
         // Mark this process as unstarted
         process.state = ProcessStateCell::new(process.kernel);
         process.fault_policy = fault_policy;
@@ -1671,6 +2092,9 @@ impl<C: 'static + Chip> ProcessStandard<'_, C> {
         ];
         process.tasks = MapCell::new(tasks);
         process.process_name = process_name.unwrap_or("");
+        process.short_id = short_id;
+        process.storage_permissions =
+            StoragePermissions::from_header(&process.header, short_id, verified);
 
         process.debug = MapCell::new(ProcessStandardDebug {
             fixed_address_flash: fixed_address_flash,
@@ -1682,6 +2106,9 @@ impl<C: 'static + Chip> ProcessStandard<'_, C> {
             last_syscall: None,
             dropped_upcall_count: 0,
             timeslice_expiration_count: 0,
+            execution_time_us: 0,
+            kernel_time_us: 0,
+            wakeup_count: 0,
         });
 
         let flash_protected_size = process.header.get_protected_size() as usize;
@@ -1733,6 +2160,153 @@ impl<C: 'static + Chip> ProcessStandard<'_, C> {
         Ok((Some(process), unused_memory))
     }
 
+    /// Set the return value of the last system call of `thread`.
+    fn set_thread_syscall_return_value(&self, thread: usize, return_value: SyscallReturn) {
+        match self.map_thread_stored_state(thread, |stored_state| unsafe {
+            // Actually set the return value for a particular process.
+            //
+            // The UKB implementation uses the bounds of process-accessible
+            // memory to verify that any memory changes are valid. Here, the
+            // unsafe promise we are making is that the bounds passed to the UKB
+            // are correct.
+            self.chip
+                .userspace_kernel_boundary()
+                .set_syscall_return_value(
+                    self.mem_start(),
+                    self.app_break.get(),
+                    stored_state,
+                    return_value,
+                )
+        }) {
+            Some(Ok(())) => {
+                // If we get an `Ok` we are all set.
+            }
+
+            Some(Err(())) => {
+                // If we get an `Err`, then the UKB implementation could not set
+                // the return value, likely because the process's stack is no
+                // longer accessible to it. All we can do is fault.
+                self.set_fault_state();
+            }
+
+            None => {
+                // We should never be here since `stored_state` should always be
+                // occupied.
+                self.set_fault_state();
+            }
+        }
+    }
+
+    /// Run `closure` on the stored state of `thread`. Returns `None` if the
+    /// thread has no stored state.
+    fn map_thread_stored_state<F, R>(&self, thread: usize, closure: F) -> Option<R>
+    where
+        F: FnOnce(&mut StoredState<C>) -> R,
+    {
+        match thread {
+            0 => self.stored_state.map(closure),
+            thread => self.thread_stored_states[thread - 1]
+                .get()
+                // ### Safety
+                //
+                // The state was allocated in the grant region of this process
+                // by `thread_stored_state_or_allocate` and is pinned there. The
+                // pointer is cleared before the grant region is reset on
+                // restart.
+                .and_then(|stored_state| unsafe { stored_state.as_ref() }.map(closure)),
+        }
+    }
+
+    /// Return the stored state for `thread`, allocating it in the grant
+    /// region if this is the first time the thread slot is used.
+    fn thread_stored_state_or_allocate(
+        &self,
+        thread: usize,
+    ) -> Option<NonNull<MapCell<StoredState<C>>>> {
+        let slot = &self.thread_stored_states[thread - 1];
+        if slot.get().is_none() {
+            let ptr = self.allocate_in_grant_region_internal(
+                mem::size_of::<MapCell<StoredState<C>>>(),
+                mem::align_of::<MapCell<StoredState<C>>>(),
+            )?;
+            // Like custom grants, the state must never be moved when grants
+            // are compacted.
+            if (ptr.as_ptr() as *const u8) < self.custom_grant_break.get() {
+                self.custom_grant_break.set(ptr.as_ptr());
+            }
+            let stored_state = ptr.cast::<MapCell<StoredState<C>>>();
+            // ### Safety
+            //
+            // The memory was just allocated for this type, and nothing else
+            // refers to it.
+            unsafe {
+                ptr::write(stored_state.as_ptr(), MapCell::new(Default::default()));
+            }
+            slot.set(Some(stored_state));
+        }
+        slot.get()
+    }
+
+    /// Pick the thread to run next, after the current thread blocked or
+    /// yielded. Upcalls pending for the main thread take precedence, then the
+    /// other runnable threads take turns. If no thread can run, the process
+    /// yields.
+    fn switch_thread(&self) {
+        if self.threads[0].get() == ThreadState::Waiting && self.upcalls_deliverable() {
+            self.current_thread.set(0);
+            self.state.update(State::Yielded);
+            return;
+        }
+        let current = self.current_thread.get();
+        match (1..=MAX_THREADS)
+            .map(|offset| (current + offset) % MAX_THREADS)
+            .find(|thread| self.threads[*thread].get() == ThreadState::Runnable)
+        {
+            Some(thread) => self.current_thread.set(thread),
+            None => {
+                // Every chain of joining threads ends at a runnable or exited
+                // thread, so only the main thread can be waiting here.
+                self.current_thread.set(0);
+                self.state.update(State::Yielded);
+            }
+        }
+    }
+
+    /// Whether the main thread can run a queued upcall.
+    fn upcalls_deliverable(&self) -> bool {
+        self.yielded_for.get().is_none() && self.tasks.map_or(false, |tasks| tasks.has_elements())
+    }
+
+    /// Return the upcall the main thread waits for to it, and let it run.
+    fn resume_yielded_for(&self, function_call: FunctionCall) {
+        self.yielded_for.set(None);
+        self.set_thread_syscall_return_value(0, upcall_return_value(function_call));
+        self.threads[0].set(ThreadState::Runnable);
+        match self.state.get() {
+            State::Yielded => {
+                self.current_thread.set(0);
+                self.state.update(State::Running);
+            }
+            State::StoppedYielded => {
+                self.current_thread.set(0);
+                self.state.update(State::StoppedRunning);
+            }
+            // Another thread is running, and the main thread gets its turn
+            // once that thread yields.
+            _ => {}
+        }
+    }
+
+    /// Forget all threads but the main thread, which waits to be started.
+    fn reset_threads(&self) {
+        self.threads
+            .iter()
+            .for_each(|thread| thread.set(ThreadState::Free));
+        self.threads[0].set(ThreadState::Waiting);
+        self.current_thread.set(0);
+        self.yielded_for.set(None);
+    }
+
     /// Restart the process, resetting all of its state and re-initializing
     /// it to start running.  Assumes the process is not running but is still in flash
     /// and still has its memory region allocated to it. This implements
@@ -1754,6 +2328,9 @@ impl<C: 'static + Chip> ProcessStandard<'_, C> {
             debug.last_syscall = None;
             debug.dropped_upcall_count = 0;
             debug.timeslice_expiration_count = 0;
+            debug.execution_time_us = 0;
+            debug.kernel_time_us = 0;
+            debug.wakeup_count = 0;
         });
 
         // FLASH
@@ -1796,7 +2373,7 @@ impl<C: 'static + Chip> ProcessStandard<'_, C> {
             .initial_process_app_brk_size();
 
         // Recalculate initial_kernel_memory_size as was done in create()
-        let grant_ptr_size = mem::size_of::<(usize, *mut u8)>();
+        let grant_ptr_size = mem::size_of::<GrantPointerEntry>();
         let grant_ptrs_num = self.kernel.get_grant_count_and_finalize();
         let grant_ptrs_offset = grant_ptrs_num * grant_ptr_size;
 
@@ -1836,6 +2413,11 @@ impl<C: 'static + Chip> ProcessStandard<'_, C> {
             .wrapping_add(app_mpu_mem_len)
             .wrapping_sub(initial_kernel_memory_size);
         self.kernel_memory_break.set(kernel_brk);
+        self.custom_grant_break.set(kernel_brk);
+        // The stored state of the other threads lived in the grant region.
+        self.thread_stored_states
+            .iter()
+            .for_each(|stored_state| stored_state.set(None));
         // High water mark for `allow`ed memory is reset to the start of the
         // process's memory region.
         self.allow_high_water_mark.set(app_mpu_mem_start);
@@ -1871,6 +2453,7 @@ impl<C: 'static + Chip> ProcessStandard<'_, C> {
 
         // Mark the state as `Unstarted` for the scheduler.
         self.state.update(State::Unstarted);
+        self.reset_threads();
 
         // Mark that we restarted this process.
         self.restart_count.increment();
@@ -1924,6 +2507,8 @@ impl<C: 'static + Chip> ProcessStandard<'_, C> {
             for grant_entry in grant_pointers.iter_mut() {
                 grant_entry.driver_num = 0;
                 grant_entry.grant_ptr = ptr::null_mut();
+                grant_entry.size = 0;
+                grant_entry.align = 0;
             }
         });
     }
@@ -1933,10 +2518,86 @@ impl<C: 'static + Chip> ProcessStandard<'_, C> {
     /// Ensures that the allocation is of `size` bytes and aligned to `align`
     /// bytes.
     ///
-    /// If there is not enough memory, or the MPU cannot isolate the process
-    /// accessible region from the new kernel memory break after doing the
-    /// allocation, then this will return `None`.
+    /// If there is not enough memory, the grant region is compacted to reclaim
+    /// the memory of freed grants. If there is still not enough memory, or the
+    /// MPU cannot isolate the process accessible region from the new kernel
+    /// memory break after doing the allocation, then this will return `None`.
     fn allocate_in_grant_region_internal(&self, size: usize, align: usize) -> Option<NonNull<u8>> {
+        self.try_allocate_in_grant_region(size, align).or_else(|| {
+            self.compact_grants();
+            self.try_allocate_in_grant_region(size, align)
+        })
+    }
+
+    /// Move the grants below the custom grants up over the memory of freed
+    /// grants, and raise the kernel memory break to the lowest remaining
+    /// grant.
+    ///
+    /// Grants are only ever accessed through the grant pointer table, so they
+    /// can be moved as long as they are not entered. An entered grant stays
+    /// where it is, and the grants below it are moved up to it instead.
+    fn compact_grants(&self) {
+        let mut cursor = self.custom_grant_break.get() as usize;
+        self.grant_pointers.map(|grant_pointers| {
+            // Visit the grants from the highest address down.
+            let mut upper = cursor;
+            while let Some(grant_entry) = grant_pointers
+                .iter_mut()
+                .filter(|grant_entry| {
+                    !grant_entry.grant_ptr.is_null()
+                        && (grant_entry.grant_ptr as usize & !0x1) < upper
+                })
+                .max_by_key(|grant_entry| grant_entry.grant_ptr as usize)
+            {
+                let address = grant_entry.grant_ptr as usize & !0x1;
+                upper = address;
+                if (grant_entry.grant_ptr as usize) & 0x1 == 0x1 {
+                    cursor = address;
+                    continue;
+                }
+
+                // The grant ends below `cursor` and is aligned, so the target
+                // is never below its current address.
+                let align = cmp::max(grant_entry.align, 2);
+                let target = (cursor - grant_entry.size) & !(align - 1);
+                if target > address {
+                    // ### Safety
+                    //
+                    // Both ranges are inside the grant region of this process,
+                    // and the kernel holds no references into the grant since
+                    // it is not entered. `copy` handles the overlap.
+                    unsafe {
+                        ptr::copy(address as *const u8, target as *mut u8, grant_entry.size);
+                    }
+                    grant_entry.grant_ptr = target as *mut u8;
+                }
+                cursor = target;
+            }
+        });
+
+        let new_break = cursor as *const u8;
+        if new_break <= self.kernel_memory_break.get() {
+            return;
+        }
+        self.mpu_config.map(|mut config| {
+            if self
+                .chip
+                .mpu()
+                .update_app_memory_region(
+                    self.app_break.get(),
+                    new_break,
+                    mpu::Permissions::ReadWriteOnly,
+                    &mut config,
+                )
+                .is_ok()
+            {
+                self.kernel_memory_break.set(new_break);
+            }
+        });
+    }
+
+    /// Allocate memory in the grant region without compacting it first.
+    fn try_allocate_in_grant_region(&self, size: usize, align: usize) -> Option<NonNull<u8>> {
         self.mpu_config.and_then(|mut config| {
             // First, compute the candidate new pointer. Note that at this
             // point we have not yet checked whether there is space for
@@ -2031,3 +2692,12 @@ impl<C: 'static + Chip> ProcessStandard<'_, C> {
         current_state != State::Terminated && current_state != State::Faulted
     }
 }
+
+/// The return value of a yield-wait-for that waited for `function_call`.
+fn upcall_return_value(function_call: FunctionCall) -> SyscallReturn {
+    SyscallReturn::SuccessU32U32U32(
+        function_call.argument0 as u32,
+        function_call.argument1 as u32,
+        function_call.argument2 as u32,
+    )
+}
@@ -2,12 +2,15 @@
 
 use core::convert::TryInto;
 use core::fmt;
+use core::slice;
 
 use crate::capabilities::ProcessManagementCapability;
 use crate::config;
 use crate::debug;
+use crate::errorcode::ErrorCode;
 use crate::platform::Chip;
-use crate::process::Process;
+use crate::process::{Process, ProcessId, ShortID};
+use crate::process_checker::{self, AppCredentialsChecker, AppIdPolicy, CheckResult};
 use crate::process_policies::ProcessFaultPolicy;
 use crate::process_standard::ProcessStandard;
 use crate::sched::Kernel;
@@ -45,6 +48,18 @@ pub enum ProcessLoadError {
         expected_address: u32,
     },
 
+    /// There is no free slot in the processes array to load the process into.
+    NoProcessSlot,
+
+    /// The TBF is padding or a disabled app, so no process was created.
+    NotAnEnabledApp,
+
+    /// The app's credentials were not approved by the credentials checker.
+    CredentialsCheckFailed,
+
+    /// Another process already has the short ID of the app.
+    DuplicateShortId,
+
     /// Process loading error due (likely) to a bug in the kernel. If you get
     /// this error please open a bug report.
     InternalError,
@@ -98,6 +113,16 @@ impl fmt::Debug for ProcessLoadError {
                 actual_address, expected_address
             ),
 
+            ProcessLoadError::NoProcessSlot => write!(f, "No free process slot"),
+
+            ProcessLoadError::NotAnEnabledApp => write!(f, "TBF is not an enabled app"),
+
+            ProcessLoadError::CredentialsCheckFailed => {
+                write!(f, "App credentials were not approved")
+            }
+
+            ProcessLoadError::DuplicateShortId => write!(f, "Short ID is already in use"),
+
             ProcessLoadError::InternalError => write!(f, "Error in kernel. Likely a bug."),
         }
     }
@@ -114,7 +139,7 @@ impl fmt::Debug for ProcessLoadError {
 /// ensuring that this code cannot hold onto the slice past the end of this function
 /// (instead, processes store a pointer and length), which necessary for later
 /// creation of `ProcessBuffer`s in this memory region to be sound.
-/// A reference to each process is stored in the provided `procs` array.
+/// Each process is added to a free slot of the kernel's processes array.
 /// How process faults are handled by the
 /// kernel must be provided and is assigned to every created process.
 ///
@@ -130,9 +155,72 @@ pub fn load_processes<C: Chip>(
     chip: &'static C,
     app_flash: &'static [u8],
     app_memory: &mut [u8], // not static, so that process.rs cannot hold on to slice w/o unsafe
-    procs: &'static mut [Option<&'static dyn Process>],
     fault_policy: &'static dyn ProcessFaultPolicy,
     _capability: &dyn ProcessManagementCapability,
+) -> Result<(), ProcessLoadError> {
+    load_processes_from_flash(
+        kernel,
+        chip,
+        app_flash,
+        app_memory,
+        fault_policy,
+        None,
+        &process_checker::DEFAULT_APP_ID_POLICY,
+    )
+}
+
+/// Load processes like `load_processes()`, but only load apps whose
+/// credentials are approved by `checker`.
+///
+/// Before a process is created for an enabled app, the credentials in the
+/// footer of its TBF are passed to `checker`. Apps that are not approved are
+/// skipped: no process is created for them and they do not use any process
+/// memory. Each process is given the short ID `id_policy` assigns to its app.
+pub fn load_and_check_processes<C: Chip>(
+    kernel: &'static Kernel,
+    chip: &'static C,
+    app_flash: &'static [u8],
+    app_memory: &mut [u8], // not static, so that process.rs cannot hold on to slice w/o unsafe
+    fault_policy: &'static dyn ProcessFaultPolicy,
+    checker: &'static dyn AppCredentialsChecker,
+    id_policy: &'static dyn AppIdPolicy,
+    _capability: &dyn ProcessManagementCapability,
+) -> Result<(), ProcessLoadError> {
+    load_processes_from_flash(
+        kernel,
+        chip,
+        app_flash,
+        app_memory,
+        fault_policy,
+        Some(checker),
+        id_policy,
+    )
+}
+
+/// Whether a process in `kernel` already has the fixed short ID `short_id`.
+/// Apps with such a short ID are not loaded, so that no app can take the
+/// identity of another one.
+fn short_id_in_use(kernel: &Kernel, short_id: ShortID) -> bool {
+    let in_use = match short_id {
+        ShortID::Fixed(_) => kernel
+            .get_process_iter()
+            .any(|process| process.short_app_id() == short_id),
+        ShortID::LocallyUnique => false,
+    };
+    if in_use && config::CONFIG.debug_load_processes {
+        debug!("Short ID {} is already in use", short_id);
+    }
+    in_use
+}
+
+fn load_processes_from_flash<C: Chip>(
+    kernel: &'static Kernel,
+    chip: &'static C,
+    app_flash: &'static [u8],
+    app_memory: &mut [u8],
+    fault_policy: &'static dyn ProcessFaultPolicy,
+    checker: Option<&dyn AppCredentialsChecker>,
+    id_policy: &dyn AppIdPolicy,
 ) -> Result<(), ProcessLoadError> {
     if config::CONFIG.debug_load_processes {
         debug!(
@@ -147,8 +235,8 @@ pub fn load_processes<C: Chip>(
     let mut remaining_flash = app_flash;
     let mut remaining_memory = app_memory;
 
-    // Try to discover up to `procs.len()` processes in flash.
-    for i in 0..procs.len() {
+    // Discover processes in flash until the processes array is full.
+    while let Some(i) = kernel.free_process_slot() {
         // Get the first eight bytes of flash to check if there is another
         // app.
         let test_header_slice = match remaining_flash.get(0..8) {
@@ -200,7 +288,40 @@ pub fn load_processes<C: Chip>(
 
         // Need to reassign remaining_memory in every iteration so the compiler
         // knows it will not be re-borrowed.
-        remaining_memory = if header_length > 0 {
+        // If the board checks credentials, skip enabled apps whose
+        // credentials are not approved. Disabled apps and padding do not get
+        // a process anyway, so there is nothing to check. Apps whose short ID
+        // another process already has are skipped too.
+        let (approved, short_id, verified) = if header_length > 0 {
+            let header_flash = entry_flash
+                .get(0..header_length as usize)
+                .ok_or(ProcessLoadError::NotEnoughFlash)?;
+            let header = tock_tbf::parse::parse_tbf_header(header_flash, version)?;
+            let credentials = match checker {
+                Some(checker) if header.is_app() && header.enabled() => {
+                    process_checker::check_app_credentials(checker, &header, entry_flash)
+                }
+                _ => CheckResult::Pass,
+            };
+            let verified = credentials == CheckResult::Accept;
+            let short_id = id_policy.short_id(&header, verified);
+            (
+                credentials != CheckResult::Reject && !short_id_in_use(kernel, short_id),
+                short_id,
+                verified,
+            )
+        } else {
+            (true, ShortID::LocallyUnique, false)
+        };
+        if !approved && config::CONFIG.debug_load_processes {
+            debug!(
+                "App in flash={:#010X}-{:#010X} was refused, not loading",
+                entry_flash.as_ptr() as usize,
+                entry_flash.as_ptr() as usize + entry_flash.len() - 1,
+            );
+        }
+
+        remaining_memory = if header_length > 0 && approved {
             // If we found an actual app header, try to create a `Process`
             // object. We also need to shrink the amount of remaining memory
             // based on whatever is assigned to the new process if one is
@@ -218,10 +339,12 @@ pub fn load_processes<C: Chip>(
                     version,
                     remaining_memory,
                     fault_policy,
+                    short_id,
+                    verified,
                     i,
                 )?
             };
-            process_option.map(|process| {
+            if let Some(process) = process_option {
                 if config::CONFIG.debug_load_processes {
                     debug!(
                         "Loaded process[{}] from flash={:#010X}-{:#010X} into sram={:#010X}-{:#010X} = {:?}",
@@ -235,15 +358,243 @@ pub fn load_processes<C: Chip>(
                 }
 
                 // Save the reference to this process in the processes array.
-                procs[i] = Some(process);
-            });
+                kernel
+                    .insert_process(process)
+                    .or(Err(ProcessLoadError::InternalError))?;
+            }
             unused_memory
         } else {
-            // We are just skipping over this region of flash, so we have the
-            // same amount of process memory to allocate from.
+            // We are just skipping over this region of flash (or an app that
+            // failed its credentials check), so we have the same amount of
+            // process memory to allocate from.
             remaining_memory
         };
     }
 
     Ok(())
 }
+
+/// Interface for loading a single process after the kernel has booted.
+///
+/// This allows capsules that receive new apps at runtime to start them
+/// without access to the processes array or process memory.
+pub trait DynamicProcessLoading {
+    /// Create a process for the app in `app_flash`, which must contain exactly
+    /// one TBF, and insert it into a free process slot. The new process is
+    /// runnable when this returns.
+    fn load_process(&self, app_flash: &'static [u8]) -> Result<ProcessId, ProcessLoadError>;
+
+    /// Terminate the process and remove it from the processes array, freeing
+    /// its slot for another process. Returns the address and length of the
+    /// flash region the process was loaded from, which the caller may now
+    /// erase or reuse.
+    ///
+    /// If the process's memory came from the loader, it can be used by
+    /// processes loaded later. Memory of processes loaded at boot is not
+    /// reclaimed.
+    fn unload_process(
+        &self,
+        processid: ProcessId,
+        capability: &dyn ProcessManagementCapability,
+    ) -> Result<(usize, usize), ErrorCode>;
+}
+
+/// Loads processes into free process slots at runtime.
+///
+/// Processes are given memory from `app_memory`, which must not overlap the
+/// memory given to processes at boot. Memory used by processes it created
+/// that are later removed from the processes array becomes available again.
+/// The memory of removed processes that were loaded at boot is not reclaimed.
+///
+/// The processes the loader creates live inside `app_memory`, so the loader
+/// only keeps its address range. It never holds a reference to the whole
+/// pool, which would alias the memory of those processes.
+pub struct DynamicProcessLoader<C: 'static + Chip> {
+    kernel: &'static Kernel,
+    chip: &'static C,
+    memory_start: usize,
+    memory_len: usize,
+    fault_policy: &'static dyn ProcessFaultPolicy,
+    checker: Option<&'static dyn AppCredentialsChecker>,
+    id_policy: &'static dyn AppIdPolicy,
+}
+
+impl<C: 'static + Chip> DynamicProcessLoader<C> {
+    /// Create a loader that adds processes to free slots of `kernel`'s
+    /// processes array.
+    ///
+    /// Loading processes requires the `ProcessManagementCapability`. If
+    /// `checker` is provided, apps are only loaded if it approves their
+    /// credentials. New processes get the short ID `id_policy` assigns to
+    /// their app, which should be the policy used at boot.
+    pub fn new(
+        kernel: &'static Kernel,
+        chip: &'static C,
+        app_memory: &'static mut [u8],
+        fault_policy: &'static dyn ProcessFaultPolicy,
+        checker: Option<&'static dyn AppCredentialsChecker>,
+        id_policy: &'static dyn AppIdPolicy,
+        _capability: &dyn ProcessManagementCapability,
+    ) -> DynamicProcessLoader<C> {
+        DynamicProcessLoader {
+            kernel,
+            chip,
+            memory_start: app_memory.as_mut_ptr() as usize,
+            memory_len: app_memory.len(),
+            fault_policy,
+            checker,
+            id_policy,
+        }
+    }
+
+    /// Try to create the process in each unused part of `app_memory` in
+    /// turn, until one of them is large enough.
+    fn create_in_free_memory(
+        &self,
+        app_flash: &'static [u8],
+        header_length: usize,
+        version: u16,
+        short_id: ShortID,
+        verified: bool,
+        index: usize,
+    ) -> Result<&'static dyn Process, ProcessLoadError> {
+        let memory_start = self.memory_start;
+        let memory_end = memory_start + self.memory_len;
+
+        // Processes whose memory lies in our pool, i.e. those this loader
+        // created that are still in the processes array.
+        let in_pool = |process: &&'static dyn Process| {
+            let start = process.mem_start() as usize;
+            start >= memory_start && start < memory_end
+        };
+
+        let mut gap_start = memory_start;
+        while gap_start < memory_end {
+            // The next process in memory bounds the gap.
+            let next = self
+                .kernel
+                .get_process_iter()
+                .filter(in_pool)
+                .filter(|p| p.mem_start() as usize >= gap_start)
+                .min_by_key(|p| p.mem_start() as usize);
+            let gap_end = next.map_or(memory_end, |p| p.mem_start() as usize);
+
+            // Safety: `new()` took the pool as a `&'static mut`, so nothing but
+            // the processes this loader created uses it. The gap lies between
+            // `gap_start`, the end of the previous such process or the start of
+            // the pool, and the start of the next one, so it overlaps neither
+            // the memory of a live process nor any other reference into the
+            // pool.
+            let gap =
+                unsafe { slice::from_raw_parts_mut(gap_start as *mut u8, gap_end - gap_start) };
+            let result = unsafe {
+                ProcessStandard::create(
+                    self.kernel,
+                    self.chip,
+                    app_flash,
+                    header_length,
+                    version,
+                    gap,
+                    self.fault_policy,
+                    short_id,
+                    verified,
+                    index,
+                )
+            };
+            match result {
+                Ok((Some(process), _)) => return Ok(process),
+                Ok((None, _)) => return Err(ProcessLoadError::NotAnEnabledApp),
+                Err(ProcessLoadError::NotEnoughMemory) => {}
+                Err(err) => return Err(err),
+            }
+
+            match next {
+                Some(process) => gap_start = process.mem_end() as usize,
+                None => break,
+            }
+        }
+        Err(ProcessLoadError::NotEnoughMemory)
+    }
+}
+
+impl<C: 'static + Chip> DynamicProcessLoading for DynamicProcessLoader<C> {
+    fn load_process(&self, app_flash: &'static [u8]) -> Result<ProcessId, ProcessLoadError> {
+        let (version, header_length, entry_length) = tock_tbf::parse::parse_tbf_header_lengths(
+            app_flash
+                .get(0..8)
+                .ok_or(ProcessLoadError::NotEnoughFlash)?
+                .try_into()
+                .or(Err(ProcessLoadError::InternalError))?,
+        )
+        .or(Err(ProcessLoadError::NotAnEnabledApp))?;
+        let app_flash = app_flash
+            .get(0..entry_length as usize)
+            .ok_or(ProcessLoadError::NotEnoughFlash)?;
+        let header_flash = app_flash
+            .get(0..header_length as usize)
+            .ok_or(ProcessLoadError::NotEnoughFlash)?;
+        let header = tock_tbf::parse::parse_tbf_header(header_flash, version)?;
+        if !header.is_app() || !header.enabled() {
+            return Err(ProcessLoadError::NotAnEnabledApp);
+        }
+        let credentials = match self.checker {
+            Some(checker) => process_checker::check_app_credentials(checker, &header, app_flash),
+            None => CheckResult::Pass,
+        };
+        if credentials == CheckResult::Reject {
+            return Err(ProcessLoadError::CredentialsCheckFailed);
+        }
+        let short_id = self
+            .id_policy
+            .short_id(&header, credentials == CheckResult::Accept);
+        if short_id_in_use(self.kernel, short_id) {
+            return Err(ProcessLoadError::DuplicateShortId);
+        }
+
+        let index = self
+            .kernel
+            .free_process_slot()
+            .ok_or(ProcessLoadError::NoProcessSlot)?;
+        let process = self.create_in_free_memory(
+            app_flash,
+            header_length as usize,
+            version,
+            short_id,
+            credentials == CheckResult::Accept,
+            index,
+        )?;
+
+        if config::CONFIG.debug_load_processes {
+            debug!(
+                "Loaded process[{}] from flash={:#010X}-{:#010X} into sram={:#010X}-{:#010X} = {:?}",
+                index,
+                app_flash.as_ptr() as usize,
+                app_flash.as_ptr() as usize + app_flash.len() - 1,
+                process.mem_start() as usize,
+                process.mem_end() as usize - 1,
+                process.get_process_name()
+            );
+        }
+        self.kernel
+            .insert_process(process)
+            .or(Err(ProcessLoadError::InternalError))?;
+        Ok(process.processid())
+    }
+
+    fn unload_process(
+        &self,
+        processid: ProcessId,
+        capability: &dyn ProcessManagementCapability,
+    ) -> Result<(usize, usize), ErrorCode> {
+        let process = self.kernel.remove_process(processid, capability)?;
+        if config::CONFIG.debug_load_processes {
+            debug!(
+                "Unloaded process[{}] {:?}",
+                processid.index,
+                process.get_process_name()
+            );
+        }
+        let flash_start = process.flash_start() as usize;
+        Ok((flash_start, process.flash_end() as usize - flash_start))
+    }
+}
@@ -6,16 +6,18 @@
 //! selected by a board.
 
 pub(crate) mod cooperative;
+pub(crate) mod edf;
 pub(crate) mod mlfq;
 pub(crate) mod priority;
 pub(crate) mod round_robin;
+pub(crate) mod tbf_priority;
 
 use core::cell::Cell;
 use core::ptr::NonNull;
 
 use crate::capabilities;
-use crate::common::cells::NumericCellExt;
-use crate::common::dynamic_deferred_call::DynamicDeferredCall;
+use crate::common::cells::{NumericCellExt, OptionalCell};
+use crate::common::deferred_call;
 use crate::config;
 use crate::debug;
 use crate::driver::CommandReturn;
@@ -31,6 +33,8 @@ use crate::process::ProcessId;
 use crate::process::{self, Task};
 use crate::syscall::{ContextSwitchReason, SyscallReturn};
 use crate::syscall::{Syscall, YieldCall};
+use crate::thread;
+use crate::trace::Tracer;
 use crate::upcall::{Upcall, UpcallId};
 
 /// Threshold in microseconds to consider a process's timeslice to be exhausted.
@@ -59,7 +63,7 @@ pub trait Scheduler<C: Chip> {
     fn result(&self, result: StoppedExecutingReason, execution_time_us: Option<u32>);
 
     /// Tell the scheduler to execute kernel work such as interrupt bottom
-    /// halves and dynamic deferred calls. Most schedulers will use this default
+    /// halves and deferred calls. Most schedulers will use this default
     /// implementation, but schedulers which at times wish to defer interrupt
     /// handling will reimplement it.
     ///
@@ -74,7 +78,7 @@ pub trait Scheduler<C: Chip> {
     /// as this function is called in the core kernel loop.
     unsafe fn execute_kernel_work(&self, chip: &C) {
         chip.service_pending_interrupts();
-        DynamicDeferredCall::call_global_instance_while(|| !chip.has_pending_interrupts());
+        deferred_call::service_while(|| !chip.has_pending_interrupts());
     }
 
     /// Ask the scheduler whether to take a break from executing userspace
@@ -82,8 +86,7 @@ pub trait Scheduler<C: Chip> {
     /// implementation, which always prioritizes kernel work, but schedulers
     /// that wish to defer interrupt handling may reimplement it.
     unsafe fn do_kernel_work_now(&self, chip: &C) -> bool {
-        chip.has_pending_interrupts()
-            || DynamicDeferredCall::global_instance_calls_pending().unwrap_or(false)
+        chip.has_pending_interrupts() || deferred_call::has_tasks()
     }
 
     /// Ask the scheduler whether to continue trying to execute a process.
@@ -103,8 +106,7 @@ pub trait Scheduler<C: Chip> {
     ///
     /// `id` is the identifier of the currently active process.
     unsafe fn continue_process(&self, _id: ProcessId, chip: &C) -> bool {
-        !(chip.has_pending_interrupts()
-            || DynamicDeferredCall::global_instance_calls_pending().unwrap_or(false))
+        !(chip.has_pending_interrupts() || deferred_call::has_tasks())
     }
 }
 
@@ -129,8 +131,8 @@ pub struct Kernel {
     /// outstanding upcalls and processes in the Running state.
     work: Cell<usize>,
 
-    /// This holds a pointer to the static array of Process pointers.
-    processes: &'static [Option<&'static dyn process::Process>],
+    /// This holds a pointer to the static array of process slots.
+    processes: &'static [process::ProcessSlot],
 
     /// A counter which keeps track of how many process identifiers have been
     /// created. This is used to create new unique identifiers for processes.
@@ -146,11 +148,15 @@ pub struct Kernel {
     /// created and the data structures for grants have already been
     /// established.
     grants_finalized: Cell<bool>,
+
+    /// Where to record system calls, upcalls and scheduling decisions, if the
+    /// board traces them.
+    tracer: OptionalCell<&'static Tracer>,
 }
 
 /// Enum used to inform scheduler why a process stopped executing (aka why
 /// `do_process()` returned).
-#[derive(PartialEq, Eq)]
+#[derive(Copy, Clone, Debug, PartialEq, Eq)]
 pub enum StoppedExecutingReason {
     /// The process returned because it is no longer ready to run.
     NoWorkLeft,
@@ -171,19 +177,43 @@ pub enum StoppedExecutingReason {
     /// interrupt), or because the scheduler no longer wants to execute that
     /// process.
     KernelPreemption,
+
+    /// The process used all of the execution time reserved for it in the
+    /// current period before yielding. The kernel reports this as
+    /// `TimesliceExpired`; real-time schedulers report it to their clients.
+    BudgetOverrun,
+
+    /// The process still had work to do at the end of its period, although
+    /// it had not used all of its reserved execution time. Only reported by
+    /// real-time schedulers.
+    DeadlineMissed,
 }
 
 impl Kernel {
-    pub fn new(processes: &'static [Option<&'static dyn process::Process>]) -> Kernel {
+    pub fn new(processes: &'static [process::ProcessSlot]) -> Kernel {
         Kernel {
             work: Cell::new(0),
             processes,
             process_identifier_max: Cell::new(0),
             grant_counter: Cell::new(0),
             grants_finalized: Cell::new(false),
+            tracer: OptionalCell::empty(),
         }
     }
 
+    /// Record system calls, upcalls and scheduling decisions in `tracer`
+    /// while it is enabled.
+    ///
+    /// The trace shows what every process does, so setting it requires the
+    /// `MainLoopCapability` that the board's main loop is started with.
+    pub fn set_tracer(
+        &self,
+        tracer: &'static Tracer,
+        _capability: &dyn capabilities::MainLoopCapability,
+    ) {
+        self.tracer.set(tracer);
+    }
+
     /// Something was scheduled for a process, so there is more work to do.
     ///
     /// This is only exposed in the core kernel crate.
@@ -237,12 +267,16 @@ impl Kernel {
         // However, we are not guaranteed that the app still exists at that
         // index in the processes array. To avoid additional overhead, we do the
         // lookup and check here, rather than calling `.index()`.
-        match self.processes.get(processid.index) {
-            Some(Some(process)) => {
+        match self
+            .processes
+            .get(processid.index)
+            .and_then(|slot| slot.get())
+        {
+            Some(process) => {
                 // Check that the process stored here matches the identifier
                 // in the `appid`.
                 if process.processid() == processid {
-                    Some(*process)
+                    Some(process)
                 } else {
                     None
                 }
@@ -277,13 +311,8 @@ impl Kernel {
     where
         F: Fn(&dyn process::Process),
     {
-        for process in self.processes.iter() {
-            match process {
-                Some(p) => {
-                    closure(*p);
-                }
-                None => {}
-            }
+        for process in self.get_process_iter() {
+            closure(process);
         }
     }
 
@@ -291,13 +320,11 @@ impl Kernel {
     pub(crate) fn get_process_iter(
         &self,
     ) -> core::iter::FilterMap<
-        core::slice::Iter<Option<&dyn process::Process>>,
-        fn(&Option<&'static dyn process::Process>) -> Option<&'static dyn process::Process>,
+        core::slice::Iter<process::ProcessSlot>,
+        fn(&process::ProcessSlot) -> Option<&'static dyn process::Process>,
     > {
-        fn keep_some(
-            &x: &Option<&'static dyn process::Process>,
-        ) -> Option<&'static dyn process::Process> {
-            x
+        fn keep_some(slot: &process::ProcessSlot) -> Option<&'static dyn process::Process> {
+            slot.get()
         }
         self.processes.iter().filter_map(keep_some)
     }
@@ -315,16 +342,30 @@ impl Kernel {
     ) where
         F: Fn(&dyn process::Process),
     {
-        for process in self.processes.iter() {
-            match process {
-                Some(p) => {
-                    closure(*p);
-                }
-                None => {}
-            }
+        for process in self.get_process_iter() {
+            closure(process);
         }
     }
 
+    /// Run a closure on the process `appid` refers to, or return `default` if
+    /// that process no longer exists.
+    ///
+    /// This is functionally the same as `process_map_or()`, but this method is
+    /// available outside the kernel crate and requires a
+    /// `ProcessManagementCapability` to use.
+    pub fn process_map_or_capability<F, R>(
+        &self,
+        _capability: &dyn capabilities::ProcessManagementCapability,
+        default: R,
+        appid: ProcessId,
+        closure: F,
+    ) -> R
+    where
+        F: FnOnce(&dyn process::Process) -> R,
+    {
+        self.process_map_or(default, appid, closure)
+    }
+
     /// Run a closure on every process, but only continue if the closure returns `None`. That is,
     /// if the closure returns any non-`None` value, iteration stops and the value is returned from
     /// this function to the called.
@@ -332,15 +373,10 @@ impl Kernel {
     where
         F: Fn(&dyn process::Process) -> Option<T>,
     {
-        for process in self.processes.iter() {
-            match process {
-                Some(p) => {
-                    let ret = closure(*p);
-                    if ret.is_some() {
-                        return ret;
-                    }
-                }
-                None => {}
+        for process in self.get_process_iter() {
+            let ret = closure(process);
+            if ret.is_some() {
+                return ret;
             }
         }
         None
@@ -351,15 +387,9 @@ impl Kernel {
     /// as from userspace) and needs to be expanded to a full `ProcessId` for use
     /// with other APIs.
     pub(crate) fn lookup_app_by_identifier(&self, identifier: usize) -> Option<ProcessId> {
-        self.processes.iter().find_map(|&p| {
-            p.map_or(None, |p2| {
-                if p2.processid().id() == identifier {
-                    Some(p2.processid())
-                } else {
-                    None
-                }
-            })
-        })
+        self.get_process_iter()
+            .map(|process| process.processid())
+            .find(|processid| processid.id() == identifier)
     }
 
     /// Checks if the provided `ProcessId` is still valid given the processes stored
@@ -369,11 +399,74 @@ impl Kernel {
     /// This is needed for `ProcessId` itself to implement the `.index()` command to
     /// verify that the referenced app is still at the correct index.
     pub(crate) fn processid_is_valid(&self, appid: &ProcessId) -> bool {
-        self.processes.get(appid.index).map_or(false, |p| {
-            p.map_or(false, |process| process.processid().id() == appid.id())
+        self.processes.get(appid.index).map_or(false, |slot| {
+            slot.get()
+                .map_or(false, |process| process.processid().id() == appid.id())
         })
     }
 
+    /// The index of an empty slot in the processes array, if there is one. A
+    /// process created for this index can be added with `install_process()`.
+    pub fn free_process_slot(&self) -> Option<usize> {
+        self.processes.iter().position(|slot| slot.get().is_none())
+    }
+
+    /// Add a process to the processes array, at the index of its
+    /// `ProcessId`. That slot must be empty.
+    ///
+    /// Only callers with the `ProcessManagementCapability` can add processes.
+    pub fn install_process(
+        &self,
+        process: &'static dyn process::Process,
+        _capability: &dyn capabilities::ProcessManagementCapability,
+    ) -> Result<(), ErrorCode> {
+        self.insert_process(process)
+    }
+
+    /// Add a process to its slot in the processes array, for process loaders
+    /// in the core kernel crate.
+    pub(crate) fn insert_process(
+        &self,
+        process: &'static dyn process::Process,
+    ) -> Result<(), ErrorCode> {
+        let slot = self
+            .processes
+            .get(process.processid().index)
+            .ok_or(ErrorCode::INVAL)?;
+        if slot.get().is_some() {
+            return Err(ErrorCode::BUSY);
+        }
+        slot.set(Some(process));
+        Ok(())
+    }
+
+    /// Terminate a process and remove it from the processes array, so that
+    /// its slot can be reused. Returns the removed process.
+    ///
+    /// This does not free the process's RAM. The `DynamicProcessLoader`
+    /// reuses the memory of processes it created, but the memory of processes
+    /// loaded at boot stays unused until the board reboots.
+    ///
+    /// Only callers with the `ProcessManagementCapability` can remove
+    /// processes.
+    pub fn remove_process(
+        &self,
+        processid: ProcessId,
+        _capability: &dyn capabilities::ProcessManagementCapability,
+    ) -> Result<&'static dyn process::Process, ErrorCode> {
+        let slot = self
+            .processes
+            .get(processid.index)
+            .ok_or(ErrorCode::INVAL)?;
+        let process = slot
+            .get()
+            .filter(|process| process.processid() == processid)
+            .ok_or(ErrorCode::INVAL)?;
+        process.terminate(0);
+        slot.set(None);
+        Ok(process)
+    }
+
     /// Create a new grant. This is used in board initialization to setup grants
     /// that capsules use to interact with processes.
     ///
@@ -449,10 +542,8 @@ impl Kernel {
     /// function, since capsules should not be able to arbitrarily restart all
     /// apps.
     pub fn hardfault_all_apps<C: capabilities::ProcessManagementCapability>(&self, _c: &C) {
-        for p in self.processes.iter() {
-            p.map(|process| {
-                process.set_fault_state();
-            });
+        for process in self.get_process_iter() {
+            process.set_fault_state();
         }
     }
 
@@ -488,7 +579,9 @@ impl Kernel {
         no_sleep: bool,
         _capability: &dyn capabilities::MainLoopCapability,
     ) {
-        chip.watchdog().tickle();
+        if platform.watchdog_healthy() {
+            chip.watchdog().tickle();
+        }
         unsafe {
             // Ask the scheduler if we should do tasks inside of the kernel,
             // such as handle interrupts. A scheduler may want to prioritize
@@ -505,6 +598,8 @@ impl Kernel {
                     match scheduler.next(self) {
                         SchedulingDecision::RunProcess((appid, timeslice_us)) => {
                             self.process_map_or((), appid, |process| {
+                                self.tracer
+                                    .map(|tracer| tracer.scheduled(appid, timeslice_us));
                                 let (reason, time_executed) = self.do_process(
                                     platform,
                                     chip,
@@ -513,6 +608,8 @@ impl Kernel {
                                     ipc,
                                     timeslice_us,
                                 );
+                                self.tracer
+                                    .map(|tracer| tracer.stopped(appid, reason, time_executed));
                                 scheduler.result(reason, time_executed);
                             });
                         }
@@ -532,13 +629,30 @@ impl Kernel {
                                     // starts, the interrupt will not be
                                     // serviced and the chip will never wake
                                     // from sleep.
-                                    if !chip.has_pending_interrupts()
-                                        && !DynamicDeferredCall::global_instance_calls_pending()
-                                            .unwrap_or(false)
+                                    if !chip.has_pending_interrupts() && !deferred_call::has_tasks()
                                     {
-                                        chip.watchdog().suspend();
-                                        chip.sleep();
-                                        chip.watchdog().resume();
+                                        self.tracer.map(|tracer| tracer.sleep());
+
+                                        // Let the board pick how deeply to
+                                        // sleep, if the chip has a choice.
+                                        let states = chip.sleep_states();
+                                        // Resuming the watchdog tickles it,
+                                        // so leave it running if the board
+                                        // is not healthy.
+                                        let healthy = platform.watchdog_healthy();
+                                        if healthy {
+                                            chip.watchdog().suspend();
+                                        }
+                                        match platform
+                                            .select_sleep_state(states)
+                                            .filter(|state| *state < states.len())
+                                        {
+                                            Some(state) => chip.sleep_in_state(state),
+                                            None => chip.sleep(),
+                                        }
+                                        if healthy {
+                                            chip.watchdog().resume();
+                                        }
                                     }
                                 });
                             }
@@ -634,11 +748,18 @@ impl Kernel {
         // `start()`.
         scheduler_timer.reset();
         timeslice_us.map(|timeslice| scheduler_timer.start(timeslice));
+        self.tracer.map(|tracer| tracer.timeslice_started());
 
         // Need to track why the process is no longer executing so that we can
         // inform the scheduler.
         let mut return_reason = StoppedExecutingReason::NoWorkLeft;
 
+        // Time the process spent executing in userspace, as opposed to in the
+        // kernel on its behalf. Once the scheduler timer reports the timeslice
+        // expired we must not read it again, so remember that as well.
+        let mut user_time_us = 0;
+        let mut expired = false;
+
         // Since the timeslice counts both the process's execution time and the
         // time spent in the kernel on behalf of the process (setting it up and
         // handling its syscalls), we intend to keep running the process until
@@ -646,10 +767,11 @@ impl Kernel {
         // no longer wants to execute this process or if it exceeds its
         // timeslice.
         loop {
-            let stop_running = match scheduler_timer.get_remaining_us() {
-                Some(us) => us <= MIN_QUANTA_THRESHOLD_US,
-                None => true,
-            };
+            let stop_running = expired
+                || match scheduler_timer.get_remaining_us() {
+                    Some(us) => us <= MIN_QUANTA_THRESHOLD_US,
+                    None => true,
+                };
             if stop_running {
                 // Process ran out of time while the kernel was executing.
                 process.debug_timeslice_expired();
@@ -682,10 +804,31 @@ impl Kernel {
                     process.setup_mpu();
 
                     chip.mpu().enable_app_mpu();
+                    let switched_at = timeslice_us.and(scheduler_timer.get_remaining_us());
                     scheduler_timer.arm();
                     let context_switch_reason = process.switch_to();
                     scheduler_timer.disarm();
                     chip.mpu().disable_app_mpu();
+                    if let (Some(timeslice), Some(before)) = (timeslice_us, switched_at) {
+                        let after = match scheduler_timer.get_remaining_us() {
+                            Some(after) => after,
+                            None => {
+                                expired = true;
+                                0
+                            }
+                        };
+                        user_time_us += before.saturating_sub(after);
+                        self.tracer.map(|tracer| {
+                            tracer.timeslice_elapsed(timeslice.saturating_sub(after))
+                        });
+                    }
+                    self.tracer.map(|tracer| {
+                        tracer.context_switch(
+                            process.processid(),
+                            &context_switch_reason,
+                            process.current_thread(),
+                        )
+                    });
 
                     // Now the process has returned back to the kernel. Check
                     // why and handle the process as appropriate.
@@ -702,7 +845,7 @@ impl Kernel {
                             self.handle_syscall(platform, process, syscall);
                         }
                         Some(ContextSwitchReason::Interrupted) => {
-                            if scheduler_timer.get_remaining_us().is_none() {
+                            if expired || scheduler_timer.get_remaining_us().is_none() {
                                 // This interrupt was a timeslice expiration.
                                 process.debug_timeslice_expired();
                                 return_reason = StoppedExecutingReason::TimesliceExpired;
@@ -730,6 +873,9 @@ impl Kernel {
                         None => break,
                         Some(cb) => match cb {
                             Task::FunctionCall(ccb) => {
+                                if process.get_state() == process::State::Yielded {
+                                    process.debug_woken_up();
+                                }
                                 if config::CONFIG.trace_syscalls {
                                     debug!(
                                         "[{:?}] function_call @{:#x}({:#x}, {:#x}, {:#x}, {:#x})",
@@ -741,6 +887,8 @@ impl Kernel {
                                         ccb.argument3,
                                     );
                                 }
+                                self.tracer
+                                    .map(|tracer| tracer.upcall(process.processid(), &ccb));
                                 process.set_process_function(ccb);
                             }
                             Task::IPC((otherapp, ipc_type)) => {
@@ -783,6 +931,12 @@ impl Kernel {
             }
         }
 
+        // Threads of a process share its timeslice, so a thread that used it
+        // up lets the next thread of the process run next time.
+        if return_reason == StoppedExecutingReason::TimesliceExpired {
+            process.yield_thread();
+        }
+
         // Check how much time the process used while it was executing, and
         // return the value so we can provide it to the scheduler.
         let time_executed_us = timeslice_us.map_or(None, |timeslice| {
@@ -799,6 +953,14 @@ impl Kernel {
             }
         });
 
+        // Charge the time to the process. Whatever it did not spend executing
+        // in userspace the kernel spent on its behalf.
+        if let Some(time_executed) = time_executed_us {
+            process.debug_executed(time_executed, time_executed.saturating_sub(user_time_us));
+            self.tracer
+                .map(|tracer| tracer.timeslice_elapsed(time_executed));
+        }
+
         // Reset the scheduler timer in case it unconditionally triggers
         // interrupts upon expiration. We do not want it to expire while the
         // chip is sleeping, for example.
@@ -821,6 +983,8 @@ impl Kernel {
     ) {
         // Hook for process debugging.
         process.debug_syscall_called(syscall);
+        self.tracer
+            .map(|tracer| tracer.syscall(process.processid(), &syscall));
 
         // Enforce platform-specific syscall filtering here.
         //
@@ -841,6 +1005,10 @@ impl Kernel {
                 which: _,
                 address: _,
             } => {} // Yield is not filterable
+            Syscall::YieldWaitFor {
+                driver_number: _,
+                subdriver_number: _,
+            } => {} // Yield is not filterable
             Syscall::Exit {
                 which: _,
                 completion_code: _,
@@ -852,6 +1020,14 @@ impl Kernel {
             _ => {
                 // Check all other syscalls for filtering
                 if let Err(response) = platform.filter_syscall(process, &syscall) {
+                    if config::CONFIG.debug_syscall_filter {
+                        debug!(
+                            "[{:?}] syscall filter denied {:?}: {:?}",
+                            process.processid(),
+                            syscall,
+                            response
+                        );
+                    }
                     process.set_syscall_return_value(SyscallReturn::Failure(response));
 
                     return;
@@ -874,6 +1050,25 @@ impl Kernel {
                 }
                 process.set_syscall_return_value(rval);
             }
+            Syscall::Thread {
+                operation,
+                arg0,
+                arg1,
+                arg2,
+            } => {
+                if config::CONFIG.trace_syscalls {
+                    debug!(
+                        "[{:?}] thread({}, {:#x}, {:#x}, {:#x}) on thread {}",
+                        process.processid(),
+                        operation,
+                        arg0,
+                        arg1,
+                        arg2,
+                        process.current_thread(),
+                    );
+                }
+                thread::thread(process, operation, arg0, arg1, arg2);
+            }
             Syscall::Yield { which, address } => {
                 if config::CONFIG.trace_syscalls {
                     debug!("[{:?}] yield. which: {}", process.processid(), which);
@@ -885,6 +1080,21 @@ impl Kernel {
                     // the stack; just return control to the process.
                     return;
                 }
+                if process.current_thread() != 0 {
+                    // Upcalls only run on the main thread, so for any other
+                    // thread both yield-no-wait and yield-wait just let the
+                    // other threads run. A yield-wait does not block, as no
+                    // upcall could ever wake the thread. No upcall ran, which
+                    // the process learns if it passed an address.
+                    //
+                    // Safety: as below, no references to the process's memory
+                    // exist.
+                    unsafe {
+                        process.set_byte(address, 0);
+                    }
+                    process.yield_thread();
+                    return;
+                }
                 let wait = which == (YieldCall::Wait as usize);
                 // If this is a yield-no-wait AND there are no pending
                 // tasks, then return immediately. Otherwise, go into the
@@ -916,6 +1126,29 @@ impl Kernel {
                     process.set_yielded_state();
                 }
             }
+            Syscall::YieldWaitFor {
+                driver_number,
+                subdriver_number,
+            } => {
+                if config::CONFIG.trace_syscalls {
+                    debug!(
+                        "[{:?}] yield-wait-for({:#x}, {})",
+                        process.processid(),
+                        driver_number,
+                        subdriver_number
+                    );
+                }
+                if process.current_thread() != 0 {
+                    // Only the main thread receives upcalls, so another
+                    // thread cannot wait for one.
+                    process.set_syscall_return_value(SyscallReturn::Failure(ErrorCode::INVAL));
+                    return;
+                }
+                process.set_yielded_for_state(UpcallId {
+                    driver_num: driver_number,
+                    subscribe_num: subdriver_number,
+                });
+            }
             Syscall::Subscribe {
                 driver_number,
                 subdriver_number,
@@ -1063,6 +1296,16 @@ impl Kernel {
                     // there are no pending upcalls with the same identifier but
                     // with the old function pointer, we clear them now.
                     process.remove_pending_upcalls(upcall_id);
+
+                    // Let the capsule know if this was an unsubscribe, so
+                    // that it can free its grant once nothing is left in it.
+                    if upcall_ptr.is_null() {
+                        platform.with_driver(driver_number, |driver| {
+                            if let Some(d) = driver {
+                                d.unsubscribed(process.processid(), subdriver_number);
+                            }
+                        });
+                    }
                 }
 
                 if config::CONFIG.trace_syscalls {
@@ -0,0 +1,390 @@
+//! Earliest Deadline First Scheduler for Tock
+//!
+//! This scheduler gives timing guarantees to real-time processes. Each
+//! real-time process reserves a budget of execution time in every period,
+//! either with the `RealTime` TLV in its TBF header or with a reservation set
+//! by the board. The work a process has at the start of a period must be
+//! finished by the end of that period, which is its deadline.
+//!
+//! Among the real-time processes that are ready and have budget left, the
+//! scheduler runs the one with the earliest deadline, and its timeslice is
+//! limited by its remaining budget. Processes without a reservation run
+//! round robin, only when no real-time process can run.
+//!
+//! Admission control keeps the reservations feasible: a process is only
+//! admitted if the total utilization (the sum of budget / period over all
+//! real-time processes) stays within the bound given to the scheduler, which
+//! must not be more than 100% for EDF to meet every deadline. Processes that
+//! still want to run after using their whole budget are not scheduled until
+//! their next period, so they cannot cause others to miss deadlines. Such
+//! budget overruns, and deadlines missed with budget left (for example
+//! because interrupt handling took too long), are counted and reported to an
+//! `OverrunClient` with a `StoppedExecutingReason`.
+//!
+//! The scheduler keeps time with an alarm, which it also uses to wake the
+//! chip when a real-time process gets its next budget, so the board must set
+//! the scheduler as the client of the alarm. While the chip sleeps the alarm
+//! is always armed, at most half the alarm's wrap period ahead, so that the
+//! scheduler notices every time the alarm counter wraps around.
+
+use crate::common::cells::OptionalCell;
+use crate::common::list::{List, ListLink, ListNode};
+use crate::errorcode::ErrorCode;
+use crate::hil::time::{self, Frequency, Ticks};
+use crate::platform::Chip;
+use crate::process::{ProcessId, ProcessSlot};
+use crate::sched::{
+    Kernel, Scheduler, SchedulingDecision, StoppedExecutingReason, MIN_QUANTA_THRESHOLD_US,
+};
+use core::cell::Cell;
+use core::cmp;
+
+/// A node in the list of processes the scheduler can run.
+///
+/// The node belongs to a process slot rather than to a process. When the
+/// process in the slot changes, because one was loaded into an empty slot,
+/// removed or restarted, the scheduler releases the reservation of the old
+/// process and admits the new one.
+pub struct EDFProcessNode<'a> {
+    proc: &'static ProcessSlot,
+    /// Reservation set by the board, which overrides the TBF header.
+    fixed_reservation: Option<(u32, u32)>,
+    /// The process admission was last done for.
+    process: Cell<Option<ProcessId>>,
+    /// Whether that process was admitted, and so can be scheduled.
+    admitted: Cell<bool>,
+    /// Period and budget in microseconds, if the process is real-time.
+    reservation: Cell<Option<(u32, u32)>>,
+    /// End of the current period, in alarm ticks since the scheduler started.
+    deadline: Cell<u64>,
+    budget_remaining_us: Cell<u32>,
+    overruns: Cell<usize>,
+    deadline_misses: Cell<usize>,
+    next: ListLink<'a, EDFProcessNode<'a>>,
+}
+
+impl<'a> EDFProcessNode<'a> {
+    /// Create a node for a process that is real-time if its TBF header has a
+    /// `RealTime` TLV.
+    pub fn new(proc: &'static ProcessSlot) -> EDFProcessNode<'a> {
+        EDFProcessNode {
+            proc,
+            fixed_reservation: None,
+            process: Cell::new(None),
+            admitted: Cell::new(false),
+            reservation: Cell::new(None),
+            deadline: Cell::new(0),
+            budget_remaining_us: Cell::new(0),
+            overruns: Cell::new(0),
+            deadline_misses: Cell::new(0),
+            next: ListLink::empty(),
+        }
+    }
+
+    /// Create a node for a real-time process that reserves `budget_us` of
+    /// every `period_us`, regardless of its TBF header.
+    pub fn with_reservation(
+        proc: &'static ProcessSlot,
+        period_us: u32,
+        budget_us: u32,
+    ) -> EDFProcessNode<'a> {
+        EDFProcessNode {
+            fixed_reservation: Some((period_us, budget_us)),
+            ..EDFProcessNode::new(proc)
+        }
+    }
+
+    /// Number of times the process used its whole budget before yielding.
+    pub fn overrun_count(&self) -> usize {
+        self.overruns.get()
+    }
+
+    /// Number of periods in which the process had budget left but did not
+    /// finish its work.
+    pub fn deadline_miss_count(&self) -> usize {
+        self.deadline_misses.get()
+    }
+
+    fn ready(&self) -> bool {
+        self.admitted.get() && self.proc.get().map_or(false, |proc| proc.ready())
+    }
+
+    /// Whether the process in the slot is no longer the one admission was
+    /// done for.
+    fn process_changed(&self) -> bool {
+        self.proc.get().map(|proc| proc.processid()) != self.process.get()
+    }
+}
+
+impl<'a> ListNode<'a, EDFProcessNode<'a>> for EDFProcessNode<'a> {
+    fn next(&'a self) -> &'a ListLink<'a, EDFProcessNode> {
+        &self.next
+    }
+}
+
+/// Receives reports of real-time processes not meeting their reservation.
+pub trait OverrunClient {
+    /// `process` overran its budget (`BudgetOverrun`) or missed its deadline
+    /// (`DeadlineMissed`).
+    fn overrun(&self, process: ProcessId, reason: StoppedExecutingReason);
+}
+
+/// Earliest Deadline First Scheduler
+pub struct EDFSched<'a, A: 'static + time::Alarm<'static>> {
+    alarm: &'static A,
+    processes: List<'a, EDFProcessNode<'a>>,
+    /// Maximum total utilization of real-time processes, in parts per million.
+    utilization_bound: u32,
+    utilization: Cell<u32>,
+    /// Alarm ticks since the scheduler started, which unlike the alarm does
+    /// not wrap.
+    ticks: Cell<u64>,
+    last_now: Cell<A::Ticks>,
+    running: OptionalCell<&'a EDFProcessNode<'a>>,
+    client: OptionalCell<&'a dyn OverrunClient>,
+}
+
+impl<'a, A: 'static + time::Alarm<'static>> EDFSched<'a, A> {
+    /// How long a process without a reservation can run before being
+    /// pre-empted.
+    const DEFAULT_TIMESLICE_US: u32 = 10000;
+
+    /// Create a scheduler that admits real-time processes until their total
+    /// utilization reaches `utilization_bound_percent`.
+    pub fn new(alarm: &'static A, utilization_bound_percent: u32) -> EDFSched<'a, A> {
+        EDFSched {
+            alarm,
+            processes: List::new(),
+            utilization_bound: cmp::min(utilization_bound_percent, 100) * 10_000,
+            utilization: Cell::new(0),
+            ticks: Cell::new(0),
+            last_now: Cell::new(alarm.now()),
+            running: OptionalCell::empty(),
+            client: OptionalCell::empty(),
+        }
+    }
+
+    pub fn set_overrun_client(&self, client: &'a dyn OverrunClient) {
+        self.client.set(client);
+    }
+
+    /// Add a process slot to the scheduler. Returns `INVAL` if the
+    /// reservation of the process in it is malformed, or `SIZE` if it would
+    /// exceed the utilization bound, in which case the slot is not added.
+    ///
+    /// The slot may be empty; a process loaded into it later goes through
+    /// the same admission control, and is not scheduled if it fails it.
+    pub fn add_process(&self, node: &'a EDFProcessNode<'a>) -> Result<(), ErrorCode> {
+        self.admit(node)?;
+        self.processes.push_tail(node);
+        Ok(())
+    }
+
+    /// Redo admission control for the process in `node`'s slot, releasing
+    /// the reservation of the process admitted before.
+    fn admit(&self, node: &EDFProcessNode<'a>) -> Result<(), ErrorCode> {
+        if let Some((period_us, budget_us)) = node.reservation.take() {
+            let utilization = Self::utilization_of(period_us, budget_us);
+            self.utilization.set(self.utilization.get() - utilization);
+        }
+        let process = node.proc.get();
+        node.process.set(process.map(|proc| proc.processid()));
+        node.admitted.set(false);
+
+        let reservation = process.and_then(|proc| {
+            node.fixed_reservation
+                .or_else(|| proc.get_real_time_reservation())
+        });
+        if let Some((period_us, budget_us)) = reservation {
+            if period_us == 0 || budget_us == 0 || budget_us > period_us {
+                return Err(ErrorCode::INVAL);
+            }
+            let utilization = Self::utilization_of(period_us, budget_us);
+            if self.utilization.get() + utilization > self.utilization_bound {
+                return Err(ErrorCode::SIZE);
+            }
+            self.utilization.set(self.utilization.get() + utilization);
+            node.reservation.set(Some((period_us, budget_us)));
+            node.deadline.set(self.now() + Self::us_to_ticks(period_us));
+            node.budget_remaining_us.set(budget_us);
+        }
+        node.admitted.set(true);
+        Ok(())
+    }
+
+    /// Total utilization of the admitted real-time processes, in parts per
+    /// million.
+    pub fn utilization(&self) -> u32 {
+        self.utilization.get()
+    }
+
+    /// Utilization of a reservation, in parts per million.
+    fn utilization_of(period_us: u32, budget_us: u32) -> u32 {
+        (budget_us as u64 * 1_000_000 / period_us as u64) as u32
+    }
+
+    fn us_to_ticks(us: u32) -> u64 {
+        us as u64 * A::Frequency::frequency() as u64 / 1_000_000
+    }
+
+    fn ticks_to_us(ticks: u64) -> u64 {
+        ticks * 1_000_000 / A::Frequency::frequency() as u64
+    }
+
+    fn now(&self) -> u64 {
+        let now = self.alarm.now();
+        let elapsed = now.wrapping_sub(self.last_now.get()).into_u32();
+        self.last_now.set(now);
+        self.ticks.set(self.ticks.get() + elapsed as u64);
+        self.ticks.get()
+    }
+
+    /// Start a new period for every real-time process whose deadline has
+    /// passed, reporting those that did not finish their work in time.
+    fn replenish(&self, now: u64) {
+        for node in self.processes.iter() {
+            if let Some((period_us, budget_us)) = node.reservation.get() {
+                if now < node.deadline.get() {
+                    continue;
+                }
+                if node.ready() && node.budget_remaining_us.get() > MIN_QUANTA_THRESHOLD_US {
+                    node.deadline_misses.set(node.deadline_misses.get() + 1);
+                    self.report(node, StoppedExecutingReason::DeadlineMissed);
+                }
+                // Skip any periods the process slept through.
+                let period = cmp::max(Self::us_to_ticks(period_us), 1);
+                let missed = (now - node.deadline.get()) / period + 1;
+                node.deadline.set(node.deadline.get() + missed * period);
+                node.budget_remaining_us.set(budget_us);
+            }
+        }
+    }
+
+    fn report(&self, node: &EDFProcessNode, reason: StoppedExecutingReason) {
+        node.proc.get().map(|proc| {
+            let processid = proc.processid();
+            self.client.map(|client| client.overrun(processid, reason));
+        });
+    }
+
+    /// Move `node` to the back of the list, so best-effort processes run
+    /// round robin.
+    fn move_to_tail(&self, node: &EDFProcessNode<'a>) {
+        let mut count = self.processes.iter().count();
+        while count > 0 {
+            count -= 1;
+            let head = self.processes.pop_head().unwrap();
+            self.processes.push_tail(head);
+            if core::ptr::eq(head, node) {
+                break;
+            }
+        }
+    }
+}
+
+impl<'a, A: 'static + time::Alarm<'static>, C: Chip> Scheduler<C> for EDFSched<'a, A> {
+    fn next(&self, _kernel: &Kernel) -> SchedulingDecision {
+        for node in self.processes.iter() {
+            if node.process_changed() {
+                // A process that is not admitted is simply not scheduled.
+                let _ = self.admit(node);
+            }
+        }
+        let now = self.now();
+        self.replenish(now);
+
+        // The next time a real-time process gets new budget, which is the end
+        // of its current period. A process released then may have an earlier
+        // deadline than the one running, so no process runs past it.
+        let next_release = self
+            .processes
+            .iter()
+            .filter(|node| node.reservation.get().is_some())
+            .map(|node| node.deadline.get())
+            .min();
+        let until_release = next_release.map_or(Self::DEFAULT_TIMESLICE_US as u64, |release| {
+            Self::ticks_to_us(release - now)
+        });
+
+        // Run the ready real-time process with the earliest deadline, for as
+        // long as its budget lasts or until the next release, which is no
+        // later than its own deadline.
+        let mut earliest: Option<(&EDFProcessNode, u32)> = None;
+        for node in self.processes.iter() {
+            if node.reservation.get().is_none() || !node.ready() {
+                continue;
+            }
+            let timeslice = cmp::min(node.budget_remaining_us.get() as u64, until_release) as u32;
+            if timeslice <= MIN_QUANTA_THRESHOLD_US {
+                continue;
+            }
+            if earliest.map_or(true, |(best, _)| node.deadline.get() < best.deadline.get()) {
+                earliest = Some((node, timeslice));
+            }
+        }
+
+        // Otherwise run a best-effort process, but only until the next
+        // real-time process gets new budget.
+        let decision = earliest.or_else(|| {
+            let timeslice = cmp::min(Self::DEFAULT_TIMESLICE_US as u64, until_release) as u32;
+            self.processes
+                .iter()
+                .find(|node| node.reservation.get().is_none() && node.ready())
+                .filter(|_| timeslice > MIN_QUANTA_THRESHOLD_US)
+                .map(|node| (node, timeslice))
+        });
+
+        match decision {
+            Some((node, timeslice)) => {
+                self.running.set(node);
+                // The node was found in `processes`, so its process exists.
+                let processid = node.proc.get().unwrap().processid();
+                SchedulingDecision::RunProcess((processid, Some(timeslice)))
+            }
+            None => {
+                // Wake up when the next real-time process gets new budget,
+                // and before the alarm counter wraps, so that `now()` sees
+                // every wrap.
+                let half_wrap = A::Ticks::max_value().into_u32() as u64 / 2;
+                let dt =
+                    next_release.map_or(half_wrap, |release| cmp::min(release - now, half_wrap));
+                self.alarm
+                    .set_alarm(self.last_now.get(), A::Ticks::from(cmp::max(dt, 1) as u32));
+                SchedulingDecision::TrySleep
+            }
+        }
+    }
+
+    fn result(&self, result: StoppedExecutingReason, execution_time_us: Option<u32>) {
+        // Never fails, as processes are never run cooperatively.
+        let execution_time_us = execution_time_us.unwrap();
+        let node = match self.running.take() {
+            Some(node) => node,
+            None => return,
+        };
+        if node.reservation.get().is_some() {
+            let remaining = node
+                .budget_remaining_us
+                .get()
+                .saturating_sub(execution_time_us);
+            node.budget_remaining_us.set(remaining);
+            if result == StoppedExecutingReason::TimesliceExpired
+                && remaining <= MIN_QUANTA_THRESHOLD_US
+            {
+                node.overruns.set(node.overruns.get() + 1);
+                self.report(node, StoppedExecutingReason::BudgetOverrun);
+            }
+        } else if result != StoppedExecutingReason::KernelPreemption {
+            self.move_to_tail(node);
+        }
+    }
+}
+
+impl<'a, A: 'static + time::Alarm<'static>> time::AlarmClient for EDFSched<'a, A> {
+    fn alarm(&self) {
+        // Account for the time that passed. The interrupt woke the kernel,
+        // which will ask the scheduler for the next process and so re-arm
+        // the alarm.
+        self.now();
+    }
+}
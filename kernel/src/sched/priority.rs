@@ -11,7 +11,7 @@
 //! for an interrupt to occur, which will cause the process to stop running.
 
 use crate::common::cells::OptionalCell;
-use crate::common::dynamic_deferred_call::DynamicDeferredCall;
+use crate::common::deferred_call;
 use crate::platform::Chip;
 use crate::process::ProcessId;
 use crate::sched::{Kernel, Scheduler, SchedulingDecision, StoppedExecutingReason};
@@ -57,7 +57,7 @@ impl<C: Chip> Scheduler<C> for PrioritySched {
         // a system call by this process could make another process ready, if
         // this app is communicating via IPC with a higher priority app.
         !(chip.has_pending_interrupts()
-            || DynamicDeferredCall::global_instance_calls_pending().unwrap_or(false)
+            || deferred_call::has_tasks()
             || self
                 .kernel
                 .get_process_iter()
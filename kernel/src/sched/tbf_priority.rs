@@ -0,0 +1,255 @@
+//! Fixed Priority Scheduler for Tock with priorities from TBF headers
+//!
+//! Unlike `PrioritySched`, which derives the priority of a process from its
+//! position in the `PROCESSES` array, this scheduler reads the priority of each
+//! process from the `Priority` TLV in its TBF header. Lower values are higher
+//! priorities. Processes without the TLV get a default priority chosen by the
+//! board, and the board can cap the priority that any process may ask for, so
+//! that an app cannot place itself above the processes the board trusts.
+//!
+//! The scheduler always runs the highest priority process that is ready, and
+//! preempts it as soon as a higher priority process becomes ready. Processes
+//! with the same priority take turns, with a timeslice when more than one of
+//! them is ready. Kernel tasks always take priority over userspace processes.
+//!
+//! ### Priority Inheritance
+//!
+//! When set as the observer of the IPC driver, the scheduler implements
+//! priority inheritance for IPC services: after a client notifies a service,
+//! the service runs with the priority of the client until the service notifies
+//! the client back. This keeps a medium priority process from delaying a high
+//! priority client by starving the low priority service it waits on.
+//! Inheritance is not transitive. The scheduler tracks up to `NUM_PROCS`
+//! outstanding requests, and forgets a request once its client or service
+//! stops, restarts or is replaced.
+//!
+//! ```rust,ignore
+//! let scheduler = static_init!(
+//!     TbfPrioritySched<NUM_PROCS>,
+//!     TbfPrioritySched::new(board_kernel, 8, Some(2))
+//! );
+//! platform.ipc.set_observer(scheduler);
+//! ```
+
+use core::cell::Cell;
+
+use crate::common::deferred_call;
+use crate::ipc::IPCObserver;
+use crate::platform::Chip;
+use crate::process::{Process, ProcessId, State};
+use crate::sched::{Kernel, Scheduler, SchedulingDecision, StoppedExecutingReason};
+
+/// Timeslice given to a process when another process with the same priority is
+/// also ready.
+const SHARED_TIMESLICE_US: u32 = 10000;
+
+/// An IPC request that the service has not answered yet.
+#[derive(Clone, Copy, PartialEq)]
+struct Request {
+    client: ProcessId,
+    service: ProcessId,
+}
+
+/// Priority scheduler based on the `Priority` TLV of each process.
+pub struct TbfPrioritySched<const NUM_PROCS: usize> {
+    kernel: &'static Kernel,
+    default_priority: u32,
+    highest_priority: u32,
+    /// Index after the process that ran last, so that processes with the
+    /// same priority take turns.
+    next: Cell<usize>,
+    /// Outstanding IPC requests, through which services inherit the priority
+    /// of their clients.
+    requests: Cell<[Option<Request>; NUM_PROCS]>,
+}
+
+impl<const NUM_PROCS: usize> TbfPrioritySched<NUM_PROCS> {
+    /// Create the scheduler. Processes without a `Priority` TLV get
+    /// `default_priority`, and processes asking for a priority higher than
+    /// `highest_priority` (a lower value) get `highest_priority` instead.
+    pub const fn new(
+        kernel: &'static Kernel,
+        default_priority: u32,
+        highest_priority: Option<u32>,
+    ) -> Self {
+        Self {
+            kernel,
+            default_priority,
+            highest_priority: match highest_priority {
+                Some(priority) => priority,
+                None => 0,
+            },
+            next: Cell::new(0),
+            requests: Cell::new([None; NUM_PROCS]),
+        }
+    }
+
+    /// The priority of `proc` from its TBF header, the default and the cap.
+    pub fn base_priority(&self, proc: &dyn Process) -> u32 {
+        core::cmp::max(
+            proc.get_priority().unwrap_or(self.default_priority),
+            self.highest_priority,
+        )
+    }
+
+    /// The priority `proc` runs with, which includes the priorities of the
+    /// clients with an outstanding request to it.
+    pub fn priority(&self, proc: &dyn Process) -> u32 {
+        proc.processid()
+            .index()
+            .and_then(|index| self.priorities().get(index).copied())
+            .unwrap_or_else(|| self.base_priority(proc))
+    }
+
+    /// The priority of every process, by index. Requests whose client or
+    /// service is gone, stopped or restarted are dropped on the way, so this
+    /// takes time linear in the number of processes.
+    fn priorities(&self) -> [u32; NUM_PROCS] {
+        let mut priorities = [u32::MAX; NUM_PROCS];
+        for proc in self.kernel.get_process_iter() {
+            if let Some(priority) = proc
+                .processid()
+                .index()
+                .and_then(|index| priorities.get_mut(index))
+            {
+                *priority = self.base_priority(proc);
+            }
+        }
+
+        let mut requests = self.requests.get();
+        for slot in requests.iter_mut() {
+            let inherited = slot.and_then(|request| {
+                let client = self.kernel.get_process(request.client).filter(alive)?;
+                let service = self.kernel.get_process(request.service).filter(alive)?;
+                Some((service, self.base_priority(client)))
+            });
+            match inherited {
+                Some((service, priority)) => {
+                    if let Some(current) = service
+                        .processid()
+                        .index()
+                        .and_then(|index| priorities.get_mut(index))
+                    {
+                        *current = core::cmp::min(*current, priority);
+                    }
+                }
+                None => *slot = None,
+            }
+        }
+        self.requests.set(requests);
+        priorities
+    }
+
+    /// How far after the last process to run `index` is, so that among
+    /// processes with the same priority the one after the last to run wins.
+    fn turn(&self, index: usize) -> usize {
+        (index + NUM_PROCS - self.next.get()) % NUM_PROCS
+    }
+}
+
+/// Whether `proc` can still take part in an IPC request. A restarted process
+/// gets a new `ProcessId`, so its old requests are not found at all.
+fn alive(proc: &&dyn Process) -> bool {
+    !matches!(
+        proc.get_state(),
+        State::Terminated | State::Faulted | State::Unstarted
+    )
+}
+
+impl<C: Chip, const NUM_PROCS: usize> Scheduler<C> for TbfPrioritySched<NUM_PROCS> {
+    fn next(&self, kernel: &Kernel) -> SchedulingDecision {
+        if kernel.processes_blocked() {
+            // No processes ready
+            return SchedulingDecision::TrySleep;
+        }
+
+        let priorities = self.priorities();
+        let priority = |proc: &dyn Process| {
+            proc.processid()
+                .index()
+                .and_then(|index| priorities.get(index).copied())
+                .unwrap_or(u32::MAX)
+        };
+        let next = self
+            .kernel
+            .get_process_iter()
+            .filter(|proc| proc.ready())
+            .min_by_key(|proc| {
+                (
+                    priority(*proc),
+                    proc.processid().index().map_or(NUM_PROCS, |i| self.turn(i)),
+                )
+            });
+        match next {
+            Some(proc) => {
+                let shared = self
+                    .kernel
+                    .get_process_iter()
+                    .filter(|other| other.ready() && priority(*other) == priority(proc))
+                    .count()
+                    > 1;
+                let processid = proc.processid();
+                if let Some(index) = processid.index() {
+                    self.next.set((index + 1) % NUM_PROCS);
+                }
+                SchedulingDecision::RunProcess((
+                    processid,
+                    if shared {
+                        Some(SHARED_TIMESLICE_US)
+                    } else {
+                        None
+                    },
+                ))
+            }
+            None => SchedulingDecision::TrySleep,
+        }
+    }
+
+    unsafe fn continue_process(&self, id: ProcessId, chip: &C) -> bool {
+        // As in `PrioritySched`, also check if a higher priority process
+        // became ready, for example because this process notified it over
+        // IPC. Notifying an IPC client also returns any priority this process
+        // inherited from it.
+        if chip.has_pending_interrupts() || deferred_call::has_tasks() {
+            return false;
+        }
+        let priorities = self.priorities();
+        let priority = |id: ProcessId| {
+            id.index()
+                .and_then(|index| priorities.get(index).copied())
+                .unwrap_or(u32::MAX)
+        };
+        let current = priority(id);
+        !self.kernel.get_process_iter().any(|proc| {
+            proc.processid() != id && proc.ready() && priority(proc.processid()) < current
+        })
+    }
+
+    fn result(&self, _: StoppedExecutingReason, _: Option<u32>) {}
+}
+
+impl<const NUM_PROCS: usize> IPCObserver for TbfPrioritySched<NUM_PROCS> {
+    fn service_notified(&self, client: ProcessId, service: ProcessId) {
+        if client == service {
+            return;
+        }
+        let request = Some(Request { client, service });
+        let mut requests = self.requests.get();
+        if !requests.contains(&request) {
+            // Without a free slot the service does not inherit the priority.
+            if let Some(slot) = requests.iter_mut().find(|slot| slot.is_none()) {
+                *slot = request;
+            }
+        }
+        self.requests.set(requests);
+    }
+
+    fn client_notified(&self, service: ProcessId, client: ProcessId) {
+        let request = Some(Request { client, service });
+        let mut requests = self.requests.get();
+        for slot in requests.iter_mut().filter(|slot| **slot == request) {
+            *slot = None;
+        }
+        self.requests.set(requests);
+    }
+}
@@ -0,0 +1,105 @@
+//! Permissions of processes to access persistent storage.
+//!
+//! Capsules that store data for processes, such as nonvolatile storage or a
+//! key-value store, tag the data a process writes with the process's write ID.
+//! A process can read data tagged with its write ID or one of its read IDs,
+//! and modify (overwrite or delete) data tagged with its write ID or one of its
+//! modify IDs. The kernel only provides the permissions; each capsule enforces
+//! them for the data it stores.
+//!
+//! Apps declare their permissions in the Storage Permissions TLV of their TBF
+//! header. As the TLV can name any storage ID, it is only honored for apps
+//! whose credentials were accepted. Other apps, and apps without the TLV, can
+//! read and modify only their own data, which is tagged with their short ID.
+//! Apps without a fixed short ID cannot access persistent storage at all,
+//! because data they write could not be attributed to them after a restart.
+
+use core::num::NonZeroU32;
+
+use tock_tbf::types::{TbfHeader, NUM_STORAGE_PERMISSIONS};
+
+use crate::process::ShortID;
+
+/// The storage identifiers a process may read, write and modify.
+#[derive(Clone, Copy, Debug, PartialEq, Eq)]
+pub struct StoragePermissions {
+    write_id: Option<NonZeroU32>,
+    read_count: usize,
+    read_ids: [u32; NUM_STORAGE_PERMISSIONS],
+    modify_count: usize,
+    modify_ids: [u32; NUM_STORAGE_PERMISSIONS],
+}
+
+impl StoragePermissions {
+    /// Permissions that do not allow any access to storage.
+    pub const fn new_null() -> StoragePermissions {
+        StoragePermissions {
+            write_id: None,
+            read_count: 0,
+            read_ids: [0; NUM_STORAGE_PERMISSIONS],
+            modify_count: 0,
+            modify_ids: [0; NUM_STORAGE_PERMISSIONS],
+        }
+    }
+
+    /// Permissions that only allow access to data tagged with `write_id`.
+    pub const fn new_self_only(write_id: NonZeroU32) -> StoragePermissions {
+        StoragePermissions {
+            write_id: Some(write_id),
+            ..StoragePermissions::new_null()
+        }
+    }
+
+    /// Decide the permissions of a process running the app with TBF header
+    /// `header` and short ID `short_id`. `verified` is whether the app's
+    /// credentials were accepted.
+    pub(crate) fn from_header(
+        header: &TbfHeader,
+        short_id: ShortID,
+        verified: bool,
+    ) -> StoragePermissions {
+        let short_id = match short_id {
+            ShortID::Fixed(short_id) => short_id,
+            ShortID::LocallyUnique => return StoragePermissions::new_null(),
+        };
+        if !verified {
+            return StoragePermissions::new_self_only(short_id);
+        }
+        match (
+            header.get_storage_read_ids(),
+            header.get_storage_modify_ids(),
+        ) {
+            (Some((read_count, read_ids)), Some((modify_count, modify_ids))) => {
+                StoragePermissions {
+                    write_id: header.get_storage_write_id(),
+                    read_count,
+                    read_ids,
+                    modify_count,
+                    modify_ids,
+                }
+            }
+            _ => StoragePermissions::new_self_only(short_id),
+        }
+    }
+
+    /// The storage identifier to tag data the process writes with, or `None`
+    /// if the process may not write to storage.
+    pub fn write_id(&self) -> Option<NonZeroU32> {
+        self.write_id
+    }
+
+    /// Whether the process may read data tagged with `storage_id`.
+    pub fn can_read(&self, storage_id: u32) -> bool {
+        self.is_write_id(storage_id) || self.read_ids[..self.read_count].contains(&storage_id)
+    }
+
+    /// Whether the process may overwrite or delete data tagged with
+    /// `storage_id`.
+    pub fn can_modify(&self, storage_id: u32) -> bool {
+        self.is_write_id(storage_id) || self.modify_ids[..self.modify_count].contains(&storage_id)
+    }
+
+    fn is_write_id(&self, storage_id: u32) -> bool {
+        self.write_id.map_or(false, |id| id.get() == storage_id)
+    }
+}
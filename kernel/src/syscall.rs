@@ -37,6 +37,7 @@ pub enum SyscallClass {
     ReadOnlyAllow = 4,
     Memop = 5,
     Exit = 6,
+    Thread = 7,
 }
 
 /// Enumeration of the yield system calls based on the Yield identifier
@@ -45,6 +46,7 @@ pub enum SyscallClass {
 pub enum YieldCall {
     NoWait = 0,
     Wait = 1,
+    WaitFor = 2,
 }
 
 // Required as long as no solution to
@@ -62,6 +64,7 @@ impl TryFrom<u8> for SyscallClass {
             4 => Ok(SyscallClass::ReadOnlyAllow),
             5 => Ok(SyscallClass::Memop),
             6 => Ok(SyscallClass::Exit),
+            7 => Ok(SyscallClass::Thread),
             i => Err(i),
         }
     }
@@ -74,6 +77,14 @@ pub enum Syscall {
     /// `which` is the Yield identifier value and `address` is the no wait field.
     Yield { which: usize, address: *mut u8 },
 
+    /// Structure representing an invocation of the Yield system call class
+    /// with the WaitFor identifier. `driver_number` and `subdriver_number`
+    /// identify the upcall to wait for.
+    YieldWaitFor {
+        driver_number: usize,
+        subdriver_number: usize,
+    },
+
     /// Structure representing an invocation of the Subscribe system call
     /// class. `driver_number` is the driver identifier, `subdriver_number`
     /// is the subscribe identifier, `upcall_ptr` is upcall pointer,
@@ -129,8 +140,23 @@ pub enum Syscall {
         which: usize,
         completion_code: usize,
     },
+
+    /// Structure representing an invocation of the Thread system call
+    /// class. `operation` selects what to do with the threads of the process,
+    /// and `arg0` to `arg2` are its arguments.
+    Thread {
+        operation: usize,
+        arg0: usize,
+        arg1: usize,
+        arg2: usize,
+    },
 }
 
+/// The driver number under which the command permissions in the TBF header
+/// of an app list the Thread operations it may use, with one bit per
+/// `operation`.
+pub const THREAD_DRIVER_NUM: usize = 0x10004;
+
 impl Syscall {
     /// Helper function for converting raw values passed back from an application
     /// into a `Syscall` type in Tock, representing an typed version of a system
@@ -148,6 +174,12 @@ impl Syscall {
         r3: usize,
     ) -> Option<Syscall> {
         match SyscallClass::try_from(syscall_number) {
+            Ok(SyscallClass::Yield) if r0 == YieldCall::WaitFor as usize => {
+                Some(Syscall::YieldWaitFor {
+                    driver_number: r1,
+                    subdriver_number: r2,
+                })
+            }
             Ok(SyscallClass::Yield) => Some(Syscall::Yield {
                 which: r0,
                 address: r1 as *mut u8,
@@ -184,6 +216,12 @@ impl Syscall {
                 which: r0,
                 completion_code: r1,
             }),
+            Ok(SyscallClass::Thread) => Some(Syscall::Thread {
+                operation: r0,
+                arg0: r1,
+                arg1: r2,
+                arg2: r3,
+            }),
             Err(_) => None,
         }
     }
@@ -592,6 +630,36 @@ pub trait UserspaceKernelBoundary {
         state: &mut Self::StoredState,
     ) -> (ContextSwitchReason, Option<*const u8>);
 
+    /// Called by the kernel to initialize the stored state of an additional
+    /// thread of a process, whose stack grows down from `stack_pointer`. The
+    /// kernel then calls `set_process_function()` with the entry point of the
+    /// thread, which must start executing it when first switched to.
+    ///
+    /// This function must initialize the stored state like
+    /// `initialize_process()`, and may be called again on the same stored state
+    /// once the thread that used it has exited. Returns `INVAL` if the stack
+    /// cannot hold the initial frame of the thread. Architectures that cannot
+    /// run more than one thread per process return `NOSUPPORT`, which is the
+    /// default.
+    ///
+    /// ### Safety
+    ///
+    /// This function guarantees that it if needs to change process memory, it
+    /// will only change memory starting at `accessible_memory_start` and before
+    /// `app_brk`. The caller is responsible for guaranteeing that those
+    /// pointers are valid for the process, and that `stack_pointer` is between
+    /// them.
+    #[allow(unused_variables)]
+    unsafe fn initialize_thread(
+        &self,
+        accessible_memory_start: *const u8,
+        app_brk: *const u8,
+        stack_pointer: *const u8,
+        state: &mut Self::StoredState,
+    ) -> Result<(), ErrorCode> {
+        Err(ErrorCode::NOSUPPORT)
+    }
+
     /// Display architecture specific (e.g. CPU registers or status flags) data
     /// for a process identified by the stored state for that process.
     ///
@@ -608,4 +676,10 @@ pub trait UserspaceKernelBoundary {
         state: &Self::StoredState,
         writer: &mut dyn Write,
     );
+
+    /// Store the architecture specific registers in the stored state for a
+    /// process in `out`, as little-endian 32-bit words, for example to save a
+    /// crash dump. Returns the number of bytes written, or `SIZE` if `out` is
+    /// too small.
+    fn store_context(&self, state: &Self::StoredState, out: &mut [u8]) -> Result<usize, ErrorCode>;
 }
@@ -0,0 +1,75 @@
+//! Implementation of the THREAD family of syscalls.
+//!
+//! Threads let a process run several flows of control that share its memory
+//! and grants, for example a background worker next to the main thread that
+//! handles upcalls. Threads of a process never run concurrently: the kernel
+//! switches between them when the running thread blocks, yields, or uses up
+//! the timeslice of the process. Upcalls only ever run on the main thread.
+//!
+//! As a thread other than the main thread can never run an upcall, `yield`
+//! does not block on such a thread: both yield-no-wait and yield-wait let the
+//! other threads run and then return, reporting that no upcall ran. A thread
+//! that yields in a loop to wait for an event therefore busy-waits; waiting
+//! for upcalls should be left to the main thread.
+
+use crate::process::Process;
+use crate::syscall::SyscallReturn;
+use crate::ErrorCode;
+
+/// Handle the `thread` syscall.
+///
+/// ### `operation`
+///
+/// - `0`: Create a thread that runs the function at `arg0` with the argument
+///   `arg2`, on the stack that grows down from `arg1`. Returns the identifier
+///   of the new thread.
+/// - `1`: Join. Block until the thread with identifier `arg0` exits and return
+///   its completion code.
+/// - `2`: Yield. Let the other threads of the process run.
+/// - `3`: Exit the current thread with completion code `arg0`. Does not
+///   return, except with an error on the main thread.
+/// - `4`: Return the identifier of the current thread. The main thread is 0.
+pub(crate) fn thread(
+    process: &dyn Process,
+    operation: usize,
+    arg0: usize,
+    arg1: usize,
+    arg2: usize,
+) {
+    match operation {
+        0 /* Create */ => {
+            let rval = match process.create_thread(arg0 as *const u8, arg1 as *const u8, arg2) {
+                Ok(thread) => SyscallReturn::SuccessU32(thread as u32),
+                Err(e) => SyscallReturn::Failure(e),
+            };
+            process.set_syscall_return_value(rval);
+        }
+
+        // The return value is set once the thread exits.
+        1 /* Join */ => {
+            if let Err(e) = process.join_thread(arg0) {
+                process.set_syscall_return_value(SyscallReturn::Failure(e));
+            }
+        }
+
+        // The return value must be set before switching threads.
+        2 /* Yield */ => {
+            process.set_syscall_return_value(SyscallReturn::Success);
+            process.yield_thread();
+        }
+
+        3 /* Exit */ => {
+            if let Err(e) = process.exit_thread(arg0 as u32) {
+                process.set_syscall_return_value(SyscallReturn::Failure(e));
+            }
+        }
+
+        4 /* Identifier */ => {
+            process.set_syscall_return_value(SyscallReturn::SuccessU32(
+                process.current_thread() as u32,
+            ));
+        }
+
+        _ => process.set_syscall_return_value(SyscallReturn::Failure(ErrorCode::NOSUPPORT)),
+    }
+}
@@ -0,0 +1,402 @@
+//! Binary trace of system calls, upcalls, context switches and scheduling
+//! decisions.
+//!
+//! Unlike `trace_syscalls` in the kernel configuration, which prints every
+//! system call with `debug!()` and has to be compiled in, a `Tracer` records
+//! fixed-size binary events into a ring buffer the board provides. Tracing
+//! starts disabled and can be enabled and restricted to one process or one
+//! driver at runtime, so it can stay in production kernels. When the buffer
+//! is full the oldest events are overwritten and counted as dropped.
+//!
+//! Timestamps are measured with the scheduler timer: they count the
+//! microseconds processes have run under a timeslice since the kernel
+//! booted, including the time the kernel spent on their behalf. Processes run
+//! cooperatively do not advance the clock, so with cooperative schedulers the
+//! order of events is all the trace provides.
+//!
+//! Setup
+//! -----
+//!
+//! ```rust,ignore
+//! static mut TRACE_BUF: [TraceEvent; 64] = [TraceEvent::EMPTY; 64];
+//!
+//! let tracer = static_init!(Tracer, Tracer::new(&mut TRACE_BUF));
+//! board_kernel.set_tracer(tracer, &main_loop_capability);
+//! tracer.enable();
+//! ```
+//!
+//! Event format
+//! ------------
+//!
+//! `TraceEvent::to_bytes()` serializes an event into `TRACE_EVENT_LEN` bytes
+//! for tools on the host. All fields are little endian:
+//!
+//! ```text
+//! 0         4      5        6         8          12        16     20     24
+//! +---------+------+--------+---------+----------+---------+------+------+
+//! | time_us | kind | detail | (zero)  | process  | driver  | arg0 | arg1 |
+//! +---------+------+--------+---------+----------+---------+------+------+
+//! ```
+//!
+//! `process` and `driver` are `NONE` (0xFFFFFFFF) for events that are not
+//! about a process or a driver. The meaning of `detail` and the arguments
+//! depends on the kind of the event, see `TraceEventKind`.
+
+use core::cell::Cell;
+
+use crate::common::cells::{MapCell, NumericCellExt, OptionalCell};
+use crate::common::{Queue, RingBuffer};
+use crate::process::{FunctionCall, FunctionCallSource, ProcessId};
+use crate::sched::StoppedExecutingReason;
+use crate::syscall::{ContextSwitchReason, Syscall, SyscallClass};
+
+/// Length of a serialized `TraceEvent`.
+pub const TRACE_EVENT_LEN: usize = 24;
+
+/// Value of the `process` and `driver` fields of events that are not about a
+/// process or a driver.
+pub const NONE: u32 = 0xFFFFFFFF;
+
+/// What a `TraceEvent` records.
+#[derive(Copy, Clone, Debug, PartialEq, Eq)]
+#[repr(u8)]
+pub enum TraceEventKind {
+    /// A process made a system call. `detail` is the `SyscallClass`. For
+    /// driver system calls `driver` is the driver number and `arg0` the
+    /// subdriver number, otherwise `arg0` and `arg1` are the first two
+    /// arguments of the call.
+    Syscall = 0,
+
+    /// The kernel pushed an upcall onto the stack of a process. `detail` is 1
+    /// for function calls from the kernel, such as the entry point, and 0 for
+    /// upcalls from drivers, for which `driver` and `arg0` are the driver and
+    /// subscribe numbers. `arg1` is the first argument of the upcall.
+    Upcall = 1,
+
+    /// A process returned to the kernel. `detail` is 0 for a system call, 1
+    /// for a fault, 2 for an interrupt and 3 if the switch failed. `arg0` is
+    /// the thread that was running.
+    ContextSwitch = 2,
+
+    /// The scheduler chose a process to run. `arg0` is its timeslice in
+    /// microseconds, or 0 if it runs cooperatively.
+    Scheduled = 3,
+
+    /// A process stopped running. `detail` is the `StoppedExecutingReason`
+    /// and `arg0` how long it ran for in microseconds.
+    Stopped = 4,
+
+    /// The scheduler had nothing to run and the kernel put the chip to sleep.
+    Sleep = 5,
+}
+
+/// One event in the trace.
+#[derive(Copy, Clone, Debug, PartialEq, Eq)]
+pub struct TraceEvent {
+    pub time_us: u32,
+    pub kind: TraceEventKind,
+    pub detail: u8,
+    pub process: u32,
+    pub driver: u32,
+    pub arg0: u32,
+    pub arg1: u32,
+}
+
+impl TraceEvent {
+    /// An event to initialize trace buffers with.
+    pub const EMPTY: TraceEvent = TraceEvent {
+        time_us: 0,
+        kind: TraceEventKind::Sleep,
+        detail: 0,
+        process: NONE,
+        driver: NONE,
+        arg0: 0,
+        arg1: 0,
+    };
+
+    /// Serialize the event in the format described in the module
+    /// documentation.
+    pub fn to_bytes(self) -> [u8; TRACE_EVENT_LEN] {
+        let mut bytes = [0; TRACE_EVENT_LEN];
+        bytes[0..4].copy_from_slice(&self.time_us.to_le_bytes());
+        bytes[4] = self.kind as u8;
+        bytes[5] = self.detail;
+        bytes[8..12].copy_from_slice(&self.process.to_le_bytes());
+        bytes[12..16].copy_from_slice(&self.driver.to_le_bytes());
+        bytes[16..20].copy_from_slice(&self.arg0.to_le_bytes());
+        bytes[20..24].copy_from_slice(&self.arg1.to_le_bytes());
+        bytes
+    }
+}
+
+/// Ring buffer of trace events, filled in by the kernel once the board
+/// passes it to `Kernel::set_tracer()`. A buffer of N events holds the last
+/// N - 1 events.
+pub struct Tracer {
+    events: MapCell<RingBuffer<'static, TraceEvent>>,
+    dropped: Cell<usize>,
+    enabled: Cell<bool>,
+    process_filter: OptionalCell<ProcessId>,
+    driver_filter: OptionalCell<u32>,
+    /// Time at the start of the current timeslice and time now.
+    timeslice_start_us: Cell<u32>,
+    now_us: Cell<u32>,
+}
+
+impl Tracer {
+    pub fn new(events: &'static mut [TraceEvent]) -> Tracer {
+        Tracer {
+            events: MapCell::new(RingBuffer::new(events)),
+            dropped: Cell::new(0),
+            enabled: Cell::new(false),
+            process_filter: OptionalCell::empty(),
+            driver_filter: OptionalCell::empty(),
+            timeslice_start_us: Cell::new(0),
+            now_us: Cell::new(0),
+        }
+    }
+
+    pub fn enable(&self) {
+        self.enabled.set(true);
+    }
+
+    pub fn disable(&self) {
+        self.enabled.set(false);
+    }
+
+    pub fn is_enabled(&self) -> bool {
+        self.enabled.get()
+    }
+
+    /// Only record events about `process`, or about every process if `None`.
+    /// Events that are not about any process, such as the kernel going to
+    /// sleep, are not recorded while a process is selected.
+    pub fn set_process_filter(&self, process: Option<ProcessId>) {
+        self.process_filter.insert(process);
+    }
+
+    /// Only record system calls and upcalls of driver `driver_num`, or events
+    /// of every kind if `None`.
+    pub fn set_driver_filter(&self, driver_num: Option<u32>) {
+        self.driver_filter.insert(driver_num);
+    }
+
+    /// Number of events in the buffer.
+    pub fn len(&self) -> usize {
+        self.events.map_or(0, |events| events.len())
+    }
+
+    pub fn is_empty(&self) -> bool {
+        self.len() == 0
+    }
+
+    /// Number of events that were overwritten before being read.
+    pub fn dropped(&self) -> usize {
+        self.dropped.get()
+    }
+
+    /// Remove and return the oldest event in the buffer.
+    pub fn pop(&self) -> Option<TraceEvent> {
+        self.events.and_then(|events| events.dequeue())
+    }
+
+    /// Discard every event in the buffer and reset the dropped count.
+    pub fn clear(&self) {
+        self.events.map(|events| events.empty());
+        self.dropped.set(0);
+    }
+
+    pub(crate) fn timeslice_started(&self) {
+        self.timeslice_start_us.set(self.now_us.get());
+    }
+
+    /// Advance the clock to `us` microseconds after the start of the current
+    /// timeslice, as measured by the scheduler timer.
+    pub(crate) fn timeslice_elapsed(&self, us: u32) {
+        self.now_us
+            .set(self.timeslice_start_us.get().wrapping_add(us));
+    }
+
+    pub(crate) fn syscall(&self, process: ProcessId, syscall: &Syscall) {
+        let (class, driver, arg0, arg1) = match *syscall {
+            Syscall::Yield { which, address } => {
+                (SyscallClass::Yield, NONE, which, address as usize)
+            }
+            Syscall::YieldWaitFor {
+                driver_number,
+                subdriver_number,
+            } => (
+                SyscallClass::Yield,
+                driver_number as u32,
+                subdriver_number,
+                0,
+            ),
+            Syscall::Subscribe {
+                driver_number,
+                subdriver_number,
+                upcall_ptr,
+                ..
+            } => (
+                SyscallClass::Subscribe,
+                driver_number as u32,
+                subdriver_number,
+                upcall_ptr as usize,
+            ),
+            Syscall::Command {
+                driver_number,
+                subdriver_number,
+                arg0,
+                ..
+            } => (
+                SyscallClass::Command,
+                driver_number as u32,
+                subdriver_number,
+                arg0,
+            ),
+            Syscall::ReadWriteAllow {
+                driver_number,
+                subdriver_number,
+                allow_size,
+                ..
+            } => (
+                SyscallClass::ReadWriteAllow,
+                driver_number as u32,
+                subdriver_number,
+                allow_size,
+            ),
+            Syscall::ReadOnlyAllow {
+                driver_number,
+                subdriver_number,
+                allow_size,
+                ..
+            } => (
+                SyscallClass::ReadOnlyAllow,
+                driver_number as u32,
+                subdriver_number,
+                allow_size,
+            ),
+            Syscall::Memop { operand, arg0 } => (SyscallClass::Memop, NONE, operand, arg0),
+            Syscall::Exit {
+                which,
+                completion_code,
+            } => (SyscallClass::Exit, NONE, which, completion_code),
+            Syscall::Thread {
+                operation, arg0, ..
+            } => (SyscallClass::Thread, NONE, operation, arg0),
+        };
+        self.record(
+            TraceEventKind::Syscall,
+            class as u8,
+            Some(process),
+            driver,
+            arg0 as u32,
+            arg1 as u32,
+        );
+    }
+
+    pub(crate) fn upcall(&self, process: ProcessId, call: &FunctionCall) {
+        let (detail, driver, arg0) = match call.source {
+            FunctionCallSource::Driver(upcall_id) => (
+                0,
+                upcall_id.driver_num as u32,
+                upcall_id.subscribe_num as u32,
+            ),
+            FunctionCallSource::Kernel => (1, NONE, 0),
+        };
+        self.record(
+            TraceEventKind::Upcall,
+            detail,
+            Some(process),
+            driver,
+            arg0,
+            call.argument0 as u32,
+        );
+    }
+
+    pub(crate) fn context_switch(
+        &self,
+        process: ProcessId,
+        reason: &Option<ContextSwitchReason>,
+        thread: usize,
+    ) {
+        let detail = match reason {
+            Some(ContextSwitchReason::SyscallFired { .. }) => 0,
+            Some(ContextSwitchReason::Fault) => 1,
+            Some(ContextSwitchReason::Interrupted) => 2,
+            None => 3,
+        };
+        self.record(
+            TraceEventKind::ContextSwitch,
+            detail,
+            Some(process),
+            NONE,
+            thread as u32,
+            0,
+        );
+    }
+
+    pub(crate) fn scheduled(&self, process: ProcessId, timeslice_us: Option<u32>) {
+        self.record(
+            TraceEventKind::Scheduled,
+            0,
+            Some(process),
+            NONE,
+            timeslice_us.unwrap_or(0),
+            0,
+        );
+    }
+
+    pub(crate) fn stopped(
+        &self,
+        process: ProcessId,
+        reason: StoppedExecutingReason,
+        time_executed_us: Option<u32>,
+    ) {
+        self.record(
+            TraceEventKind::Stopped,
+            reason as u8,
+            Some(process),
+            NONE,
+            time_executed_us.unwrap_or(0),
+            0,
+        );
+    }
+
+    pub(crate) fn sleep(&self) {
+        self.record(TraceEventKind::Sleep, 0, None, NONE, 0, 0);
+    }
+
+    fn record(
+        &self,
+        kind: TraceEventKind,
+        detail: u8,
+        process: Option<ProcessId>,
+        driver: u32,
+        arg0: u32,
+        arg1: u32,
+    ) {
+        if !self.enabled.get()
+            || self
+                .process_filter
+                .map_or(false, |filter| process != Some(*filter))
+            || self.driver_filter.map_or(false, |filter| driver != *filter)
+        {
+            return;
+        }
+        let event = TraceEvent {
+            time_us: self.now_us.get(),
+            kind,
+            detail,
+            process: process.map_or(NONE, |process| process.id() as u32),
+            driver,
+            arg0,
+            arg1,
+        };
+        self.events.map(|events| {
+            // Overwrite the oldest event if the buffer is full.
+            if events.push(event).is_some() {
+                self.dropped.increment();
+            }
+        });
+    }
+}
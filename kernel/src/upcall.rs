@@ -120,11 +120,20 @@ impl Upcall {
         r1: usize,
         r2: usize,
     ) -> Result<(), UpcallError> {
-        let res = self.fn_ptr.map_or(
+        // A process waiting for this upcall in a yield-wait-for receives it
+        // even without an upcall function, as the function is not called.
+        let pc = self.fn_ptr.map(|fp| fp.as_ptr() as usize).or_else(|| {
+            if process.yielded_for() == Some(self.upcall_id) {
+                Some(0)
+            } else {
+                None
+            }
+        });
+        let res = pc.map_or(
             // A null-Upcall is treated as being delivered to
             // the process and ignored
             Ok(()),
-            |fp| {
+            |pc| {
                 let enqueue_res =
                     process.enqueue_task(process::Task::FunctionCall(process::FunctionCall {
                         source: process::FunctionCallSource::Driver(self.upcall_id),
@@ -132,7 +141,7 @@ impl Upcall {
                         argument1: r1,
                         argument2: r2,
                         argument3: self.appdata,
-                        pc: fp.as_ptr() as usize,
+                        pc,
                     }));
 
                 match enqueue_res {
@@ -100,11 +100,11 @@
 //! // when appending a key:
 //!
 //! // Add a key
-//! static VALUE: [u8; 32] = [0x23; 32];
-//! let ret = unsafe { tickv.append_key(get_hashed_key(b"ONE"), &VALUE) };
+//! static mut VALUE: [u8; 32] = [0x23; 32];
+//! let ret = unsafe { tickv.append_key(get_hashed_key(b"ONE"), &mut VALUE, 32) };
 //!
 //! match ret {
-//!     Err(ErrorCode::ReadNotReady(reg)) => {
+//!     Err((_, ErrorCode::ReadNotReady(reg))) => {
 //!         // There is no actual delay in the test, just continue now
 //!         tickv.set_read_buffer(&tickv.tickv.controller.buf.borrow()[reg]);
 //!         tickv
@@ -142,7 +142,8 @@ pub struct AsyncTicKV<'a, C: FlashController<S>, const S: usize> {
     /// The main TicKV struct
     pub tickv: TicKV<'a, C, S>,
     key: Cell<Option<u64>>,
-    value: Cell<Option<&'static [u8]>>,
+    value: Cell<Option<&'static mut [u8]>>,
+    value_length: Cell<usize>,
     buf: Cell<Option<&'static mut [u8]>>,
 }
 
@@ -158,6 +159,7 @@ impl<'a, C: FlashController<S>, const S: usize> AsyncTicKV<'a, C, S> {
             tickv: TicKV::<C, S>::new(controller, read_buffer, flash_size),
             key: Cell::new(None),
             value: Cell::new(None),
+            value_length: Cell::new(0),
             buf: Cell::new(None),
         }
     }
@@ -182,17 +184,34 @@ impl<'a, C: FlashController<S>, const S: usize> AsyncTicKV<'a, C, S> {
     /// `hash`: A hashed key. This key will be used in future to retrieve
     ///         or remove the `value`.
     /// `value`: A buffer containing the data to be stored to flash.
+    /// `length`: The number of bytes of `value` to store.
     ///
-    /// On success nothing will be returned.
-    /// On error a `ErrorCode` will be returned.
-    pub fn append_key(&self, hash: u64, value: &'static [u8]) -> Result<SuccessCode, ErrorCode> {
-        match self.tickv.append_key(hash, value) {
-            Ok(code) => Ok(code),
-            Err(e) => {
-                self.key.replace(Some(hash));
+    /// On success a `SuccessCode` will be returned and `value` can be
+    /// retrieved with `get_stored_value_buffer()`.
+    /// On error a `ErrorCode` will be returned, along with `value` unless
+    /// the operation will continue asynchronously.
+    pub fn append_key(
+        &self,
+        hash: u64,
+        value: &'static mut [u8],
+        length: usize,
+    ) -> Result<SuccessCode, (Option<&'static mut [u8]>, ErrorCode)> {
+        match self.tickv.append_key(hash, &value[0..length]) {
+            Ok(code) => {
                 self.value.replace(Some(value));
-                Err(e)
+                Ok(code)
             }
+            Err(e) => match e {
+                ErrorCode::ReadNotReady(_)
+                | ErrorCode::EraseNotReady(_)
+                | ErrorCode::WriteNotReady(_) => {
+                    self.key.replace(Some(hash));
+                    self.value.replace(Some(value));
+                    self.value_length.set(length);
+                    Err((None, e))
+                }
+                _ => Err((Some(value), e)),
+            },
         }
     }
 
@@ -266,7 +285,7 @@ impl<'a, C: FlashController<S>, const S: usize> AsyncTicKV<'a, C, S> {
 
     /// Get the `value` buffer that was passed in by previous
     /// commands.
-    pub fn get_stored_value_buffer(&self) -> Option<&'static [u8]> {
+    pub fn get_stored_value_buffer(&self) -> Option<&'static mut [u8]> {
         self.value.take()
     }
 
@@ -294,9 +313,14 @@ impl<'a, C: FlashController<S>, const S: usize> AsyncTicKV<'a, C, S> {
     pub fn continue_operation(&self) -> ContinueReturn {
         let ret = match self.tickv.state.get() {
             State::Init(_) => self.tickv.initalise(self.key.get().unwrap()),
-            State::AppendKey(_) => self
-                .tickv
-                .append_key(self.key.get().unwrap(), self.value.get().unwrap()),
+            State::AppendKey(_) => {
+                let value = self.value.take().unwrap();
+                let ret = self
+                    .tickv
+                    .append_key(self.key.get().unwrap(), &value[0..self.value_length.get()]);
+                self.value.replace(Some(value));
+                ret
+            }
             State::GetKey(_) => {
                 let buf = self.buf.take().unwrap();
                 let ret = self.tickv.get_key(self.key.get().unwrap(), buf);
@@ -542,11 +566,12 @@ mod store_flast_ctrl {
             ret = r;
         }
 
-        static VALUE: [u8; 32] = [0x23; 32];
+        static mut VALUE: [u8; 32] = [0x23; 32];
 
-        let ret = tickv.append_key(get_hashed_key(b"ONE"), &VALUE);
+        #[allow(unsafe_code)]
+        let ret = unsafe { tickv.append_key(get_hashed_key(b"ONE"), &mut VALUE, 32) };
         match ret {
-            Err(ErrorCode::ReadNotReady(reg)) => {
+            Err((_, ErrorCode::ReadNotReady(reg))) => {
                 // There is no actual delay in the test, just continue now
                 tickv.set_read_buffer(&tickv.tickv.controller.buf.borrow()[reg]);
                 tickv.continue_operation().0.unwrap();
@@ -555,9 +580,10 @@ mod store_flast_ctrl {
             _ => unreachable!(),
         }
 
-        let ret = tickv.append_key(get_hashed_key(b"TWO"), &VALUE);
+        #[allow(unsafe_code)]
+        let ret = unsafe { tickv.append_key(get_hashed_key(b"TWO"), &mut VALUE, 32) };
         match ret {
-            Err(ErrorCode::ReadNotReady(reg)) => {
+            Err((_, ErrorCode::ReadNotReady(reg))) => {
                 // There is no actual delay in the test, just continue now
                 tickv.set_read_buffer(&tickv.tickv.controller.buf.borrow()[reg]);
                 tickv.continue_operation().0.unwrap();
@@ -582,13 +608,14 @@ mod store_flast_ctrl {
             ret = r;
         }
 
-        static VALUE: [u8; 32] = [0x23; 32];
+        static mut VALUE: [u8; 32] = [0x23; 32];
         static mut BUF: [u8; 32] = [0; 32];
 
         println!("Add key ONE");
-        let ret = tickv.append_key(get_hashed_key(b"ONE"), &VALUE);
+        #[allow(unsafe_code)]
+        let ret = unsafe { tickv.append_key(get_hashed_key(b"ONE"), &mut VALUE, 32) };
         match ret {
-            Err(ErrorCode::ReadNotReady(reg)) => {
+            Err((_, ErrorCode::ReadNotReady(reg))) => {
                 // There is no actual delay in the test, just continue now
                 tickv.set_read_buffer(&tickv.tickv.controller.buf.borrow()[reg]);
                 tickv.continue_operation().0.unwrap();
@@ -617,9 +644,10 @@ mod store_flast_ctrl {
         }
 
         println!("Add key ONE again");
-        let ret = tickv.append_key(get_hashed_key(b"ONE"), &VALUE);
+        #[allow(unsafe_code)]
+        let ret = unsafe { tickv.append_key(get_hashed_key(b"ONE"), &mut VALUE, 32) };
         match ret {
-            Err(ErrorCode::ReadNotReady(reg)) => {
+            Err((_, ErrorCode::ReadNotReady(reg))) => {
                 // There is no actual delay in the test, just continue now
                 tickv.set_read_buffer(&tickv.tickv.controller.buf.borrow()[reg]);
                 assert_eq!(
@@ -627,14 +655,15 @@ mod store_flast_ctrl {
                     Err(ErrorCode::KeyAlreadyExists)
                 );
             }
-            Err(ErrorCode::KeyAlreadyExists) => {}
+            Err((_, ErrorCode::KeyAlreadyExists)) => {}
             _ => unreachable!(),
         }
 
         println!("Add key TWO");
-        let ret = tickv.append_key(get_hashed_key(b"TWO"), &VALUE);
+        #[allow(unsafe_code)]
+        let ret = unsafe { tickv.append_key(get_hashed_key(b"TWO"), &mut VALUE, 32) };
         match ret {
-            Err(ErrorCode::ReadNotReady(reg)) => {
+            Err((_, ErrorCode::ReadNotReady(reg))) => {
                 // There is no actual delay in the test, just continue now
                 tickv.set_read_buffer(&tickv.tickv.controller.buf.borrow()[reg]);
                 tickv.continue_operation().0.unwrap();
@@ -707,13 +736,14 @@ mod store_flast_ctrl {
             ret = r;
         }
 
-        static VALUE: [u8; 32] = [0x23; 32];
+        static mut VALUE: [u8; 32] = [0x23; 32];
         static mut BUF: [u8; 32] = [0; 32];
 
         println!("Add key ONE");
-        let ret = tickv.append_key(get_hashed_key(b"ONE"), &VALUE);
+        #[allow(unsafe_code)]
+        let ret = unsafe { tickv.append_key(get_hashed_key(b"ONE"), &mut VALUE, 32) };
         match ret {
-            Err(ErrorCode::ReadNotReady(reg)) => {
+            Err((_, ErrorCode::ReadNotReady(reg))) => {
                 // There is no actual delay in the test, just continue now
                 tickv.set_read_buffer(&tickv.tickv.controller.buf.borrow()[reg]);
                 tickv.continue_operation().0.unwrap();
@@ -764,7 +794,7 @@ mod store_flast_ctrl {
             ret = r;
         }
 
-        static VALUE: [u8; 32] = [0x23; 32];
+        static mut VALUE: [u8; 32] = [0x23; 32];
         static mut BUF: [u8; 32] = [0; 32];
 
         println!("Garbage collect empty flash");
@@ -778,9 +808,10 @@ mod store_flast_ctrl {
         }
 
         println!("Add key ONE");
-        let ret = tickv.append_key(get_hashed_key(b"ONE"), &VALUE);
+        #[allow(unsafe_code)]
+        let ret = unsafe { tickv.append_key(get_hashed_key(b"ONE"), &mut VALUE, 32) };
         match ret {
-            Err(ErrorCode::ReadNotReady(reg)) => {
+            Err((_, ErrorCode::ReadNotReady(reg))) => {
                 // There is no actual delay in the test, just continue now
                 tickv.set_read_buffer(&tickv.tickv.controller.buf.borrow()[reg]);
                 tickv.continue_operation().0.unwrap();
@@ -860,6 +891,11 @@ mod store_flast_ctrl {
         }
 
         println!("Add Key ONE");
-        tickv.append_key(get_hashed_key(b"ONE"), &VALUE).unwrap();
+        #[allow(unsafe_code)]
+        unsafe {
+            tickv
+                .append_key(get_hashed_key(b"ONE"), &mut VALUE, 32)
+                .unwrap();
+        }
     }
 }
@@ -126,10 +126,19 @@ pub fn parse_tbf_header(
                 // Places to save fields that we parse out of the header
                 // options.
                 let mut main_pointer: Option<types::TbfHeaderV2Main> = None;
+                let mut program_pointer: Option<types::TbfHeaderV2Program> = None;
                 let mut wfr_pointer: [Option<types::TbfHeaderV2WriteableFlashRegion>; 4] =
                     Default::default();
                 let mut app_name_str = "";
                 let mut fixed_address_pointer: Option<types::TbfHeaderV2FixedAddresses> = None;
+                let mut short_id_pointer: Option<types::TbfHeaderV2ShortId> = None;
+                let mut real_time_pointer: Option<types::TbfHeaderV2RealTime> = None;
+                let mut priority_pointer: Option<types::TbfHeaderV2Priority> = None;
+                let mut ipc_access_pointer: Option<types::TbfHeaderV2IpcAccess> = None;
+                let mut command_permissions_pointer: Option<types::TbfHeaderV2CommandPermissions> =
+                    None;
+                let mut storage_permissions_pointer: Option<types::TbfHeaderV2StoragePermissions> =
+                    None;
 
                 // Iterate the remainder of the header looking for TLV entries.
                 while remaining.len() > 0 {
@@ -157,6 +166,29 @@ pub fn parse_tbf_header(
                             }
                         }
 
+                        types::TbfHeaderTypes::TbfHeaderProgram => {
+                            let entry_len = mem::size_of::<types::TbfHeaderV2Program>();
+
+                            if tlv_header.length as usize == entry_len {
+                                let program: types::TbfHeaderV2Program = remaining.try_into()?;
+                                // The binary must end after the header and
+                                // within the TBF, otherwise the credentials in
+                                // the footer would not cover the header.
+                                if program.binary_end_offset < tbf_header_base.header_size as u32
+                                    || program.binary_end_offset > tbf_header_base.total_size
+                                {
+                                    return Err(types::TbfParseError::BadTlvEntry(
+                                        tlv_header.tipe as usize,
+                                    ));
+                                }
+                                program_pointer = Some(program);
+                            } else {
+                                return Err(types::TbfParseError::BadTlvEntry(
+                                    tlv_header.tipe as usize,
+                                ));
+                            }
+                        }
+
                         types::TbfHeaderTypes::TbfHeaderWriteableFlashRegions => {
                             // Length must be a multiple of the size of a region definition.
                             if tlv_header.length as usize
@@ -219,6 +251,64 @@ pub fn parse_tbf_header(
                             }
                         }
 
+                        types::TbfHeaderTypes::TbfHeaderCommandPermissions => {
+                            let entry = remaining
+                                .get(0..tlv_header.length as usize)
+                                .ok_or(types::TbfParseError::NotEnoughFlash)?;
+                            command_permissions_pointer = Some(entry.try_into().or(Err(
+                                types::TbfParseError::BadTlvEntry(tlv_header.tipe as usize),
+                            ))?);
+                        }
+
+                        types::TbfHeaderTypes::TbfHeaderStoragePermissions => {
+                            let entry = remaining
+                                .get(0..tlv_header.length as usize)
+                                .ok_or(types::TbfParseError::NotEnoughFlash)?;
+                            storage_permissions_pointer = Some(entry.try_into().or(Err(
+                                types::TbfParseError::BadTlvEntry(tlv_header.tipe as usize),
+                            ))?);
+                        }
+
+                        types::TbfHeaderTypes::TbfHeaderShortId => {
+                            let entry_len = mem::size_of::<types::TbfHeaderV2ShortId>();
+                            if tlv_header.length as usize == entry_len {
+                                short_id_pointer = Some(remaining.try_into()?);
+                            } else {
+                                return Err(types::TbfParseError::BadTlvEntry(
+                                    tlv_header.tipe as usize,
+                                ));
+                            }
+                        }
+
+                        types::TbfHeaderTypes::TbfHeaderRealTime => {
+                            let entry_len = mem::size_of::<types::TbfHeaderV2RealTime>();
+                            if tlv_header.length as usize == entry_len {
+                                real_time_pointer = Some(remaining.try_into()?);
+                            } else {
+                                return Err(types::TbfParseError::BadTlvEntry(
+                                    tlv_header.tipe as usize,
+                                ));
+                            }
+                        }
+
+                        types::TbfHeaderTypes::TbfHeaderPriority => {
+                            let entry_len = mem::size_of::<types::TbfHeaderV2Priority>();
+                            if tlv_header.length as usize == entry_len {
+                                priority_pointer = Some(remaining.try_into()?);
+                            } else {
+                                return Err(types::TbfParseError::BadTlvEntry(
+                                    tlv_header.tipe as usize,
+                                ));
+                            }
+                        }
+
+                        types::TbfHeaderTypes::TbfHeaderIpcAccess => {
+                            let entry = remaining
+                                .get(0..tlv_header.length as usize)
+                                .ok_or(types::TbfParseError::NotEnoughFlash)?;
+                            ipc_access_pointer = Some(entry.try_into()?);
+                        }
+
                         _ => {}
                     }
 
@@ -233,9 +323,16 @@ pub fn parse_tbf_header(
                 let tbf_header = types::TbfHeaderV2 {
                     base: tbf_header_base,
                     main: main_pointer,
+                    program: program_pointer,
                     package_name: Some(app_name_str),
                     writeable_regions: Some(wfr_pointer),
                     fixed_addresses: fixed_address_pointer,
+                    short_id: short_id_pointer,
+                    command_permissions: command_permissions_pointer,
+                    storage_permissions: storage_permissions_pointer,
+                    real_time: real_time_pointer,
+                    priority: priority_pointer,
+                    ipc_access: ipc_access_pointer,
                 };
 
                 Ok(types::TbfHeader::TbfHeaderV2(tbf_header))
@@ -244,3 +341,26 @@ pub fn parse_tbf_header(
         _ => Err(types::TbfParseError::UnsupportedVersion(version)),
     }
 }
+
+/// Parse one credentials entry from the footer of a TBF.
+///
+/// `footers` must start at a footer TLV, which for the first footer is at
+/// `TbfHeader::get_binary_end()`. On success this returns the credentials and
+/// the number of bytes the entry occupies (including its TLV header and
+/// padding), so the caller can advance to the next footer.
+pub fn parse_tbf_footer(
+    footers: &'static [u8],
+) -> Result<(types::TbfFooterV2Credentials, u32), types::TbfParseError> {
+    let tlv_header: types::TbfHeaderTlv = footers.try_into()?;
+    match tlv_header.tipe {
+        types::TbfHeaderTypes::TbfFooterCredentials => {
+            let credentials_buf = footers
+                .get(4..4 + tlv_header.length as usize)
+                .ok_or(types::TbfParseError::NotEnoughFlash)?;
+            let credentials = credentials_buf.try_into()?;
+            let entry_len = 4 + align4!(tlv_header.length as usize);
+            Ok((credentials, entry_len as u32))
+        }
+        _ => Err(types::TbfParseError::BadTlvEntry(tlv_header.tipe as usize)),
+    }
+}
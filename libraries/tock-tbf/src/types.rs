@@ -105,6 +105,17 @@ pub enum TbfHeaderTypes {
     TbfHeaderWriteableFlashRegions = 2,
     TbfHeaderPackageName = 3,
     TbfHeaderFixedAddresses = 5,
+    TbfHeaderStoragePermissions = 7,
+    TbfHeaderProgram = 9,
+    TbfHeaderShortId = 10,
+    TbfHeaderRealTime = 11,
+    TbfHeaderPriority = 12,
+    TbfHeaderIpcAccess = 13,
+    TbfHeaderCommandPermissions = 14,
+
+    /// Credentials (hashes or signatures) for the app. These are only valid in
+    /// the footer, after the end of the application binary.
+    TbfFooterCredentials = 128,
 
     /// Some field in the header that we do not understand. Since the TLV format
     /// specifies the length of each section, if we get a field we do not
@@ -130,6 +141,22 @@ pub struct TbfHeaderV2Main {
     minimum_ram_size: u32,
 }
 
+/// The v2 program section for apps.
+///
+/// This is a superset of the main section that also records where the
+/// application binary ends. Everything after the end of the binary and before
+/// the end of the TBF (`total_size`) is the footer, which holds the app's
+/// credentials. If an app has a program section its values take precedence
+/// over the main section.
+#[derive(Clone, Copy, Debug)]
+pub struct TbfHeaderV2Program {
+    init_fn_offset: u32,
+    protected_size: u32,
+    minimum_ram_size: u32,
+    pub(crate) binary_end_offset: u32,
+    version: u32,
+}
+
 /// Writeable flash regions only need an offset and size.
 ///
 /// There can be multiple (or zero) flash regions defined, so this is its own
@@ -164,6 +191,101 @@ pub struct TbfHeaderV2FixedAddresses {
     start_process_flash: u32,
 }
 
+/// The commands of one driver that an app may call.
+#[derive(Clone, Copy, Debug, Default)]
+pub struct TbfHeaderDriverPermission {
+    driver_number: u32,
+    /// Which block of 64 command numbers `allowed_commands` covers.
+    offset: u32,
+    /// Bit `i` allows command number `offset * 64 + i`.
+    allowed_commands: u64,
+}
+
+/// Optional list of the drivers and commands the app may use.
+///
+/// An app with permissions may only make system calls to the drivers listed,
+/// and may only call the commands allowed for each driver. Apps without
+/// permissions are not restricted.
+#[derive(Clone, Copy, Debug)]
+pub struct TbfHeaderV2CommandPermissions {
+    /// The `TbfHeaderDriverPermission` entries, 16 bytes each.
+    perms: &'static [u8],
+}
+
+/// The commands of a driver that an app's permissions allow.
+#[derive(Clone, Copy, Debug, PartialEq, Eq)]
+pub enum CommandPermissions {
+    /// The app's header has no permissions, so the app is not restricted.
+    NoPermsAtAll,
+    /// The app's permissions do not list the driver.
+    NoPermsThisDriver,
+    /// Bit `i` allows command number `offset * 64 + i`, for the `offset` that
+    /// was asked for.
+    Mask(u64),
+}
+
+/// Maximum number of storage identifiers an app can list in each of the read
+/// and modify lists of its storage permissions.
+pub const NUM_STORAGE_PERMISSIONS: usize = 8;
+
+/// Optional permissions of the app to persistent storage.
+///
+/// Data an app writes to persistent storage is tagged with the app's write ID.
+/// The app may read data tagged with its write ID or one of its read IDs, and
+/// modify (overwrite or delete) data tagged with its write ID or one of its
+/// modify IDs.
+#[derive(Clone, Copy, Debug)]
+pub struct TbfHeaderV2StoragePermissions {
+    /// Storage identifier of the data the app writes. `0` means the app cannot
+    /// write.
+    write_id: u32,
+    /// The read IDs, 4 bytes each.
+    read_ids: &'static [u8],
+    /// The modify IDs, 4 bytes each.
+    modify_ids: &'static [u8],
+}
+
+/// Optional fixed short identifier for the app.
+///
+/// The kernel uses this to identify the app across restarts, reboots and
+/// updates, for example to key the app's persistent storage. Apps that share
+/// data stored by a previous version must keep the same short ID.
+#[derive(Clone, Copy, Debug)]
+pub struct TbfHeaderV2ShortId {
+    /// The short ID. `0` means the app has no fixed short ID.
+    short_id: u32,
+}
+
+/// Optional real-time reservation for the app.
+///
+/// Real-time schedulers reserve `budget_us` microseconds of execution time
+/// for the app in every period of `period_us` microseconds, if they can
+/// admit it.
+#[derive(Clone, Copy, Debug)]
+pub struct TbfHeaderV2RealTime {
+    period_us: u32,
+    budget_us: u32,
+}
+
+/// Optional scheduling priority for the app.
+///
+/// Lower values are higher priorities: `0` is the highest priority an app can
+/// ask for. Schedulers may cap the priority an app receives.
+#[derive(Clone, Copy, Debug)]
+pub struct TbfHeaderV2Priority {
+    priority: u32,
+}
+
+/// Optional list of the apps that may connect to the IPC service of the app.
+///
+/// Each entry is the short ID of an app, as a little endian `u32`. The list is
+/// kept in the header rather than copied. Apps without this list allow any app
+/// to connect.
+#[derive(Clone, Copy, Debug)]
+pub struct TbfHeaderV2IpcAccess {
+    clients: &'static [u8],
+}
+
 // Conversion functions from slices to the various TBF fields.
 
 impl core::convert::TryFrom<&[u8]> for TbfHeaderV2Base {
@@ -209,6 +331,14 @@ impl core::convert::TryFrom<u16> for TbfHeaderTypes {
             2 => Ok(TbfHeaderTypes::TbfHeaderWriteableFlashRegions),
             3 => Ok(TbfHeaderTypes::TbfHeaderPackageName),
             5 => Ok(TbfHeaderTypes::TbfHeaderFixedAddresses),
+            7 => Ok(TbfHeaderTypes::TbfHeaderStoragePermissions),
+            9 => Ok(TbfHeaderTypes::TbfHeaderProgram),
+            10 => Ok(TbfHeaderTypes::TbfHeaderShortId),
+            11 => Ok(TbfHeaderTypes::TbfHeaderRealTime),
+            12 => Ok(TbfHeaderTypes::TbfHeaderPriority),
+            13 => Ok(TbfHeaderTypes::TbfHeaderIpcAccess),
+            14 => Ok(TbfHeaderTypes::TbfHeaderCommandPermissions),
+            128 => Ok(TbfHeaderTypes::TbfFooterCredentials),
             _ => Ok(TbfHeaderTypes::Unknown),
         }
     }
@@ -258,6 +388,40 @@ impl core::convert::TryFrom<&[u8]> for TbfHeaderV2Main {
     }
 }
 
+impl core::convert::TryFrom<&[u8]> for TbfHeaderV2Program {
+    type Error = TbfParseError;
+
+    fn try_from(b: &[u8]) -> Result<TbfHeaderV2Program, Self::Error> {
+        Ok(TbfHeaderV2Program {
+            init_fn_offset: u32::from_le_bytes(
+                b.get(0..4)
+                    .ok_or(TbfParseError::InternalError)?
+                    .try_into()?,
+            ),
+            protected_size: u32::from_le_bytes(
+                b.get(4..8)
+                    .ok_or(TbfParseError::InternalError)?
+                    .try_into()?,
+            ),
+            minimum_ram_size: u32::from_le_bytes(
+                b.get(8..12)
+                    .ok_or(TbfParseError::InternalError)?
+                    .try_into()?,
+            ),
+            binary_end_offset: u32::from_le_bytes(
+                b.get(12..16)
+                    .ok_or(TbfParseError::InternalError)?
+                    .try_into()?,
+            ),
+            version: u32::from_le_bytes(
+                b.get(16..20)
+                    .ok_or(TbfParseError::InternalError)?
+                    .try_into()?,
+            ),
+        })
+    }
+}
+
 impl core::convert::TryFrom<&[u8]> for TbfHeaderV2WriteableFlashRegion {
     type Error = TbfParseError;
 
@@ -296,6 +460,173 @@ impl core::convert::TryFrom<&[u8]> for TbfHeaderV2FixedAddresses {
     }
 }
 
+impl core::convert::TryFrom<&[u8]> for TbfHeaderDriverPermission {
+    type Error = TbfParseError;
+
+    fn try_from(b: &[u8]) -> Result<TbfHeaderDriverPermission, Self::Error> {
+        Ok(TbfHeaderDriverPermission {
+            driver_number: u32::from_le_bytes(
+                b.get(0..4)
+                    .ok_or(TbfParseError::InternalError)?
+                    .try_into()?,
+            ),
+            offset: u32::from_le_bytes(
+                b.get(4..8)
+                    .ok_or(TbfParseError::InternalError)?
+                    .try_into()?,
+            ),
+            allowed_commands: u64::from_le_bytes(
+                b.get(8..16)
+                    .ok_or(TbfParseError::InternalError)?
+                    .try_into()?,
+            ),
+        })
+    }
+}
+
+impl core::convert::TryFrom<&'static [u8]> for TbfHeaderV2CommandPermissions {
+    type Error = TbfParseError;
+
+    fn try_from(b: &'static [u8]) -> Result<TbfHeaderV2CommandPermissions, Self::Error> {
+        let number = u16::from_le_bytes(
+            b.get(0..2)
+                .ok_or(TbfParseError::InternalError)?
+                .try_into()?,
+        );
+        // The length must match the number of drivers.
+        if b.len() != 2 + 16 * number as usize {
+            return Err(TbfParseError::BadTlvEntry(
+                TbfHeaderTypes::TbfHeaderCommandPermissions as usize,
+            ));
+        }
+        Ok(TbfHeaderV2CommandPermissions { perms: &b[2..] })
+    }
+}
+
+impl TbfHeaderV2CommandPermissions {
+    fn iter(&self) -> impl Iterator<Item = TbfHeaderDriverPermission> {
+        self.perms
+            .chunks_exact(16)
+            .filter_map(|perm| perm.try_into().ok())
+    }
+}
+
+impl core::convert::TryFrom<&'static [u8]> for TbfHeaderV2StoragePermissions {
+    type Error = TbfParseError;
+
+    fn try_from(b: &'static [u8]) -> Result<TbfHeaderV2StoragePermissions, Self::Error> {
+        let read_length = u16::from_le_bytes(
+            b.get(4..6)
+                .ok_or(TbfParseError::InternalError)?
+                .try_into()?,
+        ) as usize;
+        let modify_length = u16::from_le_bytes(
+            b.get(6..8)
+                .ok_or(TbfParseError::InternalError)?
+                .try_into()?,
+        ) as usize;
+        // The length must match the number of IDs. The read IDs follow the
+        // fixed fields, and the modify IDs follow the read IDs.
+        if read_length > NUM_STORAGE_PERMISSIONS
+            || modify_length > NUM_STORAGE_PERMISSIONS
+            || b.len() != 8 + 4 * (read_length + modify_length)
+        {
+            return Err(TbfParseError::BadTlvEntry(
+                TbfHeaderTypes::TbfHeaderStoragePermissions as usize,
+            ));
+        }
+        let (read_ids, modify_ids) = b[8..].split_at(4 * read_length);
+
+        Ok(TbfHeaderV2StoragePermissions {
+            write_id: u32::from_le_bytes(
+                b.get(0..4)
+                    .ok_or(TbfParseError::InternalError)?
+                    .try_into()?,
+            ),
+            read_ids,
+            modify_ids,
+        })
+    }
+}
+
+/// The number of storage identifiers in `ids`, and the identifiers.
+fn storage_ids(ids: &[u8]) -> (usize, [u32; NUM_STORAGE_PERMISSIONS]) {
+    let mut array = [0; NUM_STORAGE_PERMISSIONS];
+    for (id, bytes) in array.iter_mut().zip(ids.chunks_exact(4)) {
+        *id = u32::from_le_bytes([bytes[0], bytes[1], bytes[2], bytes[3]]);
+    }
+    (ids.len() / 4, array)
+}
+
+impl core::convert::TryFrom<&[u8]> for TbfHeaderV2ShortId {
+    type Error = TbfParseError;
+
+    fn try_from(b: &[u8]) -> Result<TbfHeaderV2ShortId, Self::Error> {
+        Ok(TbfHeaderV2ShortId {
+            short_id: u32::from_le_bytes(
+                b.get(0..4)
+                    .ok_or(TbfParseError::InternalError)?
+                    .try_into()?,
+            ),
+        })
+    }
+}
+
+impl core::convert::TryFrom<&[u8]> for TbfHeaderV2RealTime {
+    type Error = TbfParseError;
+
+    fn try_from(b: &[u8]) -> Result<TbfHeaderV2RealTime, Self::Error> {
+        Ok(TbfHeaderV2RealTime {
+            period_us: u32::from_le_bytes(
+                b.get(0..4)
+                    .ok_or(TbfParseError::InternalError)?
+                    .try_into()?,
+            ),
+            budget_us: u32::from_le_bytes(
+                b.get(4..8)
+                    .ok_or(TbfParseError::InternalError)?
+                    .try_into()?,
+            ),
+        })
+    }
+}
+
+impl core::convert::TryFrom<&[u8]> for TbfHeaderV2Priority {
+    type Error = TbfParseError;
+
+    fn try_from(b: &[u8]) -> Result<TbfHeaderV2Priority, Self::Error> {
+        Ok(TbfHeaderV2Priority {
+            priority: u32::from_le_bytes(
+                b.get(0..4)
+                    .ok_or(TbfParseError::InternalError)?
+                    .try_into()?,
+            ),
+        })
+    }
+}
+
+impl core::convert::TryFrom<&'static [u8]> for TbfHeaderV2IpcAccess {
+    type Error = TbfParseError;
+
+    fn try_from(b: &'static [u8]) -> Result<TbfHeaderV2IpcAccess, Self::Error> {
+        if b.len() % 4 != 0 {
+            return Err(TbfParseError::BadTlvEntry(
+                TbfHeaderTypes::TbfHeaderIpcAccess as usize,
+            ));
+        }
+        Ok(TbfHeaderV2IpcAccess { clients: b })
+    }
+}
+
+impl TbfHeaderV2IpcAccess {
+    /// Whether the app with short ID `short_id` is in the list.
+    fn contains(&self, short_id: u32) -> bool {
+        self.clients
+            .chunks_exact(4)
+            .any(|client| client == short_id.to_le_bytes())
+    }
+}
+
 /// Single header that can contain all parts of a v2 header.
 ///
 /// Note, this struct limits the number of writeable regions an app can have to
@@ -305,9 +636,16 @@ impl core::convert::TryFrom<&[u8]> for TbfHeaderV2FixedAddresses {
 pub struct TbfHeaderV2 {
     pub(crate) base: TbfHeaderV2Base,
     pub(crate) main: Option<TbfHeaderV2Main>,
+    pub(crate) program: Option<TbfHeaderV2Program>,
     pub(crate) package_name: Option<&'static str>,
     pub(crate) writeable_regions: Option<[Option<TbfHeaderV2WriteableFlashRegion>; 4]>,
     pub(crate) fixed_addresses: Option<TbfHeaderV2FixedAddresses>,
+    pub(crate) short_id: Option<TbfHeaderV2ShortId>,
+    pub(crate) command_permissions: Option<TbfHeaderV2CommandPermissions>,
+    pub(crate) storage_permissions: Option<TbfHeaderV2StoragePermissions>,
+    pub(crate) real_time: Option<TbfHeaderV2RealTime>,
+    pub(crate) priority: Option<TbfHeaderV2Priority>,
+    pub(crate) ipc_access: Option<TbfHeaderV2IpcAccess>,
 }
 
 /// Type that represents the fields of the Tock Binary Format header.
@@ -348,7 +686,10 @@ impl TbfHeader {
     /// needed for this app.
     pub fn get_minimum_app_ram_size(&self) -> u32 {
         match *self {
-            TbfHeader::TbfHeaderV2(hd) => hd.main.map_or(0, |m| m.minimum_ram_size),
+            TbfHeader::TbfHeaderV2(hd) => match hd.program {
+                Some(p) => p.minimum_ram_size,
+                None => hd.main.map_or(0, |m| m.minimum_ram_size),
+            },
             _ => 0,
         }
     }
@@ -358,7 +699,11 @@ impl TbfHeader {
     pub fn get_protected_size(&self) -> u32 {
         match *self {
             TbfHeader::TbfHeaderV2(hd) => {
-                hd.main.map_or(0, |m| m.protected_size) + (hd.base.header_size as u32)
+                let protected_size = match hd.program {
+                    Some(p) => p.protected_size,
+                    None => hd.main.map_or(0, |m| m.protected_size),
+                };
+                protected_size + (hd.base.header_size as u32)
             }
             _ => 0,
         }
@@ -369,12 +714,46 @@ impl TbfHeader {
     pub fn get_init_function_offset(&self) -> u32 {
         match *self {
             TbfHeader::TbfHeaderV2(hd) => {
-                hd.main.map_or(0, |m| m.init_fn_offset) + (hd.base.header_size as u32)
+                let init_fn_offset = match hd.program {
+                    Some(p) => p.init_fn_offset,
+                    None => hd.main.map_or(0, |m| m.init_fn_offset),
+                };
+                init_fn_offset + (hd.base.header_size as u32)
             }
             _ => 0,
         }
     }
 
+    /// Get the total size of the TBF in flash, including the header, the
+    /// application binary, any footers and padding.
+    pub fn get_total_size(&self) -> u32 {
+        match *self {
+            TbfHeader::TbfHeaderV2(hd) => hd.base.total_size,
+            TbfHeader::Padding(base) => base.total_size,
+        }
+    }
+
+    /// Get the offset from the beginning of the app's flash region of the end
+    /// of the application binary. The footer starts at this offset. Apps
+    /// without a program section have no footer, so this is the total size.
+    pub fn get_binary_end(&self) -> u32 {
+        match *self {
+            TbfHeader::TbfHeaderV2(hd) => hd
+                .program
+                .map_or(hd.base.total_size, |p| p.binary_end_offset),
+            TbfHeader::Padding(base) => base.total_size,
+        }
+    }
+
+    /// Get the version of the application binary from the program section, or
+    /// 0 if it does not have one.
+    pub fn get_binary_version(&self) -> u32 {
+        match *self {
+            TbfHeader::TbfHeaderV2(hd) => hd.program.map_or(0, |p| p.version),
+            _ => 0,
+        }
+    }
+
     /// Get the name of the app.
     pub fn get_package_name(&self) -> Option<&'static str> {
         match *self {
@@ -383,6 +762,108 @@ impl TbfHeader {
         }
     }
 
+    /// Get the fixed short ID of the app, if its header specifies one.
+    pub fn get_fixed_short_id(&self) -> Option<core::num::NonZeroU32> {
+        match *self {
+            TbfHeader::TbfHeaderV2(hd) => {
+                core::num::NonZeroU32::new(hd.short_id.as_ref()?.short_id)
+            }
+            _ => None,
+        }
+    }
+
+    /// Get the real-time reservation of the app as its period and budget in
+    /// microseconds, if its header specifies one.
+    pub fn get_real_time_reservation(&self) -> Option<(u32, u32)> {
+        match *self {
+            TbfHeader::TbfHeaderV2(hd) => hd
+                .real_time
+                .map(|real_time| (real_time.period_us, real_time.budget_us)),
+            _ => None,
+        }
+    }
+
+    /// Get the scheduling priority of the app, if its header specifies one.
+    /// Lower values are higher priorities.
+    pub fn get_priority(&self) -> Option<u32> {
+        match *self {
+            TbfHeader::TbfHeaderV2(hd) => hd.priority.map(|priority| priority.priority),
+            _ => None,
+        }
+    }
+
+    /// Get whether the app with short ID `short_id` may connect to the IPC
+    /// service of this app. Apps without an IPC access list allow any app.
+    /// `0` stands for an app without a short ID, which is never in the list.
+    pub fn ipc_client_allowed(&self, short_id: u32) -> bool {
+        match *self {
+            TbfHeader::TbfHeaderV2(hd) => hd
+                .ipc_access
+                .map_or(true, |access| short_id != 0 && access.contains(short_id)),
+            _ => true,
+        }
+    }
+
+    /// Get the commands of driver `driver_num` in the block of 64 command
+    /// numbers starting at `offset * 64` that the app's permissions allow.
+    pub fn get_command_permissions(&self, driver_num: usize, offset: usize) -> CommandPermissions {
+        let permissions = match *self {
+            TbfHeader::TbfHeaderV2(hd) => match hd.command_permissions {
+                Some(permissions) => permissions,
+                None => return CommandPermissions::NoPermsAtAll,
+            },
+            _ => return CommandPermissions::NoPermsAtAll,
+        };
+
+        let mut listed = false;
+        for perm in permissions.iter() {
+            if perm.driver_number as usize == driver_num {
+                if perm.offset as usize == offset {
+                    return CommandPermissions::Mask(perm.allowed_commands);
+                }
+                listed = true;
+            }
+        }
+        if listed {
+            CommandPermissions::Mask(0)
+        } else {
+            CommandPermissions::NoPermsThisDriver
+        }
+    }
+
+    /// Get the storage identifier of the data the app writes, if its header
+    /// has storage permissions that allow writing.
+    pub fn get_storage_write_id(&self) -> Option<core::num::NonZeroU32> {
+        match *self {
+            TbfHeader::TbfHeaderV2(hd) => {
+                core::num::NonZeroU32::new(hd.storage_permissions.as_ref()?.write_id)
+            }
+            _ => None,
+        }
+    }
+
+    /// Get the number of storage identifiers the app may read, and the
+    /// identifiers, if its header has storage permissions.
+    pub fn get_storage_read_ids(&self) -> Option<(usize, [u32; NUM_STORAGE_PERMISSIONS])> {
+        match *self {
+            TbfHeader::TbfHeaderV2(hd) => hd
+                .storage_permissions
+                .map(|permissions| storage_ids(permissions.read_ids)),
+            _ => None,
+        }
+    }
+
+    /// Get the number of storage identifiers the app may modify, and the
+    /// identifiers, if its header has storage permissions.
+    pub fn get_storage_modify_ids(&self) -> Option<(usize, [u32; NUM_STORAGE_PERMISSIONS])> {
+        match *self {
+            TbfHeader::TbfHeaderV2(hd) => hd
+                .storage_permissions
+                .map(|permissions| storage_ids(permissions.modify_ids)),
+            _ => None,
+        }
+    }
+
     /// Get the number of flash regions this app has specified in its header.
     pub fn number_writeable_flash_regions(&self) -> usize {
         match *self {
@@ -435,3 +916,88 @@ impl TbfHeader {
         }
     }
 }
+
+/// Formats of credentials that can be stored in the TBF footer.
+#[derive(Clone, Copy, Debug, PartialEq, Eq)]
+pub enum TbfFooterV2CredentialsType {
+    /// Placeholder that reserves space in the footer, for example so that
+    /// credentials can be added later without moving the app.
+    Reserved = 0,
+    /// SHA-256 hash of the integrity region.
+    SHA256 = 3,
+    /// ECDSA signature over the NIST P-256 curve of the SHA-256 hash of the
+    /// integrity region, as the 64 byte concatenation of `r` and `s`.
+    EcdsaNistP256 = 6,
+    /// Ed25519 signature of the integrity region.
+    Ed25519 = 7,
+}
+
+impl TbfFooterV2CredentialsType {
+    /// Length in bytes of credentials of this format, or `None` if the length
+    /// is variable.
+    pub fn length(&self) -> Option<usize> {
+        match self {
+            TbfFooterV2CredentialsType::Reserved => None,
+            TbfFooterV2CredentialsType::SHA256 => Some(32),
+            TbfFooterV2CredentialsType::EcdsaNistP256 => Some(64),
+            TbfFooterV2CredentialsType::Ed25519 => Some(64),
+        }
+    }
+}
+
+impl core::convert::TryFrom<u32> for TbfFooterV2CredentialsType {
+    type Error = TbfParseError;
+
+    fn try_from(format: u32) -> Result<TbfFooterV2CredentialsType, Self::Error> {
+        match format {
+            0 => Ok(TbfFooterV2CredentialsType::Reserved),
+            3 => Ok(TbfFooterV2CredentialsType::SHA256),
+            6 => Ok(TbfFooterV2CredentialsType::EcdsaNistP256),
+            7 => Ok(TbfFooterV2CredentialsType::Ed25519),
+            _ => Err(TbfParseError::BadTlvEntry(
+                TbfHeaderTypes::TbfFooterCredentials as usize,
+            )),
+        }
+    }
+}
+
+/// A single credential stored in the footer of a TBF.
+///
+/// Credentials cover the app's integrity region: the TBF header and the
+/// application binary, i.e. everything from the start of the TBF up to
+/// `TbfHeader::get_binary_end()`.
+#[derive(Clone, Copy, Debug)]
+pub struct TbfFooterV2Credentials {
+    pub(crate) format: TbfFooterV2CredentialsType,
+    pub(crate) data: &'static [u8],
+}
+
+impl TbfFooterV2Credentials {
+    pub fn format(&self) -> TbfFooterV2CredentialsType {
+        self.format
+    }
+
+    pub fn data(&self) -> &'static [u8] {
+        self.data
+    }
+}
+
+impl core::convert::TryFrom<&'static [u8]> for TbfFooterV2Credentials {
+    type Error = TbfParseError;
+
+    fn try_from(b: &'static [u8]) -> Result<TbfFooterV2Credentials, Self::Error> {
+        let format: TbfFooterV2CredentialsType = u32::from_le_bytes(
+            b.get(0..4)
+                .ok_or(TbfParseError::NotEnoughFlash)?
+                .try_into()?,
+        )
+        .try_into()?;
+        let data = b.get(4..).ok_or(TbfParseError::NotEnoughFlash)?;
+        match format.length() {
+            Some(length) if length != data.len() => Err(TbfParseError::BadTlvEntry(
+                TbfHeaderTypes::TbfFooterCredentials as usize,
+            )),
+            _ => Ok(TbfFooterV2Credentials { format, data }),
+        }
+    }
+}